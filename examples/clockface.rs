@@ -4,8 +4,7 @@ use ray_tracer_challenge::canvas::Canvas;
 use ray_tracer_challenge::color::Color;
 use ray_tracer_challenge::matrix::Matrix4;
 use ray_tracer_challenge::tuple::Tuple;
-use std::error::Error;
-use std::f32::consts::{FRAC_PI_6, PI};
+use std::f64::consts::{FRAC_PI_6, PI};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -23,13 +22,13 @@ fn main() {
 
     // Move the clock face to the center of the image
     let translation = Matrix4::translation(
-        (canvas.width as f32) / 2.0,
-        (canvas.height as f32) / 2.0,
+        (canvas.width as f64) / 2.0,
+        (canvas.height as f64) / 2.0,
         0.0,
     );
 
     // Make the clock face larger
-    let clock_radius = (3.0 * canvas.width as f32) / 8.0;
+    let clock_radius = (3.0 * canvas.width as f64) / 8.0;
     let scaling = Matrix4::scaling(clock_radius, clock_radius, 0.0);
 
     // Flip the clock face horizontally since the y-axis is inverted on
@@ -54,16 +53,16 @@ fn main() {
     let path = Path::new("clockface.ppm");
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => {
-            panic!("couldn't create {}: {}", display, why.description())
+            panic!("couldn't create {}: {}", display, why)
         }
         Ok(file) => file,
     };
 
     match file.write_all(canvas.to_ppm().as_bytes()) {
         Err(why) => {
-            panic!("couldn't write to {}: {}", display, why.description())
+            panic!("couldn't write to {}: {}", display, why)
         }
         Ok(_) => println!("successfully wrote to {}", display),
     }
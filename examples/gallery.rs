@@ -0,0 +1,134 @@
+#![allow(clippy::field_reassign_with_default)]
+
+extern crate ray_tracer_challenge;
+
+use ray_tracer_challenge::canvas::Canvas;
+use ray_tracer_challenge::color::Color;
+use ray_tracer_challenge::intersection::find_hit;
+use ray_tracer_challenge::light::{lighting, PointLight};
+use ray_tracer_challenge::matrix::Matrix4;
+use ray_tracer_challenge::ray::Ray;
+use ray_tracer_challenge::scenes;
+use ray_tracer_challenge::shape::Shape;
+use ray_tracer_challenge::sphere::Sphere;
+use ray_tracer_challenge::tuple::Tuple;
+use std::env;
+use std::f64::consts::{FRAC_PI_6, PI};
+use std::fs;
+use std::path::Path;
+
+/// Renders the milestone scenes of the book's chapters into a directory
+/// (`gallery` unless another one is given as the first argument), as a
+/// quick check that everything still fits together.
+fn main() {
+    let directory = env::args().nth(1).unwrap_or_else(|| "gallery".into());
+    let directory = Path::new(&directory);
+    if let Err(why) = fs::create_dir_all(directory) {
+        panic!("couldn't create {}: {}", directory.display(), why)
+    }
+
+    save(directory, "projectile", &projectile());
+    save(directory, "clock", &clock());
+    save(directory, "silhouette", &sphere(false));
+    save(directory, "shaded_sphere", &sphere(true));
+    save(directory, "scene", &scene());
+}
+
+fn save(directory: &Path, name: &str, canvas: &Canvas) {
+    let path = directory.join(format!("{}.ppm", name));
+    match fs::write(&path, canvas.to_srgb().to_ppm()) {
+        Err(why) => panic!("couldn't write to {}: {}", path.display(), why),
+        Ok(_) => println!("successfully wrote to {}", path.display()),
+    }
+}
+
+/// Chapter 2: the path of a projectile under gravity and wind.
+fn projectile() -> Canvas {
+    let mut canvas = Canvas::new(450, 275);
+    let gravity = Tuple::vector(0.0, -0.1, 0.0);
+    let wind = Tuple::vector(-0.01, 0.0, 0.0);
+    let mut position = Tuple::point(0.0, 1.0, 0.0);
+    let mut velocity = Tuple::vector(1.0, 1.8, 0.0).normalize() * 8.0;
+    while position.y > 0.0 {
+        let x = position.x.round() as usize;
+        let y = canvas.height as f64 - position.y.round();
+        if x < canvas.width && y >= 0.0 && (y as usize) < canvas.height {
+            canvas.write_pixel(x, y as usize, Color::new(1.0, 0.8, 0.2));
+        }
+        position += velocity;
+        velocity += gravity + wind;
+    }
+    canvas
+}
+
+/// Chapter 4: the hours of a clock face, placed by rotating a point.
+fn clock() -> Canvas {
+    let mut canvas = Canvas::new(200, 200);
+    let radius = (3.0 * canvas.width as f64) / 8.0;
+    let transform = Matrix4::translation(100.0, 100.0, 0.0)
+        * Matrix4::scaling(radius, radius, 0.0)
+        * Matrix4::rotation_x(PI);
+    for hour in 0..12 {
+        let point = transform
+            * Matrix4::rotation_z(-FRAC_PI_6 * hour as f64)
+            * Tuple::point(0.0, 1.0, 0.0);
+        canvas.write_pixel(
+            point.x.round() as usize,
+            point.y.round() as usize,
+            Color::white(),
+        );
+    }
+    canvas
+}
+
+/// Chapters 5 and 6: a sphere cast onto a wall, either as a flat
+/// silhouette or lit by a point light.
+fn sphere(shaded: bool) -> Canvas {
+    let size = 200;
+    let wall_size = 7.0;
+    let pixel_size = wall_size / (size as f64);
+    let mut canvas = Canvas::new(size, size);
+
+    let mut sphere = Sphere::default();
+    sphere.material.color = Color::new(1.0, 0.2, 1.0);
+    let light =
+        PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+    let origin = Tuple::point(0.0, 0.0, -5.0);
+
+    for y in 0..size {
+        for x in 0..size {
+            let target = Tuple::point(
+                -wall_size / 2.0 + pixel_size * (x as f64),
+                wall_size / 2.0 - pixel_size * (y as f64),
+                10.0,
+            );
+            let ray = Ray::new(origin, (target - origin).normalize());
+            let hit = match find_hit(sphere.intersect(ray)) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let color = if shaded {
+                let point = ray.position(hit.t);
+                lighting(
+                    hit.object.material().clone(),
+                    light,
+                    point,
+                    -ray.direction,
+                    hit.object.normal_at(point),
+                    1.0,
+                )
+            } else {
+                Color::new(1.0, 0.0, 0.0)
+            };
+            canvas.write_pixel(x, y, color);
+        }
+    }
+    canvas
+}
+
+/// Chapters 7 and 8: three spheres in a room, rendered through a camera
+/// with shadows.
+fn scene() -> Canvas {
+    let (world, camera) = scenes::chapter7();
+    camera.render_parallel(&world)
+}
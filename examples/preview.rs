@@ -0,0 +1,25 @@
+extern crate ray_tracer_challenge;
+
+use ray_tracer_challenge::camera::Camera;
+use ray_tracer_challenge::matrix::Matrix4;
+use ray_tracer_challenge::preview;
+use ray_tracer_challenge::tuple::Tuple;
+use ray_tracer_challenge::world::World;
+use std::f64::consts::FRAC_PI_3;
+
+/// Explores the default world interactively. Run with
+/// `cargo run --example preview --features preview`.
+fn main() {
+    let world = World::default();
+
+    let mut camera = Camera::new(400, 300, FRAC_PI_3);
+    camera.transform = Matrix4::view_transform(
+        Tuple::point(0.0, 1.5, -5.0),
+        Tuple::point(0.0, 0.0, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    );
+
+    if let Err(why) = preview::run(&world, &camera, 4) {
+        panic!("couldn't run the preview: {}", why)
+    }
+}
@@ -3,7 +3,6 @@ extern crate ray_tracer_challenge;
 use ray_tracer_challenge::canvas::Canvas;
 use ray_tracer_challenge::color::Color;
 use ray_tracer_challenge::tuple::Tuple;
-use std::error::Error;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -70,16 +69,16 @@ fn main() {
     let path = Path::new("projectile.ppm");
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => {
-            panic!("couldn't create {}: {}", display, why.description())
+            panic!("couldn't create {}: {}", display, why)
         }
         Ok(file) => file,
     };
 
     match file.write_all(canvas.to_ppm().as_bytes()) {
         Err(why) => {
-            panic!("couldn't write to {}: {}", display, why.description())
+            panic!("couldn't write to {}: {}", display, why)
         }
         Ok(_) => println!("successfully wrote to {}", display),
     }
@@ -0,0 +1,33 @@
+extern crate ray_tracer_challenge;
+
+use ray_tracer_challenge::metadata::RenderInfo;
+use ray_tracer_challenge::scene_file::SceneFile;
+use std::env;
+use std::time::Instant;
+
+/// Renders a scene description, such as
+/// `cargo run --example scene_file -- scene.yml scene.png`.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <scene.yml> <output.png>", args[0]);
+        std::process::exit(2);
+    }
+
+    let scene = match SceneFile::load(&args[1]) {
+        Err(why) => {
+            panic!("couldn't load {}: {}", args[1], why)
+        }
+        Ok(scene) => scene,
+    };
+    let start = Instant::now();
+    let canvas = scene.camera.render_parallel(&scene.world);
+    let info = RenderInfo::new(&scene.world, &scene.camera, start.elapsed());
+
+    match canvas.to_srgb().save_with_info(&args[2], &info) {
+        Err(why) => {
+            panic!("couldn't write to {}: {}", args[2], why)
+        }
+        Ok(_) => println!("successfully wrote to {}", args[2]),
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::field_reassign_with_default)]
+
 extern crate ray_tracer_challenge;
 
 use ray_tracer_challenge::canvas::Canvas;
@@ -5,9 +7,9 @@ use ray_tracer_challenge::color::Color;
 use ray_tracer_challenge::intersection::find_hit;
 use ray_tracer_challenge::light::{lighting, PointLight};
 use ray_tracer_challenge::ray::Ray;
+use ray_tracer_challenge::shape::Shape;
 use ray_tracer_challenge::sphere::Sphere;
 use ray_tracer_challenge::tuple::Tuple;
-use std::error::Error;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -16,7 +18,7 @@ fn main() {
     let canvas_size: usize = 500;
     let wall_size = 5.0;
     let half_wall_size = wall_size / 2.0;
-    let pixel_size = wall_size / (canvas_size as f32);
+    let pixel_size = wall_size / (canvas_size as f64);
     let mut canvas = Canvas::new(canvas_size, canvas_size);
 
     let mut sphere = Sphere::default();
@@ -31,8 +33,8 @@ fn main() {
     for y in 0..canvas.height {
         for x in 0..canvas.width {
             let wall_point = Tuple::point(
-                -half_wall_size + (pixel_size * (x as f32)),
-                half_wall_size - (pixel_size * (y as f32)),
+                -half_wall_size + (pixel_size * (x as f64)),
+                half_wall_size - (pixel_size * (y as f64)),
                 wall_size,
             );
             let ray_direction = (wall_point - ray_origin).normalize();
@@ -44,12 +46,12 @@ fn main() {
                 let normal_vector = hit.object.normal_at(point);
                 let eye_vector = -ray.direction;
                 let color = lighting(
-                    hit.object.material,
+                    hit.object.material().clone(),
                     light,
                     point,
                     eye_vector,
                     normal_vector,
-                    false,
+                    1.0,
                 );
                 canvas.write_pixel(x, y, color);
             }
@@ -59,16 +61,16 @@ fn main() {
     let path = Path::new("sphere.ppm");
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => {
-            panic!("couldn't create {}: {}", display, why.description())
+            panic!("couldn't create {}: {}", display, why)
         }
         Ok(file) => file,
     };
 
-    match file.write_all(canvas.to_ppm().as_bytes()) {
+    match file.write_all(canvas.to_srgb().to_ppm().as_bytes()) {
         Err(why) => {
-            panic!("couldn't write to {}: {}", display, why.description())
+            panic!("couldn't write to {}: {}", display, why)
         }
         Ok(_) => println!("successfully wrote to {}", display),
     }
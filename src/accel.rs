@@ -0,0 +1,220 @@
+use crate::bounds::{component, BoundingBox};
+use crate::bvh::Bvh;
+use crate::kdtree::KdTree;
+use crate::ray::Ray;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Which acceleration structure a world builds over its objects, so that
+/// rays only test the objects near them instead of every object.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Accel {
+    /// Every ray tests every object. Best for a handful of objects.
+    None,
+    /// A bounding volume hierarchy: a tree of boxes, each around a group
+    /// of objects.
+    Bvh,
+    /// A kd-tree: space is cut in two again and again, and objects are
+    /// listed in every cell they overlap.
+    KdTree,
+}
+
+impl Accel {
+    /// Builds the structure over objects with the given world space
+    /// bounds, or returns `None` for `Accel::None`.
+    pub fn build(
+        &self,
+        bounds: &[BoundingBox],
+    ) -> Option<Arc<dyn AccelStructure>> {
+        match self {
+            Accel::None => None,
+            Accel::Bvh => Some(Arc::new(Bvh::build(bounds))),
+            Accel::KdTree => Some(Arc::new(KdTree::build(bounds))),
+        }
+    }
+}
+
+/// A structure that finds which objects a ray might hit from their
+/// bounds, built once and then shared by every ray.
+pub trait AccelStructure: Debug + Send + Sync {
+    fn accel(&self) -> Accel;
+
+    /// The number of objects the structure was built over.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the indices of the objects whose bounds the ray passes
+    /// through ahead of its origin, each once and in no particular order.
+    /// Objects with infinite bounds are always included, and objects with
+    /// empty bounds never are.
+    fn candidates(&self, ray: Ray) -> Vec<usize>;
+
+    /// Finds the candidates for four rays at once, such as the rays
+    /// through a 2x2 block of pixels. Structures can override this to
+    /// walk their nodes once for all four rays, which pays off when the
+    /// rays are close together and so visit the same nodes.
+    fn packet_candidates(&self, rays: &[Ray; 4]) -> [Vec<usize>; 4] {
+        [
+            self.candidates(rays[0]),
+            self.candidates(rays[1]),
+            self.candidates(rays[2]),
+            self.candidates(rays[3]),
+        ]
+    }
+}
+
+/// Four rays tested against boxes together. The rays' components are
+/// stored lane by lane, so that the four slab tests in `hits_box` are the
+/// same operations on neighboring values, which the compiler turns into
+/// vector instructions.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Packet {
+    origin: [[f64; 4]; 3],
+    direction: [[f64; 4]; 3],
+}
+
+impl Packet {
+    pub(crate) fn new(rays: &[Ray; 4]) -> Packet {
+        let mut packet = Packet {
+            origin: [[0.0; 4]; 3],
+            direction: [[0.0; 4]; 3],
+        };
+        for axis in 0..3 {
+            for (lane, ray) in rays.iter().enumerate() {
+                packet.origin[axis][lane] = component(ray.origin, axis);
+                packet.direction[axis][lane] = component(ray.direction, axis);
+            }
+        }
+        packet
+    }
+
+    /// Returns, for each ray, whether `ray_hits_box` holds.
+    pub(crate) fn hits_box(&self, bounds: BoundingBox) -> [bool; 4] {
+        let mut t_min = [f64::NEG_INFINITY; 4];
+        let mut t_max = [f64::INFINITY; 4];
+        for axis in 0..3 {
+            let (low, high) =
+                (component(bounds.min, axis), component(bounds.max, axis));
+            for lane in 0..4 {
+                let origin = self.origin[axis][lane];
+                let direction = self.direction[axis][lane];
+                let (near, far) = if direction != 0.0 {
+                    let t1 = (low - origin) / direction;
+                    let t2 = (high - origin) / direction;
+                    (t1.min(t2), t1.max(t2))
+                } else if origin < low || origin > high {
+                    (f64::INFINITY, f64::NEG_INFINITY)
+                } else {
+                    (f64::NEG_INFINITY, f64::INFINITY)
+                };
+                t_min[lane] = t_min[lane].max(near);
+                t_max[lane] = t_max[lane].min(far);
+            }
+        }
+        let mut hits = [false; 4];
+        for lane in 0..4 {
+            hits[lane] = t_min[lane] <= t_max[lane] && t_max[lane] >= 0.0;
+        }
+        hits
+    }
+}
+
+/// Returns whether the ray passes through the box anywhere at or ahead
+/// of its origin, using the slab method.
+pub(crate) fn ray_hits_box(ray: Ray, bounds: BoundingBox) -> bool {
+    let mut t_min = f64::NEG_INFINITY;
+    let mut t_max = f64::INFINITY;
+    for axis in 0..3 {
+        let origin = component(ray.origin, axis);
+        let direction = component(ray.direction, axis);
+        let (low, high) =
+            (component(bounds.min, axis), component(bounds.max, axis));
+        if direction == 0.0 {
+            if origin < low || origin > high {
+                return false;
+            }
+            continue;
+        }
+        let t1 = (low - origin) / direction;
+        let t2 = (high - origin) / direction;
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+    }
+    t_min <= t_max && t_max >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accel::{ray_hits_box, Accel, Packet};
+    use crate::bounds::BoundingBox;
+    use crate::ray::Ray;
+    use crate::tuple::Tuple;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-1.0, -1.0, -1.0),
+            Tuple::point(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn test_a_ray_hits_a_box_ahead_of_it() {
+        let ray = |origin: Tuple, direction: Tuple| Ray::new(origin, direction);
+        let forward = Tuple::vector(0.0, 0.0, 1.0);
+        assert!(ray_hits_box(
+            ray(Tuple::point(0.5, 0.5, -5.0), forward),
+            unit_box()
+        ));
+        assert!(ray_hits_box(
+            ray(Tuple::point(0.0, 0.0, 0.0), forward),
+            unit_box()
+        ));
+        assert!(!ray_hits_box(
+            ray(Tuple::point(0.0, 0.0, 5.0), forward),
+            unit_box()
+        ));
+        assert!(!ray_hits_box(
+            ray(Tuple::point(2.0, 0.0, -5.0), forward),
+            unit_box()
+        ));
+        let diagonal = Tuple::vector(1.0, 1.0, 1.0);
+        assert!(ray_hits_box(
+            ray(Tuple::point(-3.0, -3.0, -3.0), diagonal),
+            unit_box()
+        ));
+    }
+
+    #[test]
+    fn test_a_packet_tests_four_rays_against_a_box_at_once() {
+        let rays = [
+            Ray::new(
+                Tuple::point(0.5, 0.5, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+            Ray::new(
+                Tuple::point(2.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+            Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0)),
+            Ray::new(
+                Tuple::point(-3.0, -3.0, -3.0),
+                Tuple::vector(1.0, 1.0, 1.0),
+            ),
+        ];
+        let hits = Packet::new(&rays).hits_box(unit_box());
+        assert_eq!(hits, [true, false, false, true]);
+        for (ray, &hit) in rays.iter().zip(hits.iter()) {
+            assert_eq!(ray_hits_box(*ray, unit_box()), hit);
+        }
+    }
+
+    #[test]
+    fn test_building_a_structure_by_name() {
+        assert!(Accel::None.build(&[unit_box()]).is_none());
+        let bvh = Accel::Bvh.build(&[unit_box()]).unwrap();
+        assert_eq!((bvh.accel(), bvh.len()), (Accel::Bvh, 1));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// Sums the samples taken at every pixel over many passes of a
+/// progressive render, along with their squares and how many samples
+/// each pixel has. The sums stay in full precision and are never tone
+/// mapped, so a preview can be shown after any pass without affecting
+/// the passes that follow.
+#[derive(Clone, Debug)]
+pub struct AccumulationBuffer {
+    pub width: usize,
+    pub height: usize,
+    sums: Vec<Color>,
+    squares: Vec<Color>,
+    counts: Vec<u32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: usize, height: usize) -> AccumulationBuffer {
+        AccumulationBuffer {
+            width,
+            height,
+            sums: vec![Color::black(); width * height],
+            squares: vec![Color::black(); width * height],
+            counts: vec![0; width * height],
+        }
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, color: Color) {
+        let index = y * self.width + x;
+        self.sums[index] += color;
+        self.squares[index] += color * color;
+        self.counts[index] += 1;
+    }
+
+    /// Adds every pixel of a rendered pass as one sample.
+    pub fn add_pass(&mut self, pass: &Canvas) {
+        assert_eq!((pass.width, pass.height), (self.width, self.height));
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.add_sample(x, y, pass.pixel_at(x, y));
+            }
+        }
+    }
+
+    pub fn samples_at(&self, x: usize, y: usize) -> u32 {
+        self.counts[y * self.width + x]
+    }
+
+    /// Returns the average of the samples at the pixel, or black if it
+    /// has none yet.
+    pub fn mean_at(&self, x: usize, y: usize) -> Color {
+        let index = y * self.width + x;
+        match self.counts[index] {
+            0 => Color::black(),
+            count => self.sums[index] * (1.0 / count as f64),
+        }
+    }
+
+    /// Returns how uncertain the average at the pixel still is: the
+    /// variance of the average, averaged over the color channels. It
+    /// shrinks as samples are added, and is infinite for pixels with
+    /// fewer than two samples.
+    pub fn variance_at(&self, x: usize, y: usize) -> f64 {
+        let index = y * self.width + x;
+        let count = self.counts[index] as f64;
+        if count < 2.0 {
+            return f64::INFINITY;
+        }
+        let mean = self.sums[index] * (1.0 / count);
+        let spread = self.squares[index] * (1.0 / count) - mean * mean;
+        let variance = (spread.red + spread.green + spread.blue) / 3.0;
+        variance.max(0.0) / (count - 1.0)
+    }
+
+    /// Returns the average of every pixel as an image.
+    pub fn resolve(&self) -> Canvas {
+        self.display(|color| color)
+    }
+
+    /// Returns an image for display, with `tone_map` applied to the
+    /// average of every pixel. The accumulated samples are unchanged.
+    pub fn display<F>(&self, tone_map: F) -> Canvas
+    where
+        F: Fn(Color) -> Color,
+    {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                canvas.write_pixel(x, y, tone_map(self.mean_at(x, y)));
+            }
+        }
+        canvas
+    }
+
+    /// Discards every sample, e.g. after the camera moved.
+    pub fn clear(&mut self) {
+        self.sums.iter_mut().for_each(|sum| *sum = Color::black());
+        self.squares
+            .iter_mut()
+            .for_each(|sum| *sum = Color::black());
+        self.counts.iter_mut().for_each(|count| *count = 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accumulation::AccumulationBuffer;
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::equal_f64;
+
+    #[test]
+    fn test_accumulating_samples() {
+        let mut buffer = AccumulationBuffer::new(2, 1);
+        assert_eq!(buffer.samples_at(0, 0), 0);
+        assert_eq!(buffer.mean_at(0, 0), Color::black());
+        buffer.add_sample(0, 0, Color::new(1.0, 0.0, 0.5));
+        buffer.add_sample(0, 0, Color::new(0.0, 1.0, 0.5));
+        assert_eq!(buffer.samples_at(0, 0), 2);
+        assert_eq!(buffer.mean_at(0, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(buffer.samples_at(1, 0), 0);
+    }
+
+    #[test]
+    fn test_the_variance_of_the_accumulated_average() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+        buffer.add_sample(0, 0, Color::new(0.25, 0.25, 0.25));
+        assert_eq!(buffer.variance_at(0, 0), f64::INFINITY);
+        buffer.add_sample(0, 0, Color::new(0.25, 0.25, 0.25));
+        assert_eq!(buffer.variance_at(0, 0), 0.0);
+        buffer.add_sample(0, 0, Color::new(1.0, 1.0, 1.0));
+        buffer.add_sample(0, 0, Color::new(1.0, 1.0, 1.0));
+        // the samples spread 0.375 around their mean of 0.625
+        assert!(equal_f64(buffer.variance_at(0, 0), 0.140625 / 3.0));
+    }
+
+    #[test]
+    fn test_accumulating_whole_passes() {
+        let mut buffer = AccumulationBuffer::new(2, 2);
+        let mut pass = Canvas::new(2, 2);
+        pass.write_pixel(1, 1, Color::new(2.0, 2.0, 2.0));
+        buffer.add_pass(&pass);
+        buffer.add_pass(&Canvas::new(2, 2));
+        assert_eq!(buffer.resolve().pixel_at(1, 1), Color::white());
+        assert_eq!(buffer.samples_at(0, 1), 2);
+        buffer.clear();
+        assert_eq!(buffer.samples_at(1, 1), 0);
+        assert_eq!(buffer.mean_at(1, 1), Color::black());
+    }
+
+    #[test]
+    fn test_displaying_does_not_change_the_accumulated_samples() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+        buffer.add_sample(0, 0, Color::new(4.0, 1.0, 0.0));
+        let clamp = |c: Color| {
+            Color::new(c.red.min(1.0), c.green.min(1.0), c.blue.min(1.0))
+        };
+        assert_eq!(
+            buffer.display(clamp).pixel_at(0, 0),
+            Color::new(1.0, 1.0, 0.0)
+        );
+        buffer.add_sample(0, 0, Color::new(0.0, 1.0, 0.0));
+        assert_eq!(buffer.mean_at(0, 0), Color::new(2.0, 1.0, 0.0));
+    }
+}
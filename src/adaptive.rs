@@ -0,0 +1,279 @@
+use crate::accumulation::AccumulationBuffer;
+use crate::camera::{Camera, Lens};
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use crate::world::World;
+
+/// Settings for an adaptive progressive render, which starts with the
+/// image split into tiles and, after every pass, splits the tiles that
+/// are still noisy into smaller ones and gives only those more samples.
+/// Smooth parts of the image, such as the background, stop early, so
+/// for the same number of samples the noisy parts end up much cleaner.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Adaptive {
+    /// The width and height of the tiles the image starts out split into.
+    pub tile_size: usize,
+    /// Noisy tiles are not split any smaller than this.
+    pub min_tile_size: usize,
+    /// The number of samples every pixel gets in the first pass. At least
+    /// two are taken, so that the noise can be measured.
+    pub initial_samples: usize,
+    /// The number of samples added to every pixel of a noisy tile in
+    /// each later pass.
+    pub samples_per_pass: usize,
+    /// The number of passes after the first one.
+    pub passes: usize,
+    /// A tile is done once the variance of the average of each of its
+    /// pixels is below this.
+    pub threshold: f64,
+}
+
+impl Default for Adaptive {
+    fn default() -> Adaptive {
+        Adaptive {
+            tile_size: 32,
+            min_tile_size: 4,
+            initial_samples: 4,
+            samples_per_pass: 4,
+            passes: 8,
+            threshold: 0.0001,
+        }
+    }
+}
+
+/// A rectangle of pixels that is sampled together.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Tile {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Tile {
+    /// Splits the tile into quarters, leaving out the empty ones of a tile
+    /// that is only one pixel wide or high.
+    fn split(self) -> Vec<Tile> {
+        let left_width = self.width.div_ceil(2);
+        let top_height = self.height.div_ceil(2);
+        let columns = [
+            (self.left, left_width),
+            (self.left + left_width, self.width - left_width),
+        ];
+        let rows = [
+            (self.top, top_height),
+            (self.top + top_height, self.height - top_height),
+        ];
+        let mut tiles = vec![];
+        for &(top, height) in rows.iter() {
+            for &(left, width) in columns.iter() {
+                if width > 0 && height > 0 {
+                    tiles.push(Tile {
+                        left,
+                        top,
+                        width,
+                        height,
+                    });
+                }
+            }
+        }
+        tiles
+    }
+
+    /// Returns the largest variance of any pixel in the tile.
+    fn noise(self, buffer: &AccumulationBuffer) -> f64 {
+        let mut noise = 0.0_f64;
+        for y in self.top..self.top + self.height {
+            for x in self.left..self.left + self.width {
+                noise = noise.max(buffer.variance_at(x, y));
+            }
+        }
+        noise
+    }
+}
+
+impl Camera {
+    /// Renders the shaded image progressively, spending more samples on
+    /// the noisy parts of the image as described by `adaptive`. Returns
+    /// the accumulated samples, which tell how many samples each pixel
+    /// got besides giving the image.
+    pub fn render_adaptive(
+        &self,
+        world: &World,
+        adaptive: Adaptive,
+    ) -> AccumulationBuffer {
+        let scene = Scene::compile(world, self);
+        let inverse_transform = self.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        let mut buffer = AccumulationBuffer::new(self.hsize, self.vsize);
+
+        let size = adaptive.tile_size.max(1);
+        let mut tiles = vec![];
+        for top in (0..self.vsize).step_by(size) {
+            for left in (0..self.hsize).step_by(size) {
+                tiles.push(Tile {
+                    left,
+                    top,
+                    width: size.min(self.hsize - left),
+                    height: size.min(self.vsize - top),
+                });
+            }
+        }
+
+        let samples = adaptive.initial_samples.max(2);
+        for &tile in tiles.iter() {
+            self.sample_tile(
+                &scene,
+                inverse_transform,
+                origin,
+                tile,
+                samples,
+                &mut buffer,
+            );
+        }
+        for _ in 0..adaptive.passes {
+            tiles = tiles
+                .into_iter()
+                .flat_map(|tile| {
+                    if tile.width.max(tile.height) > adaptive.min_tile_size {
+                        tile.split()
+                    } else {
+                        vec![tile]
+                    }
+                })
+                .filter(|tile| tile.noise(&buffer) > adaptive.threshold)
+                .collect();
+            if tiles.is_empty() {
+                break;
+            }
+            for &tile in tiles.iter() {
+                self.sample_tile(
+                    &scene,
+                    inverse_transform,
+                    origin,
+                    tile,
+                    adaptive.samples_per_pass,
+                    &mut buffer,
+                );
+            }
+        }
+        buffer
+    }
+
+    /// Adds `samples` samples to every pixel of the tile, each through a
+    /// random point in the pixel and, with a lens, a random point on the
+    /// aperture.
+    fn sample_tile(
+        &self,
+        scene: &Scene,
+        inverse_transform: Matrix4,
+        origin: Tuple,
+        tile: Tile,
+        samples: usize,
+        buffer: &mut AccumulationBuffer,
+    ) {
+        for y in tile.top..tile.top + tile.height {
+            for x in tile.left..tile.left + tile.width {
+                for _ in 0..samples {
+                    let sample = buffer.samples_at(x, y) as usize;
+                    let mut rng = Rng::for_pixel(self.seed, x, y, sample);
+                    let position = (
+                        (x as f64) + rng.next_f64(),
+                        (y as f64) + rng.next_f64(),
+                    );
+                    let ray = match &self.lens {
+                        Some(lens) => {
+                            let lens = Lens {
+                                samples: 1,
+                                ..*lens
+                            };
+                            self.lens_rays(
+                                &lens,
+                                inverse_transform,
+                                position,
+                                Sampler::Random,
+                                &mut rng,
+                            )[0]
+                        }
+                        None => Ray::new(
+                            origin,
+                            self.direction_for(
+                                inverse_transform,
+                                origin,
+                                position.0,
+                                position.1,
+                            ),
+                        ),
+                    };
+                    let color = scene.camera_color_at(ray, &mut rng);
+                    buffer.add_sample(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::adaptive::{Adaptive, Tile};
+    use crate::camera::Camera;
+    use crate::matrix::Matrix4;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_splitting_a_tile_into_quarters() {
+        let tile = Tile {
+            left: 2,
+            top: 4,
+            width: 5,
+            height: 1,
+        };
+        assert_eq!(
+            tile.split(),
+            vec![
+                Tile {
+                    left: 2,
+                    top: 4,
+                    width: 3,
+                    height: 1
+                },
+                Tile {
+                    left: 5,
+                    top: 4,
+                    width: 2,
+                    height: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_noisy_tiles_get_more_samples() {
+        let mut c = Camera::new(16, 16, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let mut adaptive = Adaptive::default();
+        adaptive.tile_size = 8;
+        adaptive.min_tile_size = 2;
+        let buffer = c.render_adaptive(&World::default(), adaptive);
+        // the empty background is black in every sample, so it stops
+        // after the first pass, while the edge of the sphere keeps going
+        assert_eq!(buffer.samples_at(0, 0), 4);
+        assert_eq!(buffer.variance_at(0, 0), 0.0);
+        let most = (0..16)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .map(|(x, y)| buffer.samples_at(x, y))
+            .max();
+        assert_eq!(most, Some(4 + 4 * 8));
+    }
+}
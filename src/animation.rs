@@ -0,0 +1,299 @@
+use crate::bounds::BoundingBox;
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::matrix::Matrix4;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use crate::world::World;
+use std::f64::consts::PI;
+
+/// Returns the camera transformation after orbiting the camera by the
+/// given angle around the vertical axis passing through `target`. The
+/// camera keeps its orientation relative to the target, so a camera
+/// looking at the target keeps looking at it.
+pub fn orbit_transform(
+    camera_transform: Matrix4,
+    target: Tuple,
+    radians: f64,
+) -> Matrix4 {
+    camera_transform
+        * Matrix4::translation(target.x, target.y, target.z)
+        * Matrix4::rotation_y(-radians)
+        * Matrix4::translation(-target.x, -target.y, -target.z)
+}
+
+/// The kind of curve used to interpolate between control points.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SplineKind {
+    /// A curve that passes through every control point.
+    CatmullRom,
+    /// A single Bézier curve that passes through the first and last
+    /// control points and is pulled towards the others.
+    Bezier,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Spline {
+    pub kind: SplineKind,
+    pub points: Vec<Tuple>,
+}
+
+impl Spline {
+    pub fn catmull_rom(points: Vec<Tuple>) -> Spline {
+        assert!(!points.is_empty());
+        Spline {
+            kind: SplineKind::CatmullRom,
+            points,
+        }
+    }
+
+    pub fn bezier(points: Vec<Tuple>) -> Spline {
+        assert!(!points.is_empty());
+        Spline {
+            kind: SplineKind::Bezier,
+            points,
+        }
+    }
+
+    /// Returns the point on the curve at `t`, where 0 is the start of
+    /// the curve and 1 is the end.
+    pub fn point_at(&self, t: f64) -> Tuple {
+        let t = t.clamp(0.0, 1.0);
+        match self.kind {
+            SplineKind::CatmullRom => self.catmull_rom_at(t),
+            SplineKind::Bezier => self.bezier_at(t),
+        }
+    }
+
+    fn catmull_rom_at(&self, t: f64) -> Tuple {
+        let last = self.points.len() - 1;
+        if last == 0 {
+            return self.points[0];
+        }
+        let position = t * (last as f64);
+        let segment = (position.floor() as usize).min(last - 1);
+        let t = position - (segment as f64);
+        // the curve is extended past its ends by repeating the end points
+        let p0 = self.points[segment.saturating_sub(1)];
+        let p1 = self.points[segment];
+        let p2 = self.points[segment + 1];
+        let p3 = self.points[(segment + 2).min(last)];
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (p0 * (-t3 + 2.0 * t2 - t)
+            + p1 * (3.0 * t3 - 5.0 * t2 + 2.0)
+            + p2 * (-3.0 * t3 + 4.0 * t2 + t)
+            + p3 * (t3 - t2))
+            * 0.5
+    }
+
+    fn bezier_at(&self, t: f64) -> Tuple {
+        // de Casteljau's algorithm
+        let mut points = self.points.clone();
+        while points.len() > 1 {
+            points = points
+                .windows(2)
+                .map(|pair| pair[0] * (1.0 - t) + pair[1] * t)
+                .collect();
+        }
+        points[0]
+    }
+}
+
+/// A camera animation in which both the camera position and the point
+/// it looks at follow a spline.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CameraPath {
+    pub positions: Spline,
+    pub targets: Spline,
+    pub up: Tuple,
+}
+
+impl CameraPath {
+    pub fn new(positions: Spline, targets: Spline, up: Tuple) -> CameraPath {
+        CameraPath {
+            positions,
+            targets,
+            up,
+        }
+    }
+
+    /// Returns the camera transformation at `t`, where 0 is the start
+    /// of the path and 1 is the end.
+    pub fn transform_at(&self, t: f64) -> Matrix4 {
+        Matrix4::view_transform(
+            self.positions.point_at(t),
+            self.targets.point_at(t),
+            self.up,
+        )
+    }
+}
+
+/// Renders one frame per camera transformation returned by
+/// `transform_at` for each frame index, compiling the world only once.
+fn render_frames<F>(
+    world: &World,
+    camera: &Camera,
+    frames: usize,
+    transform_at: F,
+) -> Vec<Canvas>
+where
+    F: Fn(usize) -> Matrix4,
+{
+    let scene = Scene::new(world);
+    (0..frames)
+        .map(|frame| {
+            let mut frame_camera = *camera;
+            frame_camera.transform = transform_at(frame);
+            frame_camera.render_scene(&scene.for_camera(&frame_camera))
+        })
+        .collect()
+}
+
+/// Renders a sequence of frames in which the camera makes one full
+/// turn around the world, starting from its current position. The camera
+/// orbits the center of the box around the world's finite objects, or
+/// the origin when there are none.
+pub fn render_turntable(
+    world: &World,
+    camera: &Camera,
+    frames: usize,
+) -> Vec<Canvas> {
+    let mut bounds = BoundingBox::empty();
+    for object in world.objects.iter() {
+        let object_bounds = BoundingBox::from(object.bounds());
+        if !object_bounds.is_unbounded() {
+            bounds.add_box(object_bounds);
+        }
+    }
+    let target = if bounds.is_empty() {
+        Tuple::point(0.0, 0.0, 0.0)
+    } else {
+        bounds.centroid()
+    };
+    render_frames(world, camera, frames, |frame| {
+        let angle = 2.0 * PI * (frame as f64) / (frames as f64);
+        orbit_transform(camera.transform, target, angle)
+    })
+}
+
+/// Renders a sequence of frames with the camera following `path` from
+/// start to end. The first and last frames lie exactly on the ends of
+/// the path.
+pub fn render_camera_path(
+    world: &World,
+    camera: &Camera,
+    path: &CameraPath,
+    frames: usize,
+) -> Vec<Canvas> {
+    render_frames(world, camera, frames, |frame| {
+        let t = if frames > 1 {
+            (frame as f64) / ((frames - 1) as f64)
+        } else {
+            0.0
+        };
+        path.transform_at(t)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::{
+        orbit_transform, render_camera_path, render_turntable, CameraPath,
+        Spline,
+    };
+    use crate::camera::Camera;
+    use crate::matrix::Matrix4;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_orbiting_a_camera_a_quarter_turn() {
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        let transform = Matrix4::view_transform(from, to, up);
+        let orbited = orbit_transform(transform, to, FRAC_PI_2);
+        let origin = orbited.inverse() * Tuple::point(0.0, 0.0, 0.0);
+        assert_eq!(origin, Tuple::point(-5.0, 0.0, 0.0));
+        let forward = orbited.inverse() * Tuple::vector(0.0, 0.0, -1.0);
+        assert_eq!(forward, Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rendering_a_turntable() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let target = Tuple::point(0.0, 0.0, 0.0);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            target,
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let frames = render_turntable(&w, &c, 4);
+        assert_eq!(frames.len(), 4);
+        let first = c.render(&World::default());
+        assert_eq!(frames[0].pixel_at(5, 5), first.pixel_at(5, 5));
+        assert_ne!(frames[2].pixel_at(5, 5), frames[0].pixel_at(5, 5));
+    }
+
+    #[test]
+    fn test_a_catmull_rom_spline_passes_through_its_control_points() {
+        let s = Spline::catmull_rom(vec![
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(1.0, 2.0, 0.0),
+            Tuple::point(2.0, 0.0, 0.0),
+        ]);
+        assert_eq!(s.point_at(0.0), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(s.point_at(0.5), Tuple::point(1.0, 2.0, 0.0));
+        assert_eq!(s.point_at(1.0), Tuple::point(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_a_catmull_rom_spline_through_collinear_points_is_a_line() {
+        let s = Spline::catmull_rom(vec![
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+            Tuple::point(2.0, 0.0, 0.0),
+            Tuple::point(3.0, 0.0, 0.0),
+        ]);
+        assert_eq!(s.point_at(0.5), Tuple::point(1.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_a_bezier_spline_interpolates_its_end_points() {
+        let s = Spline::bezier(vec![
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(1.0, 2.0, 0.0),
+            Tuple::point(2.0, 0.0, 0.0),
+        ]);
+        assert_eq!(s.point_at(0.0), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(s.point_at(0.5), Tuple::point(1.0, 1.0, 0.0));
+        assert_eq!(s.point_at(1.0), Tuple::point(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rendering_along_a_camera_path() {
+        let w = World::default();
+        let c = Camera::new(11, 11, FRAC_PI_2);
+        let path = CameraPath::new(
+            Spline::catmull_rom(vec![
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, -10.0),
+            ]),
+            Spline::catmull_rom(vec![Tuple::point(0.0, 0.0, 0.0)]),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let frames = render_camera_path(&w, &c, &path, 3);
+        assert_eq!(frames.len(), 3);
+        let mut first = c;
+        first.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let expected = first.render(&World::default());
+        assert_eq!(frames[0].pixel_at(5, 5), expected.pixel_at(5, 5));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+
+/// An arbitrary output value buffer: per-pixel data such as depth or
+/// object IDs that is rendered alongside, or instead of, the final
+/// image for compositing and debugging.
+#[derive(Clone, Debug)]
+pub struct Aov<T> {
+    pub width: usize,
+    pub height: usize,
+    values: Vec<T>,
+}
+
+impl<T: Copy> Aov<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Aov<T> {
+        Aov {
+            width,
+            height,
+            values: vec![value; width * height],
+        }
+    }
+
+    pub fn write_value(&mut self, x: usize, y: usize, value: T) {
+        self.values[y * self.width + x] = value;
+    }
+
+    pub fn value_at(&self, x: usize, y: usize) -> T {
+        self.values[y * self.width + x]
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Converts the buffer into an image by mapping every value to a
+    /// color.
+    pub fn to_canvas<F>(&self, to_color: F) -> Canvas
+    where
+        F: Fn(T) -> Color,
+    {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                canvas.write_pixel(x, y, to_color(self.value_at(x, y)));
+            }
+        }
+        canvas
+    }
+}
+
+/// How depth values are mapped to gray levels when exporting a depth
+/// map. Pixels where nothing was hit are always white.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DepthMode {
+    /// Depth is divided by the given distance, so everything at or
+    /// beyond that distance is white.
+    Linear(f64),
+    /// Depth is rescaled so that the nearest visible point is black and
+    /// the farthest one is white.
+    Normalized,
+}
+
+/// Converts a depth buffer into a grayscale image where darker pixels
+/// are closer to the camera.
+pub fn depth_to_canvas(depth: &Aov<Option<f64>>, mode: DepthMode) -> Canvas {
+    let (near, far) = match mode {
+        DepthMode::Linear(max_depth) => (0.0, max_depth),
+        DepthMode::Normalized => depth
+            .values()
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(near, far), &d| {
+                (near.min(d), far.max(d))
+            }),
+    };
+    let range = far - near;
+    depth.to_canvas(|d| match d {
+        Some(d) if range > 0.0 => {
+            let gray = ((d - near) / range).clamp(0.0, 1.0);
+            Color::new(gray, gray, gray)
+        }
+        Some(_) => Color::black(),
+        None => Color::white(),
+    })
+}
+
+/// Sums the values in every square tile of `tile_size` pixels, giving
+/// each pixel the total of its tile. Applied to per-pixel render times,
+/// this shows how long every tile of a tiled render would take.
+pub fn tile_totals(values: &Aov<f64>, tile_size: usize) -> Aov<f64> {
+    assert!(tile_size > 0);
+    let tiles_x = values.width.div_ceil(tile_size);
+    let tiles_y = values.height.div_ceil(tile_size);
+    let mut totals = vec![0.0; tiles_x * tiles_y];
+    for y in 0..values.height {
+        for x in 0..values.width {
+            totals[(y / tile_size) * tiles_x + x / tile_size] +=
+                values.value_at(x, y);
+        }
+    }
+    let mut tiled = Aov::new(values.width, values.height, 0.0);
+    for y in 0..values.height {
+        for x in 0..values.width {
+            let total = totals[(y / tile_size) * tiles_x + x / tile_size];
+            tiled.write_value(x, y, total);
+        }
+    }
+    tiled
+}
+
+/// Converts a buffer of non-negative values, such as render times, into
+/// a heatmap running from blue for zero through green to red for the
+/// largest value.
+pub fn heatmap_to_canvas(values: &Aov<f64>) -> Canvas {
+    let max = values.values().iter().cloned().fold(0.0, f64::max);
+    values.to_canvas(|value| {
+        let t = if max > 0.0 { value / max } else { 0.0 };
+        if t < 0.5 {
+            Color::new(0.0, t * 2.0, 1.0 - t * 2.0)
+        } else {
+            Color::new(t * 2.0 - 1.0, 2.0 - t * 2.0, 0.0)
+        }
+    })
+}
+
+/// Returns a color for an object ID that is stable across renders and
+/// clearly different from the colors of nearby IDs.
+pub fn id_to_color(id: usize) -> Color {
+    // scramble the bits so consecutive IDs get unrelated hues
+    let mut hash = (id as u32).wrapping_add(1).wrapping_mul(0x9e37_79b9);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    let channel = |shift: u32| ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+/// Converts an object ID buffer into an image where every object has
+/// its own flat color and the background is black.
+pub fn object_ids_to_canvas(ids: &Aov<Option<usize>>) -> Canvas {
+    ids.to_canvas(|id| id.map_or(Color::black(), id_to_color))
+}
+
+/// Returns a matte that is white wherever the given object is visible
+/// and black everywhere else.
+pub fn object_mask(ids: &Aov<Option<usize>>, id: usize) -> Canvas {
+    ids.to_canvas(|visible| {
+        if visible == Some(id) {
+            Color::white()
+        } else {
+            Color::black()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aov::{
+        depth_to_canvas, heatmap_to_canvas, id_to_color, object_ids_to_canvas,
+        object_mask, tile_totals, Aov, DepthMode,
+    };
+    use crate::color::Color;
+
+    #[test]
+    fn test_writing_values_to_an_aov() {
+        let mut aov = Aov::new(4, 3, 0usize);
+        aov.write_value(2, 1, 7);
+        assert_eq!(aov.value_at(2, 1), 7);
+        assert_eq!(aov.value_at(1, 2), 0);
+        assert_eq!(aov.values().len(), 12);
+    }
+
+    #[test]
+    fn test_exporting_linear_depth() {
+        let mut depth = Aov::new(3, 1, None);
+        depth.write_value(0, 0, Some(5.0));
+        depth.write_value(1, 0, Some(20.0));
+        let image = depth_to_canvas(&depth, DepthMode::Linear(10.0));
+        assert_eq!(image.pixel_at(0, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(image.pixel_at(1, 0), Color::white());
+        assert_eq!(image.pixel_at(2, 0), Color::white());
+    }
+
+    #[test]
+    fn test_exporting_normalized_depth() {
+        let mut depth = Aov::new(3, 1, None);
+        depth.write_value(0, 0, Some(4.0));
+        depth.write_value(1, 0, Some(5.0));
+        depth.write_value(2, 0, Some(6.0));
+        let image = depth_to_canvas(&depth, DepthMode::Normalized);
+        assert_eq!(image.pixel_at(0, 0), Color::black());
+        assert_eq!(image.pixel_at(1, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(image.pixel_at(2, 0), Color::white());
+    }
+
+    #[test]
+    fn test_object_ids_have_stable_distinct_colors() {
+        assert_eq!(id_to_color(3), id_to_color(3));
+        assert_ne!(id_to_color(0), id_to_color(1));
+        assert_ne!(id_to_color(0), Color::black());
+    }
+
+    #[test]
+    fn test_exporting_object_ids_and_masks() {
+        let mut ids = Aov::new(3, 1, None);
+        ids.write_value(0, 0, Some(0));
+        ids.write_value(1, 0, Some(1));
+        let image = object_ids_to_canvas(&ids);
+        assert_eq!(image.pixel_at(0, 0), id_to_color(0));
+        assert_eq!(image.pixel_at(1, 0), id_to_color(1));
+        assert_eq!(image.pixel_at(2, 0), Color::black());
+        let mask = object_mask(&ids, 1);
+        assert_eq!(mask.pixel_at(0, 0), Color::black());
+        assert_eq!(mask.pixel_at(1, 0), Color::white());
+        assert_eq!(mask.pixel_at(2, 0), Color::black());
+    }
+
+    #[test]
+    fn test_summing_values_per_tile() {
+        let mut times = Aov::new(3, 3, 1.0);
+        times.write_value(2, 2, 5.0);
+        let tiles = tile_totals(&times, 2);
+        assert_eq!(tiles.value_at(0, 0), 4.0);
+        assert_eq!(tiles.value_at(1, 1), 4.0);
+        assert_eq!(tiles.value_at(2, 0), 2.0);
+        assert_eq!(tiles.value_at(0, 2), 2.0);
+        assert_eq!(tiles.value_at(2, 2), 5.0);
+    }
+
+    #[test]
+    fn test_exporting_a_heatmap() {
+        let mut times = Aov::new(3, 1, 0.0);
+        times.write_value(1, 0, 1.0);
+        times.write_value(2, 0, 2.0);
+        let image = heatmap_to_canvas(&times);
+        assert_eq!(image.pixel_at(0, 0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(image.pixel_at(1, 0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(image.pixel_at(2, 0), Color::new(1.0, 0.0, 0.0));
+    }
+}
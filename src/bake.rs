@@ -0,0 +1,155 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::intersection::OVER_POINT_OFFSET;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use crate::world::World;
+
+/// What is baked into a texture.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bake {
+    /// How open the surface is to its surroundings, from black where
+    /// every direction is blocked within `distance` to white where none
+    /// is. `samples` directions are tested at every texel.
+    AmbientOcclusion { samples: usize, distance: f64 },
+    /// The light arriving at the surface straight from the world's lights,
+    /// including shadows, before the material tints it.
+    Irradiance,
+}
+
+/// Bakes the lighting on the surface of `world.objects[index]` into a
+/// texture laid out by the shape's texture coordinates, with u running
+/// left to right and v bottom to top, so that a static scene can later be
+/// shaded by looking the lighting up, or be exported to another renderer.
+/// The texture is black for shapes that can't turn texture coordinates
+/// back into points on their surface.
+pub fn bake_texture(
+    world: &World,
+    index: usize,
+    width: usize,
+    height: usize,
+    bake: Bake,
+    seed: u64,
+) -> Canvas {
+    let scene = Scene::new(world);
+    let object = &world.objects[index];
+    let mut texture = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let u = ((x as f64) + 0.5) / (width as f64);
+            let v = 1.0 - ((y as f64) + 0.5) / (height as f64);
+            let local_point = match object.local_point_at_uv(u, v) {
+                Some(point) => point,
+                None => continue,
+            };
+            let point = object.transform() * local_point;
+            let normal = object.normal_at(point);
+            let over_point = point + normal * OVER_POINT_OFFSET;
+            let mut rng = Rng::for_pixel(seed, x, y, 0);
+            let color = match bake {
+                Bake::AmbientOcclusion { samples, distance } => {
+                    let open = ambient_occlusion(
+                        &scene, over_point, normal, samples, distance, &mut rng,
+                    );
+                    Color::white() * open
+                }
+                Bake::Irradiance => {
+                    world.lights.iter().fold(Color::black(), |sum, light| {
+                        let light_vector = (light.position - point).normalize();
+                        let cosine = light_vector.dot(normal).max(0.0);
+                        let visible = scene
+                            .light_intensity_at(over_point, light, &mut rng);
+                        let strength = light.strength_at(point);
+                        sum + light.intensity * (cosine * visible * strength)
+                    })
+                }
+            };
+            texture.write_pixel(x, y, color);
+        }
+    }
+    texture
+}
+
+/// Returns the fraction of cosine-weighted directions above the surface
+/// that reach at least `distance` without hitting anything.
+fn ambient_occlusion(
+    scene: &Scene,
+    point: Tuple,
+    normal: Tuple,
+    samples: usize,
+    distance: f64,
+    rng: &mut Rng,
+) -> f64 {
+    let directions =
+        scene
+            .world()
+            .sampler
+            .cosine_directions(normal, samples.max(1), rng);
+    let count = directions.len() as f64;
+    let open = directions
+        .into_iter()
+        .filter(|&direction| {
+            !scene.hit_any(Ray::new(point, direction), distance)
+        })
+        .count();
+    (open as f64) / count
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::bake::{bake_texture, Bake};
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::cube::Cube;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::world::World;
+
+    fn sphere_on_a_floor() -> World {
+        let mut w = World::default();
+        let mut floor = Plane::default();
+        floor.transform = Matrix4::translation(0.0, -1.0, 0.0);
+        w.objects = vec![Box::new(Sphere::default()), Box::new(floor)];
+        w
+    }
+
+    #[test]
+    fn test_baking_ambient_occlusion() {
+        let ao = Bake::AmbientOcclusion {
+            samples: 32,
+            distance: 10.0,
+        };
+        let mut w = sphere_on_a_floor();
+        w.objects.truncate(1);
+        let open = bake_texture(&w, 0, 8, 4, ao, 0);
+        assert_eq!(open.pixel_at(3, 3), Color::white());
+        let w = sphere_on_a_floor();
+        let texture = bake_texture(&w, 0, 8, 4, ao, 0);
+        // the bottom of the sphere is close to the floor
+        assert_eq!(texture.pixel_at(3, 0), Color::white());
+        assert!(texture.pixel_at(3, 3).red < 0.5);
+    }
+
+    #[test]
+    fn test_baking_irradiance() {
+        let w = sphere_on_a_floor();
+        let texture = bake_texture(&w, 0, 8, 4, Bake::Irradiance, 0);
+        // the light is above and in front of the sphere
+        assert!(texture.pixel_at(1, 0).red > 0.5);
+        assert_eq!(texture.pixel_at(3, 3), Color::black());
+        let texture = bake_texture(&w, 1, 8, 4, Bake::Irradiance, 0);
+        assert_eq!(texture.pixel_at(0, 0), Color::black());
+    }
+
+    #[test]
+    fn test_shapes_without_inverse_texture_coordinates_bake_black() {
+        let mut w = World::default();
+        w.objects = vec![Box::new(Cube::default())];
+        let texture = bake_texture(&w, 0, 2, 2, Bake::Irradiance, 0);
+        assert_eq!(texture, Canvas::new(2, 2));
+    }
+}
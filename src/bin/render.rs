@@ -0,0 +1,402 @@
+extern crate ray_tracer_challenge;
+
+use ray_tracer_challenge::camera::{Camera, Quality};
+use ray_tracer_challenge::canvas::Canvas;
+use ray_tracer_challenge::compare::compare;
+use ray_tracer_challenge::lut::Lut;
+use ray_tracer_challenge::metadata::RenderInfo;
+use ray_tracer_challenge::post::{apply_effects, PostEffect};
+use ray_tracer_challenge::scene::Scene;
+use ray_tracer_challenge::scene_file::SceneFile;
+use ray_tracer_challenge::scenes;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const USAGE: &str = "usage:
+    render <scene.yml> [--out <image.png>] [--watch] [--progress json]
+                       [--grade <table.cube>]
+    render check <scene.yml>
+    render bench [--scene <name>] [--size <pixels>] [--json]
+    render diff <a.png> <b.png> [--out <diff.png>] [--threshold <t>]";
+
+/// How often watched files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The exit code for usage and file errors. Successful commands exit
+/// with 0, and `diff` exits with 1 when the images differ.
+const ERROR: i32 = 2;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("check") => check(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some(_) => render(&args),
+        None => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(ERROR);
+        }
+    }
+}
+
+/// Renders a scene file to an image, by default next to the scene file
+/// with the same name. With `--watch`, renders at draft quality again
+/// whenever the scene file or the files it includes change. With
+/// `--progress json`, the render's progress is printed as JSON lines,
+/// and everything else goes to stderr. With `--grade`, the sRGB image is
+/// graded with a `.cube` lookup table before it is written.
+fn render(args: &[String]) -> Result<i32, String> {
+    let args =
+        parse_args(args, &["--out", "--progress", "--grade"], &["--watch"])?;
+    if args.positional.len() != 1 {
+        return Err(USAGE.to_string());
+    }
+    let scene_path = Path::new(&args.positional[0]);
+    let out = match args.values.get("--out") {
+        Some(out) => PathBuf::from(out),
+        None => scene_path.with_extension("png"),
+    };
+    let json = match args.values.get("--progress").map(String::as_str) {
+        Some("json") => true,
+        Some(format) => {
+            return Err(format!("unknown progress format `{}`", format))
+        }
+        None => false,
+    };
+    let mut effects = Vec::new();
+    if let Some(table) = args.values.get("--grade") {
+        let lut = Lut::load(table)
+            .map_err(|error| format!("couldn't load {}: {}", table, error))?;
+        effects.push(PostEffect::Grade(lut));
+    }
+    if args.flags.contains("--watch") {
+        watch(scene_path, &out, json, &effects)
+    } else {
+        render_scene(&load_scene(scene_path)?, &out, json, &effects)?;
+        Ok(0)
+    }
+}
+
+/// Polls the scene file and its includes for changes, rendering the
+/// scene whenever they change, until the process is stopped. Errors are
+/// reported and the files kept watching, so that they can be fixed.
+fn watch(
+    scene_path: &Path,
+    out: &Path,
+    json: bool,
+    effects: &[PostEffect],
+) -> Result<i32, String> {
+    let mut files = vec![scene_path.to_path_buf()];
+    let mut rendered = None;
+    let message = format!("watching {} for changes", scene_path.display());
+    print_message(&message, json);
+    loop {
+        // taken before loading, so that edits saved while the scene loads
+        // or renders are caught by the next check
+        let stamps = modification_times(&files);
+        if rendered.as_ref() != Some(&stamps) {
+            match load_scene(scene_path) {
+                Ok(mut scene) => {
+                    let mut loaded = vec![scene_path.to_path_buf()];
+                    loaded.extend(scene.includes.iter().cloned());
+                    rendered = Some(
+                        loaded
+                            .iter()
+                            .map(|file| {
+                                match files.iter().position(|f| f == file) {
+                                    Some(i) => stamps[i],
+                                    None => modification_time(file),
+                                }
+                            })
+                            .collect(),
+                    );
+                    files = loaded;
+                    scene.camera.quality = Quality::Draft;
+                    if let Err(message) =
+                        render_scene(&scene, out, json, effects)
+                    {
+                        eprintln!("{}", message);
+                    }
+                }
+                Err(message) => {
+                    eprintln!("{}", message);
+                    rendered = Some(stamps);
+                }
+            }
+        }
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Returns when each file was last modified, or `None` for files that
+/// can't be read.
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|file| modification_time(file)).collect()
+}
+
+fn modification_time(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Loads a scene file, printing any warnings.
+fn load_scene(scene_path: &Path) -> Result<SceneFile, String> {
+    let scene = SceneFile::load(scene_path).map_err(|error| {
+        format!("couldn't load {}: {}", scene_path.display(), error)
+    })?;
+    for warning in scene.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(scene)
+}
+
+/// Renders a loaded scene to an image, applying `effects` in order. With
+/// `json`, progress events are printed as the scene renders.
+fn render_scene(
+    scene: &SceneFile,
+    out: &Path,
+    json: bool,
+    effects: &[PostEffect],
+) -> Result<(), String> {
+    let start = Instant::now();
+    let canvas = if json {
+        scene
+            .camera
+            .render_parallel_with_progress(&scene.world, |event| {
+                println!("{}", event.to_json())
+            })
+    } else {
+        scene.camera.render_parallel(&scene.world)
+    };
+    let info = RenderInfo::new(&scene.world, &scene.camera, start.elapsed());
+    apply_effects(&canvas, effects)
+        .to_srgb()
+        .save_with_info(out, &info)
+        .map_err(|error| {
+            format!("couldn't write {}: {}", out.display(), error)
+        })?;
+    let message = format!(
+        "wrote {} in {:.3}s",
+        out.display(),
+        info.duration.as_secs_f64()
+    );
+    print_message(&message, json);
+    Ok(())
+}
+
+/// Prints a message for people, on stderr when stdout is kept for JSON.
+fn print_message(message: &str, json: bool) {
+    if json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Loads a scene file without rendering it, printing what it contains
+/// and any warnings.
+fn check(args: &[String]) -> Result<i32, String> {
+    let args = parse_args(args, &[], &[])?;
+    let paths = &args.positional;
+    if paths.len() != 1 {
+        return Err(USAGE.to_string());
+    }
+    let start = Instant::now();
+    let scene = SceneFile::load(&paths[0])
+        .map_err(|error| format!("couldn't load {}: {}", paths[0], error))?;
+    let elapsed = start.elapsed();
+    let camera = &scene.camera;
+    println!(
+        "camera: {}x{}, field of view {}",
+        camera.hsize, camera.vsize, camera.field_of_view
+    );
+    println!("{}", scene.world.stats());
+    for include in scene.includes.iter() {
+        println!("included {}", include.display());
+    }
+    for warning in scene.warnings.iter() {
+        println!("warning: {}", warning);
+    }
+    println!("loaded in {:.3}s", elapsed.as_secs_f64());
+    Ok(0)
+}
+
+/// Renders a built-in scene, printing how long each stage took and how
+/// many camera rays were traced per second, or with `--json`, printing
+/// the same as one JSON object so that runs can be tracked over time.
+/// `--size` sets the width of the image, keeping the scene's aspect
+/// ratio.
+fn bench(args: &[String]) -> Result<i32, String> {
+    let args = parse_args(args, &["--scene", "--size"], &["--json"])?;
+    if !args.positional.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let name = args.values.get("--scene").map_or("cornell", String::as_str);
+    let size = match args.values.get("--size") {
+        Some(value) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| format!("invalid size `{}`", value))?,
+        None => 256,
+    };
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let start = Instant::now();
+    let (world, scene_camera) = scenes::by_name(name).ok_or_else(|| {
+        format!(
+            "unknown scene `{}`, expected one of: {}",
+            name,
+            scenes::NAMES.join(", ")
+        )
+    })?;
+    let height = (size * scene_camera.vsize / scene_camera.hsize).max(1);
+    let mut camera = Camera::new(size, height, scene_camera.field_of_view);
+    camera.transform = scene_camera.transform;
+    let build = start.elapsed();
+    let start = Instant::now();
+    let scene = Scene::compile(&world, &camera);
+    let compile = start.elapsed();
+    let start = Instant::now();
+    let canvas = camera.render_scene_parallel(&scene, threads);
+    let render = start.elapsed();
+    let start = Instant::now();
+    canvas
+        .to_srgb()
+        .to_png()
+        .map_err(|error| format!("couldn't encode the image: {}", error))?;
+    let encode = start.elapsed();
+
+    let info = RenderInfo::new(&world, &camera, render);
+    let rays = info.width * info.height * info.samples;
+    let rays_per_second = rays as f64 / render.as_secs_f64().max(1e-9);
+    let stages = [
+        ("build", build),
+        ("compile", compile),
+        ("render", render),
+        ("encode", encode),
+    ];
+    if args.flags.contains("--json") {
+        let stages: Vec<String> = stages
+            .iter()
+            .map(|(stage, time)| {
+                format!("\"{}\": {:.6}", stage, time.as_secs_f64())
+            })
+            .collect();
+        println!(
+            "{{\"scene\": \"{}\", \"scene-hash\": \"{:016x}\", \
+             \"width\": {}, \"height\": {}, \"samples\": {}, \
+             \"threads\": {}, \"rays\": {}, \"rays-per-second\": {:.0}, \
+             \"stages\": {{{}}}}}",
+            name,
+            info.scene_hash,
+            info.width,
+            info.height,
+            info.samples,
+            threads,
+            rays,
+            rays_per_second,
+            stages.join(", ")
+        );
+    } else {
+        println!(
+            "{}: {}x{}, {} samples per pixel, {} threads",
+            name, info.width, info.height, info.samples, threads
+        );
+        for (stage, time) in stages.iter() {
+            println!("{:>8}: {:.3}s", stage, time.as_secs_f64());
+        }
+        println!("{:.0} camera rays/s", rays_per_second);
+    }
+    Ok(0)
+}
+
+/// Compares two images, printing how much they differ and optionally
+/// writing an image of the mismatched pixels.
+fn diff(args: &[String]) -> Result<i32, String> {
+    let args = parse_args(args, &["--out", "--threshold"], &[])?;
+    let (paths, options) = (&args.positional, &args.values);
+    if paths.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+    let threshold = match options.get("--threshold") {
+        Some(value) => value
+            .parse::<f64>()
+            .map_err(|_| format!("invalid threshold `{}`", value))?,
+        None => 0.01,
+    };
+    let a = read_image(&paths[0])?;
+    let b = read_image(&paths[1])?;
+    let comparison = compare(&a, &b, threshold)
+        .map_err(|error| format!("can't compare the images: {}", error))?;
+    println!(
+        "{} of {} pixels differ by more than {} (largest difference {})",
+        comparison.mismatched_pixels,
+        a.width * a.height,
+        threshold,
+        comparison.max_difference
+    );
+    if let Some(out) = options.get("--out") {
+        write_image(out, &comparison.difference)?;
+    }
+    Ok(if comparison.matches() { 0 } else { 1 })
+}
+
+/// The arguments of a command.
+struct Args {
+    positional: Vec<String>,
+    /// The values of options such as `--out image.png`.
+    values: HashMap<String, String>,
+    /// The options without values, such as `--watch`, that were given.
+    flags: HashSet<String>,
+}
+
+/// Splits the arguments into positional ones, the values of the given
+/// options, each of which takes one value, and the given flags.
+fn parse_args(
+    args: &[String],
+    options: &[&str],
+    flags: &[&str],
+) -> Result<Args, String> {
+    let mut parsed = Args {
+        positional: vec![],
+        values: HashMap::new(),
+        flags: HashSet::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if options.contains(&arg.as_str()) {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", arg))?;
+            parsed.values.insert(arg.clone(), value.clone());
+        } else if flags.contains(&arg.as_str()) {
+            parsed.flags.insert(arg.clone());
+        } else if arg.starts_with("--") {
+            return Err(format!("unknown option {}\n{}", arg, USAGE));
+        } else {
+            parsed.positional.push(arg.clone());
+        }
+    }
+    Ok(parsed)
+}
+
+fn read_image(path: &str) -> Result<Canvas, String> {
+    Canvas::load(path)
+        .map_err(|error| format!("couldn't read {}: {}", path, error))
+}
+
+fn write_image(path: &str, canvas: &Canvas) -> Result<(), String> {
+    canvas
+        .save(path)
+        .map_err(|error| format!("couldn't write {}: {}", path, error))
+}
@@ -0,0 +1,293 @@
+use crate::matrix::Matrix4;
+use crate::tuple::Tuple;
+
+/// An axis-aligned box, given by its minimum and maximum corners. A box
+/// whose minimum is above its maximum on some axis is empty, and boxes
+/// around infinite shapes, such as planes, have infinite corners.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple, max: Tuple) -> BoundingBox {
+        BoundingBox { min, max }
+    }
+
+    /// A box containing nothing, which grows to fit whatever is added to
+    /// it.
+    pub fn empty() -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Tuple::point(
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+        )
+    }
+
+    /// A box containing everything.
+    pub fn infinite() -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| component(self.min, axis) > component(self.max, axis))
+    }
+
+    /// Returns whether the box is infinite in some direction.
+    pub fn is_unbounded(&self) -> bool {
+        !self.is_empty()
+            && (0..3).any(|axis| {
+                !component(self.min, axis).is_finite()
+                    || !component(self.max, axis).is_finite()
+            })
+    }
+
+    /// Grows the box to contain the point.
+    pub fn add_point(&mut self, point: Tuple) {
+        self.min = Tuple::point(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Tuple::point(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    /// Grows the box to contain another box.
+    pub fn add_box(&mut self, other: BoundingBox) {
+        if !other.is_empty() {
+            self.add_point(other.min);
+            self.add_point(other.max);
+        }
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn merge(mut self, other: BoundingBox) -> BoundingBox {
+        self.add_box(other);
+        self
+    }
+
+    pub fn contains_point(&self, point: Tuple) -> bool {
+        (0..3).all(|axis| {
+            let value = component(point, axis);
+            component(self.min, axis) <= value
+                && value <= component(self.max, axis)
+        })
+    }
+
+    /// Returns whether the other box lies entirely inside this one. The
+    /// empty box is inside every box.
+    pub fn contains_box(&self, other: BoundingBox) -> bool {
+        other.is_empty()
+            || (self.contains_point(other.min)
+                && self.contains_point(other.max))
+    }
+
+    /// Returns the box around this box after it is transformed, such as
+    /// from the object space of a shape to world space. Boxes that are
+    /// infinite in any direction become infinite in every direction, and
+    /// empty boxes stay empty.
+    pub fn transform(&self, transform: Matrix4) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        if self.is_unbounded() {
+            // transforming the corners would multiply infinities by zero
+            return BoundingBox::infinite();
+        }
+        let mut result = BoundingBox::empty();
+        for &x in [self.min.x, self.max.x].iter() {
+            for &y in [self.min.y, self.max.y].iter() {
+                for &z in [self.min.z, self.max.z].iter() {
+                    result.add_point(transform * Tuple::point(x, y, z));
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the center of the box.
+    pub fn centroid(&self) -> Tuple {
+        Tuple::point(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    /// Returns the axis along which the box is longest, as 0, 1 or 2 for
+    /// x, y and z. Ties go to the earlier axis.
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Cuts the box in half across its longest axis.
+    pub fn split(&self) -> (BoundingBox, BoundingBox) {
+        let axis = self.longest_axis();
+        let middle =
+            (component(self.min, axis) + component(self.max, axis)) / 2.0;
+        let mut left_max = self.max;
+        let mut right_min = self.min;
+        match axis {
+            0 => {
+                left_max.x = middle;
+                right_min.x = middle;
+            }
+            1 => {
+                left_max.y = middle;
+                right_min.y = middle;
+            }
+            _ => {
+                left_max.z = middle;
+                right_min.z = middle;
+            }
+        }
+        (
+            BoundingBox::new(self.min, left_max),
+            BoundingBox::new(right_min, self.max),
+        )
+    }
+}
+
+/// Returns one of the x, y and z components of a tuple, by number.
+pub(crate) fn component(tuple: Tuple, axis: usize) -> f64 {
+    match axis {
+        0 => tuple.x,
+        1 => tuple.y,
+        _ => tuple.z,
+    }
+}
+
+impl From<(Tuple, Tuple)> for BoundingBox {
+    fn from((min, max): (Tuple, Tuple)) -> BoundingBox {
+        BoundingBox::new(min, max)
+    }
+}
+
+impl From<BoundingBox> for (Tuple, Tuple) {
+    fn from(bounds: BoundingBox) -> (Tuple, Tuple) {
+        (bounds.min, bounds.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bounds::BoundingBox;
+    use crate::matrix::Matrix4;
+    use crate::tuple::Tuple;
+    use std::f64::consts::{FRAC_PI_4, SQRT_2};
+
+    #[test]
+    fn test_adding_points_to_an_empty_bounding_box() {
+        let mut b = BoundingBox::empty();
+        assert!(b.is_empty());
+        b.add_point(Tuple::point(-5.0, 2.0, 0.0));
+        b.add_point(Tuple::point(7.0, 0.0, -3.0));
+        assert!(!b.is_empty());
+        assert_eq!(b.min, Tuple::point(-5.0, 0.0, -3.0));
+        assert_eq!(b.max, Tuple::point(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_merging_bounding_boxes() {
+        let a = BoundingBox::new(
+            Tuple::point(-5.0, -2.0, 0.0),
+            Tuple::point(7.0, 4.0, 4.0),
+        );
+        let b = BoundingBox::new(
+            Tuple::point(8.0, -7.0, -2.0),
+            Tuple::point(14.0, 2.0, 8.0),
+        );
+        let merged = a.merge(b);
+        assert_eq!(merged.min, Tuple::point(-5.0, -7.0, -2.0));
+        assert_eq!(merged.max, Tuple::point(14.0, 4.0, 8.0));
+        assert_eq!(a.merge(BoundingBox::empty()), a);
+    }
+
+    #[test]
+    fn test_checking_whether_a_box_contains_points_and_boxes() {
+        let b = BoundingBox::new(
+            Tuple::point(5.0, -2.0, 0.0),
+            Tuple::point(11.0, 4.0, 7.0),
+        );
+        assert!(b.contains_point(Tuple::point(5.0, -2.0, 0.0)));
+        assert!(b.contains_point(Tuple::point(8.0, 1.0, 3.0)));
+        assert!(!b.contains_point(Tuple::point(3.0, 0.0, 3.0)));
+        assert!(!b.contains_point(Tuple::point(8.0, 1.0, 8.0)));
+        assert!(b.contains_box(BoundingBox::new(
+            Tuple::point(6.0, -1.0, 1.0),
+            Tuple::point(10.0, 3.0, 6.0),
+        )));
+        assert!(!b.contains_box(BoundingBox::new(
+            Tuple::point(4.0, -3.0, -1.0),
+            Tuple::point(10.0, 3.0, 6.0),
+        )));
+        assert!(b.contains_box(BoundingBox::empty()));
+        assert!(!b.contains_box(BoundingBox::infinite()));
+    }
+
+    #[test]
+    fn test_transforming_a_bounding_box() {
+        let b = BoundingBox::new(
+            Tuple::point(-1.0, -1.0, -1.0),
+            Tuple::point(1.0, 1.0, 1.0),
+        );
+        let t = Matrix4::rotation_x(FRAC_PI_4) * Matrix4::rotation_y(FRAC_PI_4);
+        let transformed = b.transform(t);
+        assert_eq!(transformed.min, Tuple::point(-SQRT_2, -1.70711, -1.70711));
+        assert_eq!(transformed.max, Tuple::point(SQRT_2, 1.70711, 1.70711));
+        assert!(BoundingBox::empty().transform(t).is_empty());
+        let half_space = BoundingBox::new(
+            Tuple::point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, 0.0, f64::INFINITY),
+        );
+        let transformed = half_space.transform(t);
+        assert!(transformed.is_unbounded());
+        assert_eq!(transformed.min.y, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_splitting_a_bounding_box_across_its_longest_axis() {
+        let b = BoundingBox::new(
+            Tuple::point(-1.0, -2.0, -3.0),
+            Tuple::point(9.0, 5.5, 3.0),
+        );
+        let (left, right) = b.split();
+        assert_eq!(left.max, Tuple::point(4.0, 5.5, 3.0));
+        assert_eq!(right.min, Tuple::point(4.0, -2.0, -3.0));
+        let b = BoundingBox::new(
+            Tuple::point(-1.0, -2.0, -3.0),
+            Tuple::point(5.0, 8.0, 3.0),
+        );
+        let (left, right) = b.split();
+        assert_eq!(left.max, Tuple::point(5.0, 3.0, 3.0));
+        assert_eq!(right.min, Tuple::point(-1.0, 3.0, -3.0));
+        let (left, _) = BoundingBox::new(
+            Tuple::point(-1.0, -2.0, -3.0),
+            Tuple::point(5.0, 3.0, 7.0),
+        )
+        .split();
+        assert_eq!(left.max, Tuple::point(5.0, 3.0, 2.0));
+    }
+}
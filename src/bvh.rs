@@ -0,0 +1,510 @@
+use crate::accel::{ray_hits_box, Accel, AccelStructure, Packet};
+use crate::bounds::{component, BoundingBox};
+use crate::ray::Ray;
+use std::mem::size_of;
+use std::thread;
+
+/// Nodes with this many objects or fewer are always leaves.
+const LEAF_SIZE: usize = 2;
+
+/// The most objects a leaf may hold, even when the surface area
+/// heuristic finds splitting it not worth the cost.
+const MAX_LEAF_SIZE: usize = 8;
+
+/// How many buckets objects are sorted into along an axis when looking
+/// for the cheapest split.
+const BINS: usize = 12;
+
+/// Nodes with at least this many objects build their two halves in
+/// parallel, when there are threads to spare.
+const PARALLEL_SIZE: usize = 4096;
+
+/// The cost of visiting a node, relative to testing a ray against one
+/// object.
+const TRAVERSAL_COST: f64 = 1.0;
+
+/// A bounding volume hierarchy over the bounds of a list of objects. Each
+/// node's box contains the boxes of everything below it, so a ray that
+/// misses a node can skip all of its objects at once.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// The object indices, ordered so that every leaf holds a contiguous
+    /// run of them.
+    indices: Vec<usize>,
+    /// Objects with infinite bounds, which every ray has to test.
+    /// Objects with empty bounds can't be hit, so they are left out.
+    unbounded: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: BoundingBox,
+    kind: NodeKind,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum NodeKind {
+    /// Holds `indices[start..end]`.
+    Leaf { start: usize, end: usize },
+    /// The positions of the two children in `nodes`.
+    Interior { left: usize, right: usize },
+}
+
+impl Bvh {
+    /// Builds a hierarchy over objects with the given bounds. Each node
+    /// is split where the surface area heuristic expects rays to do the
+    /// least work: the chance of a ray entering a child is taken to be
+    /// proportional to its surface area, so the best split keeps many
+    /// objects in small boxes. Candidate splits are the edges between
+    /// equal buckets of the objects' centers along each axis.
+    ///
+    /// Large hierarchies are built on as many threads as the machine
+    /// offers.
+    pub fn build(bounds: &[BoundingBox]) -> Bvh {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Bvh::build_with_threads(bounds, threads)
+    }
+
+    /// Builds a hierarchy on at most the given number of threads. The
+    /// result is the same however many threads are used.
+    pub fn build_with_threads(bounds: &[BoundingBox], threads: usize) -> Bvh {
+        let (unbounded, mut indices): (Vec<usize>, Vec<usize>) = (0..bounds
+            .len())
+            .filter(|&i| !bounds[i].is_empty())
+            .partition(|&i| bounds[i].is_unbounded());
+        let mut nodes = vec![];
+        if !indices.is_empty() {
+            build_node(bounds, &mut indices, 0, threads.max(1), &mut nodes);
+        }
+        Bvh {
+            nodes,
+            indices,
+            unbounded,
+            len: bounds.len(),
+        }
+    }
+
+    /// The memory used by the hierarchy, including what it owns on the
+    /// heap.
+    pub fn memory_bytes(&self) -> usize {
+        size_of::<Bvh>()
+            + self.nodes.capacity() * size_of::<Node>()
+            + (self.indices.capacity() + self.unbounded.capacity())
+                * size_of::<usize>()
+    }
+}
+
+/// Adds the node for the objects in `indices` and everything below it,
+/// returning its position in `nodes`. The objects are at `offset` in the
+/// full list of indices. Once nodes are large enough, their two halves
+/// are built on separate threads, up to `threads` at once.
+fn build_node(
+    bounds: &[BoundingBox],
+    indices: &mut [usize],
+    offset: usize,
+    threads: usize,
+    nodes: &mut Vec<Node>,
+) -> usize {
+    let node_bounds = around(bounds, indices);
+    let position = nodes.len();
+    nodes.push(Node {
+        bounds: node_bounds,
+        kind: NodeKind::Leaf {
+            start: offset,
+            end: offset + indices.len(),
+        },
+    });
+    let middle = match partition(bounds, indices, node_bounds) {
+        Some(middle) => middle,
+        None => return position,
+    };
+    let parallel = threads > 1 && indices.len() >= PARALLEL_SIZE;
+    let (below, above) = indices.split_at_mut(middle);
+    let (left, right) = if parallel {
+        let half = threads / 2;
+        let (left_nodes, right_nodes) = thread::scope(|scope| {
+            let left = scope.spawn(|| {
+                let mut nodes = vec![];
+                build_node(bounds, below, offset, half, &mut nodes);
+                nodes
+            });
+            let mut right = vec![];
+            build_node(
+                bounds,
+                above,
+                offset + middle,
+                threads - half,
+                &mut right,
+            );
+            (left.join().unwrap(), right)
+        });
+        (append(nodes, left_nodes), append(nodes, right_nodes))
+    } else {
+        let left = build_node(bounds, below, offset, threads, nodes);
+        let right = build_node(bounds, above, offset + middle, threads, nodes);
+        (left, right)
+    };
+    nodes[position].kind = NodeKind::Interior { left, right };
+    position
+}
+
+/// Reorders the objects of a node into the two halves of its split and
+/// returns where the second half starts, or `None` if the node should be
+/// a leaf.
+fn partition(
+    bounds: &[BoundingBox],
+    indices: &mut [usize],
+    node_bounds: BoundingBox,
+) -> Option<usize> {
+    let count = indices.len();
+    if count <= LEAF_SIZE {
+        return None;
+    }
+    let mut centroid_bounds = BoundingBox::empty();
+    for &i in indices.iter() {
+        centroid_bounds.add_point(bounds[i].centroid());
+    }
+    match best_split(bounds, indices, node_bounds, centroid_bounds) {
+        Some((axis, split, cost)) => {
+            if cost >= count as f64 && count <= MAX_LEAF_SIZE {
+                return None;
+            }
+            let (below, above): (Vec<usize>, Vec<usize>) = indices
+                .iter()
+                .partition(|&&i| bin(bounds[i], centroid_bounds, axis) < split);
+            indices[..below.len()].copy_from_slice(&below);
+            indices[below.len()..].copy_from_slice(&above);
+            Some(below.len())
+        }
+        // every center is in the same place, so any split is as good as
+        // another
+        None => Some(count / 2),
+    }
+}
+
+/// Moves the nodes of a subtree built on its own onto the end of `nodes`,
+/// returning the new position of its root.
+fn append(nodes: &mut Vec<Node>, subtree: Vec<Node>) -> usize {
+    let base = nodes.len();
+    nodes.extend(subtree.into_iter().map(|mut node| {
+        if let NodeKind::Interior { left, right } = &mut node.kind {
+            *left += base;
+            *right += base;
+        }
+        node
+    }));
+    base
+}
+
+/// Returns the box around the objects with the given indices.
+fn around(bounds: &[BoundingBox], indices: &[usize]) -> BoundingBox {
+    let mut result = BoundingBox::empty();
+    for &i in indices.iter() {
+        result.add_box(bounds[i]);
+    }
+    result
+}
+
+/// Returns the bucket along the axis that the center of a box falls in.
+fn bin(
+    object: BoundingBox,
+    centroid_bounds: BoundingBox,
+    axis: usize,
+) -> usize {
+    let low = component(centroid_bounds.min, axis);
+    let extent = component(centroid_bounds.max, axis) - low;
+    let offset = (component(object.centroid(), axis) - low) / extent;
+    ((offset * BINS as f64) as usize).min(BINS - 1)
+}
+
+/// Returns half the surface area of a box, which is all that the
+/// heuristic needs since only ratios of areas matter. Empty boxes have
+/// no area.
+fn half_area(bounds: BoundingBox) -> f64 {
+    if bounds.is_empty() {
+        return 0.0;
+    }
+    let d = bounds.max - bounds.min;
+    d.x * d.y + d.y * d.z + d.z * d.x
+}
+
+/// Finds the cheapest split of the objects within `node_bounds`,
+/// returning the axis, the first bucket above the split, and the expected
+/// cost of a ray entering the node, relative to testing one object.
+/// Returns `None` when the centers of the objects can't be told apart
+/// along any axis.
+fn best_split(
+    bounds: &[BoundingBox],
+    indices: &[usize],
+    node_bounds: BoundingBox,
+    centroid_bounds: BoundingBox,
+) -> Option<(usize, usize, f64)> {
+    let node_area = half_area(node_bounds);
+    let mut best: Option<(usize, usize, f64)> = None;
+    for axis in 0..3 {
+        let extent = component(centroid_bounds.max, axis)
+            - component(centroid_bounds.min, axis);
+        if extent <= 0.0 {
+            continue;
+        }
+        let mut counts = [0; BINS];
+        let mut boxes = [BoundingBox::empty(); BINS];
+        for &i in indices.iter() {
+            let b = bin(bounds[i], centroid_bounds, axis);
+            counts[b] += 1;
+            boxes[b].add_box(bounds[i]);
+        }
+        // the area and count of everything below each split, then above
+        let mut below = [(0.0, 0); BINS];
+        let mut running = BoundingBox::empty();
+        let mut running_count = 0;
+        for split in 1..BINS {
+            running.add_box(boxes[split - 1]);
+            running_count += counts[split - 1];
+            below[split] = (half_area(running), running_count);
+        }
+        let mut running = BoundingBox::empty();
+        let mut running_count = 0;
+        for split in (1..BINS).rev() {
+            running.add_box(boxes[split]);
+            running_count += counts[split];
+            let (below_area, below_count) = below[split];
+            if below_count == 0 || running_count == 0 {
+                continue;
+            }
+            let above_area = half_area(running);
+            let cost = TRAVERSAL_COST
+                + (below_area * below_count as f64
+                    + above_area * running_count as f64)
+                    / node_area.max(f64::MIN_POSITIVE);
+            if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+    best
+}
+
+impl AccelStructure for Bvh {
+    fn accel(&self) -> Accel {
+        Accel::Bvh
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn candidates(&self, ray: Ray) -> Vec<usize> {
+        let mut candidates = self.unbounded.clone();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(position) = stack.pop() {
+            let node = self.nodes[position];
+            if !ray_hits_box(ray, node.bounds) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { start, end } => {
+                    candidates.extend_from_slice(&self.indices[start..end])
+                }
+                NodeKind::Interior { left, right } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        candidates
+    }
+
+    fn packet_candidates(&self, rays: &[Ray; 4]) -> [Vec<usize>; 4] {
+        let mut candidates = [
+            self.unbounded.clone(),
+            self.unbounded.clone(),
+            self.unbounded.clone(),
+            self.unbounded.clone(),
+        ];
+        let packet = Packet::new(rays);
+        // each node is visited with the rays that reached its parent
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![(0, [true; 4])]
+        };
+        while let Some((position, active)) = stack.pop() {
+            let node = self.nodes[position];
+            let hits = packet.hits_box(node.bounds);
+            let mut active = active;
+            for (lane, hit) in active.iter_mut().zip(hits.iter()) {
+                *lane = *lane && *hit;
+            }
+            if active == [false; 4] {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { start, end } => {
+                    for (lane, list) in candidates.iter_mut().enumerate() {
+                        if active[lane] {
+                            list.extend_from_slice(&self.indices[start..end]);
+                        }
+                    }
+                }
+                NodeKind::Interior { left, right } => {
+                    stack.push((left, active));
+                    stack.push((right, active));
+                }
+            }
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accel::AccelStructure;
+    use crate::bounds::BoundingBox;
+    use crate::bvh::Bvh;
+    use crate::ray::Ray;
+    use crate::tuple::Tuple;
+
+    /// Unit boxes spaced out along the x axis.
+    fn row(count: usize) -> Vec<BoundingBox> {
+        (0..count)
+            .map(|i| {
+                let x = 3.0 * i as f64;
+                BoundingBox::new(
+                    Tuple::point(x - 1.0, -1.0, -1.0),
+                    Tuple::point(x + 1.0, 1.0, 1.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_a_bvh_only_returns_objects_near_the_ray() {
+        let bvh = Bvh::build(&row(20));
+        assert_eq!(bvh.len(), 20);
+        let r = Ray::new(
+            Tuple::point(30.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let candidates = bvh.candidates(r);
+        assert!(candidates.contains(&10));
+        assert!(candidates.len() <= 4);
+        let along = Ray::new(
+            Tuple::point(-5.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+        );
+        let mut all = bvh.candidates(along);
+        all.sort();
+        assert_eq!(all, (0..20).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_the_surface_area_heuristic_isolates_a_distant_object() {
+        // a median split would leave the last box in a leaf with three
+        // of the others
+        let mut bounds = row(8);
+        bounds[7] = BoundingBox::new(
+            Tuple::point(99.0, -1.0, -1.0),
+            Tuple::point(101.0, 1.0, 1.0),
+        );
+        let bvh = Bvh::build(&bounds);
+        let r = Ray::new(
+            Tuple::point(100.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(bvh.candidates(r), vec![7]);
+    }
+
+    #[test]
+    fn test_objects_in_the_same_place_are_still_split() {
+        let unit = BoundingBox::new(
+            Tuple::point(-1.0, -1.0, -1.0),
+            Tuple::point(1.0, 1.0, 1.0),
+        );
+        let bounds = vec![unit; 40];
+        let bvh = Bvh::build(&bounds);
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut candidates = bvh.candidates(r);
+        candidates.sort();
+        assert_eq!(candidates, (0..40).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_building_on_several_threads_gives_the_same_hierarchy() {
+        // a grid large enough to be split across threads
+        let bounds: Vec<BoundingBox> = (0..80 * 80)
+            .map(|i| {
+                let (x, z) = ((i % 80) as f64 * 3.0, (i / 80) as f64 * 3.0);
+                BoundingBox::new(
+                    Tuple::point(x - 1.0, -1.0, z - 1.0),
+                    Tuple::point(x + 1.0, 1.0, z + 1.0),
+                )
+            })
+            .collect();
+        let serial = Bvh::build_with_threads(&bounds, 1);
+        let parallel = Bvh::build_with_threads(&bounds, 4);
+        assert_eq!(serial.indices, parallel.indices);
+        assert_eq!(serial.nodes.len(), parallel.nodes.len());
+        let r = Ray::new(
+            Tuple::point(30.0, 5.0, 60.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+        );
+        let candidates = parallel.candidates(r);
+        assert!(candidates.contains(&(20 * 80 + 10)) && candidates.len() <= 8);
+        assert_eq!(serial.candidates(r), candidates);
+    }
+
+    #[test]
+    fn test_a_packet_finds_the_same_candidates_as_each_ray() {
+        let mut bounds = row(20);
+        bounds.push(BoundingBox::new(
+            Tuple::point(f64::NEG_INFINITY, -2.0, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, -2.0, f64::INFINITY),
+        ));
+        let bvh = Bvh::build(&bounds);
+        let forward = Tuple::vector(0.0, 0.0, 1.0);
+        let rays = [
+            Ray::new(Tuple::point(30.0, 0.0, -5.0), forward),
+            Ray::new(Tuple::point(30.5, 0.0, -5.0), forward),
+            Ray::new(Tuple::point(31.5, 0.0, -5.0), forward),
+            Ray::new(
+                Tuple::point(-5.0, 0.0, 0.0),
+                Tuple::vector(1.0, 0.0, 0.0),
+            ),
+        ];
+        let packet = bvh.packet_candidates(&rays);
+        for (ray, candidates) in rays.iter().zip(packet.iter()) {
+            let mut expected = bvh.candidates(*ray);
+            let mut found = candidates.clone();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+        assert!(packet.iter().all(|candidates| candidates.contains(&20)));
+    }
+
+    #[test]
+    fn test_objects_with_infinite_bounds_are_always_candidates() {
+        let mut bounds = row(6);
+        bounds.push(BoundingBox::new(
+            Tuple::point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, 0.0, f64::INFINITY),
+        ));
+        bounds.push(BoundingBox::empty());
+        let bvh = Bvh::build(&bounds);
+        assert_eq!(bvh.len(), 8);
+        let away =
+            Ray::new(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(bvh.candidates(away), vec![6]);
+        assert!(Bvh::build(&[]).candidates(away).is_empty());
+    }
+}
@@ -1,29 +1,164 @@
+use crate::aov::{depth_to_canvas, Aov, DepthMode};
 use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::intersection::{Intersection, SurfaceShading};
+use crate::material::Material;
 use crate::matrix::Matrix4;
-use crate::ray::Ray;
+use crate::progress::Progress;
+use crate::ray::{Ray, RayDifferential};
+use crate::rng::Rng;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::shape::Shape;
 use crate::tuple::Tuple;
 use crate::world::World;
+use crate::EPSILON;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
 
+/// How much a draft render divides the resolution by.
+const DRAFT_SCALE: usize = 4;
+
+/// The bounce limit for draft renders.
+pub(crate) const DRAFT_MAX_DEPTH: usize = 1;
+
+/// The width and height of the tiles whose hits are shaded together,
+/// grouped by material.
+const BATCH_TILE_SIZE: usize = 16;
+
+/// A hit waiting to be shaded, along with the pixel it belongs to.
+type PendingHit<'a> = (usize, usize, Intersection<'a>);
+
+/// Hits on the same side of the same object, which share the work of
+/// shading their material.
+type HitBatch<'a> = (SurfaceShading, Vec<PendingHit<'a>>);
+
+/// Trades image quality for rendering speed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Quality {
+    /// Renders the world as it was set up.
+    Final,
+    /// Renders at a fraction of the resolution, scaled back up to the full
+    /// size, without shadows, with reflections that only see the
+    /// environment, and with at most one bounce. Meant for quickly
+    /// iterating on a scene.
+    Draft,
+}
+
+/// What the camera shows at every pixel. Everything but `Shaded` ignores
+/// lights, so it helps with finding problems in geometry and texture
+/// mapping.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RenderMode {
+    /// The fully lit image.
+    Shaded,
+    /// Surface normals, with each axis mapped from [-1, 1] to a color
+    /// channel in [0, 1].
+    Normals,
+    /// Distance from the camera, from black for the nearest visible point
+    /// to white for the farthest one.
+    Depth,
+    /// Texture coordinates, with u in the red and v in the green channel.
+    UV,
+    /// The unlit surface color.
+    Albedo,
+}
+
+/// A thin lens that gives the camera depth of field: only points at the
+/// focal distance are sharp, and everything nearer or farther is blurred
+/// more the larger the aperture is.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Lens {
+    /// The radius of the aperture, in world units.
+    pub aperture: f64,
+    /// The distance from the camera, along its line of sight, at which
+    /// the image is in focus.
+    pub focal_distance: f64,
+    /// The number of rays traced through the aperture for every pixel.
+    pub samples: usize,
+    /// The number of blades forming the aperture. Out-of-focus highlights
+    /// take its shape: fewer than three blades gives a round aperture,
+    /// six a hexagonal one.
+    pub blades: usize,
+    /// The rotation of the bladed aperture, in radians.
+    pub rotation: f64,
+}
+
+impl Lens {
+    pub fn new(aperture: f64, focal_distance: f64) -> Lens {
+        Lens {
+            aperture,
+            focal_distance,
+            samples: 16,
+            blades: 0,
+            rotation: 0.0,
+        }
+    }
+
+    /// Returns the points on the aperture that rays are traced through,
+    /// spread over its shape by the sampler, relative to its center.
+    pub fn sample_points(
+        &self,
+        sampler: Sampler,
+        rng: &mut Rng,
+    ) -> Vec<(f64, f64)> {
+        sampler
+            .points(self.samples.max(1), rng)
+            .into_iter()
+            .map(|(s, t)| {
+                let radius = s.sqrt() * self.aperture;
+                let theta = 2.0 * PI * t;
+                let radius = radius * self.polygon_extent(theta);
+                (theta.cos() * radius, theta.sin() * radius)
+            })
+            .collect()
+    }
+
+    /// Returns how far the edge of the aperture is from its center in
+    /// the given direction, relative to the radius of the aperture.
+    fn polygon_extent(&self, theta: f64) -> f64 {
+        if self.blades < 3 {
+            return 1.0;
+        }
+        let sector = 2.0 * PI / (self.blades as f64);
+        let angle = (theta - self.rotation).rem_euclid(sector) - sector / 2.0;
+        (sector / 2.0).cos() / angle.cos()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
-    pub field_of_view: f32,
+    pub field_of_view: f64,
     pub transform: Matrix4,
-    pub half_width: f32,
-    pub half_height: f32,
-    pub pixel_size: f32,
+    pub half_width: f64,
+    pub half_height: f64,
+    pub pixel_size: f64,
+    pub quality: Quality,
+    pub mode: RenderMode,
+    /// Gives the camera depth of field. Without a lens it is a pinhole
+    /// camera and everything is in focus.
+    pub lens: Option<Lens>,
+    /// Seeds the random samples taken for every pixel. Rendering the
+    /// same world with the same seed always gives the same image.
+    pub seed: u64,
 }
 
 impl Camera {
-    pub fn new(hsize: usize, vsize: usize, field_of_view: f32) -> Camera {
+    pub fn new(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
         let half_view = (field_of_view / 2.0).tan();
-        let aspect = (hsize as f32) / (vsize as f32);
+        let aspect = (hsize as f64) / (vsize as f64);
         let (half_width, half_height) = if aspect >= 1.0 {
             (half_view, half_view / aspect)
         } else {
             (half_view * aspect, half_view)
         };
-        let pixel_size = (half_width * 2.0) / (hsize as f32);
+        let pixel_size = (half_width * 2.0) / (hsize as f64);
         Camera {
             hsize,
             vsize,
@@ -32,6 +167,10 @@ impl Camera {
             half_width,
             half_height,
             pixel_size,
+            quality: Quality::Final,
+            mode: RenderMode::Shaded,
+            lens: None,
+            seed: 0,
         }
     }
 
@@ -42,43 +181,622 @@ impl Camera {
         px: usize,
         py: usize,
     ) -> Ray {
-        let x_offset = ((px as f32) + 0.5) * self.pixel_size;
-        let y_offset = ((py as f32) + 0.5) * self.pixel_size;
+        let x = (px as f64) + 0.5;
+        let y = (py as f64) + 0.5;
+        let direction = self.direction_for(inverse_transform, origin, x, y);
+        let differential = RayDifferential {
+            x_origin: origin,
+            x_direction: self.direction_for(
+                inverse_transform,
+                origin,
+                x + 1.0,
+                y,
+            ),
+            y_origin: origin,
+            y_direction: self.direction_for(
+                inverse_transform,
+                origin,
+                x,
+                y + 1.0,
+            ),
+        };
 
-        let world_x = self.half_width - x_offset;
-        let world_y = self.half_height - y_offset;
+        Ray::with_differential(origin, direction, differential)
+    }
 
-        let pixel = inverse_transform * Tuple::point(world_x, world_y, -1.0);
-        let direction = (pixel - origin).normalize();
+    /// Returns the rays from every sample point on the lens through the
+    /// point on the focal plane seen at the given position on the canvas,
+    /// measured in pixels from its top-left corner.
+    pub(crate) fn lens_rays(
+        &self,
+        lens: &Lens,
+        inverse_transform: Matrix4,
+        (x, y): (f64, f64),
+        sampler: Sampler,
+        rng: &mut Rng,
+    ) -> Vec<Ray> {
+        let x = self.half_width - x * self.pixel_size;
+        let y = self.half_height - y * self.pixel_size;
+        let focal_point = inverse_transform
+            * Tuple::point(
+                x * lens.focal_distance,
+                y * lens.focal_distance,
+                -lens.focal_distance,
+            );
+        lens.sample_points(sampler, rng)
+            .into_iter()
+            .map(|(lens_x, lens_y)| {
+                let origin =
+                    inverse_transform * Tuple::point(lens_x, lens_y, 0.0);
+                Ray::new(origin, (focal_point - origin).normalize())
+            })
+            .collect()
+    }
+
+    /// Computes the color of one pixel, averaged over the lens when the
+    /// camera has one.
+    fn pixel_color(
+        &self,
+        scene: &Scene,
+        inverse_transform: Matrix4,
+        origin: Tuple,
+        px: usize,
+        py: usize,
+    ) -> Color {
+        let mut rng = Rng::for_pixel(self.seed, px, py, 0);
+        match &self.lens {
+            Some(lens) => {
+                let rays = self.lens_rays(
+                    lens,
+                    inverse_transform,
+                    ((px as f64) + 0.5, (py as f64) + 0.5),
+                    scene.world().sampler,
+                    &mut rng,
+                );
+                let count = rays.len() as f64;
+                rays.into_iter().fold(Color::black(), |sum, ray| {
+                    sum + scene.camera_color_at(ray, &mut rng)
+                }) * (1.0 / count)
+            }
+            None => scene.camera_color_at(
+                self.ray_for_pixel(inverse_transform, origin, px, py),
+                &mut rng,
+            ),
+        }
+    }
 
+    /// Returns the ray through the center of the given pixel.
+    pub(crate) fn pixel_ray(&self, px: usize, py: usize) -> Ray {
+        let inverse_transform = self.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        self.ray_for_pixel(inverse_transform, origin, px, py)
+    }
+
+    /// Returns the object seen at the center of the given pixel, or
+    /// `None` where there is only background, e.g. to select objects by
+    /// clicking a preview. Its name finds it again after the world's
+    /// objects are reordered.
+    pub fn object_at_pixel<'w>(
+        &self,
+        scene: &Scene<'w>,
+        x: usize,
+        y: usize,
+    ) -> Option<&'w dyn Shape> {
+        let ray = self.pixel_to_ray(x as f64 + 0.5, y as f64 + 0.5);
+        let index = scene.camera_object_id_at(ray)?;
+        Some(&*scene.world().objects[index])
+    }
+
+    /// Returns where a point in camera space, in front of the camera,
+    /// lands on the canvas, in pixels from its top-left corner.
+    pub(crate) fn camera_point_to_pixel(&self, point: Tuple) -> (f64, f64) {
+        let x = point.x / -point.z;
+        let y = point.y / -point.z;
+        (
+            (self.half_width - x) / self.pixel_size,
+            (self.half_height - y) / self.pixel_size,
+        )
+    }
+
+    /// Returns the ray from the camera through a position on the canvas,
+    /// measured in pixels from its top-left corner, so (0.5, 0.5) is the
+    /// center of the top-left pixel. The lens is ignored, so the ray
+    /// starts at the center of the camera. This is the inverse of
+    /// `project_point`, e.g. for picking the object under the cursor.
+    pub fn pixel_to_ray(&self, x: f64, y: f64) -> Ray {
+        let inverse_transform = self.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        let direction = self.direction_for(inverse_transform, origin, x, y);
         Ray::new(origin, direction)
     }
 
-    pub fn render(&self, world: World) -> Canvas {
+    /// Returns where a point in world space appears on the canvas, in
+    /// pixels from its top-left corner, or `None` if it is not in front
+    /// of the camera. Points outside the field of view land outside the
+    /// canvas.
+    pub fn project_point(&self, point: Tuple) -> Option<(f64, f64)> {
+        let camera_point = self.transform * point;
+        if camera_point.z > -EPSILON {
+            return None;
+        }
+        Some(self.camera_point_to_pixel(camera_point))
+    }
+
+    /// Returns the direction from the camera through the given position
+    /// on the canvas, measured in pixels from its top-left corner.
+    pub(crate) fn direction_for(
+        &self,
+        inverse_transform: Matrix4,
+        origin: Tuple,
+        x: f64,
+        y: f64,
+    ) -> Tuple {
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
+
+        let pixel = inverse_transform * Tuple::point(world_x, world_y, -1.0);
+        (pixel - origin).normalize()
+    }
+
+    pub fn render(&self, world: &World) -> Canvas {
+        self.render_scene(&Scene::compile(world, self))
+    }
+
+    /// Renders the world like `render`, spreading the work over every
+    /// available thread.
+    pub fn render_parallel(&self, world: &World) -> Canvas {
+        let scene = Scene::compile(world, self);
+        if self.quality == Quality::Draft {
+            return self.render_draft(&scene);
+        }
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.render_scene_parallel(&scene, threads)
+    }
+
+    /// Renders a scene compiled for this camera, such as one given by
+    /// `Scene::compile` or `Scene::for_camera`, which also turn off the
+    /// effects a draft skips.
+    pub fn render_scene(&self, scene: &Scene) -> Canvas {
+        if self.quality == Quality::Draft {
+            return self.render_draft(scene);
+        }
+        match self.mode {
+            RenderMode::Shaded => self.render_shaded(scene),
+            RenderMode::Depth => depth_to_canvas(
+                &self.render_depth(scene),
+                DepthMode::Normalized,
+            ),
+            mode => self
+                .render_aov(Color::black(), |ray| false_color(scene, ray, mode))
+                .to_canvas(|color| color),
+        }
+    }
+
+    fn render_shaded(&self, scene: &Scene) -> Canvas {
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        self.render_rows(scene, 0, &mut canvas);
+        canvas
+    }
+
+    /// Renders the shaded image using the given number of threads, which
+    /// take turns rendering bands of rows. The image is the same as the
+    /// one `render_scene` gives. Only the shaded mode is rendered in
+    /// parallel.
+    pub fn render_scene_parallel(
+        &self,
+        scene: &Scene,
+        threads: usize,
+    ) -> Canvas {
+        if self.mode != RenderMode::Shaded || threads <= 1 {
+            return self.render_scene(scene);
+        }
+        self.render_bands(scene, threads, &mut |_| {})
+    }
+
+    /// Renders the world like `render_parallel`, passing events to
+    /// `report` as the render starts, as rows are finished, and when it
+    /// is done. `report` is only called by one thread at a time.
+    pub fn render_parallel_with_progress<F>(
+        &self,
+        world: &World,
+        mut report: F,
+    ) -> Canvas
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let scene = Scene::compile(world, self);
+        if self.quality == Quality::Draft {
+            let start = Instant::now();
+            report(self.started(1));
+            let canvas = self.render_draft(&scene);
+            report(self.finished(start));
+            return canvas;
+        }
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.render_scene_with_progress(&scene, threads, report)
+    }
+
+    /// Renders a scene like `render_scene_parallel`, passing events to
+    /// `report` like `render_parallel_with_progress`.
+    pub fn render_scene_with_progress<F>(
+        &self,
+        scene: &Scene,
+        threads: usize,
+        mut report: F,
+    ) -> Canvas
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let start = Instant::now();
+        let canvas = if self.mode == RenderMode::Shaded {
+            report(self.started(threads.max(1)));
+            self.render_bands(scene, threads.max(1), &mut report)
+        } else {
+            report(self.started(1));
+            let canvas = self.render_scene(scene);
+            report(Progress::Rows {
+                done: self.vsize,
+                total: self.vsize,
+            });
+            canvas
+        };
+        report(self.finished(start));
+        canvas
+    }
+
+    fn started(&self, threads: usize) -> Progress {
+        Progress::Started {
+            width: self.hsize,
+            height: self.vsize,
+            samples: self.samples_per_pixel(),
+            threads,
+        }
+    }
+
+    fn finished(&self, start: Instant) -> Progress {
+        Progress::Finished {
+            duration: start.elapsed(),
+            camera_rays: self.hsize * self.vsize * self.samples_per_pixel(),
+        }
+    }
+
+    /// Returns the number of camera rays traced for every pixel.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.lens.map_or(1, |lens| lens.samples.max(1))
+    }
+
+    /// Renders the shaded image with threads that take turns rendering
+    /// bands of rows, reporting the rows finished after every band.
+    fn render_bands<F>(
+        &self,
+        scene: &Scene,
+        threads: usize,
+        report: &mut F,
+    ) -> Canvas
+    where
+        F: FnMut(Progress) + Send,
+    {
         let mut canvas = Canvas::new(self.hsize, self.vsize);
+        self.render_bands_into(scene, threads, report, &mut |top, rows| {
+            for y in 0..rows.height {
+                for x in 0..rows.width {
+                    canvas.write_pixel(x, top + y, rows.pixel_at(x, y));
+                }
+            }
+        });
+        canvas
+    }
+
+    /// Renders the shaded image like `render_bands`, handing every band
+    /// to `write` with the row it starts at as soon as it is finished,
+    /// so the whole image never has to be held in one canvas. `write`
+    /// and `report` are only called by one thread at a time.
+    pub(crate) fn render_bands_into<F, W>(
+        &self,
+        scene: &Scene,
+        threads: usize,
+        report: &mut F,
+        write: &mut W,
+    ) where
+        F: FnMut(Progress) + Send,
+        W: FnMut(usize, &Canvas) + Send,
+    {
+        let bands = self.vsize.div_ceil(BATCH_TILE_SIZE);
+        let next_band = AtomicUsize::new(0);
+        let finished = Mutex::new((0, report, write));
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1).min(bands) {
+                scope.spawn(|| loop {
+                    let band = next_band.fetch_add(1, Ordering::Relaxed);
+                    if band >= bands {
+                        return;
+                    }
+                    let top = band * BATCH_TILE_SIZE;
+                    let height = BATCH_TILE_SIZE.min(self.vsize - top);
+                    let mut rows = Canvas::new(self.hsize, height);
+                    self.render_rows(scene, top, &mut rows);
+                    let mut finished = finished.lock().unwrap();
+                    let (done, report, write) = &mut *finished;
+                    write(top, &rows);
+                    *done += height;
+                    report(Progress::Rows {
+                        done: *done,
+                        total: self.vsize,
+                    });
+                });
+            }
+        });
+    }
 
+    /// Renders the rows of the shaded image starting at `top` into
+    /// `rows`, whose height is the number of rows to render. `top` must
+    /// be at the start of a tile.
+    fn render_rows(&self, scene: &Scene, top: usize, rows: &mut Canvas) {
         let inverse_transform = self.transform.inverse();
         let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        let bottom = top + rows.height;
+
+        if self.lens.is_none() {
+            for tile_top in (top..bottom).step_by(BATCH_TILE_SIZE) {
+                for left in (0..self.hsize).step_by(BATCH_TILE_SIZE) {
+                    self.render_batched_tile(
+                        scene,
+                        inverse_transform,
+                        origin,
+                        (left, tile_top),
+                        top,
+                        rows,
+                    );
+                }
+            }
+            return;
+        }
 
+        for y in top..bottom {
+            for x in 0..self.hsize {
+                let color =
+                    self.pixel_color(scene, inverse_transform, origin, x, y);
+                rows.write_pixel(x, y - top, color);
+            }
+        }
+    }
+
+    /// Renders one tile by first finding the hit at every pixel and then
+    /// shading the hits one material at a time, working out what shading
+    /// needs from each material only once. Hits are found for 2x2 blocks
+    /// of pixels at once, as ray packets.
+    fn render_batched_tile(
+        &self,
+        scene: &Scene,
+        inverse_transform: Matrix4,
+        origin: Tuple,
+        (left, top): (usize, usize),
+        first_row: usize,
+        rows: &mut Canvas,
+    ) {
+        let mut batches: Vec<HitBatch> = vec![];
+        let mut batch_for = HashMap::new();
+        let right = (left + BATCH_TILE_SIZE).min(self.hsize);
+        let bottom = (top + BATCH_TILE_SIZE).min(self.vsize);
+        let ray_for =
+            |(x, y)| self.ray_for_pixel(inverse_transform, origin, x, y);
+        for y in (top..bottom).step_by(2) {
+            for x in (left..right).step_by(2) {
+                let block = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+                // blocks cut off by the edge of the image are traced one
+                // ray at a time
+                let traced: Vec<((usize, usize), Ray, Option<Intersection>)> =
+                    if x + 1 < right && y + 1 < bottom {
+                        let rays = block.map(ray_for);
+                        let hits = scene.camera_hits(&rays);
+                        block
+                            .iter()
+                            .zip(rays)
+                            .zip(hits)
+                            .map(|((&pixel, ray), hit)| (pixel, ray, hit))
+                            .collect()
+                    } else {
+                        block
+                            .iter()
+                            .filter(|&&(x, y)| x < right && y < bottom)
+                            .map(|&pixel| {
+                                let ray = ray_for(pixel);
+                                (pixel, ray, scene.camera_hit(ray))
+                            })
+                            .collect()
+                    };
+                for ((x, y), ray, hit) in traced {
+                    let hit = match hit {
+                        Some(hit) => hit,
+                        None => {
+                            let color = scene.environment_color(ray.direction);
+                            rows.write_pixel(x, y - first_row, color);
+                            continue;
+                        }
+                    };
+                    // the material and object are told apart by address,
+                    // which is much cheaper than comparing materials
+                    let key = (
+                        hit.object as *const dyn Shape as *const (),
+                        hit.material() as *const Material,
+                    );
+                    let index = *batch_for.entry(key).or_insert_with(|| {
+                        batches.push((SurfaceShading::new(&hit), vec![]));
+                        batches.len() - 1
+                    });
+                    batches[index].1.push((x, y, hit));
+                }
+            }
+        }
+        for (shading, hits) in batches {
+            for (x, y, hit) in hits {
+                let mut rng = Rng::for_pixel(self.seed, x, y, 0);
+                let color = hit.shade_hit_with(
+                    &shading,
+                    scene,
+                    scene.max_depth(),
+                    &mut rng,
+                );
+                rows.write_pixel(x, y - first_row, color);
+            }
+        }
+    }
+
+    fn render_draft(&self, scene: &Scene) -> Canvas {
+        let small = self.render_small_draft(scene);
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        self.upscale_draft(&small, |x, y, pixel| {
+            canvas.write_pixel(x, y, pixel)
+        });
+        canvas
+    }
+
+    /// Renders a draft at a fraction of this camera's size, to be
+    /// stretched back to full size with `upscale_draft`.
+    pub(crate) fn render_small_draft(&self, scene: &Scene) -> Canvas {
+        let mut draft = Camera::new(
+            (self.hsize / DRAFT_SCALE).max(1),
+            (self.vsize / DRAFT_SCALE).max(1),
+            self.field_of_view,
+        );
+        draft.transform = self.transform;
+        draft.mode = self.mode;
+        draft.seed = self.seed;
+        draft.render_scene(scene)
+    }
+
+    /// Stretches a draft image rendered at a fraction of this camera's
+    /// size back to full size, handing every pixel to `write`.
+    pub(crate) fn upscale_draft<W>(&self, small: &Canvas, mut write: W)
+    where
+        W: FnMut(usize, usize, Color),
+    {
         for y in 0..self.vsize {
             for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(inverse_transform, origin, x, y);
-                let color = world.color_at(ray);
+                let pixel = small.pixel_at(
+                    (x * small.width / self.hsize).min(small.width - 1),
+                    (y * small.height / self.vsize).min(small.height - 1),
+                );
+                write(x, y, pixel);
+            }
+        }
+    }
+
+    /// Renders the world like `render`, also recording how many seconds
+    /// every pixel took, to find the parts of a scene that are slow to
+    /// render.
+    pub fn render_timed(&self, world: &World) -> (Canvas, Aov<f64>) {
+        let scene = Scene::compile(world, self);
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        let mut times = Aov::new(self.hsize, self.vsize, 0.0);
+
+        let inverse_transform = self.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let start = Instant::now();
+                let color =
+                    self.pixel_color(&scene, inverse_transform, origin, x, y);
+                times.write_value(x, y, start.elapsed().as_secs_f64());
                 canvas.write_pixel(x, y, color);
             }
         }
-        canvas
+        (canvas, times)
+    }
+
+    /// Renders a per-pixel buffer by calling `value_for_ray` with the
+    /// ray through the center of every pixel.
+    pub fn render_aov<T, F>(&self, default: T, value_for_ray: F) -> Aov<T>
+    where
+        T: Copy,
+        F: Fn(Ray) -> T,
+    {
+        let mut aov = Aov::new(self.hsize, self.vsize, default);
+
+        let inverse_transform = self.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let ray = self.ray_for_pixel(inverse_transform, origin, x, y);
+                aov.write_value(x, y, value_for_ray(ray));
+            }
+        }
+        aov
+    }
+
+    /// Renders the depth of the visible point at every pixel, measured
+    /// along the camera's line of sight, or `None` where the ray misses
+    /// everything.
+    pub fn render_depth(&self, scene: &Scene) -> Aov<Option<f64>> {
+        let forward = (self.transform.inverse()
+            * Tuple::vector(0.0, 0.0, -1.0))
+        .normalize();
+        self.render_aov(None, |ray| {
+            scene
+                .camera_hit(ray)
+                .map(|hit| hit.t * ray.direction.dot(forward))
+        })
+    }
+
+    /// Renders the world space normal of the visible surface at every
+    /// pixel, facing the camera, or `None` where the ray misses
+    /// everything.
+    pub fn render_normals(&self, scene: &Scene) -> Aov<Option<Tuple>> {
+        self.render_aov(None, |ray| {
+            scene.camera_hit(ray).map(|hit| hit.normal_vector.unwrap())
+        })
+    }
+
+    /// Renders the index in `World::objects` of the object visible at
+    /// every pixel, or `None` where the ray misses everything.
+    pub fn render_object_ids(&self, scene: &Scene) -> Aov<Option<usize>> {
+        self.render_aov(None, |ray| scene.camera_object_id_at(ray))
+    }
+}
+
+/// Returns the color of the visible surface for one of the unlit
+/// render modes, or black where the ray misses everything.
+fn false_color(scene: &Scene, ray: Ray, mode: RenderMode) -> Color {
+    let hit = match scene.camera_hit(ray) {
+        Some(hit) => hit,
+        None => return Color::black(),
+    };
+    match mode {
+        RenderMode::Normals => {
+            let n = hit.normal_vector.unwrap();
+            Color::new(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5)
+        }
+        RenderMode::UV => {
+            let (u, v) = hit.object.uv_at_hit_with(
+                hit.point.unwrap(),
+                &hit,
+                hit.object_inverse(),
+            );
+            Color::new(u, v, 0.0)
+        }
+        _ => hit.albedo(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::camera::Camera;
+    use crate::camera::{Camera, Lens, Quality, RenderMode};
     use crate::color::Color;
+    use crate::intersection::Intersection;
     use crate::matrix::Matrix4;
+    use crate::pattern::StripePattern;
+    use crate::progress::Progress;
+    use crate::rng::Rng;
+    use crate::sampler::Sampler;
+    use crate::scene::Scene;
+    use crate::sphere::Sphere;
     use crate::tuple::Tuple;
     use crate::world::World;
-    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+    use crate::{equal_f64, EPSILON};
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
+    use std::sync::Arc;
 
     #[test]
     fn test_constructing_a_camera() {
@@ -95,13 +813,13 @@ mod tests {
     #[test]
     fn test_the_pixel_size_for_a_horizontal_canvas() {
         let c = Camera::new(200, 125, FRAC_PI_2);
-        assert_eq!(c.pixel_size, 0.01);
+        assert!(equal_f64(c.pixel_size, 0.01));
     }
 
     #[test]
     fn test_the_pixel_size_for_a_vertical_canvas() {
         let c = Camera::new(125, 200, FRAC_PI_2);
-        assert_eq!(c.pixel_size, 0.01);
+        assert!(equal_f64(c.pixel_size, 0.01));
     }
 
     #[test]
@@ -147,7 +865,399 @@ mod tests {
         let to = Tuple::point(0.0, 0.0, 0.0);
         let up = Tuple::vector(0.0, 1.0, 0.0);
         c.transform = Matrix4::view_transform(from, to, up);
-        let image = c.render(w);
+        let image = c.render(&w);
         assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn test_rendering_one_world_from_two_angles() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        let front =
+            Matrix4::view_transform(Tuple::point(0.0, 0.0, -5.0), to, up);
+        let back = Matrix4::view_transform(Tuple::point(0.0, 0.0, 5.0), to, up);
+        c.transform = front;
+        let first = c.render(&w);
+        c.transform = back;
+        assert_ne!(c.render(&w), first);
+        c.transform = front;
+        assert_eq!(c.render(&w), first);
+        // rendering compiles a copy, leaving the world as it was
+        assert_eq!(w.dirty_objects().len(), 2);
+    }
+
+    #[test]
+    fn test_rendering_in_parallel_matches_rendering_serially() {
+        let w = World::default();
+        // several bands of rows, the last one cut off
+        let mut c = Camera::new(23, 37, FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = Matrix4::view_transform(from, to, up);
+        let scene = Scene::compile(&w, &c);
+        assert_eq!(c.render_scene_parallel(&scene, 3), c.render(&w));
+        c.lens = Some(Lens {
+            samples: 2,
+            ..Lens::new(0.1, 5.0)
+        });
+        assert_eq!(c.render_scene_parallel(&scene, 2), c.render(&w));
+        assert_eq!(c.render_parallel(&w), c.render(&w));
+    }
+
+    #[test]
+    fn test_reporting_progress_while_rendering() {
+        let w = World::default();
+        let mut c = Camera::new(23, 37, FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = Matrix4::view_transform(from, to, up);
+        let mut events = vec![];
+        let canvas = c.render_parallel_with_progress(&w, |e| events.push(e));
+        assert_eq!(canvas, c.render(&w));
+        assert!(matches!(
+            events[0],
+            Progress::Started {
+                width: 23,
+                height: 37,
+                samples: 1,
+                ..
+            }
+        ));
+        let rows: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match *event {
+                Progress::Rows { done, total: 37 } => Some(done),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(rows.last(), Some(&37));
+        assert!(matches!(
+            events.last(),
+            Some(Progress::Finished {
+                camera_rays: 851,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_batched_rendering_matches_shading_every_pixel() {
+        let mut w = World::default();
+        let mut stripes = StripePattern::new(Color::white(), Color::black());
+        stripes.transform = Matrix4::scaling(0.2, 0.2, 0.2);
+        w.objects[0].material_mut().pattern = Some(Arc::new(stripes));
+        // odd sizes leave blocks of pixels cut off at the edges
+        let mut c = Camera::new(21, 19, FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = Matrix4::view_transform(from, to, up);
+        let image = c.render(&w);
+        for y in 0..19 {
+            for x in 0..21 {
+                assert_eq!(
+                    image.pixel_at(x, y),
+                    Scene::new(&w).color_at(c.pixel_ray(x, y))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rendering_false_color_modes() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        c.mode = RenderMode::Normals;
+        let image = c.render(&World::default());
+        assert_eq!(image.pixel_at(5, 5), Color::new(0.5, 0.5, 0.0));
+        assert_eq!(image.pixel_at(0, 0), Color::black());
+        c.mode = RenderMode::Albedo;
+        let image = c.render(&World::default());
+        assert_eq!(image.pixel_at(5, 5), Color::new(0.8, 1.0, 0.6));
+        c.mode = RenderMode::Depth;
+        let image = c.render(&World::default());
+        assert_eq!(image.pixel_at(5, 5), Color::black());
+        assert_eq!(image.pixel_at(0, 0), Color::white());
+        c.mode = RenderMode::UV;
+        let image = c.render(&World::default());
+        assert_eq!(image.pixel_at(5, 4).red, image.pixel_at(5, 6).red);
+        assert!(image.pixel_at(5, 4).green > image.pixel_at(5, 6).green);
+    }
+
+    #[test]
+    fn test_a_round_aperture_samples_a_disk() {
+        let lens = Lens::new(0.5, 10.0);
+        let points = lens.sample_points(Sampler::Fibonacci, &mut Rng::new(0));
+        assert_eq!(points.len(), 16);
+        for (x, y) in points {
+            assert!((x * x + y * y).sqrt() <= 0.5);
+        }
+    }
+
+    #[test]
+    fn test_a_bladed_aperture_samples_a_polygon() {
+        let mut lens = Lens::new(1.0, 10.0);
+        lens.blades = 6;
+        lens.samples = 200;
+        let points = lens.sample_points(Sampler::Fibonacci, &mut Rng::new(0));
+        // every point lies within all six half-planes of the hexagon,
+        // whose edges are cos(30 degrees) from the center
+        for &(x, y) in points.iter() {
+            for i in 0..6 {
+                let angle = PI / 6.0 + (i as f64) * PI / 3.0;
+                let distance = x * angle.cos() + y * angle.sin();
+                assert!(distance <= (PI / 6.0).cos() + EPSILON);
+            }
+        }
+        // and some reach out into the corners, beyond the inner circle
+        assert!(points
+            .iter()
+            .any(|&(x, y)| (x * x + y * y).sqrt() > (PI / 6.0).cos()));
+    }
+
+    #[test]
+    fn test_lens_rays_converge_on_the_focal_plane() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let lens = Lens::new(0.5, 4.0);
+        let rays = c.lens_rays(
+            &lens,
+            c.transform.inverse(),
+            (5.5, 5.5),
+            Sampler::Fibonacci,
+            &mut Rng::new(0),
+        );
+        assert_eq!(rays.len(), 16);
+        for r in rays {
+            let t = 4.0 / r.direction.z;
+            assert_eq!(r.position(t), Tuple::point(0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn test_rendering_with_depth_of_field() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let sharp = c.render(&World::default());
+        c.lens = Some(Lens::new(0.5, 2.0));
+        let blurred = c.render(&World::default());
+        // the focus is two units in front of the sphere, so its edge is
+        // blurred while the empty background stays the same
+        assert_eq!(blurred.pixel_at(0, 0), sharp.pixel_at(0, 0));
+        assert_ne!(blurred.pixel_at(4, 5), sharp.pixel_at(4, 5));
+    }
+
+    #[test]
+    fn test_the_seed_decides_the_random_samples() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let mut lens = Lens::new(0.5, 2.0);
+        lens.samples = 4;
+        c.lens = Some(lens);
+        let first = c.render(&World::default());
+        assert_eq!(c.render(&World::default()), first);
+        c.seed = 1;
+        assert_ne!(c.render(&World::default()), first);
+    }
+
+    #[test]
+    fn test_rendering_a_draft() {
+        let mut w = World::default();
+        w.objects[0].material_mut().reflective = 0.5;
+        let mut c = Camera::new(44, 44, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        c.quality = Quality::Draft;
+        let image = c.render(&w);
+        assert_eq!(image.width, 44);
+        assert_eq!(image.height, 44);
+        assert_eq!(image.pixel_at(20, 20), image.pixel_at(23, 23));
+        assert_ne!(image.pixel_at(20, 20), image.pixel_at(24, 24));
+    }
+
+    #[test]
+    fn test_timing_every_pixel_of_a_render() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let (image, times) = c.render_timed(&w);
+        assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(times.width, 11);
+        assert_eq!(times.height, 11);
+        assert!(times.values().iter().all(|&t| t >= 0.0));
+        assert!(times.values().iter().sum::<f64>() > 0.0);
+    }
+
+    #[test]
+    fn test_picking_the_object_at_a_pixel() {
+        let mut w = World::default();
+        w.objects[0].set_name(Some("outer".to_string()));
+        w.objects[1].set_name(Some("inner".to_string()));
+        w.objects[1].set_transform(Matrix4::translation(0.0, 2.0, -2.0));
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let name_at = |w: &World, x, y| {
+            w.object_at_pixel(&c, x, y)
+                .map(|object| object.name().unwrap().to_string())
+        };
+        assert_eq!(name_at(&w, 5, 5).as_deref(), Some("outer"));
+        assert_eq!(name_at(&w, 5, 2).as_deref(), Some("inner"));
+        assert_eq!(name_at(&w, 0, 10), None);
+        // names stay with their objects when the objects are reordered
+        w.objects.swap(0, 1);
+        assert_eq!(name_at(&w, 5, 2).as_deref(), Some("inner"));
+        assert_eq!(w.index_of("inner"), Some(0));
+        assert_eq!(w.index_of("missing"), None);
+    }
+
+    #[test]
+    fn test_rendering_the_depth_of_a_world() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let depth = c.render_depth(&Scene::new(&w));
+        assert_eq!(depth.value_at(5, 5), Some(4.0));
+        assert_eq!(depth.value_at(0, 0), None);
+    }
+
+    #[test]
+    fn test_buffers_see_through_holdout_objects() {
+        let mut w = World::default();
+        w.objects[0].material_mut().holdout = true;
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let scene = Scene::new(&w);
+        assert_eq!(c.render_depth(&scene).value_at(5, 5), Some(4.5));
+        assert_eq!(c.render_object_ids(&scene).value_at(5, 5), Some(1));
+        let picked = c.object_at_pixel(&scene, 5, 5).unwrap();
+        assert!(picked == &*w.objects[1]);
+        c.mode = RenderMode::Albedo;
+        let image = c.render_scene(&scene);
+        assert_eq!(image.pixel_at(5, 5), w.objects[1].material().color);
+    }
+
+    #[test]
+    fn test_rendering_the_normals_of_a_world() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let normals = c.render_normals(&Scene::new(&w));
+        assert_eq!(normals.value_at(5, 5), Some(Tuple::vector(0.0, 0.0, -1.0)));
+        assert_eq!(normals.value_at(0, 0), None);
+    }
+
+    #[test]
+    fn test_rendering_the_object_ids_of_a_world() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let ids = c.render_object_ids(&Scene::new(&w));
+        assert_eq!(ids.value_at(5, 5), Some(0));
+        assert_eq!(ids.value_at(0, 0), None);
+    }
+
+    #[test]
+    fn test_projecting_points_back_onto_the_canvas() {
+        let mut c = Camera::new(201, 101, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(1.0, 2.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let r = c.pixel_to_ray(100.5, 50.5);
+        assert_eq!(r.direction, c.pixel_ray(100, 50).direction);
+        let (x, y) = c.project_point(r.position(3.0)).unwrap();
+        assert!(equal_f64(x, 100.5) && equal_f64(y, 50.5));
+        let (x, y) = c
+            .project_point(c.pixel_to_ray(12.0, 80.25).position(7.0))
+            .unwrap();
+        assert!((x - 12.0).abs() < 0.001 && (y - 80.25).abs() < 0.001);
+        let behind = c.pixel_to_ray(100.5, 50.5).position(-1.0);
+        assert_eq!(c.project_point(behind), None);
+    }
+
+    #[test]
+    fn test_camera_rays_carry_differentials_to_neighboring_pixels() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let inverse_transform = c.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        let r = c.ray_for_pixel(inverse_transform, origin, 100, 50);
+        let right = c.ray_for_pixel(inverse_transform, origin, 101, 50);
+        let below = c.ray_for_pixel(inverse_transform, origin, 100, 51);
+        let d = r.differential.unwrap();
+        assert_eq!(d.x_origin, origin);
+        assert_eq!(d.x_direction, right.direction);
+        assert_eq!(d.y_origin, origin);
+        assert_eq!(d.y_direction, below.direction);
+    }
+
+    #[test]
+    fn test_the_pixel_footprint_grows_with_distance() {
+        let mut c = Camera::new(201, 101, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let inverse_transform = c.transform.inverse();
+        let origin = inverse_transform * Tuple::point(0.0, 0.0, 0.0);
+        let r = c.ray_for_pixel(inverse_transform, origin, 100, 50);
+        let s = Sphere::default();
+        let mut i = Intersection::new(4.0, &s);
+        i.prepare_hit(r);
+        let near = i.footprint().unwrap();
+        assert!((near - 0.04).abs() < 0.001);
+        let mut far = Intersection::new(8.0, &s);
+        far.prepare_hit(r);
+        assert!(far.footprint().unwrap() > near);
+    }
 }
@@ -1,11 +1,22 @@
 use crate::clamp_i32;
-use crate::color::Color;
+use crate::color::{Color, ColorSpace};
+use crate::font;
+use crate::metadata::RenderInfo;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
 const PPM_LINE_LENGTH: usize = 70;
 
+#[derive(Clone, PartialEq, Debug)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
+    /// How the pixels are encoded. Renders are linear; images loaded
+    /// from files are usually sRGB, and are decoded wherever they are
+    /// sampled as textures.
+    pub color_space: ColorSpace,
     pixels: Vec<Color>,
 }
 
@@ -15,6 +26,7 @@ impl Canvas {
         Canvas {
             width,
             height,
+            color_space: ColorSpace::Linear,
             pixels,
         }
     }
@@ -27,49 +39,501 @@ impl Canvas {
         self.pixels[y * self.width + x]
     }
 
+    /// Returns the pixel as a linear color, decoding it if the canvas is
+    /// sRGB-encoded. Anything that samples a canvas for shading reads
+    /// pixels this way.
+    pub fn linear_pixel_at(&self, x: usize, y: usize) -> Color {
+        match self.color_space {
+            ColorSpace::Linear => self.pixel_at(x, y),
+            ColorSpace::Srgb => self.pixel_at(x, y).to_linear(),
+        }
+    }
+
+    /// Returns the pixel encoded for an image file: linear pixels are
+    /// encoded with the sRGB curve, and sRGB ones are left as they are.
+    fn encoded_pixel_at(&self, x: usize, y: usize) -> Color {
+        match self.color_space {
+            ColorSpace::Linear => self.pixel_at(x, y).to_srgb(),
+            ColorSpace::Srgb => self.pixel_at(x, y),
+        }
+    }
+
+    /// Returns a copy encoded with the sRGB curve, for writing to image
+    /// files meant for display. A canvas that is already sRGB-encoded is
+    /// copied as it is, so it is never encoded twice.
+    pub fn to_srgb(&self) -> Canvas {
+        self.converted(ColorSpace::Srgb, |color| color.to_srgb())
+    }
+
+    /// Returns a copy with linear values, decoding an sRGB-encoded canvas.
+    /// A canvas that is already linear is copied as it is.
+    pub fn to_linear(&self) -> Canvas {
+        self.converted(ColorSpace::Linear, |color| color.to_linear())
+    }
+
+    fn converted<F>(&self, color_space: ColorSpace, convert: F) -> Canvas
+    where
+        F: Fn(&Color) -> Color,
+    {
+        let mut canvas = self.clone();
+        if self.color_space != color_space {
+            canvas.pixels = self.pixels.iter().map(convert).collect();
+            canvas.color_space = color_space;
+        }
+        canvas
+    }
+
+    /// Draws a one pixel wide line between two points given in pixels,
+    /// clipped to the canvas.
+    pub fn draw_line(
+        &mut self,
+        from: (f64, f64),
+        to: (f64, f64),
+        color: Color,
+    ) {
+        // Liang-Barsky clipping against the canvas rectangle
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (mut start, mut end) = (0.0_f64, 1.0_f64);
+        let edges = [
+            (-dx, from.0),
+            (dx, self.width as f64 - from.0),
+            (-dy, from.1),
+            (dy, self.height as f64 - from.1),
+        ];
+        for &(p, q) in edges.iter() {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else if p < 0.0 {
+                start = start.max(q / p);
+            } else {
+                end = end.min(q / p);
+            }
+        }
+        if start > end {
+            return;
+        }
+        let steps =
+            ((dx.abs().max(dy.abs()) * (end - start)).ceil() as usize).max(1);
+        for i in 0..=steps {
+            let t = start + (end - start) * (i as f64) / (steps as f64);
+            let x = (from.0 + dx * t).floor();
+            let y = (from.1 + dy * t).floor();
+            if x >= 0.0 && y >= 0.0 {
+                let (x, y) = (x as usize, y as usize);
+                if x < self.width && y < self.height {
+                    self.write_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Stamps text onto the canvas with a tiny built-in bitmap font, with
+    /// the top-left corner of the first character at (x, y) and every
+    /// font pixel drawn as a `scale` by `scale` square. Letters are drawn
+    /// in upper case, `\n` starts a new line, and anything that falls
+    /// outside the canvas is clipped.
+    pub fn draw_text(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: &str,
+        color: Color,
+        scale: usize,
+    ) {
+        let (mut left, mut top) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                left = x;
+                top += font::LINE_HEIGHT * scale;
+                continue;
+            }
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for column in 0..font::GLYPH_WIDTH {
+                    if bits & (1 << (font::GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let px = left + column * scale + dx;
+                            let py = top + row * scale + dy;
+                            if px < self.width && py < self.height {
+                                self.write_pixel(px, py, color);
+                            }
+                        }
+                    }
+                }
+            }
+            left += font::ADVANCE * scale;
+        }
+    }
+
+    /// Writes the pixels sRGB-encoded, as image files usually are:
+    /// a linear canvas is encoded on the way out, and an sRGB one is
+    /// written as it is, so reading the file back gives the same colors.
     pub fn to_ppm(&self) -> String {
-        let mut ppm = String::new();
-        ppm.push_str(
-            format!("P3\n{} {}\n255\n", self.width, self.height).as_str(),
-        );
-        let mut values: Vec<i32> = Vec::new();
-        for pixel in self.pixels.iter() {
-            let scaled_color = *pixel * 255.0;
-            let red = clamp_i32(scaled_color.red.round() as i32, 0, 255);
-            let green = clamp_i32(scaled_color.green.round() as i32, 0, 255);
-            let blue = clamp_i32(scaled_color.blue.round() as i32, 0, 255);
-            values.push(red);
-            values.push(green);
-            values.push(blue);
-        }
-        let mut line = String::new();
-        for (i, value) in values.iter().enumerate() {
-            let value = format!("{}", value);
-            if line.len() + 1 + value.len() >= PPM_LINE_LENGTH {
-                line.push('\n');
-                ppm.push_str(line.as_str());
-                line = String::new();
+        self.to_ppm_with_comments(&[])
+    }
+
+    /// Writes the pixels like `to_ppm`, recording how the image was
+    /// rendered in comments after the first line of the header, such as
+    /// `# samples: 16`.
+    pub fn to_ppm_with_info(&self, info: &RenderInfo) -> String {
+        self.to_ppm_with_comments(&info.entries())
+    }
+
+    fn to_ppm_with_comments(&self, text: &[(&str, String)]) -> String {
+        let mut ppm = Vec::new();
+        self.write_ppm_with_comments(&mut ppm, text)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(ppm).expect("PPM output is ASCII")
+    }
+
+    /// Writes the same file as `to_ppm` straight into `out` a row at a
+    /// time, without building the whole file in memory first. Each row is
+    /// a separate write, so files and stdout are best wrapped in a
+    /// `BufWriter`.
+    pub fn write_ppm<W: Write>(&self, out: W) -> io::Result<()> {
+        self.write_ppm_with_comments(out, &[])
+    }
+
+    fn write_ppm_with_comments<W: Write>(
+        &self,
+        mut out: W,
+        text: &[(&str, String)],
+    ) -> io::Result<()> {
+        let comments: Vec<String> = text
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        write_ppm(&mut out, self.width, self.height, &comments, |x, y| {
+            self.encoded_pixel_at(x, y)
+        })
+    }
+
+    /// Encodes the pixels in an 8-bit RGB PNG file, sRGB-encoded like
+    /// `to_ppm`. PNG files can't be empty or wider or taller than
+    /// `u32::MAX` pixels, so such canvases can't be encoded.
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        self.to_png_with_text(&[])
+    }
+
+    /// Encodes the pixels like `to_png`, recording how the image was
+    /// rendered in text chunks, one for each setting.
+    pub fn to_png_with_info(&self, info: &RenderInfo) -> io::Result<Vec<u8>> {
+        self.to_png_with_text(&info.entries())
+    }
+
+    fn to_png_with_text(&self, text: &[(&str, String)]) -> io::Result<Vec<u8>> {
+        let too_large = |_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "a {}x{} image is too large for PNG",
+                    self.width, self.height
+                ),
+            )
+        };
+        let width = u32::try_from(self.width).map_err(too_large)?;
+        let height = u32::try_from(self.height).map_err(too_large)?;
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (keyword, value) in text {
+            encoder
+                .add_text_chunk(keyword.to_string(), value.clone())
+                .map_err(invalid_png)?;
+        }
+        let data: Vec<u8> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let color = self.encoded_pixel_at(x, y);
+                vec![
+                    channel_byte(color.red),
+                    channel_byte(color.green),
+                    channel_byte(color.blue),
+                ]
+            })
+            .collect();
+        let mut writer = encoder.write_header().map_err(invalid_png)?;
+        writer.write_image_data(&data).map_err(invalid_png)?;
+        writer.finish().map_err(invalid_png)?;
+        Ok(png)
+    }
+
+    /// Writes the image to a file, as a PPM or PNG file depending on the
+    /// path's extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save_as(path.as_ref(), &[])
+    }
+
+    /// Writes the image to a file like `save`, recording how the image
+    /// was rendered.
+    pub fn save_with_info<P: AsRef<Path>>(
+        &self,
+        path: P,
+        info: &RenderInfo,
+    ) -> io::Result<()> {
+        self.save_as(path.as_ref(), &info.entries())
+    }
+
+    fn save_as(&self, path: &Path, text: &[(&str, String)]) -> io::Result<()> {
+        match image_format(path)? {
+            ImageFormat::Ppm => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_ppm_with_comments(&mut out, text)?;
+                out.flush()
+            }
+            ImageFormat::Png => fs::write(path, self.to_png_with_text(text)?),
+        }
+    }
+
+    /// Reads a PPM file, either plain (`P3`) or binary (`P6`). The
+    /// pixels are scaled from the file's maximum value to [0, 1], and the
+    /// canvas is tagged as sRGB, as image files usually are.
+    pub fn from_ppm<R: Read>(mut reader: R) -> io::Result<Canvas> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let mut words = PpmWords {
+            bytes: &bytes,
+            position: 0,
+        };
+        let binary = match words.next() {
+            Some("P3") => false,
+            Some("P6") => true,
+            _ => {
+                return Err(invalid_image("expected the P3 or P6 magic number"))
+            }
+        };
+        let number = |words: &mut PpmWords, what: &str| {
+            words
+                .next()
+                .and_then(|word| word.parse::<usize>().ok())
+                .ok_or_else(|| invalid_image(&format!("expected {}", what)))
+        };
+        let width = number(&mut words, "the width")?;
+        let height = number(&mut words, "the height")?;
+        let max_value = number(&mut words, "the maximum value")?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid_image("the maximum value must be 1 to 65535"));
+        }
+        // every channel takes at least a byte, so this also stops huge
+        // sizes in the header from allocating huge canvases
+        let channels = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .filter(|&channels| channels <= bytes.len())
+            .ok_or_else(|| invalid_image("the file is too short"))?;
+
+        let values: Vec<usize> = if binary {
+            // a single whitespace character separates the header from
+            // the pixels, which take two bytes each above 255
+            let data = bytes.get(words.position + 1..).unwrap_or_default();
+            if max_value < 256 {
+                data.iter().take(channels).map(|&b| b as usize).collect()
+            } else {
+                data.chunks_exact(2)
+                    .take(channels)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+                    .collect()
             }
-            if !line.is_empty() {
-                line.push(' ');
+        } else {
+            (0..channels)
+                .map_while(|_| words.next()?.parse::<usize>().ok())
+                .collect()
+        };
+        if values.len() < channels {
+            return Err(invalid_image("expected more pixels"));
+        }
+        if values.iter().any(|&value| value > max_value) {
+            return Err(invalid_image("a pixel is above the maximum value"));
+        }
+        let scale = |value: usize| value as f64 / max_value as f64;
+        let mut canvas = Canvas::new(width, height);
+        canvas.color_space = ColorSpace::Srgb;
+        for (pixel, rgb) in canvas.pixels.iter_mut().zip(values.chunks(3)) {
+            *pixel = Color::new(scale(rgb[0]), scale(rgb[1]), scale(rgb[2]));
+        }
+        Ok(canvas)
+    }
+
+    /// Reads a PNG file of any color type and bit depth, ignoring any
+    /// transparency, and tags the canvas as sRGB.
+    pub fn from_png<R: Read>(reader: R) -> io::Result<Canvas> {
+        let mut decoder = png::Decoder::new(reader);
+        // expands palettes and bit depths below 8 into whole bytes
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder
+            .read_info()
+            .map_err(|error| invalid_image(&error.to_string()))?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let frame = reader
+            .next_frame(&mut data)
+            .map_err(|error| invalid_image(&error.to_string()))?;
+        let sample = |index: usize| match frame.bit_depth {
+            png::BitDepth::Sixteen => {
+                let pair = [data[index * 2], data[index * 2 + 1]];
+                u16::from_be_bytes(pair) as f64 / 65535.0
             }
-            line.push_str(value.as_str());
-            if (i + 1) % (self.width * 3) == 0 {
-                line.push('\n');
-                ppm.push_str(line.as_str());
-                line = String::new();
+            _ => data[index] as f64 / 255.0,
+        };
+        let samples = frame.color_type.samples();
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let mut canvas = Canvas::new(width, height);
+        canvas.color_space = ColorSpace::Srgb;
+        for (index, pixel) in canvas.pixels.iter_mut().enumerate() {
+            let first = index * samples;
+            *pixel = if samples < 3 {
+                let gray = sample(first);
+                Color::new(gray, gray, gray)
+            } else {
+                Color::new(sample(first), sample(first + 1), sample(first + 2))
+            };
+        }
+        Ok(canvas)
+    }
+
+    /// Reads a PPM or PNG file, depending on the path's extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Canvas> {
+        let path = path.as_ref();
+        match image_format(path)? {
+            ImageFormat::Ppm => {
+                Canvas::from_ppm(BufReader::new(File::open(path)?))
+            }
+            ImageFormat::Png => {
+                Canvas::from_png(BufReader::new(File::open(path)?))
+            }
+        }
+    }
+}
+
+enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+/// Tells the format of an image file from its extension.
+fn image_format(path: &Path) -> io::Result<ImageFormat> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("ppm") => Ok(ImageFormat::Ppm),
+        Some("png") => Ok(ImageFormat::Png),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "can't tell the image format of {}, which should end in \
+                 .ppm or .png",
+                path.display()
+            ),
+        )),
+    }
+}
+
+/// Splits the text of a PPM file into words, skipping whitespace and
+/// comments, which run from `#` to the end of the line.
+struct PpmWords<'a> {
+    bytes: &'a [u8],
+    /// Where the next word is looked for, which is just after the last
+    /// word found.
+    position: usize,
+}
+
+impl<'a> PpmWords<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let bytes = self.bytes;
+        let mut position = self.position;
+        loop {
+            match bytes.get(position)? {
+                b'#' => {
+                    while bytes.get(position).is_some_and(|&b| b != b'\n') {
+                        position += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => position += 1,
+                _ => break,
+            }
+        }
+        let start = position;
+        while bytes
+            .get(position)
+            .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'#')
+        {
+            position += 1;
+        }
+        self.position = position;
+        std::str::from_utf8(&bytes[start..position]).ok()
+    }
+}
+
+/// Scales a channel from [0, 1] to a byte, clamping it to the range.
+fn channel_byte(channel: f64) -> u8 {
+    clamp_i32((channel * 255.0).round() as i32, 0, 255) as u8
+}
+
+fn invalid_image(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reports a canvas the PNG encoder rejected, such as an empty one.
+fn invalid_png(error: png::EncodingError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+/// Encodes an image as a plain PPM file, one line at a time, asking
+/// `pixel_at` for the pixels in row order. Each comment is written on
+/// its own line after the magic number.
+pub(crate) fn write_ppm<W, F>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    comments: &[String],
+    pixel_at: F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(usize, usize) -> Color,
+{
+    writeln!(out, "P3")?;
+    for comment in comments {
+        writeln!(out, "# {}", comment)?;
+    }
+    write!(out, "{} {}\n255\n", width, height)?;
+    let mut line = String::new();
+    for y in 0..height {
+        for x in 0..width {
+            let color = pixel_at(x, y);
+            for &channel in [color.red, color.green, color.blue].iter() {
+                let value = channel_byte(channel).to_string();
+                if line.len() + 1 + value.len() >= PPM_LINE_LENGTH {
+                    line.push('\n');
+                    out.write_all(line.as_bytes())?;
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(value.as_str());
             }
         }
         line.push('\n');
-        ppm.push_str(line.as_str());
-        ppm
+        out.write_all(line.as_bytes())?;
+        line.clear();
     }
+    out.write_all(b"\n")
 }
 
 #[cfg(test)]
 mod tests {
     use crate::canvas::Canvas;
-    use crate::color::Color;
+    use crate::color::{Color, ColorSpace};
+    use crate::metadata::RenderInfo;
+    use std::io;
+    use std::time::Duration;
 
     #[test]
     fn test_creating_a_canvas() {
@@ -102,6 +566,8 @@ mod tests {
     #[test]
     fn test_constructing_the_ppm_pixel_data() {
         let mut c = Canvas::new(5, 3);
+        // the book's values are already encoded for display
+        c.color_space = ColorSpace::Srgb;
         let c1 = Color::new(1.5, 0.0, 0.0);
         let c2 = Color::new(0.0, 0.5, 0.0);
         let c3 = Color::new(-0.5, 0.0, 1.0);
@@ -118,6 +584,7 @@ mod tests {
     #[test]
     fn test_splitting_long_lines_in_ppm_files() {
         let mut c = Canvas::new(10, 2);
+        c.color_space = ColorSpace::Srgb;
         for y in 0..c.height {
             for x in 0..c.width {
                 c.write_pixel(x, y, Color::new(1.0, 0.8, 0.6));
@@ -143,10 +610,300 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_recording_render_settings_in_ppm_comments() {
+        let c = Canvas::new(5, 3);
+        let info = RenderInfo {
+            scene_hash: 0xabc,
+            width: 5,
+            height: 3,
+            samples: 4,
+            duration: Duration::from_millis(250),
+        };
+        let ppm = c.to_ppm_with_info(&info);
+        let mut lines = ppm.lines();
+        assert_eq!(Some("P3"), lines.next());
+        assert_eq!(Some("# scene-hash: 0000000000000abc"), lines.next());
+        assert_eq!(Some("# resolution: 5x3"), lines.next());
+        assert_eq!(Some("# samples: 4"), lines.next());
+        assert_eq!(Some("# duration: 0.250s"), lines.next());
+        assert_eq!(Some("5 3"), lines.next());
+        // the pixels are the same as without the comments
+        assert!(lines.eq(c.to_ppm().lines().skip(2)));
+    }
+
+    #[test]
+    fn test_reading_a_ppm_file() {
+        let ppm = "P3\n\
+                   # a comment\n\
+                   2 1 # width and height\n\
+                   255\n\
+                   255 0 51 0 255 255\n";
+        let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!((c.width, c.height), (2, 1));
+        assert_eq!(c.color_space, ColorSpace::Srgb);
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.0, 0.2));
+        assert_eq!(c.pixel_at(1, 0), Color::new(0.0, 1.0, 1.0));
+        // what the canvas writes it reads back
+        let mut written = Canvas::new(4, 3);
+        written.color_space = ColorSpace::Srgb;
+        written.write_pixel(3, 2, Color::new(0.2, 0.4, 0.6));
+        let read = Canvas::from_ppm(written.to_ppm().as_bytes()).unwrap();
+        assert_eq!(read.pixel_at(3, 2), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn test_reading_a_binary_ppm_file() {
+        let mut ppm = b"P6 # binary\n2 1\n255\n".to_vec();
+        ppm.extend_from_slice(&[255, 0, 51, 0, 32, 10]);
+        let c = Canvas::from_ppm(ppm.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.0, 0.2));
+        // the whitespace after the header isn't mistaken for a pixel
+        assert_eq!(
+            c.pixel_at(1, 0),
+            Color::new(0.0, 32.0 / 255.0, 10.0 / 255.0)
+        );
+        let mut ppm = b"P6\n1 1\n1000\n".to_vec();
+        ppm.extend_from_slice(&[0x03, 0xe8, 0x01, 0xf4, 0, 0]);
+        let c = Canvas::from_ppm(ppm.as_slice()).unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_reading_a_png_file() {
+        let mut c = Canvas::new(3, 2);
+        c.color_space = ColorSpace::Srgb;
+        c.write_pixel(2, 1, Color::new(1.0, 0.2, 0.4));
+        let read = Canvas::from_png(c.to_png().unwrap().as_slice()).unwrap();
+        assert_eq!((read.width, read.height), (3, 2));
+        assert_eq!(read.color_space, ColorSpace::Srgb);
+        assert_eq!(read.pixel_at(2, 1), Color::new(1.0, 0.2, 0.4));
+        // a 16-bit grayscale image with an alpha channel
+        let mut png = vec![];
+        let mut encoder = png::Encoder::new(&mut png, 1, 1);
+        encoder.set_color(png::ColorType::GrayscaleAlpha);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0x80, 0x00, 0xff, 0xff]).unwrap();
+        writer.finish().unwrap();
+        let read = Canvas::from_png(png.as_slice()).unwrap();
+        let gray = 32768.0 / 65535.0;
+        assert_eq!(read.pixel_at(0, 0), Color::new(gray, gray, gray));
+        let error = Canvas::from_png(&b"not a png"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_loading_picks_the_format_from_the_extension() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(1, 0, Color::white());
+        let dir = std::env::temp_dir();
+        for name in ["load.ppm", "load.png"].iter() {
+            let path =
+                dir.join(format!("canvas-{}-{}", std::process::id(), name));
+            c.save(&path).unwrap();
+            let loaded = Canvas::load(&path).unwrap();
+            assert_eq!(loaded.pixel_at(1, 0), Color::white());
+            assert_eq!(loaded.pixel_at(0, 1), Color::black());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_a_saved_linear_canvas_loads_back_the_same() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(0.5, 0.2, 0.05));
+        let dir = std::env::temp_dir();
+        for name in ["linear.ppm", "linear.png"].iter() {
+            let path =
+                dir.join(format!("canvas-{}-{}", std::process::id(), name));
+            c.save(&path).unwrap();
+            let loaded = Canvas::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            // encoded once on the way out and decoded once on the way in
+            assert_eq!(loaded.color_space, ColorSpace::Srgb);
+            let pixel = loaded.linear_pixel_at(0, 0);
+            let expected = c.pixel_at(0, 0);
+            for (a, b) in [
+                (pixel.red, expected.red),
+                (pixel.green, expected.green),
+                (pixel.blue, expected.blue),
+            ]
+            .iter()
+            {
+                assert!((a - b).abs() < 0.005, "{}: {} != {}", name, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_ppm_files_are_rejected() {
+        for ppm in [
+            "P6\n1 1\n255\n",
+            "P5\n1 1\n255\n0\n",
+            "P3\n100000 100000\n255\n0 0 0\n",
+            "P3\n1 1\n",
+            "P3\n1 1\n0\n0 0 0\n",
+            "P3\n2 1\n255\n0 0 0\n",
+            "P3\n1 1\n10\n11 0 0\n",
+            "P3\n1 1\n255\nred green blue\n",
+        ]
+        .iter()
+        {
+            let error = Canvas::from_ppm(ppm.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", ppm);
+        }
+    }
+
+    #[test]
+    fn test_encoding_a_png_file() {
+        let mut c = Canvas::new(2, 1);
+        c.color_space = ColorSpace::Srgb;
+        c.write_pixel(0, 0, Color::new(1.5, 0.5, -0.5));
+        let png = c.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut data).unwrap();
+        assert_eq!((frame.width, frame.height), (2, 1));
+        assert_eq!(&data[..6], &[255, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_empty_canvases_cant_be_encoded_as_png() {
+        let error = Canvas::new(0, 3).to_png().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let path = std::env::temp_dir()
+            .join(format!("empty-canvas-{}.png", std::process::id()));
+        assert!(Canvas::new(3, 0).save(&path).is_err());
+    }
+
+    #[test]
+    fn test_recording_render_settings_in_png_text_chunks() {
+        let c = Canvas::new(2, 2);
+        let info = RenderInfo {
+            scene_hash: 1,
+            width: 2,
+            height: 2,
+            samples: 1,
+            duration: Duration::from_secs(3),
+        };
+        let png = c.to_png_with_info(&info).unwrap();
+        let reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let text = &reader.info().uncompressed_latin1_text;
+        assert_eq!(text.len(), 4);
+        assert_eq!(text[1].keyword, "resolution");
+        assert_eq!(text[1].text, "2x2");
+    }
+
+    #[test]
+    fn test_saving_picks_the_format_from_the_extension() {
+        let c = Canvas::new(3, 2);
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("canvas-{}.PNG", std::process::id()));
+        c.save(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), c.to_png().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let path = dir.join(format!("canvas-{}.ppm", std::process::id()));
+        c.save(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), c.to_ppm());
+        std::fs::remove_file(&path).unwrap();
+        let error = c.save(dir.join("canvas.gif")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn test_ppm_files_are_terminated_by_a_newline_character() {
         let c = Canvas::new(5, 3);
         let ppm = c.to_ppm();
         assert!(ppm.ends_with('\n'));
     }
+
+    #[test]
+    fn test_streaming_a_ppm_file() {
+        let mut c = Canvas::new(30, 4);
+        c.write_pixel(3, 2, Color::new(1.0, 0.5, 0.25));
+        let mut ppm = Vec::new();
+        c.write_ppm(&mut ppm).unwrap();
+        assert_eq!(String::from_utf8(ppm).unwrap(), c.to_ppm());
+    }
+
+    /// Returns the canvas as rows of '#' (lit) and '.' (dark) pixels.
+    fn ascii_art(c: &Canvas) -> Vec<String> {
+        (0..c.height)
+            .map(|y| {
+                (0..c.width)
+                    .map(|x| {
+                        if c.pixel_at(x, y) == Color::black() {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_drawing_lines_on_a_canvas() {
+        let mut c = Canvas::new(5, 4);
+        c.draw_line((0.5, 0.5), (4.5, 0.5), Color::white());
+        c.draw_line((0.5, 1.5), (2.5, 3.5), Color::white());
+        assert_eq!(ascii_art(&c), ["#####", "#....", ".#...", "..#.."]);
+    }
+
+    #[test]
+    fn test_lines_outside_the_canvas_are_clipped() {
+        let mut c = Canvas::new(3, 3);
+        c.draw_line((-100.0, 1.5), (100.0, 1.5), Color::white());
+        c.draw_line((-5.0, -5.0), (-1.0, 10.0), Color::white());
+        assert_eq!(ascii_art(&c), ["...", "###", "..."]);
+    }
+
+    #[test]
+    fn test_drawing_text_on_a_canvas() {
+        let mut c = Canvas::new(8, 5);
+        c.draw_text(0, 0, "1a", Color::white(), 1);
+        assert_eq!(
+            ascii_art(&c),
+            [".#...#..", "##..#.#.", ".#..###.", ".#..#.#.", "###.#.#."]
+        );
+    }
+
+    #[test]
+    fn test_drawing_scaled_text_on_multiple_lines() {
+        let mut c = Canvas::new(6, 22);
+        c.draw_text(0, 0, "-\n_", Color::white(), 2);
+        let art = ascii_art(&c);
+        assert_eq!(art[3], "......");
+        assert_eq!(art[4], "######");
+        assert_eq!(art[5], "######");
+        assert_eq!(art[19], "......");
+        assert_eq!(art[20], "######");
+        assert_eq!(art[21], "######");
+    }
+
+    #[test]
+    fn test_text_outside_the_canvas_is_clipped() {
+        let mut c = Canvas::new(4, 4);
+        c.draw_text(2, 2, "frame 12", Color::white(), 3);
+        assert_eq!(c.pixel_at(0, 0), Color::black());
+        assert_eq!(c.pixel_at(2, 2), Color::white());
+    }
+
+    #[test]
+    fn test_converting_a_canvas_between_color_spaces() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(0.5, 0.0, 1.0));
+        assert_eq!(c.color_space, ColorSpace::Linear);
+        let encoded = c.to_srgb();
+        assert_eq!(encoded.color_space, ColorSpace::Srgb);
+        assert_eq!(encoded.pixel_at(0, 0), Color::new(0.73536, 0.0, 1.0));
+        assert_eq!(encoded.linear_pixel_at(0, 0), c.pixel_at(0, 0));
+        // converting to the space a canvas is already in changes nothing
+        assert_eq!(encoded.to_srgb(), encoded);
+        assert_eq!(encoded.to_linear(), c);
+    }
 }
@@ -0,0 +1,184 @@
+use crate::camera::{Camera, Quality};
+use crate::canvas::{write_ppm, Canvas};
+use crate::color::Color;
+use crate::scene::Scene;
+use crate::world::World;
+use std::io::{self, Write};
+use std::thread;
+
+/// About how many pixels are stored together in one chunk.
+const CHUNK_PIXELS: usize = 1 << 20;
+
+/// A canvas for very large images, such as poster-size renders, stored as
+/// bands of rows that are only allocated once something is written to
+/// them, so the whole image never needs to fit in one allocation. Rows
+/// that were never written are black.
+#[derive(Clone, Debug)]
+pub struct ChunkedCanvas {
+    pub width: usize,
+    pub height: usize,
+    rows_per_chunk: usize,
+    chunks: Vec<Option<Vec<Color>>>,
+}
+
+impl ChunkedCanvas {
+    pub fn new(width: usize, height: usize) -> ChunkedCanvas {
+        let rows_per_chunk = (CHUNK_PIXELS / width.max(1)).max(1);
+        ChunkedCanvas::with_rows_per_chunk(width, height, rows_per_chunk)
+    }
+
+    /// Creates a canvas whose chunks hold the given number of rows.
+    pub fn with_rows_per_chunk(
+        width: usize,
+        height: usize,
+        rows_per_chunk: usize,
+    ) -> ChunkedCanvas {
+        assert!(rows_per_chunk > 0);
+        ChunkedCanvas {
+            width,
+            height,
+            rows_per_chunk,
+            chunks: vec![None; height.div_ceil(rows_per_chunk)],
+        }
+    }
+
+    /// Returns the number of chunks that have been allocated.
+    pub fn allocated_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
+    }
+
+    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
+        assert!(x < self.width && y < self.height);
+        let (chunk, index) = self.locate(x, y);
+        let pixels = self.rows_per_chunk * self.width;
+        self.chunks[chunk]
+            .get_or_insert_with(|| vec![Color::black(); pixels])[index] = color;
+    }
+
+    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+        assert!(x < self.width && y < self.height);
+        let (chunk, index) = self.locate(x, y);
+        self.chunks[chunk]
+            .as_ref()
+            .map_or(Color::black(), |pixels| pixels[index])
+    }
+
+    /// Copies a smaller canvas into this one with its top-left corner at
+    /// (x, y), e.g. one band of a render.
+    pub fn write_canvas(&mut self, x: usize, y: usize, canvas: &Canvas) {
+        for row in 0..canvas.height {
+            for column in 0..canvas.width {
+                let color = canvas.pixel_at(column, row);
+                self.write_pixel(x + column, y + row, color);
+            }
+        }
+    }
+
+    /// Encodes the canvas as a plain PPM file straight into `out`, without
+    /// building the whole file in memory first. The pixels are linear, so
+    /// they are sRGB-encoded like a linear `Canvas`'s.
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_ppm(out, self.width, self.height, &[], |x, y| {
+            self.pixel_at(x, y).to_srgb()
+        })
+    }
+
+    fn locate(&self, x: usize, y: usize) -> (usize, usize) {
+        let chunk = y / self.rows_per_chunk;
+        let row = y % self.rows_per_chunk;
+        (chunk, row * self.width + x)
+    }
+}
+
+impl Camera {
+    /// Renders the shaded image straight into a chunked canvas, for
+    /// images too large for an ordinary canvas. Bands of rows are
+    /// rendered on every available thread and copied into the chunks as
+    /// they finish, so the image matches the one `render` gives.
+    pub fn render_chunked(&self, world: &World) -> ChunkedCanvas {
+        let scene = Scene::compile(world, self);
+        let mut canvas = ChunkedCanvas::new(self.hsize, self.vsize);
+        if self.quality == Quality::Draft {
+            let small = self.render_small_draft(&scene);
+            self.upscale_draft(&small, |x, y, pixel| {
+                canvas.write_pixel(x, y, pixel)
+            });
+            return canvas;
+        }
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.render_bands_into(
+            &scene,
+            threads,
+            &mut |_| {},
+            &mut |top, rows| canvas.write_canvas(0, top, rows),
+        );
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::{Camera, Lens};
+    use crate::canvas::Canvas;
+    use crate::chunked::ChunkedCanvas;
+    use crate::color::Color;
+    use crate::matrix::Matrix4;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_chunks_are_allocated_when_written() {
+        let mut c = ChunkedCanvas::with_rows_per_chunk(4, 10, 3);
+        assert_eq!(c.allocated_chunks(), 0);
+        assert_eq!(c.pixel_at(3, 9), Color::black());
+        c.write_pixel(3, 9, Color::white());
+        c.write_pixel(0, 4, Color::new(0.5, 0.0, 0.0));
+        assert_eq!(c.allocated_chunks(), 2);
+        assert_eq!(c.pixel_at(3, 9), Color::white());
+        assert_eq!(c.pixel_at(0, 4), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(c.pixel_at(0, 5), Color::black());
+    }
+
+    #[test]
+    fn test_a_chunked_canvas_writes_the_same_ppm_as_a_canvas() {
+        let mut canvas = Canvas::new(10, 5);
+        canvas.write_pixel(9, 4, Color::new(1.0, 0.8, 0.6));
+        canvas.write_pixel(2, 1, Color::new(0.0, 0.5, 0.0));
+        let mut chunked = ChunkedCanvas::with_rows_per_chunk(10, 5, 2);
+        chunked.write_canvas(0, 0, &canvas);
+        let mut ppm = Vec::new();
+        chunked.write_ppm(&mut ppm).unwrap();
+        assert_eq!(String::from_utf8(ppm).unwrap(), canvas.to_ppm());
+    }
+
+    #[test]
+    fn test_rendering_into_a_chunked_canvas() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = Matrix4::view_transform(from, to, up);
+        let image = c.render_chunked(&w);
+        assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn test_a_chunked_render_matches_a_render_through_a_lens() {
+        let w = World::default();
+        let mut c = Camera::new(20, 18, FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = Matrix4::view_transform(from, to, up);
+        c.lens = Some(Lens::new(0.5, 3.0));
+        let image = c.render(&w);
+        let chunked = c.render_chunked(&w);
+        for y in 0..c.vsize {
+            for x in 0..c.hsize {
+                assert_eq!(chunked.pixel_at(x, y), image.pixel_at(x, y));
+            }
+        }
+    }
+}
@@ -1,15 +1,26 @@
-use crate::equal_f32;
-use std::ops::{Add, Mul, Sub};
+use crate::equal_f64;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+/// How the values of colors in an image are encoded.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ColorSpace {
+    /// Proportional to the amount of light, as rendered. Lighting math
+    /// and texture filtering are only correct on linear values.
+    Linear,
+    /// Encoded with the sRGB transfer curve, as most image files and
+    /// displays expect, spending more precision on dark values.
+    Srgb,
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Color {
-    pub red: f32,
-    pub green: f32,
-    pub blue: f32,
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
 }
 
 impl Color {
-    pub fn new(red: f32, green: f32, blue: f32) -> Color {
+    pub fn new(red: f64, green: f64, blue: f64) -> Color {
         Color { red, green, blue }
     }
 
@@ -20,13 +31,72 @@ impl Color {
     pub fn white() -> Color {
         Color::new(1.0, 1.0, 1.0)
     }
+
+    /// Returns the approximate color of a black body glowing at the given
+    /// temperature in kelvin, normalized so that 6600K is white. Lower
+    /// temperatures are warmer (redder) and higher ones cooler (bluer).
+    /// Temperatures are clamped to 1000K..40000K.
+    pub fn from_kelvin(temperature: f64) -> Color {
+        // curve fit by Tanner Helland to the CIE 1964 blackbody data
+        let t = temperature.clamp(1000.0, 40000.0) / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.69873 * (t - 60.0).powf(-0.13320476)
+        };
+        let green = if t <= 66.0 {
+            99.4708 * t.ln() - 161.11957
+        } else {
+            288.12216 * (t - 60.0).powf(-0.075514846)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.51773 * (t - 10.0).ln() - 305.0448
+        };
+        let channel = |value: f64| value.clamp(0.0, 255.0) / 255.0;
+        Color::new(channel(red), channel(green), channel(blue))
+    }
+
+    /// Encodes a linear color with the sRGB transfer curve.
+    pub fn to_srgb(&self) -> Color {
+        let encode = |c: f64| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color::new(encode(self.red), encode(self.green), encode(self.blue))
+    }
+
+    /// Decodes an sRGB-encoded color back to linear values, undoing
+    /// `to_srgb`.
+    pub fn to_linear(&self) -> Color {
+        let decode = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(self.red), decode(self.green), decode(self.blue))
+    }
+
+    /// Returns how bright the color looks, weighting the channels by the
+    /// Rec. 709 coefficients, since the eye is most sensitive to green.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl PartialEq for Color {
     fn eq(&self, other: &Color) -> bool {
-        equal_f32(self.red, other.red)
-            && equal_f32(self.green, other.green)
-            && equal_f32(self.blue, other.blue)
+        equal_f64(self.red, other.red)
+            && equal_f64(self.green, other.green)
+            && equal_f64(self.blue, other.blue)
     }
 }
 
@@ -54,10 +124,10 @@ impl Sub for Color {
     }
 }
 
-impl Mul<f32> for Color {
+impl Mul<f64> for Color {
     type Output = Color;
 
-    fn mul(self, scalar: f32) -> Color {
+    fn mul(self, scalar: f64) -> Color {
         Color::new(self.red * scalar, self.green * scalar, self.blue * scalar)
     }
 }
@@ -74,9 +144,28 @@ impl Mul<Color> for Color {
     }
 }
 
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Color {
+    fn sub_assign(&mut self, other: Color) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f64> for Color {
+    fn mul_assign(&mut self, scalar: f64) {
+        *self = *self * scalar;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::color::Color;
+    use crate::equal_f64;
 
     #[test]
     fn test_colors_are_red_green_blue_tuples() {
@@ -112,4 +201,44 @@ mod tests {
         let c2 = Color::new(0.9, 1.0, 0.1);
         assert_eq!(c1 * c2, Color::new(0.9, 0.2, 0.04));
     }
+
+    #[test]
+    fn test_assignment_operators_on_colors() {
+        let mut c = Color::new(0.9, 0.6, 0.75);
+        c += Color::new(0.7, 0.1, 0.25);
+        assert_eq!(c, Color::new(1.6, 0.7, 1.0));
+        c -= Color::new(0.6, 0.2, 0.5);
+        assert_eq!(c, Color::new(1.0, 0.5, 0.5));
+        c *= 2.0;
+        assert_eq!(c, Color::new(2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_color_temperatures() {
+        assert_eq!(Color::from_kelvin(6600.0), Color::white());
+        let candle = Color::from_kelvin(1900.0);
+        assert_eq!(candle.red, 1.0);
+        assert!(candle.green < 0.6);
+        assert_eq!(candle.blue, 0.0);
+        let sky = Color::from_kelvin(10000.0);
+        assert!(sky.red < sky.green && sky.green < sky.blue);
+        assert_eq!(sky.blue, 1.0);
+        assert_eq!(Color::from_kelvin(100.0), Color::from_kelvin(1000.0));
+    }
+
+    #[test]
+    fn test_the_luminance_of_a_color() {
+        assert!(equal_f64(Color::white().luminance(), 1.0));
+        assert_eq!(Color::black().luminance(), 0.0);
+        let green = Color::new(0.0, 1.0, 0.0).luminance();
+        assert!(green > Color::new(1.0, 0.0, 1.0).luminance());
+    }
+
+    #[test]
+    fn test_encoding_and_decoding_srgb() {
+        let c = Color::new(0.002, 0.2, 1.0);
+        let encoded = c.to_srgb();
+        assert_eq!(encoded, Color::new(0.02584, 0.48453, 1.0));
+        assert_eq!(encoded.to_linear(), c);
+    }
 }
@@ -0,0 +1,110 @@
+use crate::canvas::Canvas;
+use crate::color::{Color, ColorSpace};
+use std::io;
+
+/// How two images of the same size differ, for checking renders against
+/// reference images.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// The largest difference in any channel of any pixel.
+    pub max_difference: f64,
+    /// The number of pixels with a channel that differs by more than the
+    /// threshold.
+    pub mismatched_pixels: usize,
+    /// An image highlighting the mismatched pixels in red over a dimmed,
+    /// gray copy of the first image.
+    pub difference: Canvas,
+}
+
+impl Comparison {
+    pub fn matches(&self) -> bool {
+        self.mismatched_pixels == 0
+    }
+}
+
+/// Compares two images pixel by pixel, where pixels match if none of
+/// their channels differ by more than `threshold`. The second image is
+/// converted to the first one's color space before comparing. Images of
+/// different sizes can't be compared.
+pub fn compare(
+    a: &Canvas,
+    b: &Canvas,
+    threshold: f64,
+) -> io::Result<Comparison> {
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the images are {}x{} and {}x{}",
+                a.width, a.height, b.width, b.height
+            ),
+        ));
+    }
+    let b = match a.color_space {
+        ColorSpace::Linear => b.to_linear(),
+        ColorSpace::Srgb => b.to_srgb(),
+    };
+    let mut max_difference: f64 = 0.0;
+    let mut mismatched_pixels = 0;
+    let mut difference = Canvas::new(a.width, a.height);
+    difference.color_space = a.color_space;
+    for y in 0..a.height {
+        for x in 0..a.width {
+            let (pa, pb) = (a.pixel_at(x, y), b.pixel_at(x, y));
+            let channel_difference = (pa.red - pb.red)
+                .abs()
+                .max((pa.green - pb.green).abs())
+                .max((pa.blue - pb.blue).abs());
+            max_difference = max_difference.max(channel_difference);
+            let color = if channel_difference > threshold {
+                mismatched_pixels += 1;
+                Color::new(1.0, 0.0, 0.0)
+            } else {
+                let gray = pa.luminance() * 0.25;
+                Color::new(gray, gray, gray)
+            };
+            difference.write_pixel(x, y, color);
+        }
+    }
+    Ok(Comparison {
+        max_difference,
+        mismatched_pixels,
+        difference,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::compare::compare;
+
+    #[test]
+    fn test_comparing_images() {
+        let mut a = Canvas::new(3, 2);
+        a.write_pixel(0, 0, Color::white());
+        let mut b = a.clone();
+        let comparison = compare(&a, &b, 0.01).unwrap();
+        assert!(comparison.matches());
+        assert_eq!(comparison.max_difference, 0.0);
+        b.write_pixel(1, 1, Color::new(0.0, 0.005, 0.0));
+        b.write_pixel(2, 1, Color::new(0.0, 0.0, 0.5));
+        let comparison = compare(&a, &b, 0.01).unwrap();
+        assert!(!comparison.matches());
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_difference, 0.5);
+        let difference = comparison.difference;
+        assert_eq!(difference.pixel_at(2, 1), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(difference.pixel_at(1, 1), Color::black());
+        assert_eq!(difference.pixel_at(0, 0), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn test_images_are_compared_in_the_same_color_space() {
+        let mut a = Canvas::new(1, 1);
+        a.write_pixel(0, 0, Color::new(0.5, 0.5, 0.5));
+        let encoded = a.to_srgb();
+        assert!(compare(&a, &encoded, 0.001).unwrap().matches());
+        assert!(compare(&a, &Canvas::new(1, 2), 0.001).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::scene_file::{SceneSettings, SceneValue};
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::f64::consts::PI;
+
+/// A double-napped cone around the y axis in object space, with its apex
+/// at the origin and a radius equal to the distance from the apex along
+/// the axis. It is infinite unless cut off at `minimum` and `maximum`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cone {
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+    /// The height below which the cone is cut off, not included.
+    pub minimum: f64,
+    /// The height above which the cone is cut off, not included.
+    pub maximum: f64,
+    /// Whether the ends of a cut-off cone are capped, making it solid.
+    pub closed: bool,
+}
+
+impl Cone {
+    /// Adds the intersections with the caps at either end, if the cone is
+    /// closed and the ray isn't parallel to them.
+    fn intersect_caps<'a>(&'a self, ray: Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || ray.direction.y.abs() < EPSILON {
+            return;
+        }
+        for &y in [self.minimum, self.maximum].iter() {
+            let t = (y - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t, y.abs()) {
+                xs.push(Intersection::new(t, self));
+            }
+        }
+    }
+}
+
+/// Returns whether the ray at `t` is within `radius` of the y axis.
+fn check_cap(ray: Ray, t: f64, radius: f64) -> bool {
+    let x = ray.origin.x + t * ray.direction.x;
+    let z = ray.origin.z + t * ray.direction.z;
+    x * x + z * z <= radius * radius + EPSILON
+}
+
+impl Shape for Cone {
+    fn kind(&self) -> &'static str {
+        "cone"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        let (origin, direction) =
+            (transformed_ray.origin, transformed_ray.direction);
+        let a = direction.x * direction.x - direction.y * direction.y
+            + direction.z * direction.z;
+        let b = 2.0 * origin.x * direction.x - 2.0 * origin.y * direction.y
+            + 2.0 * origin.z * direction.z;
+        let c = origin.x * origin.x - origin.y * origin.y + origin.z * origin.z;
+        let mut ts = vec![];
+        if a.abs() < EPSILON {
+            // the ray is parallel to one of the nappes, so it crosses
+            // the other one at most once
+            if b.abs() >= EPSILON {
+                ts.push(-c / (2.0 * b));
+            }
+        } else {
+            // rounding can push the discriminant of a tangent ray below 0
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant > -EPSILON {
+                let root = discriminant.max(0.0).sqrt();
+                let t0 = (-b - root) / (2.0 * a);
+                let t1 = (-b + root) / (2.0 * a);
+                ts.push(t0.min(t1));
+                ts.push(t0.max(t1));
+            }
+        }
+        let mut xs: Vec<Intersection> = ts
+            .into_iter()
+            .filter(|&t| {
+                let y = origin.y + t * direction.y;
+                self.minimum < y && y < self.maximum
+            })
+            .map(|t| Intersection::new(t, self))
+            .collect();
+        self.intersect_caps(transformed_ray, &mut xs);
+        xs.sort();
+        xs
+    }
+
+    /// The apex has no single normal, so the axis of the cone is used
+    /// there.
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let distance = point.x * point.x + point.z * point.z;
+        let radius = |y: f64| y * y;
+        if distance < radius(self.maximum) && point.y >= self.maximum - EPSILON
+        {
+            return Tuple::vector(0.0, 1.0, 0.0);
+        }
+        if distance < radius(self.minimum) && point.y <= self.minimum + EPSILON
+        {
+            return Tuple::vector(0.0, -1.0, 0.0);
+        }
+        if distance < EPSILON * EPSILON {
+            return Tuple::vector(0.0, 1.0, 0.0);
+        }
+        let y = distance.sqrt();
+        let y = if point.y > 0.0 { -y } else { y };
+        Tuple::vector(point.x, y, point.z)
+    }
+
+    /// Returns texture coordinates with `u` running once around the cone
+    /// and `v` repeating every unit along its height.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        let u = 0.5 + point.x.atan2(point.z) / (2.0 * PI);
+        (u, point.y.rem_euclid(1.0))
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        let radius = self.minimum.abs().max(self.maximum.abs());
+        (
+            Tuple::point(-radius, self.minimum, -radius),
+            Tuple::point(radius, self.maximum, radius),
+        )
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![
+            ("min", SceneValue::Number(self.minimum)),
+            ("max", SceneValue::Number(self.maximum)),
+            ("closed", SceneValue::Flag(self.closed)),
+        ])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+impl Default for Cone {
+    fn default() -> Cone {
+        Cone {
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+            name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::cone::Cone;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use std::f64::consts::SQRT_2;
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray::new(
+            Tuple::point(origin.0, origin.1, origin.2),
+            Tuple::vector(direction.0, direction.1, direction.2).normalize(),
+        )
+    }
+
+    #[test]
+    fn test_intersecting_a_cone_with_a_ray() {
+        let shape = Cone::default();
+        let examples = [
+            ((0.0, 0.0, -5.0), (0.0, 0.0, 1.0), 5.0, 5.0),
+            ((0.0, 0.0, -5.0), (1.0, 1.0, 1.0), 8.66025, 8.66025),
+            ((1.0, 1.0, -5.0), (-0.5, -1.0, 1.0), 4.55006, 49.44994),
+        ];
+        for &(origin, direction, t0, t1) in examples.iter() {
+            let xs = shape.local_intersect(ray(origin, direction));
+            assert_eq!(xs.len(), 2);
+            // the expected values are rounded to five decimals
+            assert!((xs[0].t - t0).abs() < 0.0001);
+            assert!((xs[1].t - t1).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_a_cone_with_a_ray_parallel_to_one_of_its_halves() {
+        let shape = Cone::default();
+        let xs = shape.local_intersect(ray((0.0, 0.0, -1.0), (0.0, 1.0, 1.0)));
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].t - 0.35355).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_intersecting_the_end_caps_of_a_cone() {
+        let mut shape = Cone::default();
+        shape.minimum = -0.5;
+        shape.maximum = 0.5;
+        shape.closed = true;
+        let examples = [
+            ((0.0, 0.0, -5.0), (0.0, 1.0, 0.0), 0),
+            ((0.0, 0.0, -0.25), (0.0, 1.0, 1.0), 2),
+            ((0.0, 0.0, -0.25), (0.0, 1.0, 0.0), 4),
+        ];
+        for &(origin, direction, count) in examples.iter() {
+            let xs = shape.local_intersect(ray(origin, direction));
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn test_computing_the_normal_vector_on_a_cone() {
+        let shape = Cone::default();
+        let examples = [
+            ((0.0, 0.0, 0.0), (0.0, 1.0, 0.0)),
+            ((1.0, 1.0, 1.0), (1.0, -SQRT_2, 1.0)),
+            ((-1.0, -1.0, 0.0), (-1.0, 1.0, 0.0)),
+        ];
+        for &((px, py, pz), (nx, ny, nz)) in examples.iter() {
+            let n = shape.local_normal_at(Tuple::point(px, py, pz));
+            assert_eq!(n, Tuple::vector(nx, ny, nz));
+        }
+        assert!(!shape.normal_at(Tuple::point(0.0, 0.0, 0.0)).x.is_nan());
+    }
+
+    #[test]
+    fn test_the_normal_on_the_caps_of_a_cone() {
+        let mut shape = Cone::default();
+        shape.minimum = -1.0;
+        shape.maximum = 2.0;
+        shape.closed = true;
+        let top = shape.local_normal_at(Tuple::point(0.5, 2.0, 0.0));
+        let bottom = shape.local_normal_at(Tuple::point(0.0, -1.0, 0.5));
+        assert_eq!(top, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(bottom, Tuple::vector(0.0, -1.0, 0.0));
+        assert_eq!(
+            shape.local_bounds(),
+            (Tuple::point(-2.0, -1.0, -2.0), Tuple::point(2.0, 2.0, 2.0))
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::scene_file::SceneSettings;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+/// An axis-aligned cube from -1 to 1 along every axis in object space.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cube {
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+/// Returns where a ray enters and leaves the slab between the two planes
+/// at -1 and 1 along one axis, given the ray's origin and direction along
+/// that axis.
+fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
+    let tmin_numerator = -1.0 - origin;
+    let tmax_numerator = 1.0 - origin;
+    // a ray parallel to the slab is either inside it everywhere or never;
+    // infinities of the right sign handle both cases
+    let (tmin, tmax) = if direction.abs() >= EPSILON {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f64::INFINITY,
+            tmax_numerator * f64::INFINITY,
+        )
+    };
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+impl Shape for Cube {
+    fn kind(&self) -> &'static str {
+        "cube"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        let (origin, direction) =
+            (transformed_ray.origin, transformed_ray.direction);
+        let (xtmin, xtmax) = check_axis(origin.x, direction.x);
+        let (ytmin, ytmax) = check_axis(origin.y, direction.y);
+        let (ztmin, ztmax) = check_axis(origin.z, direction.z);
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        if tmin > tmax {
+            return vec![];
+        }
+        vec![Intersection::new(tmin, self), Intersection::new(tmax, self)]
+    }
+
+    /// Returns the normal of the face the point is on, which is the axis
+    /// along which the point is farthest from the center.
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let (x, y, z) = (point.x.abs(), point.y.abs(), point.z.abs());
+        let max = x.max(y).max(z);
+        if max == x {
+            Tuple::vector(point.x, 0.0, 0.0)
+        } else if max == y {
+            Tuple::vector(0.0, point.y, 0.0)
+        } else {
+            Tuple::vector(0.0, 0.0, point.z)
+        }
+    }
+
+    /// Returns texture coordinates that cover every face once, from the
+    /// face's own point of view.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        let (x, y, z) = (point.x.abs(), point.y.abs(), point.z.abs());
+        let max = x.max(y).max(z);
+        let (u, v) = if max == x {
+            (-point.z * point.x.signum(), point.y)
+        } else if max == y {
+            (point.x, -point.z * point.y.signum())
+        } else {
+            (point.x * point.z.signum(), point.y)
+        };
+        ((u + 1.0) / 2.0, (v + 1.0) / 2.0)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        (Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+impl Default for Cube {
+    fn default() -> Cube {
+        Cube {
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::cube::Cube;
+    use crate::matrix::Matrix4;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+
+    #[test]
+    fn test_a_ray_intersects_a_cube() {
+        let c = Cube::default();
+        let examples = [
+            ((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0), 4.0, 6.0),
+            ((-5.0, 0.5, 0.0), (1.0, 0.0, 0.0), 4.0, 6.0),
+            ((0.5, 5.0, 0.0), (0.0, -1.0, 0.0), 4.0, 6.0),
+            ((0.5, -5.0, 0.0), (0.0, 1.0, 0.0), 4.0, 6.0),
+            ((0.5, 0.0, 5.0), (0.0, 0.0, -1.0), 4.0, 6.0),
+            ((0.5, 0.0, -5.0), (0.0, 0.0, 1.0), 4.0, 6.0),
+            ((0.0, 0.5, 0.0), (0.0, 0.0, 1.0), -1.0, 1.0),
+        ];
+        for &((ox, oy, oz), (dx, dy, dz), t1, t2) in examples.iter() {
+            let r =
+                Ray::new(Tuple::point(ox, oy, oz), Tuple::vector(dx, dy, dz));
+            let xs = c.local_intersect(r);
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].t, t1);
+            assert_eq!(xs[1].t, t2);
+        }
+    }
+
+    #[test]
+    fn test_a_ray_misses_a_cube() {
+        let c = Cube::default();
+        let examples = [
+            ((-2.0, 0.0, 0.0), (0.2673, 0.5345, 0.8018)),
+            ((0.0, -2.0, 0.0), (0.8018, 0.2673, 0.5345)),
+            ((0.0, 0.0, -2.0), (0.5345, 0.8018, 0.2673)),
+            ((2.0, 0.0, 2.0), (0.0, 0.0, -1.0)),
+            ((0.0, 2.0, 2.0), (0.0, -1.0, 0.0)),
+            ((2.0, 2.0, 0.0), (-1.0, 0.0, 0.0)),
+        ];
+        for &((ox, oy, oz), (dx, dy, dz)) in examples.iter() {
+            let r =
+                Ray::new(Tuple::point(ox, oy, oz), Tuple::vector(dx, dy, dz));
+            assert!(c.local_intersect(r).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_the_normal_on_the_surface_of_a_cube() {
+        let c = Cube::default();
+        let examples = [
+            ((1.0, 0.5, -0.8), (1.0, 0.0, 0.0)),
+            ((-1.0, -0.2, 0.9), (-1.0, 0.0, 0.0)),
+            ((-0.4, 1.0, -0.1), (0.0, 1.0, 0.0)),
+            ((0.3, -1.0, -0.7), (0.0, -1.0, 0.0)),
+            ((-0.6, 0.3, 1.0), (0.0, 0.0, 1.0)),
+            ((0.4, 0.4, -1.0), (0.0, 0.0, -1.0)),
+            ((1.0, 1.0, 1.0), (1.0, 0.0, 0.0)),
+            ((-1.0, -1.0, -1.0), (-1.0, 0.0, 0.0)),
+        ];
+        for &((px, py, pz), (nx, ny, nz)) in examples.iter() {
+            let normal = c.local_normal_at(Tuple::point(px, py, pz));
+            assert_eq!(normal, Tuple::vector(nx, ny, nz));
+        }
+    }
+
+    #[test]
+    fn test_a_transformed_cube_in_a_world() {
+        let mut c = Cube::default();
+        c.transform = Matrix4::translation(0.0, 0.0, 10.0)
+            * Matrix4::scaling(2.0, 2.0, 2.0);
+        let mut w = World::default();
+        w.objects = vec![Box::new(c)];
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut hit = Scene::new(&w).hit_sorted(r).unwrap();
+        assert_eq!(hit.t, 8.0);
+        hit.prepare_hit(r);
+        assert_eq!(hit.normal_vector, Some(Tuple::vector(0.0, 0.0, -1.0)));
+    }
+}
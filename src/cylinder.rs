@@ -0,0 +1,300 @@
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::scene_file::{SceneSettings, SceneValue};
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::f64::consts::PI;
+
+/// A cylinder of radius 1 around the y axis in object space, infinitely
+/// long unless cut off at `minimum` and `maximum`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cylinder {
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+    /// The height below which the cylinder is cut off, not included.
+    pub minimum: f64,
+    /// The height above which the cylinder is cut off, not included.
+    pub maximum: f64,
+    /// Whether the ends of a cut-off cylinder are capped, making it solid.
+    pub closed: bool,
+}
+
+impl Cylinder {
+    /// Adds the intersections with the caps at either end, if the cylinder
+    /// is closed and the ray isn't parallel to them.
+    fn intersect_caps<'a>(&'a self, ray: Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || ray.direction.y.abs() < EPSILON {
+            return;
+        }
+        for &y in [self.minimum, self.maximum].iter() {
+            let t = (y - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t) {
+                xs.push(Intersection::new(t, self));
+            }
+        }
+    }
+}
+
+/// Returns whether the ray at `t` is within the unit radius of the y axis.
+fn check_cap(ray: Ray, t: f64) -> bool {
+    let x = ray.origin.x + t * ray.direction.x;
+    let z = ray.origin.z + t * ray.direction.z;
+    x * x + z * z <= 1.0 + EPSILON
+}
+
+impl Shape for Cylinder {
+    fn kind(&self) -> &'static str {
+        "cylinder"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        let (origin, direction) =
+            (transformed_ray.origin, transformed_ray.direction);
+        let mut xs = vec![];
+        let a = direction.x * direction.x + direction.z * direction.z;
+        // a ray parallel to the y axis can only hit the caps
+        if a.abs() >= EPSILON {
+            let b = 2.0 * origin.x * direction.x + 2.0 * origin.z * direction.z;
+            let c = origin.x * origin.x + origin.z * origin.z - 1.0;
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return vec![];
+            }
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+            for &t in [t0.min(t1), t0.max(t1)].iter() {
+                let y = origin.y + t * direction.y;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(Intersection::new(t, self));
+                }
+            }
+        }
+        self.intersect_caps(transformed_ray, &mut xs);
+        xs.sort();
+        xs
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let distance = point.x * point.x + point.z * point.z;
+        if distance < 1.0 && point.y >= self.maximum - EPSILON {
+            Tuple::vector(0.0, 1.0, 0.0)
+        } else if distance < 1.0 && point.y <= self.minimum + EPSILON {
+            Tuple::vector(0.0, -1.0, 0.0)
+        } else {
+            Tuple::vector(point.x, 0.0, point.z)
+        }
+    }
+
+    /// Returns texture coordinates with `u` running once around the
+    /// cylinder and `v` repeating every unit along its height.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        let u = 0.5 + point.x.atan2(point.z) / (2.0 * PI);
+        (u, point.y.rem_euclid(1.0))
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        (
+            Tuple::point(-1.0, self.minimum, -1.0),
+            Tuple::point(1.0, self.maximum, 1.0),
+        )
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![
+            ("min", SceneValue::Number(self.minimum)),
+            ("max", SceneValue::Number(self.maximum)),
+            ("closed", SceneValue::Flag(self.closed)),
+        ])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+impl Default for Cylinder {
+    fn default() -> Cylinder {
+        Cylinder {
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+            name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::cylinder::Cylinder;
+    use crate::matrix::Matrix4;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray::new(
+            Tuple::point(origin.0, origin.1, origin.2),
+            Tuple::vector(direction.0, direction.1, direction.2).normalize(),
+        )
+    }
+
+    #[test]
+    fn test_a_ray_misses_a_cylinder() {
+        let cyl = Cylinder::default();
+        let examples = [
+            ((1.0, 0.0, 0.0), (0.0, 1.0, 0.0)),
+            ((0.0, 0.0, 0.0), (0.0, 1.0, 0.0)),
+            ((0.0, 0.0, -5.0), (1.0, 1.0, 1.0)),
+        ];
+        for &(origin, direction) in examples.iter() {
+            assert!(cyl.local_intersect(ray(origin, direction)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_a_ray_strikes_a_cylinder() {
+        let cyl = Cylinder::default();
+        let examples = [
+            ((1.0, 0.0, -5.0), (0.0, 0.0, 1.0), 5.0, 5.0),
+            ((0.0, 0.0, -5.0), (0.0, 0.0, 1.0), 4.0, 6.0),
+            ((0.5, 0.0, -5.0), (0.1, 1.0, 1.0), 6.80798, 7.08872),
+        ];
+        for &(origin, direction, t0, t1) in examples.iter() {
+            let xs = cyl.local_intersect(ray(origin, direction));
+            assert_eq!(xs.len(), 2);
+            assert!((xs[0].t - t0).abs() < 0.0001);
+            assert!((xs[1].t - t1).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_the_normal_on_a_cylinder() {
+        let cyl = Cylinder::default();
+        let examples = [
+            ((1.0, 0.0, 0.0), (1.0, 0.0, 0.0)),
+            ((0.0, 5.0, -1.0), (0.0, 0.0, -1.0)),
+            ((0.0, -2.0, 1.0), (0.0, 0.0, 1.0)),
+            ((-1.0, 1.0, 0.0), (-1.0, 0.0, 0.0)),
+        ];
+        for &((px, py, pz), (nx, ny, nz)) in examples.iter() {
+            let n = cyl.local_normal_at(Tuple::point(px, py, pz));
+            assert_eq!(n, Tuple::vector(nx, ny, nz));
+        }
+    }
+
+    #[test]
+    fn test_intersecting_a_constrained_cylinder() {
+        let mut cyl = Cylinder::default();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        let examples = [
+            ((0.0, 1.5, 0.0), (0.1, 1.0, 0.0), 0),
+            ((0.0, 3.0, -5.0), (0.0, 0.0, 1.0), 0),
+            ((0.0, 0.0, -5.0), (0.0, 0.0, 1.0), 0),
+            ((0.0, 2.0, -5.0), (0.0, 0.0, 1.0), 0),
+            ((0.0, 1.0, -5.0), (0.0, 0.0, 1.0), 0),
+            ((0.0, 1.5, -2.0), (0.0, 0.0, 1.0), 2),
+        ];
+        for &(origin, direction, count) in examples.iter() {
+            let xs = cyl.local_intersect(ray(origin, direction));
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_the_caps_of_a_closed_cylinder() {
+        let mut cyl = Cylinder::default();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        cyl.closed = true;
+        let examples = [
+            ((0.0, 3.0, 0.0), (0.0, -1.0, 0.0)),
+            ((0.0, 3.0, -2.0), (0.0, -1.0, 2.0)),
+            ((0.0, 4.0, -2.0), (0.0, -1.0, 1.0)),
+            ((0.0, 0.0, -2.0), (0.0, 1.0, 2.0)),
+            ((0.0, -1.0, -2.0), (0.0, 1.0, 1.0)),
+        ];
+        for &(origin, direction) in examples.iter() {
+            let xs = cyl.local_intersect(ray(origin, direction));
+            assert_eq!(xs.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_the_normal_on_the_end_caps_of_a_cylinder() {
+        let mut cyl = Cylinder::default();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        cyl.closed = true;
+        let examples = [
+            ((0.0, 1.0, 0.0), (0.0, -1.0, 0.0)),
+            ((0.5, 1.0, 0.0), (0.0, -1.0, 0.0)),
+            ((0.0, 1.0, 0.5), (0.0, -1.0, 0.0)),
+            ((0.0, 2.0, 0.0), (0.0, 1.0, 0.0)),
+            ((0.5, 2.0, 0.0), (0.0, 1.0, 0.0)),
+            ((0.0, 2.0, 0.5), (0.0, 1.0, 0.0)),
+        ];
+        for &((px, py, pz), (nx, ny, nz)) in examples.iter() {
+            let n = cyl.local_normal_at(Tuple::point(px, py, pz));
+            assert_eq!(n, Tuple::vector(nx, ny, nz));
+        }
+    }
+
+    #[test]
+    fn test_rendering_a_cylinder() {
+        let mut cyl = Cylinder::default();
+        cyl.minimum = -1.0;
+        cyl.maximum = 1.0;
+        cyl.closed = true;
+        cyl.material.color = Color::new(1.0, 0.0, 0.0);
+        let mut w = World::default();
+        w.objects = vec![Box::new(cyl)];
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 3.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let image = c.render(&w);
+        assert!(c.object_at_pixel(&Scene::new(&w), 5, 5).is_some());
+        assert_ne!(image.pixel_at(5, 5), Color::black());
+        assert_eq!(image.pixel_at(0, 0), Color::black());
+        assert_eq!(w.objects[0].bounds().1, Tuple::point(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,302 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use crate::world::{ReflectionMode, World};
+use std::fmt;
+
+/// Everything that happened to one ray: what it hit, how the hit was
+/// lit, and the rays it spawned, together with the color it returned.
+#[derive(Clone, Debug)]
+pub struct RayTrace {
+    pub ray: Ray,
+    /// The number of further bounces that were allowed for this ray.
+    pub remaining: usize,
+    pub hit: Option<HitTrace>,
+    pub color: Color,
+}
+
+#[derive(Clone, Debug)]
+pub struct HitTrace {
+    pub t: f64,
+    /// The index of the object in `World::objects`.
+    pub object: usize,
+    pub point: Tuple,
+    pub normal: Tuple,
+    pub inside: bool,
+    /// The fraction of each light that reached the point, in the order of
+    /// `World::lights`, where 0 means it was in shadow.
+    pub light_visibility: Vec<f64>,
+    /// The light scattered by the surface itself.
+    pub surface: Color,
+    /// The light reaching the surface by bouncing off other surfaces,
+    /// scattered by its diffuse term.
+    pub indirect: Color,
+    /// The reflected color, before the Fresnel effect is applied.
+    pub reflected: Color,
+    /// The refracted color, before the Fresnel effect is applied.
+    pub refracted: Color,
+    /// The traced reflection ray, which `reflected` comes from. Rough
+    /// surfaces average many rays instead, which aren't recorded.
+    pub reflection: Option<Box<RayTrace>>,
+    /// The traced refraction ray, with the same caveat as `reflection`.
+    /// Dispersive surfaces trace one ray per channel, also unrecorded.
+    pub refraction: Option<Box<RayTrace>>,
+}
+
+/// Traces the ray through the center of one pixel, recording the whole
+/// tree of rays behind its color, to find out why a pixel looks the way
+/// it does.
+pub fn debug_pixel(
+    world: &World,
+    camera: &Camera,
+    x: usize,
+    y: usize,
+) -> RayTrace {
+    let scene = Scene::compile(world, camera);
+    let mut rng = Rng::for_pixel(camera.seed, x, y, 0);
+    let ray = camera.pixel_ray(x, y);
+    trace_ray(&scene, ray, true, scene.max_depth(), &mut rng)
+}
+
+/// Traces one ray, taking the same steps with the same random numbers as
+/// shading it does, so that the recorded parts add up to its color. Rays
+/// from the camera pass through holdout objects.
+fn trace_ray(
+    scene: &Scene,
+    ray: Ray,
+    from_camera: bool,
+    remaining: usize,
+    rng: &mut Rng,
+) -> RayTrace {
+    let world = scene.world();
+    let hit = if from_camera {
+        scene.camera_hit(ray)
+    } else {
+        scene.shading_hit(ray)
+    };
+    let hit = match hit {
+        Some(hit) => hit,
+        None => {
+            return RayTrace {
+                ray,
+                remaining,
+                hit: None,
+                color: scene.environment_color(ray.direction),
+            }
+        }
+    };
+    let light_visibility: Vec<f64> = world
+        .lights
+        .iter()
+        .map(|light| {
+            scene.light_intensity_at(hit.over_point.unwrap(), light, rng)
+        })
+        .collect();
+    let surface = hit.lit_color(scene, &light_visibility);
+    let indirect = hit.indirect_color(scene, remaining, rng);
+    let material = hit.material();
+    let depth = hit.remaining_depth(remaining);
+    // rough surfaces average many rays, which are shaded without being
+    // recorded; a sharp one traces a single ray, recorded in full
+    let sharp = material.roughness <= 0.0 || material.glossy_samples <= 1;
+    let (reflected, reflection) = if material.reflective > 0.0
+        && depth > 0
+        && sharp
+        && scene.reflection_mode() == ReflectionMode::Traced
+    {
+        let trace = trace_ray(scene, hit.reflect_ray(), false, depth - 1, rng);
+        (trace.color * material.reflective, Some(Box::new(trace)))
+    } else {
+        (hit.reflected_color(scene, remaining, rng), None)
+    };
+    let direction = hit.refract_direction(hit.n1.unwrap(), hit.n2.unwrap());
+    let (refracted, refraction) = match direction {
+        Some(direction)
+            if material.transparency > 0.0
+                && depth > 0
+                && sharp
+                && material.dispersion == 0.0 =>
+        {
+            let refract_ray = Ray::new(hit.under_point.unwrap(), direction);
+            let trace = trace_ray(scene, refract_ray, false, depth - 1, rng);
+            (trace.color * material.transparency, Some(Box::new(trace)))
+        }
+        _ => (hit.refracted_color(scene, remaining, rng), None),
+    };
+    RayTrace {
+        ray,
+        remaining,
+        hit: Some(HitTrace {
+            t: hit.t,
+            object: if from_camera {
+                scene.camera_object_id_at(ray).unwrap()
+            } else {
+                scene.object_id_at(ray).unwrap()
+            },
+            point: hit.point.unwrap(),
+            normal: hit.normal_vector.unwrap(),
+            inside: hit.inside.unwrap(),
+            light_visibility,
+            surface,
+            indirect,
+            reflected,
+            refracted,
+            reflection,
+            refraction,
+        }),
+        color: hit.combine(surface + indirect, reflected, refracted),
+    }
+}
+
+impl RayTrace {
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        let (o, d, c) = (self.ray.origin, self.ray.direction, self.color);
+        writeln!(
+            f,
+            "{}ray from ({:.3}, {:.3}, {:.3}) towards ({:.3}, {:.3}, {:.3}) \
+             => color ({:.3}, {:.3}, {:.3})",
+            pad, o.x, o.y, o.z, d.x, d.y, d.z, c.red, c.green, c.blue
+        )?;
+        let hit = match &self.hit {
+            Some(hit) => hit,
+            None => {
+                return writeln!(f, "{}  missed, sees the environment", pad)
+            }
+        };
+        let p = hit.point;
+        writeln!(
+            f,
+            "{}  hit object {} at t = {:.3}, point ({:.3}, {:.3}, {:.3}){}",
+            pad,
+            hit.object,
+            hit.t,
+            p.x,
+            p.y,
+            p.z,
+            if hit.inside { ", from inside" } else { "" }
+        )?;
+        let visibility: Vec<String> = hit
+            .light_visibility
+            .iter()
+            .map(|visibility| format!("{:.3}", visibility))
+            .collect();
+        writeln!(
+            f,
+            "{}  light visibility [{}], surface ({:.3}, {:.3}, {:.3})",
+            pad,
+            visibility.join(", "),
+            hit.surface.red,
+            hit.surface.green,
+            hit.surface.blue
+        )?;
+        if let Some(reflection) = &hit.reflection {
+            writeln!(f, "{}  reflection:", pad)?;
+            reflection.write(f, indent + 2)?;
+        }
+        if let Some(refraction) = &hit.refraction {
+            writeln!(f, "{}  refraction:", pad)?;
+            refraction.write(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RayTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::debug::debug_pixel;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::rng::Rng;
+    use crate::scene::Scene;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        c
+    }
+
+    #[test]
+    fn test_debugging_a_pixel_matches_the_render() {
+        let w = World::default();
+        let trace = debug_pixel(&w, &camera(), 5, 5);
+        assert_eq!(trace.color, Color::new(0.38066, 0.47583, 0.2855));
+        let hit = trace.hit.unwrap();
+        assert_eq!(hit.object, 0);
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.light_visibility, vec![1.0]);
+        assert!(hit.reflection.is_none());
+        assert!(hit.refraction.is_none());
+    }
+
+    #[test]
+    fn test_debugging_a_pixel_that_misses_everything() {
+        let w = World::default();
+        let trace = debug_pixel(&w, &camera(), 0, 0);
+        assert!(trace.hit.is_none());
+        assert_eq!(trace.color, Color::black());
+        assert!(trace.to_string().contains("missed"));
+    }
+
+    #[test]
+    fn test_debugging_a_pixel_records_secondary_rays() {
+        let mut w = World::default();
+        w.objects[0].material_mut().reflective = 0.5;
+        w.objects[0].material_mut().transparency = 0.5;
+        w.objects[0].material_mut().refractive_index = 1.5;
+        let trace = debug_pixel(&w, &camera(), 5, 5);
+        let hit = trace.hit.as_ref().unwrap();
+        let reflection = hit.reflection.as_ref().unwrap();
+        assert_eq!(reflection.remaining, w.max_depth - 1);
+        let refraction = hit.refraction.as_ref().unwrap();
+        assert_eq!(refraction.hit.as_ref().unwrap().object, 1);
+        let text = trace.to_string();
+        assert!(text.contains("reflection:"));
+        assert!(text.contains("refraction:"));
+    }
+
+    #[test]
+    fn test_the_recorded_parts_add_up_to_the_rendered_color() {
+        let mut w = World::default();
+        w.lights[0].radius = 2.0;
+        w.lights[0].samples = 4;
+        w.objects[0].material_mut().reflective = 0.5;
+        w.objects.push(Box::new(Plane {
+            transform: Matrix4::translation(0.0, 0.0, -20.0)
+                * Matrix4::rotation_x(FRAC_PI_2),
+            ..Plane::default()
+        }));
+        w.update();
+        let c = camera();
+        let trace = debug_pixel(&w, &c, 5, 5);
+        let mut rng = Rng::for_pixel(c.seed, 5, 5, 0);
+        let rendered = Scene::new(&w).color_at_depth(
+            c.pixel_ray(5, 5),
+            w.max_depth,
+            &mut rng,
+        );
+        assert_eq!(trace.color, rendered);
+        let hit = trace.hit.unwrap();
+        let reflection = hit.reflection.unwrap();
+        assert_eq!(hit.reflected, reflection.color * 0.5);
+        assert_eq!(trace.color, hit.surface + hit.indirect + hit.reflected);
+    }
+}
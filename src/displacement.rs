@@ -0,0 +1,168 @@
+use crate::canvas::Canvas;
+use crate::material::Material;
+use crate::pattern::WrapMode;
+use crate::plane::Plane;
+use crate::triangle::Triangle;
+use crate::tuple::Tuple;
+use crate::uv::sample_bilinear;
+
+/// Real geometric detail for flat surfaces: the surface is cut into a
+/// grid of small triangles whose corners are pushed along the normal by
+/// a height map. Unlike bump mapping, the displaced surface casts
+/// shadows onto itself and shows the detail along its silhouette.
+#[derive(Clone, Debug)]
+pub struct Displacement {
+    /// The height at every point of the surface, from the brightness of
+    /// the image, which covers the surface once with its bottom row at
+    /// the start of the second edge. Black is not displaced at all.
+    pub height_map: Canvas,
+    /// How far white areas of the height map are pushed out.
+    pub amplitude: f64,
+    /// The number of grid cells along each edge of the surface. Every
+    /// cell becomes two triangles.
+    pub divisions: usize,
+}
+
+impl Displacement {
+    pub fn new(height_map: Canvas, amplitude: f64) -> Displacement {
+        Displacement {
+            height_map,
+            amplitude,
+            divisions: 32,
+        }
+    }
+
+    fn height_at(&self, u: f64, v: f64) -> f64 {
+        let color = sample_bilinear(
+            &self.height_map,
+            u,
+            v,
+            WrapMode::Clamp,
+            WrapMode::Clamp,
+        );
+        (color.red + color.green + color.blue) / 3.0 * self.amplitude
+    }
+
+    /// Tessellates and displaces the parallelogram with one corner at
+    /// `corner` and edges `edge_u` and `edge_v`. The surface is pushed
+    /// towards the side that `edge_v` turns to `edge_u` around, the side
+    /// the triangles face.
+    pub fn displace_quad(
+        &self,
+        corner: Tuple,
+        edge_u: Tuple,
+        edge_v: Tuple,
+        material: &Material,
+    ) -> Vec<Triangle> {
+        let divisions = self.divisions.max(1);
+        let normal = edge_v.cross(edge_u).normalize();
+        let vertex = |i: usize, j: usize| {
+            let u = i as f64 / divisions as f64;
+            let v = j as f64 / divisions as f64;
+            corner + edge_u * u + edge_v * v + normal * self.height_at(u, v)
+        };
+        let mut triangles = Vec::with_capacity(2 * divisions * divisions);
+        for j in 0..divisions {
+            for i in 0..divisions {
+                let (p00, p10) = (vertex(i, j), vertex(i + 1, j));
+                let (p01, p11) = (vertex(i, j + 1), vertex(i + 1, j + 1));
+                for &(p1, p2, p3) in [(p00, p10, p11), (p00, p11, p01)].iter() {
+                    let mut triangle = Triangle::new(p1, p2, p3);
+                    triangle.material = material.clone();
+                    triangles.push(triangle);
+                }
+            }
+        }
+        triangles
+    }
+
+    /// Tessellates and displaces a square of the plane, `size` units on a
+    /// side and centered on its origin, taking on its transformation and
+    /// material. The height map's x axis runs along the plane's x axis
+    /// and its y axis along the plane's z axis.
+    pub fn displace_plane(&self, plane: &Plane, size: f64) -> Vec<Triangle> {
+        let half = size / 2.0;
+        let mut triangles = self.displace_quad(
+            Tuple::point(-half, 0.0, -half),
+            Tuple::vector(size, 0.0, 0.0),
+            Tuple::vector(0.0, 0.0, size),
+            &plane.material,
+        );
+        for triangle in triangles.iter_mut() {
+            triangle.transform = plane.transform;
+        }
+        triangles
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::displacement::Displacement;
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+
+    #[test]
+    fn test_a_flat_height_map_tessellates_without_displacing() {
+        let mut d = Displacement::new(Canvas::new(2, 2), 1.0);
+        d.divisions = 3;
+        let triangles = d.displace_quad(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+            &Material::default(),
+        );
+        assert_eq!(triangles.len(), 18);
+        for triangle in triangles.iter() {
+            assert_eq!(triangle.normal, Tuple::vector(0.0, 1.0, 0.0));
+            assert_eq!(triangle.p1.y, 0.0);
+        }
+    }
+
+    /// A plane with a white bump in the middle of a black height map.
+    fn bumpy_floor() -> World {
+        let mut height_map = Canvas::new(3, 3);
+        height_map.write_pixel(1, 1, Color::white());
+        let mut d = Displacement::new(height_map, 0.5);
+        d.divisions = 6;
+        let mut plane = Plane::default();
+        plane.transform = Matrix4::translation(0.0, -1.0, 0.0);
+        let mut w = World::new();
+        w.lights =
+            vec![PointLight::new(Tuple::point(3.0, 0.0, 0.0), Color::white())];
+        for triangle in d.displace_plane(&plane, 3.0) {
+            w.objects.push(Box::new(triangle));
+        }
+        w
+    }
+
+    #[test]
+    fn test_displacing_a_plane_raises_real_geometry() {
+        let w = bumpy_floor();
+        assert_eq!(w.stats().triangles, 72);
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let scene = Scene::new(&w);
+        let top = scene.hit_sorted(Ray::new(Tuple::point(0.0, 1.0, 0.0), down));
+        assert!((top.unwrap().t - 1.5).abs() < 0.0001);
+        let edge =
+            scene.hit_sorted(Ray::new(Tuple::point(1.4, 1.0, 1.4), down));
+        assert!((edge.unwrap().t - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_a_displaced_surface_shadows_itself() {
+        let w = bumpy_floor();
+        let scene = Scene::new(&w);
+        // behind the bump, as seen from the light low on one side
+        assert!(scene.is_shadowed(&w.lights[0], Tuple::point(-1.0, -0.99, 0.0)));
+        assert!(!scene.is_shadowed(&w.lights[0], Tuple::point(1.0, -0.99, 0.0)));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::panorama::Panorama;
+use crate::tuple::Tuple;
+
+/// What is seen in the distance, along rays that miss every object.
+#[derive(Clone, Debug)]
+pub enum Environment {
+    /// The same color in every direction.
+    Uniform(Color),
+    /// An equirectangular (latitude/longitude) image wrapped around the
+    /// scene, with the top row at the zenith and the center column
+    /// looking towards positive z.
+    Map(Canvas),
+}
+
+impl Environment {
+    /// Returns the environment's color in the given direction.
+    pub fn color_at(&self, direction: Tuple) -> Color {
+        match self {
+            Environment::Uniform(color) => *color,
+            Environment::Map(canvas) => {
+                Panorama::Equirectangular.color_at(canvas, direction)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::environment::Environment;
+    use crate::tuple::Tuple;
+
+    #[test]
+    fn test_a_uniform_environment_is_the_same_everywhere() {
+        let env = Environment::Uniform(Color::new(0.2, 0.4, 0.6));
+        assert_eq!(
+            env.color_at(Tuple::vector(0.0, 1.0, 0.0)),
+            Color::new(0.2, 0.4, 0.6)
+        );
+        assert_eq!(
+            env.color_at(Tuple::vector(1.0, -2.0, 3.0)),
+            Color::new(0.2, 0.4, 0.6)
+        );
+    }
+
+    #[test]
+    fn test_sampling_an_environment_map() {
+        let mut canvas = Canvas::new(4, 2);
+        let sky = Color::new(0.5, 0.7, 1.0);
+        let ground = Color::new(0.3, 0.2, 0.1);
+        for x in 0..4 {
+            canvas.write_pixel(x, 0, sky);
+            canvas.write_pixel(x, 1, ground);
+        }
+        canvas.write_pixel(2, 0, Color::white());
+        let env = Environment::Map(canvas);
+        assert_eq!(env.color_at(Tuple::vector(0.0, 1.0, -0.01)), sky);
+        assert_eq!(env.color_at(Tuple::vector(0.0, -1.0, 0.0)), ground);
+        assert_eq!(env.color_at(Tuple::vector(0.0, 0.1, 1.0)), Color::white());
+        assert_eq!(env.color_at(Tuple::vector(0.0, 0.1, -1.0)), sky);
+    }
+}
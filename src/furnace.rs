@@ -0,0 +1,160 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::environment::Environment;
+use crate::irradiance::Indirect;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::sphere::Sphere;
+use crate::tuple::Tuple;
+use crate::world::World;
+use std::f64::consts::FRAC_PI_3;
+
+/// A white furnace test: a sphere inside a uniform environment, lit by
+/// nothing but the environment. A white surface that neither absorbs nor
+/// adds light disappears against the background, so any pixel brighter
+/// than the environment shows that shading creates energy, and any
+/// darker one that it loses energy. Run it after changing how materials
+/// or lights are shaded.
+#[derive(Clone, Debug)]
+pub struct Furnace {
+    /// The material of the sphere. By default a white, purely diffuse
+    /// surface.
+    pub material: Material,
+    /// The color of the environment. It needs to be nonzero in every
+    /// channel.
+    pub environment: Color,
+    /// The number of indirect rays traced at every hit.
+    pub samples: usize,
+    /// The width and height of the rendered image.
+    pub size: usize,
+    /// How far above or below the environment, relative to it, a pixel
+    /// may be.
+    pub tolerance: f64,
+}
+
+impl Furnace {
+    pub fn new() -> Furnace {
+        Furnace {
+            material: Material {
+                ambient: 0.0,
+                diffuse: 1.0,
+                specular: 0.0,
+                color: Color::white(),
+                ..Material::default()
+            },
+            environment: Color::new(0.5, 0.5, 0.5),
+            samples: 64,
+            size: 32,
+            tolerance: 0.01,
+        }
+    }
+
+    /// Returns the test scene: a unit sphere at the origin with no lights
+    /// and indirect lighting turned on.
+    #[allow(clippy::field_reassign_with_default)]
+    pub fn world(&self) -> World {
+        let mut sphere = Sphere::default();
+        sphere.material = self.material.clone();
+        let mut world = World::new();
+        world.objects.push(Box::new(sphere));
+        world.environment = Some(Environment::Uniform(self.environment));
+        world.indirect = Indirect::Sampled {
+            samples: self.samples,
+        };
+        world
+    }
+
+    /// Returns a camera whose view is mostly filled by the sphere.
+    pub fn camera(&self) -> Camera {
+        let mut camera = Camera::new(self.size, self.size, FRAC_PI_3);
+        camera.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -2.5),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        camera
+    }
+
+    /// Renders the test scene and compares every channel of every pixel
+    /// with the environment.
+    pub fn run(&self) -> FurnaceReport {
+        let canvas = self.camera().render(&self.world());
+        let mut report = FurnaceReport {
+            brightest: 1.0,
+            darkest: 1.0,
+            tolerance: self.tolerance,
+        };
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let pixel = canvas.pixel_at(x, y);
+                let ratios = [
+                    pixel.red / self.environment.red,
+                    pixel.green / self.environment.green,
+                    pixel.blue / self.environment.blue,
+                ];
+                for ratio in ratios {
+                    report.brightest = report.brightest.max(ratio);
+                    report.darkest = report.darkest.min(ratio);
+                }
+            }
+        }
+        report
+    }
+}
+
+impl Default for Furnace {
+    fn default() -> Furnace {
+        Furnace::new()
+    }
+}
+
+/// The outcome of a furnace test, with pixels measured relative to the
+/// environment.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FurnaceReport {
+    pub brightest: f64,
+    pub darkest: f64,
+    pub tolerance: f64,
+}
+
+impl FurnaceReport {
+    /// Whether some pixel is brighter than the environment by more than
+    /// the tolerance.
+    pub fn gains_energy(&self) -> bool {
+        self.brightest > 1.0 + self.tolerance
+    }
+
+    /// Whether some pixel is darker than the environment by more than
+    /// the tolerance.
+    pub fn loses_energy(&self) -> bool {
+        self.darkest < 1.0 - self.tolerance
+    }
+
+    pub fn passed(&self) -> bool {
+        !self.gains_energy() && !self.loses_energy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::furnace::Furnace;
+
+    #[test]
+    fn test_a_white_diffuse_sphere_passes_the_furnace_test() {
+        let report = Furnace::new().run();
+        assert!(report.passed(), "{:?}", report);
+    }
+
+    #[test]
+    fn test_the_furnace_test_catches_gained_and_lost_energy() {
+        let mut furnace = Furnace::new();
+        furnace.material.reflective = 0.5;
+        let report = furnace.run();
+        assert!(report.gains_energy() && !report.loses_energy());
+        let mut furnace = Furnace::new();
+        furnace.material.color = Color::new(0.5, 1.0, 1.0);
+        let report = furnace.run();
+        assert!(report.loses_energy() && !report.gains_energy());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::accel::ray_hits_box;
+use crate::bounds::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::scene_file::{SceneSettings, SceneValue};
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use std::mem::{size_of, size_of_val};
+
+/// A collection of shapes treated as one, which rays only test when they
+/// pass through the box around all of them. Dividing a group with many
+/// shapes nests them in subgroups, each with a smaller box, so that a ray
+/// tests a number of boxes that grows with the logarithm of the number
+/// of shapes instead of testing every shape.
+///
+/// The shapes are placed directly in world space, so the group's own
+/// transform is always the identity, and hits are always on the shapes
+/// themselves, in their own materials.
+#[derive(Clone, Debug)]
+pub struct Group {
+    children: Vec<Box<dyn Shape>>,
+    bounds: BoundingBox,
+    /// Not used for shading, since hits are never on the group itself.
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl Group {
+    pub fn new(children: Vec<Box<dyn Shape>>) -> Group {
+        let mut group = Group {
+            children,
+            bounds: BoundingBox::empty(),
+            material: Material::default(),
+            name: None,
+        };
+        group.fit_bounds();
+        group
+    }
+
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    pub fn add_child(&mut self, child: Box<dyn Shape>) {
+        self.bounds.add_box(child.bounds().into());
+        self.children.push(child);
+    }
+
+    fn fit_bounds(&mut self) {
+        self.bounds = BoundingBox::empty();
+        for child in self.children.iter() {
+            self.bounds.add_box(child.bounds().into());
+        }
+    }
+
+    /// Takes out the shapes that fit entirely in either half of the box
+    /// around the finite shapes, split across its longest axis, leaving
+    /// the shapes that straddle the middle. A half is left empty if it
+    /// would take every shape, which could never be split further.
+    fn partition_children(&mut self) -> (Group, Group) {
+        let mut finite = BoundingBox::empty();
+        for child in self.children.iter() {
+            let bounds = BoundingBox::from(child.bounds());
+            if !bounds.is_unbounded() {
+                finite.add_box(bounds);
+            }
+        }
+        let (left_bounds, right_bounds) = finite.split();
+
+        let count = self.children.len();
+        let mut left = vec![];
+        let mut right = vec![];
+        let mut rest = vec![];
+        for child in self.children.drain(..) {
+            let bounds = BoundingBox::from(child.bounds());
+            // the empty box fits in either half, but there's nothing to
+            // gain from moving it
+            if bounds.is_empty() {
+                rest.push(child);
+            } else if left_bounds.contains_box(bounds) {
+                left.push(child);
+            } else if right_bounds.contains_box(bounds) {
+                right.push(child);
+            } else {
+                rest.push(child);
+            }
+        }
+        if left.len() == count {
+            rest.append(&mut left);
+        }
+        if right.len() == count {
+            rest.append(&mut right);
+        }
+        self.children = rest;
+        (Group::new(left), Group::new(right))
+    }
+}
+
+impl Shape for Group {
+    fn kind(&self) -> &'static str {
+        "group"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        Matrix4::identity()
+    }
+
+    /// Applies the transform to every shape in the group, on top of their
+    /// own transforms.
+    fn set_transform(&mut self, transform: Matrix4) {
+        for child in self.children.iter_mut() {
+            child.set_transform(transform * child.transform());
+        }
+        self.fit_bounds();
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        if !ray_hits_box(ray, self.bounds) {
+            return vec![];
+        }
+        self.children
+            .iter()
+            .flat_map(|child| child.intersect(ray))
+            .collect()
+    }
+
+    /// Hits are always on the shapes in the group, never on the group
+    /// itself, so this is never needed.
+    fn local_normal_at(&self, _point: Tuple) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+    }
+
+    fn local_uv_at(&self, _point: Tuple) -> (f64, f64) {
+        (0.0, 0.0)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        self.bounds.into()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| child.triangle_count())
+            .sum()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        size_of_val(self)
+            + self.children.capacity() * size_of::<Box<dyn Shape>>()
+            + self
+                .children
+                .iter()
+                .map(|child| child.memory_bytes())
+                .sum::<usize>()
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        if self.children.len() >= threshold {
+            let (left, right) = self.partition_children();
+            if !left.children.is_empty() {
+                self.children.push(Box::new(left));
+            }
+            if !right.children.is_empty() {
+                self.children.push(Box::new(right));
+            }
+        }
+        for child in self.children.iter_mut() {
+            child.divide(threshold);
+        }
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![("children", SceneValue::Shapes(&self.children))])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::group::Group;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+
+    fn sphere_at(x: f64, y: f64, z: f64) -> Box<dyn Shape> {
+        let mut s = Sphere::default();
+        s.transform = Matrix4::translation(x, y, z);
+        Box::new(s)
+    }
+
+    #[test]
+    fn test_intersecting_a_ray_with_a_group() {
+        let mut g = Group::new(vec![]);
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert!(g.intersect(r).is_empty());
+        g.add_child(Box::new(Sphere::default()));
+        g.add_child(sphere_at(0.0, 0.0, -3.0));
+        g.add_child(sphere_at(5.0, 0.0, 0.0));
+        let xs = g.intersect(r);
+        assert_eq!(xs.len(), 4);
+        assert_eq!(
+            g.bounds(),
+            (Tuple::point(-1.0, -1.0, -4.0), Tuple::point(6.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_transforming_a_group_transforms_its_children() {
+        let mut g = Group::new(vec![sphere_at(5.0, 0.0, 0.0)]);
+        g.set_transform(Matrix4::scaling(2.0, 2.0, 2.0));
+        let r = Ray::new(
+            Tuple::point(10.0, 0.0, -10.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = g.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(
+            xs[0].object.normal_at(Tuple::point(10.0, 0.0, -2.0)),
+            Tuple::vector(0.0, 0.0, -1.0)
+        );
+        assert_eq!(g.bounds().1, Tuple::point(12.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn test_dividing_a_group_nests_its_children() {
+        let mut g = Group::new(vec![
+            sphere_at(-2.0, 0.0, 0.0),
+            sphere_at(2.0, 0.0, 0.0),
+            Box::new(Sphere::default()),
+            Box::new(Plane::default()),
+            Box::new(Group::new(vec![])),
+        ]);
+        g.divide(2);
+        // the sphere in the middle and the plane straddle the split, and
+        // the empty group stays where it is
+        assert_eq!(g.children().len(), 5);
+        assert_eq!(g.children()[2].kind(), "group");
+        assert!(g.children()[2].bounds_of().is_empty());
+        assert_eq!(g.children()[3].kind(), "group");
+        assert_eq!(g.children()[4].kind(), "group");
+        assert_eq!(g.children()[3].bounds().1.x, -1.0);
+        assert_eq!(g.children()[4].bounds().0.x, 1.0);
+    }
+
+    #[test]
+    fn test_a_divided_group_is_hit_like_the_original() {
+        let mut children = vec![];
+        for i in 0..10 {
+            for j in 0..10 {
+                let (x, y) = (i as f64 * 2.5, j as f64 * 2.5);
+                children.push(sphere_at(x, y, 0.0));
+            }
+        }
+        let g = Group::new(children);
+        let mut divided = g.clone();
+        divided.divide(4);
+        assert!(divided.children().len() < g.children().len());
+        for k in 0..40 {
+            let x = k as f64 * 0.6 - 1.0;
+            let r = Ray::new(
+                Tuple::point(x, 0.3 * x, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            );
+            let mut expected: Vec<f64> =
+                g.intersect(r).iter().map(|i| i.t).collect();
+            let mut actual: Vec<f64> =
+                divided.intersect(r).iter().map(|i| i.t).collect();
+            expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            actual.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(actual, expected);
+        }
+    }
+}
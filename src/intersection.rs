@@ -1,24 +1,57 @@
 use crate::color::Color;
+use crate::irradiance::{gather_irradiance, Indirect};
 use crate::light::lighting;
-use crate::ray::Ray;
-use crate::sphere::Sphere;
-use crate::tuple::Tuple;
-use crate::world::World;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::{Ray, RayDifferential};
+use crate::rng::Rng;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::shape::Shape;
+use crate::tuple::{build_onb, Tuple};
+use crate::world::ReflectionMode;
+use crate::EPSILON;
 use std::cmp::Ordering;
+use std::f64::consts::PI;
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub struct Intersection {
-    pub t: f32,
-    pub object: Sphere,
+/// How far shading points are pushed off the surface along the normal,
+/// before scaling by the material's shadow bias.
+pub(crate) const OVER_POINT_OFFSET: f64 = 0.01;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
     pub point: Option<Tuple>,
     pub eye_vector: Option<Tuple>,
     pub normal_vector: Option<Tuple>,
     pub inside: Option<bool>,
     pub over_point: Option<Tuple>,
+    pub reflect_vector: Option<Tuple>,
+    /// The differential of the ray that made the hit, for filtering
+    /// textures over the pixel's footprint and reflecting it along with
+    /// the ray. Both are worked out only when shading asks for them.
+    pub differential: Option<RayDifferential>,
+    /// The point just below the surface, where refracted rays start.
+    pub under_point: Option<Tuple>,
+    /// The refractive index of the material the ray is leaving.
+    pub n1: Option<f64>,
+    /// The refractive index of the material the ray is entering.
+    pub n2: Option<f64>,
+    /// The barycentric coordinates of the hit on a triangle, for
+    /// interpolating normals and texture coordinates across it. Other
+    /// shapes leave them unset.
+    pub u: Option<f64>,
+    pub v: Option<f64>,
+    /// Which face of a mesh was hit.
+    pub face: Option<usize>,
+    /// The inverse of the object's transformation, kept when the hit is
+    /// prepared so that shading doesn't invert it again.
+    pub object_inverse: Option<Matrix4>,
 }
 
-impl Intersection {
-    pub fn new(t: f32, object: Sphere) -> Intersection {
+impl<'a> Intersection<'a> {
+    pub fn new(t: f64, object: &'a dyn Shape) -> Intersection<'a> {
         Intersection {
             t,
             object,
@@ -27,41 +60,542 @@ impl Intersection {
             normal_vector: None,
             inside: None,
             over_point: None,
+            reflect_vector: None,
+            differential: None,
+            under_point: None,
+            n1: None,
+            n2: None,
+            u: None,
+            v: None,
+            face: None,
+            object_inverse: None,
         }
     }
 
+    /// Creates an intersection that remembers where on a triangle it
+    /// happened, as barycentric coordinates.
+    pub fn with_uv(
+        t: f64,
+        object: &'a dyn Shape,
+        u: f64,
+        v: f64,
+    ) -> Intersection<'a> {
+        let mut intersection = Intersection::new(t, object);
+        intersection.u = Some(u);
+        intersection.v = Some(v);
+        intersection
+    }
+
     pub fn prepare_hit(&mut self, ray: Ray) {
+        self.prepare_hit_with(ray, self.object.transform().inverse());
+    }
+
+    /// Like `prepare_hit`, given the inverse of the object's
+    /// transformation, such as the one a `Scene` keeps.
+    pub fn prepare_hit_with(&mut self, ray: Ray, object_inverse: Matrix4) {
         let point = ray.position(self.t);
         let eye_vector = -ray.direction;
-        let mut normal_vector = self.object.normal_at(point);
-        let inside;
-        if normal_vector.dot(eye_vector) < 0.0 {
-            inside = true;
+        let mut normal_vector =
+            self.object.normal_at_hit_with(point, self, object_inverse);
+        let inside = normal_vector.dot(eye_vector) < 0.0;
+        self.inside = Some(inside);
+        if let Some(bump) = self.material().bump {
+            normal_vector = bump.perturb(point, normal_vector);
+        }
+        if inside {
             normal_vector = -normal_vector;
-        } else {
-            inside = false;
         }
-        let over_point = point + (normal_vector * 0.01);
+        let offset = OVER_POINT_OFFSET * self.material().shadow_bias;
+        let over_point = point + (normal_vector * offset);
+        let under_point = point - (normal_vector * offset);
+        let reflect_vector = ray.direction.reflect(normal_vector);
         self.point = Some(point);
         self.eye_vector = Some(eye_vector);
         self.normal_vector = Some(normal_vector);
-        self.inside = Some(inside);
         self.over_point = Some(over_point);
+        self.reflect_vector = Some(reflect_vector);
+        self.under_point = Some(under_point);
+        // assume the ray crosses between this object and empty space
+        // until prepare_refraction says otherwise
+        let index = self.object.material().refractive_index;
+        let (n1, n2) = if inside { (index, 1.0) } else { (1.0, index) };
+        self.n1 = Some(n1);
+        self.n2 = Some(n2);
+        self.differential = ray.differential;
+        self.object_inverse = Some(object_inverse);
     }
 
-    pub fn shade_hit(&self, world: &World) -> Color {
-        lighting(
-            self.object.material,
-            world.light.unwrap(),
-            self.point.unwrap(),
-            self.eye_vector.unwrap(),
-            self.normal_vector.unwrap(),
-            world.is_shadowed(self.over_point.unwrap()),
+    /// Returns the inverse of the object's transformation, as kept by
+    /// `prepare_hit`, inverting it only for hits that weren't prepared.
+    pub(crate) fn object_inverse(&self) -> Matrix4 {
+        self.object_inverse
+            .unwrap_or_else(|| self.object.transform().inverse())
+    }
+
+    /// Returns the material of the side of the surface that was hit: the
+    /// object's back material for hits from the inside, when it has one.
+    pub fn material(&self) -> &'a Material {
+        match (self.inside, self.object.back_material()) {
+            (Some(true), Some(back_material)) => back_material,
+            _ => self.object.material(),
+        }
+    }
+
+    /// Finds the refractive indices on either side of the hit from all
+    /// the intersections along the ray, sorted by `t`, so that rays
+    /// passing between overlapping objects bend correctly.
+    pub fn prepare_refraction(&mut self, intersections: &[Intersection]) {
+        let mut containers: Vec<&dyn Shape> = vec![];
+        let outermost_index = |containers: &Vec<&dyn Shape>| {
+            medium(containers)
+                .map_or(1.0, |object| object.material().refractive_index)
+        };
+        for i in intersections {
+            let is_hit = i.t == self.t && i.object == self.object;
+            if is_hit {
+                self.n1 = Some(outermost_index(&containers));
+            }
+            match containers.iter().position(|&object| object == i.object) {
+                Some(position) => {
+                    containers.remove(position);
+                }
+                None => containers.push(i.object),
+            }
+            if is_hit {
+                self.n2 = Some(outermost_index(&containers));
+                return;
+            }
+        }
+    }
+
+    /// Follows the auxiliary rays of the differential to the plane
+    /// tangent to the surface at the hit, returning where they cross it.
+    fn tangent_points(&self) -> Option<(Tuple, Tuple)> {
+        let differential = self.differential?;
+        let point = self.point?;
+        let normal_vector = self.normal_vector?;
+        let on_tangent_plane = |origin: Tuple, direction: Tuple| {
+            let denominator = normal_vector.dot(direction);
+            if denominator.abs() < EPSILON {
+                return None;
+            }
+            let t = normal_vector.dot(point - origin) / denominator;
+            Some(origin + direction * t)
+        };
+        let x_point =
+            on_tangent_plane(differential.x_origin, differential.x_direction)?;
+        let y_point =
+            on_tangent_plane(differential.y_origin, differential.y_direction)?;
+        Some((x_point, y_point))
+    }
+
+    /// The approximate width of the area covered by one pixel around the
+    /// hit. Only known when the ray carries a differential.
+    pub fn footprint(&self) -> Option<f64> {
+        let point = self.point?;
+        let (x_point, y_point) = self.tangent_points()?;
+        Some(
+            (x_point - point)
+                .magnitude()
+                .max((y_point - point).magnitude()),
+        )
+    }
+
+    /// Reflects the ray's differential along with the ray, so that
+    /// reflected rays keep filtering the textures they hit.
+    pub fn reflect_differential(&self) -> Option<RayDifferential> {
+        let differential = self.differential?;
+        let (x_point, y_point) = self.tangent_points()?;
+        let inverse = self.object_inverse();
+        // use the surface normal at each offset point, so curved
+        // surfaces spread the reflected differential apart
+        Some(RayDifferential {
+            x_origin: x_point,
+            x_direction: differential
+                .x_direction
+                .reflect(self.object.normal_at_with(x_point, inverse)),
+            y_origin: y_point,
+            y_direction: differential
+                .y_direction
+                .reflect(self.object.normal_at_with(y_point, inverse)),
+        })
+    }
+
+    /// Computes the color at the hit, where `remaining` is the number of
+    /// further reflections and refractions that may still be traced.
+    pub fn shade_hit(
+        &self,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        self.shade_hit_with(&SurfaceShading::new(self), scene, remaining, rng)
+    }
+
+    /// Like `shade_hit`, taking what shading needs from the material from
+    /// `shading`, which must have been made for a hit on the same side of
+    /// the same object.
+    pub(crate) fn shade_hit_with(
+        &self,
+        shading: &SurfaceShading,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        let visibility = self.light_visibility(scene, rng);
+        let surface = self.lit_with(shading, scene, &visibility)
+            + self.indirect_with(shading, scene, remaining, rng);
+        let reflected = self.reflected_color(scene, remaining, rng);
+        let refracted = self.refracted_color(scene, remaining, rng);
+        self.combine(surface, reflected, refracted)
+    }
+
+    /// Adds up the parts of the color at the hit, weighting reflections
+    /// and refractions by the Fresnel effect when the surface has both.
+    pub(crate) fn combine(
+        &self,
+        surface: Color,
+        reflected: Color,
+        refracted: Color,
+    ) -> Color {
+        let material = self.material();
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = self.schlick();
+            surface + reflected * reflectance + refracted * (1.0 - reflectance)
+        } else {
+            surface + reflected + refracted
+        }
+    }
+
+    /// Computes the light scattered by the surface itself at the hit,
+    /// leaving out reflections and refractions.
+    pub fn surface_color(&self, scene: &Scene, rng: &mut Rng) -> Color {
+        let visibility = self.light_visibility(scene, rng);
+        self.lit_color(scene, &visibility)
+    }
+
+    /// Returns the fraction of each light that reaches the hit, in the
+    /// order of `World::lights`.
+    pub(crate) fn light_visibility(
+        &self,
+        scene: &Scene,
+        rng: &mut Rng,
+    ) -> Vec<f64> {
+        scene
+            .world()
+            .lights
+            .iter()
+            .map(|light| {
+                scene.light_intensity_at(self.over_point.unwrap(), light, rng)
+            })
+            .collect()
+    }
+
+    /// Like `surface_color`, with the fraction of each light that reaches
+    /// the hit already known, in the order of `World::lights`.
+    pub(crate) fn lit_color(&self, scene: &Scene, visibility: &[f64]) -> Color {
+        self.lit_with(&SurfaceShading::new(self), scene, visibility)
+    }
+
+    fn lit_with(
+        &self,
+        shading: &SurfaceShading,
+        scene: &Scene,
+        visibility: &[f64],
+    ) -> Color {
+        let mut material = shading.material.clone();
+        material.color = shading.albedo(self);
+        material.toon = material.toon.or(scene.world().toon);
+        scene.world().lights.iter().zip(visibility.iter()).fold(
+            Color::black(),
+            |color, (light, &visibility)| {
+                color
+                    + lighting(
+                        material.clone(),
+                        *light,
+                        self.point.unwrap(),
+                        self.eye_vector.unwrap(),
+                        self.normal_vector.unwrap(),
+                        visibility,
+                    )
+            },
         )
     }
+
+    /// Computes the light reaching the surface at the hit by bouncing off
+    /// other surfaces, scattered by its diffuse term. Rays traced to find
+    /// it have no remaining depth, so only one bounce is followed.
+    pub fn indirect_color(
+        &self,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        self.indirect_with(&SurfaceShading::new(self), scene, remaining, rng)
+    }
+
+    fn indirect_with(
+        &self,
+        shading: &SurfaceShading,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        let material = &shading.material;
+        let diffuse = if material.casts_diffuse {
+            material.diffuse
+        } else {
+            0.0
+        };
+        if scene.world().indirect == Indirect::Off
+            || remaining == 0
+            || diffuse <= 0.0
+        {
+            return Color::black();
+        }
+        let point = self.over_point.unwrap();
+        let normal = self.normal_vector.unwrap();
+        let cached = match (scene.world().indirect, scene.irradiance_cache()) {
+            (Indirect::Cached { .. }, Some(cache)) => {
+                cache.interpolate(point, normal)
+            }
+            _ => None,
+        };
+        let irradiance = cached.unwrap_or_else(|| {
+            let samples = match scene.world().indirect {
+                Indirect::Sampled { samples } => samples,
+                Indirect::Cached { samples, .. } => samples,
+                Indirect::Off => 0,
+            };
+            gather_irradiance(scene, point, normal, samples, rng).0
+        });
+        shading.albedo(self) * irradiance * diffuse
+    }
+
+    /// Returns the unlit color of the surface at the hit, taken from the
+    /// material's pattern if it has one.
+    pub fn albedo(&self) -> Color {
+        SurfaceShading::new(self).albedo(self)
+    }
+
+    /// Looks the pattern up around the hit, using `color_at` to find the
+    /// color at a point in world space.
+    fn filtered_pattern<F>(&self, color_at: F) -> Color
+    where
+        F: Fn(Tuple) -> Color,
+    {
+        let point = self.point.unwrap();
+        match self.tangent_points() {
+            // box-filter the pattern over the pixel's footprint, so that
+            // distant patterns blur instead of sparkling
+            Some((x_point, y_point)) => {
+                let dx = (x_point - point) * 0.25;
+                let dy = (y_point - point) * 0.25;
+                average(
+                    [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                        .iter()
+                        .map(|&(i, j)| color_at(point + dx * i + dy * j)),
+                )
+            }
+            None => color_at(point),
+        }
+    }
+
+    /// Limits the remaining depth to the material's own cap, if any.
+    pub(crate) fn remaining_depth(&self, remaining: usize) -> usize {
+        self.material()
+            .max_depth
+            .map_or(remaining, |max_depth| remaining.min(max_depth))
+    }
+
+    /// Returns the mirror reflection of the ray at the hit, with its
+    /// differential when the hit has one.
+    pub(crate) fn reflect_ray(&self) -> Ray {
+        match self.reflect_differential() {
+            Some(differential) => Ray::with_differential(
+                self.over_point.unwrap(),
+                self.reflect_vector.unwrap(),
+                differential,
+            ),
+            None => {
+                Ray::new(self.over_point.unwrap(), self.reflect_vector.unwrap())
+            }
+        }
+    }
+
+    pub fn reflected_color(
+        &self,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        let material = self.material();
+        let remaining = self.remaining_depth(remaining);
+        if material.reflective == 0.0 || remaining == 0 {
+            return Color::black();
+        }
+        let directions = glossy_directions(
+            self.reflect_vector.unwrap(),
+            self.normal_vector.unwrap(),
+            material.roughness,
+            material.glossy_samples,
+            scene.world().sampler,
+            rng,
+        );
+        if scene.reflection_mode() == ReflectionMode::Environment {
+            return average(
+                directions.iter().map(|&d| scene.environment_color(d)),
+            ) * material.reflective;
+        }
+        if directions.len() > 1 {
+            let over_point = self.over_point.unwrap();
+            return average(directions.iter().map(|&d| {
+                scene.color_at_depth(
+                    Ray::new(over_point, d),
+                    remaining - 1,
+                    rng,
+                )
+            })) * material.reflective;
+        }
+        scene.color_at_depth(self.reflect_ray(), remaining - 1, rng)
+            * material.reflective
+    }
+
+    pub fn refracted_color(
+        &self,
+        scene: &Scene,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        let material = self.material();
+        let remaining = self.remaining_depth(remaining);
+        if material.transparency == 0.0 || remaining == 0 {
+            return Color::black();
+        }
+        let n1 = self.n1.unwrap();
+        let n2 = self.n2.unwrap();
+        let color = if material.dispersion == 0.0 {
+            self.refract(scene, remaining, n1, n2, rng)
+        } else {
+            // trace one ray per channel, with this object's index spread
+            // evenly between red (lowest) and blue (highest)
+            let mut channel = |shift: f64| {
+                let shift = shift * material.dispersion;
+                if self.inside.unwrap() {
+                    self.refract(scene, remaining, n1 + shift, n2, rng)
+                } else {
+                    self.refract(scene, remaining, n1, n2 + shift, rng)
+                }
+            };
+            Color::new(channel(-0.5).red, channel(0.0).green, channel(0.5).blue)
+        };
+        color * material.transparency
+    }
+
+    /// Traces the ray refracted from `n1` into `n2`, or returns black on
+    /// total internal reflection.
+    fn refract(
+        &self,
+        scene: &Scene,
+        remaining: usize,
+        n1: f64,
+        n2: f64,
+        rng: &mut Rng,
+    ) -> Color {
+        let direction = match self.refract_direction(n1, n2) {
+            Some(direction) => direction,
+            None => return Color::black(),
+        };
+        let material = self.material();
+        let under_point = self.under_point.unwrap();
+        let directions = glossy_directions(
+            direction,
+            -self.normal_vector.unwrap(),
+            material.roughness,
+            material.glossy_samples,
+            scene.world().sampler,
+            rng,
+        );
+        average(directions.iter().map(|&d| {
+            scene.color_at_depth(Ray::new(under_point, d), remaining - 1, rng)
+        }))
+    }
+
+    /// Returns the direction of the ray refracted from `n1` into `n2`
+    /// following Snell's law, or `None` on total internal reflection.
+    pub fn refract_direction(&self, n1: f64, n2: f64) -> Option<Tuple> {
+        let eye_vector = self.eye_vector.unwrap();
+        let normal_vector = self.normal_vector.unwrap();
+        let n_ratio = n1 / n2;
+        let cos_i = eye_vector.dot(normal_vector);
+        let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(normal_vector * (n_ratio * cos_i - cos_t) - eye_vector * n_ratio)
+    }
+
+    /// Approximates the Fresnel effect, returning the fraction of light
+    /// that is reflected rather than refracted at the hit.
+    pub fn schlick(&self) -> f64 {
+        let n1 = self.n1.unwrap();
+        let n2 = self.n2.unwrap();
+        let mut cos = self.eye_vector.unwrap().dot(self.normal_vector.unwrap());
+        if n1 > n2 {
+            let n_ratio = n1 / n2;
+            let sin2_t = n_ratio * n_ratio * (1.0 - cos * cos);
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+            cos = (1.0 - sin2_t).sqrt();
+        }
+        let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
+}
+
+/// What shading a hit needs from its material that doesn't depend on
+/// where the hit is, worked out once so that a batch of hits on the same
+/// side of the same object can share it.
+pub(crate) struct SurfaceShading {
+    material: Material,
+    /// The inverses of the object's and of the pattern's transformations,
+    /// when the material has a pattern.
+    pattern_inverses: Option<(Matrix4, Matrix4)>,
+}
+
+impl SurfaceShading {
+    pub(crate) fn new(hit: &Intersection) -> SurfaceShading {
+        let material = hit.material().clone();
+        let pattern_inverses = material.pattern.as_ref().map(|pattern| {
+            (hit.object_inverse(), pattern.transform().inverse())
+        });
+        SurfaceShading {
+            material,
+            pattern_inverses,
+        }
+    }
+
+    /// Returns the unlit color of the surface at the hit, like
+    /// `Intersection::albedo`.
+    fn albedo(&self, hit: &Intersection) -> Color {
+        let (pattern, (object_inverse, pattern_inverse)) =
+            match (&self.material.pattern, self.pattern_inverses) {
+                (Some(pattern), Some(inverses)) => (pattern, inverses),
+                _ => return self.material.color,
+            };
+        hit.filtered_pattern(|point| {
+            pattern.pattern_at_object(
+                hit.object,
+                object_inverse * point,
+                pattern_inverse,
+            )
+        })
+    }
 }
 
-impl Ord for Intersection {
+impl Ord for Intersection<'_> {
     fn cmp(&self, other: &Intersection) -> Ordering {
         if self.t < other.t {
             Ordering::Less
@@ -73,43 +607,146 @@ impl Ord for Intersection {
     }
 }
 
-impl PartialOrd for Intersection {
+impl PartialOrd for Intersection<'_> {
     fn partial_cmp(&self, other: &Intersection) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Eq for Intersection {}
+impl Eq for Intersection<'_> {}
+
+/// Spreads `samples` directions over a cone around `direction` with the
+/// sampler, with `roughness` the tangent of the cone's half-angle.
+/// Directions that would cross to the wrong side of the surface, facing
+/// away from `side`, are mirrored back.
+fn glossy_directions(
+    direction: Tuple,
+    side: Tuple,
+    roughness: f64,
+    samples: usize,
+    sampler: Sampler,
+    rng: &mut Rng,
+) -> Vec<Tuple> {
+    if roughness <= 0.0 || samples <= 1 {
+        return vec![direction];
+    }
+    let direction = direction.normalize();
+    let (tangent, bitangent) = build_onb(direction);
+    sampler
+        .points(samples, rng)
+        .into_iter()
+        .map(|(s, t)| {
+            let radius = s.sqrt();
+            let theta = 2.0 * PI * t;
+            let offset = tangent * (theta.cos() * radius)
+                + bitangent * (theta.sin() * radius);
+            let jittered = (direction + offset * roughness).normalize();
+            if jittered.dot(side) < 0.0 {
+                jittered.reflect(side)
+            } else {
+                jittered
+            }
+        })
+        .collect()
+}
 
-pub fn find_hit(intersections: Vec<Intersection>) -> Option<Intersection> {
+fn average<I>(colors: I) -> Color
+where
+    I: ExactSizeIterator<Item = Color>,
+{
+    let count = colors.len() as f64;
+    colors.fold(Color::black(), |sum, color| sum + color) * (1.0 / count)
+}
+
+pub fn find_hit(
+    intersections: Vec<Intersection<'_>>,
+) -> Option<Intersection<'_>> {
     intersections.iter().filter(|i| i.t >= 0.0).min().cloned()
 }
 
+/// Returns the object whose material fills the space inside all the
+/// given objects: the one with the highest priority, or the last entered
+/// of those tied for it.
+fn medium<'a>(containers: &[&'a dyn Shape]) -> Option<&'a dyn Shape> {
+    containers
+        .iter()
+        .max_by_key(|object| object.material().priority)
+        .copied()
+}
+
+/// Returns the position of the first intersection at or after the
+/// origin of the ray that lies on a visible surface, given all the
+/// intersections along the ray sorted by `t`. Surfaces inside an object
+/// with a higher priority are skipped, since that object's material
+/// replaces theirs.
+pub fn find_true_hit(intersections: &[Intersection]) -> Option<usize> {
+    let mut containers: Vec<&dyn Shape> = vec![];
+    for (index, i) in intersections.iter().enumerate() {
+        let position = containers.iter().position(|&object| object == i.object);
+        if let Some(position) = position {
+            containers.remove(position);
+        }
+        let visible = medium(&containers).is_none_or(|outer| {
+            i.object.material().priority >= outer.material().priority
+        });
+        if i.t >= 0.0 && visible {
+            return Some(index);
+        }
+        if position.is_none() {
+            containers.push(i.object);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
+    use crate::canvas::Canvas;
     use crate::color::Color;
-    use crate::intersection::{find_hit, Intersection};
+    use crate::environment::Environment;
+    use crate::intersection::{
+        find_hit, find_true_hit, glossy_directions, Intersection,
+    };
+    use crate::light::PointLight;
+    use crate::material::Material;
     use crate::matrix::Matrix4;
-    use crate::ray::Ray;
+    use crate::pattern::{CheckersPattern, PlanarImagePattern};
+    use crate::plane::Plane;
+    use crate::ray::{Ray, RayDifferential};
+    use crate::rng::Rng;
+    use crate::sampler::Sampler;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
     use crate::sphere::Sphere;
     use crate::tuple::Tuple;
     use crate::world::World;
-    use crate::EPSILON;
+    use crate::{equal_f64, EPSILON};
+    use std::sync::Arc;
 
     #[test]
     fn test_an_intersection_encapsulates_t_and_an_object() {
         let s = Sphere::default();
-        let i = Intersection::new(3.5, s);
+        let i = Intersection::new(3.5, &s);
         assert_eq!(i.t, 3.5);
-        assert_eq!(i.object, s);
+        assert_eq!(i.object, &s as &dyn Shape);
+        assert_eq!((i.u, i.v), (None, None));
+    }
+
+    #[test]
+    fn test_an_intersection_can_encapsulate_u_and_v() {
+        let s = Sphere::default();
+        let i = Intersection::with_uv(3.5, &s, 0.2, 0.4);
+        assert_eq!(i.u, Some(0.2));
+        assert_eq!(i.v, Some(0.4));
     }
 
     #[test]
     fn test_aggregating_intersections() {
         let s = Sphere::default();
-        let i1 = Intersection::new(1.0, s);
-        let i2 = Intersection::new(2.0, s);
-        let intersections = vec![i1, i2];
+        let i1 = Intersection::new(1.0, &s);
+        let i2 = Intersection::new(2.0, &s);
+        let intersections = [i1, i2];
         assert_eq!(intersections.len(), 2);
         assert_eq!(intersections[0].t, 1.0);
         assert_eq!(intersections[1].t, 2.0);
@@ -124,16 +761,16 @@ mod tests {
         let s = Sphere::default();
         let xs = s.intersect(r);
         assert_eq!(xs.len(), 2);
-        assert_eq!(xs[0].object, s);
-        assert_eq!(xs[1].object, s);
+        assert_eq!(xs[0].object, &s as &dyn Shape);
+        assert_eq!(xs[1].object, &s as &dyn Shape);
     }
 
     #[test]
     fn test_the_hit_when_all_intersections_have_positive_t() {
         let s = Sphere::default();
-        let i1 = Intersection::new(1.0, s);
-        let i2 = Intersection::new(2.0, s);
-        let xs = vec![i2, i1];
+        let i1 = Intersection::new(1.0, &s);
+        let i2 = Intersection::new(2.0, &s);
+        let xs = vec![i2.clone(), i1.clone()];
         let i = find_hit(xs);
         assert!(i.is_some());
         assert_eq!(i.unwrap(), i1);
@@ -142,9 +779,9 @@ mod tests {
     #[test]
     fn test_the_hit_when_some_intersections_have_negative_t() {
         let s = Sphere::default();
-        let i1 = Intersection::new(-1.0, s);
-        let i2 = Intersection::new(1.0, s);
-        let xs = vec![i2, i1];
+        let i1 = Intersection::new(-1.0, &s);
+        let i2 = Intersection::new(1.0, &s);
+        let xs = vec![i2.clone(), i1.clone()];
         let i = find_hit(xs);
         assert!(i.is_some());
         assert_eq!(i.unwrap(), i2);
@@ -153,9 +790,9 @@ mod tests {
     #[test]
     fn test_the_hit_when_all_intersections_have_negative_t() {
         let s = Sphere::default();
-        let i1 = Intersection::new(-2.0, s);
-        let i2 = Intersection::new(-1.0, s);
-        let xs = vec![i2, i1];
+        let i1 = Intersection::new(-2.0, &s);
+        let i2 = Intersection::new(-1.0, &s);
+        let xs = vec![i2.clone(), i1.clone()];
         let i = find_hit(xs);
         assert!(i.is_none());
     }
@@ -163,11 +800,11 @@ mod tests {
     #[test]
     fn test_the_hit_is_always_the_lowest_nonnegative_intersection() {
         let s = Sphere::default();
-        let i1 = Intersection::new(5.0, s);
-        let i2 = Intersection::new(7.0, s);
-        let i3 = Intersection::new(-3.0, s);
-        let i4 = Intersection::new(2.0, s);
-        let xs = vec![i1, i2, i3, i4];
+        let i1 = Intersection::new(5.0, &s);
+        let i2 = Intersection::new(7.0, &s);
+        let i3 = Intersection::new(-3.0, &s);
+        let i4 = Intersection::new(2.0, &s);
+        let xs = vec![i1.clone(), i2.clone(), i3.clone(), i4.clone()];
         let i = find_hit(xs);
         assert!(i.is_some());
         assert_eq!(i.unwrap(), i4);
@@ -221,10 +858,10 @@ mod tests {
             Tuple::point(0.0, 0.0, -5.0),
             Tuple::vector(0.0, 0.0, 1.0),
         );
-        let shape = w.objects[0];
+        let shape = &*w.objects[0];
         let mut i = find_hit(shape.intersect(r)).unwrap();
         i.prepare_hit(r);
-        let c = i.shade_hit(&w);
+        let c = i.shade_hit(&Scene::new(&w), w.max_depth, &mut Rng::new(0));
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
@@ -236,9 +873,568 @@ mod tests {
         );
         let mut shape = Sphere::default();
         shape.transform = Matrix4::translation(0.0, 0.0, 1.0);
-        let mut i = Intersection::new(5.0, shape);
+        let mut i = Intersection::new(5.0, &shape);
         i.prepare_hit(r);
         assert!(i.over_point.unwrap().z < -EPSILON / 2.0);
         assert!(i.point.unwrap().z > i.over_point.unwrap().z);
     }
+
+    #[test]
+    fn test_precomputing_the_reflection_vector() {
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let shape = Sphere::default();
+        let mut i = Intersection::new(4.0, &shape);
+        i.prepare_hit(r);
+        assert_eq!(i.reflect_vector, Some(Tuple::vector(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_the_reflected_color_for_a_nonreflective_material() {
+        let mut w = World::default();
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        w.objects[1].material_mut().ambient = 1.0;
+        let mut i = Intersection::new(0.5, &*w.objects[1]);
+        i.prepare_hit(r);
+        assert_eq!(
+            i.reflected_color(&Scene::new(&w), w.max_depth, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_the_reflected_color_for_a_reflective_material() {
+        let (w, r) = mirror_world();
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        let scene = Scene::new(&w);
+        assert_eq!(
+            i.reflected_color(&scene, w.max_depth, &mut Rng::new(0)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            i.shade_hit(&scene, w.max_depth, &mut Rng::new(0)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_the_reflected_color_at_the_maximum_recursive_depth() {
+        let (w, r) = mirror_world();
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        assert_eq!(
+            i.reflected_color(&Scene::new(&w), 0, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_a_material_can_cap_its_own_reflection_depth() {
+        let (mut w, r) = mirror_world();
+        w.objects[0].material_mut().max_depth = Some(0);
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        assert_eq!(
+            i.reflected_color(&Scene::new(&w), w.max_depth, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_a_rough_mirror_blurs_its_reflection() {
+        let (mut w, r) = mirror_world();
+        w.objects[0].material_mut().roughness = 0.2;
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        let c =
+            i.reflected_color(&Scene::new(&w), w.max_depth, &mut Rng::new(0));
+        assert!(c.red > 0.0 && c.red < 1.0);
+        assert_eq!(c.green, 0.0);
+    }
+
+    #[test]
+    fn test_glossy_directions_stay_within_the_cone() {
+        let direction = Tuple::vector(0.0, 0.0, 1.0);
+        let side = Tuple::vector(0.0, 0.0, 1.0);
+        assert_eq!(
+            glossy_directions(
+                direction,
+                side,
+                0.0,
+                16,
+                Sampler::Fibonacci,
+                &mut Rng::new(0)
+            ),
+            [direction]
+        );
+        let directions = glossy_directions(
+            direction,
+            side,
+            0.5,
+            16,
+            Sampler::Sobol,
+            &mut Rng::new(0),
+        );
+        assert_eq!(directions.len(), 16);
+        let min_cos = 1.0 / (1.0f64 + 0.5 * 0.5).sqrt();
+        for d in directions {
+            assert!(d.dot(direction) >= min_cos - EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_glossy_directions_never_cross_the_surface() {
+        let direction = Tuple::vector(1.0, 0.01, 0.0);
+        let side = Tuple::vector(0.0, 1.0, 0.0);
+        for d in glossy_directions(
+            direction,
+            side,
+            0.5,
+            16,
+            Sampler::Halton,
+            &mut Rng::new(0),
+        ) {
+            assert!(d.dot(side) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_hits_from_the_inside_use_the_back_material() {
+        let mut shape = Sphere::default();
+        let mut back = Material::default();
+        back.color = Color::new(1.0, 0.0, 0.0);
+        shape.back_material = Some(back.clone());
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut outside = Intersection::new(4.0, &shape);
+        outside.prepare_hit(r);
+        assert_eq!(*outside.material(), shape.material);
+        let mut inside = Intersection::new(6.0, &shape);
+        inside.prepare_hit(r);
+        assert_eq!(*inside.material(), back);
+    }
+
+    #[test]
+    fn test_shading_the_inside_of_a_two_sided_sphere() {
+        let mut w = World::default();
+        w.lights = vec![PointLight::new(
+            Tuple::point(0.0, 0.25, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        )];
+        let mut inner = Sphere::default();
+        inner.transform = Matrix4::scaling(0.5, 0.5, 0.5);
+        let mut back = inner.material.clone();
+        back.color = Color::new(1.0, 0.0, 0.0);
+        inner.back_material = Some(back);
+        w.objects[1] = Box::new(inner);
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut i = Intersection::new(0.5, &*w.objects[1]);
+        i.prepare_hit(r);
+        let c = i.shade_hit(&Scene::new(&w), w.max_depth, &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.90498, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_shading_a_hit_on_a_patterned_surface() {
+        let mut canvas = Canvas::new(2, 2);
+        for y in 0..2 {
+            canvas.write_pixel(0, y, Color::new(1.0, 0.0, 0.0));
+            canvas.write_pixel(1, y, Color::new(0.0, 0.0, 1.0));
+        }
+        let mut shape = Sphere::default();
+        shape.material.ambient = 1.0;
+        shape.material.diffuse = 0.0;
+        shape.material.specular = 0.0;
+        shape.material.pattern =
+            Some(Arc::new(PlanarImagePattern::new(canvas, 2.0, 2.0)));
+        let mut w = World::default();
+        w.objects = vec![Box::new(shape)];
+        let left = Ray::new(
+            Tuple::point(-0.5, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let right = Ray::new(
+            Tuple::point(0.5, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let scene = Scene::new(&w);
+        assert_eq!(scene.color_at(left), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(scene.color_at(right), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_a_bumpy_material_perturbs_the_normal() {
+        let r = Ray::new(
+            Tuple::point(0.3, 0.2, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let shape = Sphere::default();
+        let mut flat = find_hit(shape.intersect(r)).unwrap();
+        flat.prepare_hit(r);
+        let mut water = Sphere::default();
+        water.material = Material::water();
+        let mut bumpy = find_hit(water.intersect(r)).unwrap();
+        bumpy.prepare_hit(r);
+        assert_eq!(bumpy.point, flat.point);
+        assert_ne!(bumpy.normal_vector, flat.normal_vector);
+        assert!(
+            bumpy
+                .normal_vector
+                .unwrap()
+                .dot(flat.normal_vector.unwrap())
+                > 0.0
+        );
+    }
+
+    /// A world with a mirror at the origin that reflects a red sphere
+    /// behind the ray's origin back to it.
+    fn mirror_world() -> (World, Ray) {
+        let mut mirror = Sphere::default();
+        mirror.material.ambient = 0.0;
+        mirror.material.diffuse = 0.0;
+        mirror.material.specular = 0.0;
+        mirror.material.reflective = 1.0;
+        let mut red = Sphere::default();
+        red.transform = Matrix4::translation(0.0, 0.0, -10.0);
+        red.material.color = Color::new(1.0, 0.0, 0.0);
+        red.material.ambient = 1.0;
+        red.material.diffuse = 0.0;
+        red.material.specular = 0.0;
+        let mut w = World::default();
+        w.objects = vec![Box::new(mirror), Box::new(red)];
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        (w, r)
+    }
+
+    #[test]
+    fn test_reflecting_a_ray_differential_off_a_curved_surface() {
+        let d = RayDifferential {
+            x_origin: Tuple::point(0.0, 0.0, -5.0),
+            x_direction: Tuple::vector(0.01, 0.0, 1.0).normalize(),
+            y_origin: Tuple::point(0.0, 0.0, -5.0),
+            y_direction: Tuple::vector(0.0, 0.01, 1.0).normalize(),
+        };
+        let r = Ray::with_differential(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+            d,
+        );
+        let s = Sphere::default();
+        let mut i = Intersection::new(4.0, &s);
+        i.prepare_hit(r);
+        let reflected = i.reflect_differential().unwrap();
+        let incoming_spread = (d.x_direction - r.direction).magnitude();
+        let reflected_spread =
+            (reflected.x_direction - i.reflect_vector.unwrap()).magnitude();
+        assert!(reflected_spread > incoming_spread);
+    }
+
+    #[test]
+    fn test_patterns_are_filtered_over_the_pixel_footprint() {
+        let mut floor = Plane::default();
+        floor.material.pattern = Some(Arc::new(CheckersPattern::new(
+            Color::white(),
+            Color::black(),
+        )));
+        let origin = Tuple::point(0.25, 1.0, 0.25);
+        let direction = Tuple::vector(0.0, -1.0, 0.0);
+        let sharp = Ray::new(origin, direction);
+        let mut i = Intersection::new(1.0, &floor);
+        i.prepare_hit(sharp);
+        assert_eq!(i.albedo(), Color::white());
+        // neighboring pixels land two checkers away, so the pixel covers
+        // as much black as white
+        let blurred = Ray::with_differential(
+            origin,
+            direction,
+            RayDifferential {
+                x_origin: origin,
+                x_direction: Tuple::vector(2.0, -1.0, 0.0).normalize(),
+                y_origin: origin,
+                y_direction: Tuple::vector(0.0, -1.0, 2.0).normalize(),
+            },
+        );
+        let mut i = Intersection::new(1.0, &floor);
+        i.prepare_hit(blurred);
+        assert_eq!(i.albedo(), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_the_shadow_bias_scales_the_point_offset() {
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut shape = Sphere::default();
+        shape.material.shadow_bias = 10.0;
+        let mut i = Intersection::new(4.0, &shape);
+        i.prepare_hit(r);
+        assert_eq!(i.over_point, Some(Tuple::point(0.0, 0.0, -1.1)));
+        assert_eq!(i.under_point, Some(Tuple::point(0.0, 0.0, -0.9)));
+        shape.material.shadow_bias = 0.0;
+        let mut i = Intersection::new(4.0, &shape);
+        i.prepare_hit(r);
+        assert_eq!(i.over_point, i.point);
+        assert_eq!(i.under_point, i.point);
+    }
+
+    fn glass_sphere() -> Sphere {
+        let mut s = Sphere::default();
+        s.material.transparency = 1.0;
+        s.material.refractive_index = 1.5;
+        s
+    }
+
+    #[test]
+    fn test_finding_n1_and_n2_at_various_intersections() {
+        let mut a = glass_sphere();
+        a.transform = Matrix4::scaling(2.0, 2.0, 2.0);
+        a.material.refractive_index = 1.5;
+        let mut b = glass_sphere();
+        b.transform = Matrix4::translation(0.0, 0.0, -0.25);
+        b.material.refractive_index = 2.0;
+        let mut c = glass_sphere();
+        c.transform = Matrix4::translation(0.0, 0.0, 0.25);
+        c.material.refractive_index = 2.5;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -4.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = vec![
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(3.25, &c),
+            Intersection::new(4.75, &b),
+            Intersection::new(5.25, &c),
+            Intersection::new(6.0, &a),
+        ];
+        let expected = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+        for (i, (n1, n2)) in xs.iter().zip(expected.iter()) {
+            let mut i = i.clone();
+            i.prepare_hit(r);
+            i.prepare_refraction(&xs);
+            assert_eq!(i.n1, Some(*n1));
+            assert_eq!(i.n2, Some(*n2));
+        }
+    }
+
+    #[test]
+    fn test_overlapping_media_resolve_by_priority() {
+        let mut glass = glass_sphere();
+        glass.material.priority = 2;
+        let mut water = glass_sphere();
+        water.transform = Matrix4::translation(0.0, 0.0, 1.0);
+        water.material.refractive_index = 1.33;
+        water.material.priority = 1;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = vec![
+            Intersection::new(4.0, &glass),
+            Intersection::new(5.0, &water),
+            Intersection::new(6.0, &glass),
+            Intersection::new(7.0, &water),
+        ];
+        assert_eq!(find_true_hit(&xs), Some(0));
+        // from inside the glass, the water's surface is ignored
+        let inside: Vec<Intersection> = xs
+            .iter()
+            .map(|i| Intersection::new(i.t - 4.5, i.object))
+            .collect();
+        assert_eq!(find_true_hit(&inside), Some(2));
+        let expected = [(1.0, 1.5), (1.5, 1.33), (1.33, 1.0)];
+        for (&index, (n1, n2)) in [0, 2, 3].iter().zip(expected.iter()) {
+            let mut i = xs[index].clone();
+            i.prepare_hit(r);
+            i.prepare_refraction(&xs);
+            assert_eq!(i.n1, Some(*n1));
+            assert_eq!(i.n2, Some(*n2));
+        }
+        // without priorities every surface is visible
+        water.material.priority = 2;
+        let xs = [
+            Intersection::new(-1.0, &glass),
+            Intersection::new(5.0, &water),
+        ];
+        assert_eq!(find_true_hit(&xs), Some(1));
+    }
+
+    #[test]
+    fn test_the_under_point_is_offset_below_the_surface() {
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut shape = glass_sphere();
+        shape.transform = Matrix4::translation(0.0, 0.0, 1.0);
+        let mut i = Intersection::new(5.0, &shape);
+        i.prepare_hit(r);
+        i.prepare_refraction(&[i.clone()]);
+        assert!(i.under_point.unwrap().z > EPSILON / 2.0);
+        assert!(i.point.unwrap().z < i.under_point.unwrap().z);
+    }
+
+    #[test]
+    fn test_the_refracted_color_with_an_opaque_surface() {
+        let w = World::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        assert_eq!(
+            i.refracted_color(&Scene::new(&w), w.max_depth, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_the_refracted_color_at_the_maximum_recursive_depth() {
+        let mut w = World::default();
+        w.objects[0].material_mut().transparency = 1.0;
+        w.objects[0].material_mut().refractive_index = 1.5;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut i = find_hit(w.objects[0].intersect(r)).unwrap();
+        i.prepare_hit(r);
+        assert_eq!(
+            i.refracted_color(&Scene::new(&w), 0, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_the_refracted_color_under_total_internal_reflection() {
+        let mut w = World::default();
+        w.objects[0].material_mut().transparency = 1.0;
+        w.objects[0].material_mut().refractive_index = 1.5;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, 2f64.sqrt() / 2.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let xs = vec![
+            Intersection::new(-(2f64.sqrt()) / 2.0, &*w.objects[0]),
+            Intersection::new(2f64.sqrt() / 2.0, &*w.objects[0]),
+        ];
+        let mut i = xs[1].clone();
+        i.prepare_hit(r);
+        i.prepare_refraction(&xs);
+        assert_eq!(
+            i.refracted_color(&Scene::new(&w), w.max_depth, &mut Rng::new(0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_a_transparent_object_with_the_index_of_air_is_invisible() {
+        let mut glass = Sphere::default();
+        glass.material.ambient = 0.0;
+        glass.material.diffuse = 0.0;
+        glass.material.specular = 0.0;
+        glass.material.transparency = 1.0;
+        let mut w = World::default();
+        w.objects = vec![Box::new(glass)];
+        w.environment = Some(Environment::Uniform(Color::new(0.2, 0.4, 0.6)));
+        let r = Ray::new(
+            Tuple::point(0.3, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(Scene::new(&w).color_at(r), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn test_dispersion_refracts_each_channel_separately() {
+        let mut glass = glass_sphere();
+        glass.material.ambient = 0.0;
+        glass.material.diffuse = 0.0;
+        glass.material.specular = 0.0;
+        let mut w = World::default();
+        w.objects = vec![Box::new(glass)];
+        // a gray gradient around the horizon, so every direction has
+        // equal channels but different directions have different grays
+        let mut canvas = Canvas::new(360, 1);
+        for x in 0..360 {
+            let gray = (x as f64) / 359.0;
+            canvas.write_pixel(x, 0, Color::new(gray, gray, gray));
+        }
+        w.environment = Some(Environment::Map(canvas));
+        let r = Ray::new(
+            Tuple::point(0.5, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let plain = Scene::new(&w).color_at(r);
+        assert!(equal_f64(plain.red, plain.green));
+        assert!(equal_f64(plain.green, plain.blue));
+        w.objects[0].material_mut().dispersion = 0.3;
+        let dispersed = Scene::new(&w).color_at(r);
+        assert!(equal_f64(dispersed.green, plain.green));
+        assert!(!equal_f64(dispersed.red, dispersed.blue));
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_under_total_internal_reflection() {
+        let shape = glass_sphere();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, 2f64.sqrt() / 2.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let xs = vec![
+            Intersection::new(-(2f64.sqrt()) / 2.0, &shape),
+            Intersection::new(2f64.sqrt() / 2.0, &shape),
+        ];
+        let mut i = xs[1].clone();
+        i.prepare_hit(r);
+        i.prepare_refraction(&xs);
+        assert_eq!(i.schlick(), 1.0);
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_with_a_perpendicular_viewing_angle() {
+        let shape = glass_sphere();
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = vec![
+            Intersection::new(-1.0, &shape),
+            Intersection::new(1.0, &shape),
+        ];
+        let mut i = xs[1].clone();
+        i.prepare_hit(r);
+        i.prepare_refraction(&xs);
+        assert!(equal_f64(i.schlick(), 0.04));
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_with_small_angle_and_n2_greater() {
+        let shape = glass_sphere();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.99, -2.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = vec![Intersection::new(1.8589, &shape)];
+        let mut i = xs[0].clone();
+        i.prepare_hit(r);
+        i.prepare_refraction(&xs);
+        assert!((i.schlick() - 0.48873).abs() < 0.0001);
+    }
 }
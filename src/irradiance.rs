@@ -0,0 +1,305 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+/// How light bouncing off diffuse surfaces onto other surfaces is
+/// handled. Only one bounce is followed: the surfaces it reaches are lit
+/// directly, without further bounces.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Indirect {
+    /// Diffuse surfaces are only lit by the light and the ambient term.
+    Off,
+    /// Traces `samples` rays over the hemisphere at every hit. Accurate
+    /// but slow, and noisy with few samples.
+    Sampled { samples: usize },
+    /// Traces `samples` rays at a sparse set of points stored in an
+    /// irradiance cache, filled before every render, and interpolates
+    /// between them elsewhere. Points further than `max_error` from any
+    /// stored point, in the cache's measure of distance, are traced.
+    Cached { samples: usize, max_error: f64 },
+}
+
+/// The spacing, in pixels, of the camera rays that fill the cache before
+/// a render.
+const CACHE_SPACING: usize = 4;
+
+/// The light gathered at one point.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Record {
+    point: Tuple,
+    normal: Tuple,
+    irradiance: Color,
+    /// The harmonic mean distance to the surfaces seen from the point.
+    /// Irradiance changes quickly near other surfaces, so records there
+    /// cover a smaller area.
+    radius: f64,
+}
+
+/// Stores indirect diffuse light gathered at sparse points, so that it
+/// can be interpolated everywhere else (Ward et al., "A Ray Tracing
+/// Solution for Diffuse Interreflection", 1988).
+#[derive(Clone, PartialEq, Debug)]
+pub struct IrradianceCache {
+    /// Larger values reuse records further away: faster, but blurrier.
+    pub max_error: f64,
+    records: Vec<Record>,
+}
+
+impl IrradianceCache {
+    pub fn new(max_error: f64) -> IrradianceCache {
+        IrradianceCache {
+            max_error,
+            records: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Stores the irradiance gathered at a point with the given normal,
+    /// where `radius` is the harmonic mean distance to what was seen.
+    pub fn insert(
+        &mut self,
+        point: Tuple,
+        normal: Tuple,
+        irradiance: Color,
+        radius: f64,
+    ) {
+        self.records.push(Record {
+            point,
+            normal,
+            irradiance,
+            radius,
+        });
+    }
+
+    /// Returns the irradiance at the point, interpolated from the records
+    /// close enough to it, or `None` if there aren't any.
+    pub fn interpolate(&self, point: Tuple, normal: Tuple) -> Option<Color> {
+        let mut sum = Color::black();
+        let mut total = 0.0;
+        for record in self.records.iter() {
+            let error = (point - record.point).magnitude() / record.radius
+                + (1.0 - normal.dot(record.normal)).max(0.0).sqrt();
+            if error < EPSILON {
+                return Some(record.irradiance);
+            }
+            let weight = 1.0 / error;
+            if weight > 1.0 / self.max_error {
+                sum += record.irradiance * weight;
+                total += weight;
+            }
+        }
+        if total > 0.0 {
+            Some(sum * (1.0 / total))
+        } else {
+            None
+        }
+    }
+}
+
+/// Traces `samples` rays over the hemisphere around the normal and
+/// returns the average light arriving along them, along with the
+/// harmonic mean distance to the surfaces they hit (infinite if they all
+/// miss). The surfaces hit are only lit directly.
+pub fn gather_irradiance(
+    scene: &Scene,
+    point: Tuple,
+    normal: Tuple,
+    samples: usize,
+    rng: &mut Rng,
+) -> (Color, f64) {
+    let directions = scene
+        .world()
+        .sampler
+        .cosine_directions(normal, samples, rng);
+    let count = directions.len() as f64;
+    let mut sum = Color::black();
+    let mut inverse_distances = 0.0;
+    for direction in directions {
+        let ray = Ray::new(point, direction);
+        sum += match scene.shading_hit(ray) {
+            Some(hit) => {
+                inverse_distances += 1.0 / hit.t.max(EPSILON);
+                hit.shade_hit(scene, 0, rng)
+            }
+            None => scene.environment_color(ray.direction),
+        };
+    }
+    let radius = if inverse_distances > 0.0 {
+        count / inverse_distances
+    } else {
+        f64::INFINITY
+    };
+    (sum * (1.0 / count), radius)
+}
+
+impl Camera {
+    /// Fills an irradiance cache by gathering light at the surfaces seen
+    /// through a sparse grid of pixels, or returns `None` if the world
+    /// doesn't use one. Called when a scene is compiled.
+    pub(crate) fn irradiance_cache(
+        &self,
+        scene: &Scene,
+    ) -> Option<IrradianceCache> {
+        let (samples, max_error) = match scene.world().indirect {
+            Indirect::Cached { samples, max_error } => (samples, max_error),
+            _ => return None,
+        };
+        let mut cache = IrradianceCache::new(max_error);
+        for y in (0..self.vsize).step_by(CACHE_SPACING) {
+            for x in (0..self.hsize).step_by(CACHE_SPACING) {
+                let hit = match scene.camera_hit(self.pixel_ray(x, y)) {
+                    Some(hit) => hit,
+                    None => continue,
+                };
+                if hit.material().diffuse <= 0.0 {
+                    continue;
+                }
+                let point = hit.over_point.unwrap();
+                let normal = hit.normal_vector.unwrap();
+                if cache.interpolate(point, normal).is_none() {
+                    let mut rng = Rng::for_pixel(self.seed, x, y, 1);
+                    let (irradiance, radius) = gather_irradiance(
+                        scene, point, normal, samples, &mut rng,
+                    );
+                    cache.insert(point, normal, irradiance, radius);
+                }
+            }
+        }
+        Some(cache)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::irradiance::{gather_irradiance, Indirect, IrradianceCache};
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::rng::Rng;
+    use crate::scene::Scene;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_interpolating_between_cached_records() {
+        let mut cache = IrradianceCache::new(0.6);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(cache.interpolate(Tuple::point(0.0, 0.0, 0.0), up), None);
+        cache.insert(Tuple::point(0.0, 0.0, 0.0), up, Color::white(), 1.0);
+        cache.insert(Tuple::point(1.0, 0.0, 0.0), up, Color::black(), 1.0);
+        assert_eq!(cache.len(), 2);
+        // halfway between, both records are equally close
+        assert_eq!(
+            cache.interpolate(Tuple::point(0.5, 0.0, 0.0), up),
+            Some(Color::new(0.5, 0.5, 0.5))
+        );
+        assert_eq!(
+            cache.interpolate(Tuple::point(0.0, 0.0, 0.0), up),
+            Some(Color::white())
+        );
+        // too far away, or facing another way
+        assert_eq!(cache.interpolate(Tuple::point(5.0, 0.0, 0.0), up), None);
+        let side = Tuple::vector(1.0, 0.0, 0.0);
+        assert_eq!(cache.interpolate(Tuple::point(0.0, 0.0, 0.0), side), None);
+    }
+
+    /// A white floor under a red ceiling, lit from between them.
+    fn red_ceiling() -> World {
+        let mut w = World::new();
+        w.lights =
+            vec![PointLight::new(Tuple::point(0.0, 1.0, 0.0), Color::white())];
+        w.shadows = false;
+        let mut floor = Plane::default();
+        floor.material = Material::default();
+        floor.material.specular = 0.0;
+        let mut ceiling = Plane::default();
+        ceiling.transform = Matrix4::translation(0.0, 2.0, 0.0);
+        ceiling.material.color = Color::new(1.0, 0.0, 0.0);
+        ceiling.material.specular = 0.0;
+        w.objects = vec![Box::new(floor), Box::new(ceiling)];
+        w
+    }
+
+    #[test]
+    fn test_gathering_the_light_bounced_off_a_nearby_surface() {
+        let w = red_ceiling();
+        let (irradiance, radius) = gather_irradiance(
+            &Scene::new(&w),
+            Tuple::point(0.0, 0.01, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            16,
+            &mut Rng::new(0),
+        );
+        assert!(irradiance.red > 0.0);
+        assert_eq!(irradiance.green, 0.0);
+        assert!(radius > 1.0 && radius < 4.0);
+    }
+
+    #[test]
+    fn test_indirect_light_tints_diffuse_surfaces() {
+        let mut c = Camera::new(9, 9, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 1.0, -1.0),
+            Tuple::point(0.0, 0.0, 1.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let direct = c.render(&red_ceiling());
+        let mut w = red_ceiling();
+        w.indirect = Indirect::Sampled { samples: 8 };
+        let sampled = c.render(&w);
+        let mut w = red_ceiling();
+        w.indirect = Indirect::Cached {
+            samples: 8,
+            max_error: 0.5,
+        };
+        let cached = c.render(&w);
+        // the floor picks up red light from the ceiling
+        let floor = (4, 7);
+        assert_eq!(
+            direct.pixel_at(floor.0, floor.1).red,
+            direct.pixel_at(floor.0, floor.1).green
+        );
+        for image in [sampled, cached].iter() {
+            let pixel = image.pixel_at(floor.0, floor.1);
+            assert!(pixel.red > pixel.green);
+            assert_eq!(pixel.green, direct.pixel_at(floor.0, floor.1).green);
+        }
+    }
+
+    #[test]
+    fn test_compiling_a_scene_fills_the_irradiance_cache() {
+        let mut c = Camera::new(16, 16, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let mut w = World::default();
+        w.objects.push(Box::new(Sphere::default()));
+        w.indirect = Indirect::Cached {
+            samples: 4,
+            max_error: 0.5,
+        };
+        let scene = Scene::compile(&w, &c);
+        let cache = scene.irradiance_cache().unwrap();
+        assert!(!cache.is_empty());
+        assert!(cache.len() < 16);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::accel::{ray_hits_box, Accel, AccelStructure};
+use crate::bounds::{component, BoundingBox};
+use crate::ray::Ray;
+
+/// The most objects a cell of the tree holds before it is split.
+const LEAF_SIZE: usize = 4;
+
+/// How many times space may be cut in two along any path from the root,
+/// which bounds the size of the tree when objects overlap heavily.
+const MAX_DEPTH: usize = 24;
+
+/// A kd-tree over the bounds of a list of objects. Space is cut by
+/// planes perpendicular to the axes, and objects that straddle a plane
+/// are listed on both sides of it, so unlike a bounding volume hierarchy
+/// the cells never overlap.
+#[derive(Clone, Debug)]
+pub struct KdTree {
+    nodes: Vec<KdNode>,
+    /// Objects with infinite bounds, which every ray has to test.
+    /// Objects with empty bounds can't be hit, so they are left out.
+    unbounded: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct KdNode {
+    /// The region of space covered by the cell.
+    region: BoundingBox,
+    kind: KdNodeKind,
+}
+
+#[derive(Clone, Debug)]
+enum KdNodeKind {
+    Leaf(Vec<usize>),
+    /// The positions of the cells on either side of the cut in `nodes`.
+    Split {
+        below: usize,
+        above: usize,
+    },
+}
+
+impl KdTree {
+    /// Builds a tree over objects with the given bounds, cutting each
+    /// cell at the median of its objects' centers along its longest
+    /// axis.
+    pub fn build(bounds: &[BoundingBox]) -> KdTree {
+        let (unbounded, bounded): (Vec<usize>, Vec<usize>) = (0..bounds.len())
+            .filter(|&i| !bounds[i].is_empty())
+            .partition(|&i| bounds[i].is_unbounded());
+        let mut nodes = vec![];
+        if !bounded.is_empty() {
+            let mut region = BoundingBox::empty();
+            for &i in bounded.iter() {
+                region.add_box(bounds[i]);
+            }
+            build_node(bounds, bounded, region, 0, &mut nodes);
+        }
+        KdTree {
+            nodes,
+            unbounded,
+            len: bounds.len(),
+        }
+    }
+}
+
+/// Adds the cell for the objects within `region` and everything below
+/// it, returning its position in `nodes`.
+fn build_node(
+    bounds: &[BoundingBox],
+    objects: Vec<usize>,
+    region: BoundingBox,
+    depth: usize,
+    nodes: &mut Vec<KdNode>,
+) -> usize {
+    let position = nodes.len();
+    nodes.push(KdNode {
+        region,
+        kind: KdNodeKind::Leaf(vec![]),
+    });
+    if objects.len() <= LEAF_SIZE || depth >= MAX_DEPTH {
+        nodes[position].kind = KdNodeKind::Leaf(objects);
+        return position;
+    }
+    let extent = region.max - region.min;
+    let axis = (0..3)
+        .max_by(|&a, &b| component(extent, a).total_cmp(&component(extent, b)))
+        .unwrap();
+    let mut centers: Vec<f64> = objects
+        .iter()
+        .map(|&i| component(bounds[i].centroid(), axis))
+        .collect();
+    centers.sort_by(f64::total_cmp);
+    let cut = centers[centers.len() / 2];
+    let below: Vec<usize> = objects
+        .iter()
+        .copied()
+        .filter(|&i| component(bounds[i].min, axis) <= cut)
+        .collect();
+    let above: Vec<usize> = objects
+        .iter()
+        .copied()
+        .filter(|&i| component(bounds[i].max, axis) >= cut)
+        .collect();
+    // stop when the cut separates nothing
+    if below.len() == objects.len() && above.len() == objects.len() {
+        nodes[position].kind = KdNodeKind::Leaf(objects);
+        return position;
+    }
+    let (mut below_region, mut above_region) = (region, region);
+    match axis {
+        0 => (below_region.max.x, above_region.min.x) = (cut, cut),
+        1 => (below_region.max.y, above_region.min.y) = (cut, cut),
+        _ => (below_region.max.z, above_region.min.z) = (cut, cut),
+    }
+    let below = build_node(bounds, below, below_region, depth + 1, nodes);
+    let above = build_node(bounds, above, above_region, depth + 1, nodes);
+    nodes[position].kind = KdNodeKind::Split { below, above };
+    position
+}
+
+impl AccelStructure for KdTree {
+    fn accel(&self) -> Accel {
+        Accel::KdTree
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn candidates(&self, ray: Ray) -> Vec<usize> {
+        let mut candidates = vec![];
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(position) = stack.pop() {
+            let node = &self.nodes[position];
+            if !ray_hits_box(ray, node.region) {
+                continue;
+            }
+            match &node.kind {
+                KdNodeKind::Leaf(objects) => candidates.extend(objects),
+                KdNodeKind::Split { below, above } => {
+                    stack.push(*below);
+                    stack.push(*above);
+                }
+            }
+        }
+        // objects straddling a cut can be found in several cells
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates.extend_from_slice(&self.unbounded);
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accel::AccelStructure;
+    use crate::bounds::BoundingBox;
+    use crate::kdtree::KdTree;
+    use crate::ray::Ray;
+    use crate::tuple::Tuple;
+
+    #[test]
+    fn test_a_kd_tree_lists_straddling_objects_once() {
+        // a long bar across a row of small boxes
+        let mut bounds: Vec<BoundingBox> = (0..12)
+            .map(|i| {
+                let x = 2.0 * i as f64;
+                BoundingBox::new(
+                    Tuple::point(x - 0.5, -0.5, -0.5),
+                    Tuple::point(x + 0.5, 0.5, 0.5),
+                )
+            })
+            .collect();
+        bounds.push(BoundingBox::new(
+            Tuple::point(-1.0, 1.0, -1.0),
+            Tuple::point(23.0, 2.0, 1.0),
+        ));
+        let tree = KdTree::build(&bounds);
+        assert_eq!(tree.len(), 13);
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let through =
+            tree.candidates(Ray::new(Tuple::point(10.0, 5.0, 0.0), down));
+        assert!(through.contains(&5) && through.contains(&12));
+        assert!(through.len() < 13);
+        let along = Ray::new(
+            Tuple::point(-5.0, 1.5, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+        );
+        let mut found = tree.candidates(along);
+        let count = found.len();
+        found.dedup();
+        assert!(found.contains(&12) && found.len() == count);
+        let away = Ray::new(Tuple::point(10.0, 5.0, 0.0), -down);
+        assert!(tree.candidates(away).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::accel::ray_hits_box;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::mesh::Mesh;
+use crate::metadata::SceneHash;
+use crate::ray::Ray;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use std::fmt;
+use std::hash::Hasher;
+use std::mem::size_of_val;
+use std::sync::{Arc, OnceLock};
+
+/// Produces the mesh of a `LazyMesh`, such as by reading a model file.
+pub type MeshLoader = dyn Fn() -> Mesh + Send + Sync;
+
+/// A mesh that isn't loaded until a ray first enters its bounds, so that
+/// scenes with many large optional models start rendering quickly and
+/// never load models that no ray reaches. The bounds are given up front,
+/// as a proxy for the model, and must contain it.
+///
+/// Clones share the loaded mesh, so a model placed many times is loaded
+/// once. The mesh's own transformation and material are ignored in favor
+/// of the lazy mesh's.
+#[derive(Clone)]
+pub struct LazyMesh {
+    pub bounds: (Tuple, Tuple),
+    loader: Arc<MeshLoader>,
+    mesh: Arc<OnceLock<Mesh>>,
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl LazyMesh {
+    pub fn new<F>(bounds: (Tuple, Tuple), loader: F) -> LazyMesh
+    where
+        F: Fn() -> Mesh + Send + Sync + 'static,
+    {
+        LazyMesh {
+            bounds,
+            loader: Arc::new(loader),
+            mesh: Arc::new(OnceLock::new()),
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.mesh.get().is_some()
+    }
+
+    /// Returns the mesh, loading it first if no ray has needed it yet.
+    pub fn mesh(&self) -> &Mesh {
+        self.mesh.get_or_init(|| (self.loader)())
+    }
+}
+
+impl fmt::Debug for LazyMesh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LazyMesh")
+            .field("bounds", &self.bounds)
+            .field("loaded", &self.is_loaded())
+            .field("transform", &self.transform)
+            .field("material", &self.material)
+            .finish()
+    }
+}
+
+impl Shape for LazyMesh {
+    fn kind(&self) -> &'static str {
+        "lazy mesh"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Hits are reported on the lazy mesh itself, so that its
+    /// transformation and material apply.
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        if !ray_hits_box(transformed_ray, self.bounds.into()) {
+            return vec![];
+        }
+        let mut xs = self.mesh().local_intersect(transformed_ray);
+        for hit in xs.iter_mut() {
+            hit.object = self;
+        }
+        xs
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        self.mesh().local_normal_at(point)
+    }
+
+    fn local_normal_at_hit(&self, point: Tuple, hit: &Intersection) -> Tuple {
+        self.mesh().local_normal_at_hit(point, hit)
+    }
+
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        self.mesh().local_uv_at(point)
+    }
+
+    fn local_uv_at_hit(&self, point: Tuple, hit: &Intersection) -> (f64, f64) {
+        self.mesh().local_uv_at_hit(point, hit)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        self.bounds
+    }
+
+    /// Only counts the triangles once the mesh is loaded.
+    fn triangle_count(&self) -> usize {
+        self.mesh.get().map_or(0, |mesh| mesh.triangle_count())
+    }
+
+    fn memory_bytes(&self) -> usize {
+        size_of_val(self)
+            + self.mesh.get().map_or(0, |mesh| mesh.memory_bytes())
+    }
+
+    /// Only hashes the bounds, since hashing the mesh would load it.
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        [self.bounds.0, self.bounds.1].hash_into(state);
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::lazy::LazyMesh;
+    use crate::matrix::Matrix4;
+    use crate::mesh::{Mesh, MeshData};
+    use crate::metadata::scene_hash;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A lazy unit square in the xz plane that counts how often it is
+    /// loaded.
+    fn lazy_square(loads: Arc<AtomicUsize>) -> LazyMesh {
+        let bounds = (Tuple::point(0.0, 0.0, 0.0), Tuple::point(1.0, 0.0, 1.0));
+        LazyMesh::new(bounds, move || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Mesh::new(MeshData {
+                positions: vec![
+                    0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+                ],
+                indices: vec![0, 1, 2, 0, 2, 3],
+                ..MeshData::default()
+            })
+        })
+    }
+
+    #[test]
+    fn test_a_lazy_mesh_loads_when_a_ray_enters_its_bounds() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let m = lazy_square(loads.clone());
+        let copy = m.clone();
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let miss = Ray::new(Tuple::point(2.0, 1.0, 0.5), down);
+        assert!(m.intersect(miss).is_empty());
+        assert!(!m.is_loaded());
+        assert_eq!(m.triangle_count(), 0);
+        let xs = m.intersect(Ray::new(Tuple::point(0.7, 1.0, 0.4), down));
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].object, &m as &dyn Shape);
+        // the copy shares what was loaded
+        assert!(copy.is_loaded());
+        copy.intersect(Ray::new(Tuple::point(0.2, 1.0, 0.5), down));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(copy.triangle_count(), 2);
+    }
+
+    #[test]
+    fn test_hits_on_a_lazy_mesh_use_its_transformation() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let mut m = lazy_square(loads);
+        m.transform = Matrix4::rotation_z(FRAC_PI_2);
+        let mut w = World::new();
+        w.objects.push(Box::new(m));
+        let r = Ray::new(
+            Tuple::point(-2.0, 0.5, 0.5),
+            Tuple::vector(1.0, 0.0, 0.0),
+        );
+        let hit = Scene::new(&w).shading_hit(r).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.normal_vector, Some(Tuple::vector(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_loading_a_lazy_mesh_leaves_the_scene_hash_alone() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let mut w = World::new();
+        w.objects.push(Box::new(lazy_square(loads.clone())));
+        let c = Camera::new(10, 10, FRAC_PI_2);
+        let before = scene_hash(&w, &c);
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        Scene::new(&w).intersect(Ray::new(Tuple::point(0.5, 1.0, 0.5), down));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(scene_hash(&w, &c), before);
+    }
+}
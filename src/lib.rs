@@ -1,18 +1,62 @@
+pub mod accel;
+pub mod accumulation;
+pub mod adaptive;
+pub mod animation;
+pub mod aov;
+pub mod bake;
+pub mod bounds;
+pub mod bvh;
 pub mod camera;
 pub mod canvas;
+pub mod chunked;
 pub mod color;
+pub mod compare;
+pub mod cone;
+pub mod cube;
+pub mod cylinder;
+pub mod debug;
+pub mod displacement;
+pub mod environment;
+mod font;
+pub mod furnace;
+pub mod group;
 pub mod intersection;
+pub mod irradiance;
+pub mod kdtree;
+pub mod lazy;
 pub mod light;
+pub mod lod;
+pub mod lut;
 pub mod material;
 pub mod matrix;
+pub mod mesh;
+pub mod metadata;
+pub mod noise;
+pub mod obj_file;
+pub mod overlay;
+pub mod panorama;
+pub mod particles;
+pub mod pattern;
+pub mod plane;
+pub mod post;
+pub mod preview;
+pub mod progress;
 pub mod ray;
+pub mod rng;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod scenes;
+pub mod shape;
 pub mod sphere;
+pub mod triangle;
 pub mod tuple;
+pub mod uv;
 pub mod world;
 
-const EPSILON: f32 = 0.00001;
+const EPSILON: f64 = 0.00001;
 
-fn equal_f32(a: f32, b: f32) -> bool {
+fn equal_f64(a: f64, b: f64) -> bool {
     (a - b).abs() < EPSILON
 }
 
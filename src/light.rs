@@ -1,11 +1,122 @@
 use crate::color::Color;
 use crate::material::Material;
+use crate::rng::Rng;
+use crate::sampler::Sampler;
 use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::f64::consts::PI;
+
+/// How a light fades from full strength to nothing as `t` goes from 0 to
+/// 1 across the edge of its falloff.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Curve {
+    Linear,
+    /// Eases in and out, hiding where the fade starts and ends.
+    Smooth,
+    /// The light's strength at evenly spaced steps across the edge, from
+    /// the inside to the outside, interpolated in between.
+    Table([f64; 8]),
+}
+
+impl Curve {
+    /// Returns the strength of the light at `t`, clamped to `[0, 1]`.
+    pub fn at(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Curve::Linear => 1.0 - t,
+            Curve::Smooth => 1.0 - t * t * (3.0 - 2.0 * t),
+            Curve::Table(table) => {
+                let position = t * (table.len() - 1) as f64;
+                let index = (position as usize).min(table.len() - 2);
+                let fraction = position - index as f64;
+                table[index] + (table[index + 1] - table[index]) * fraction
+            }
+        }
+    }
+}
+
+/// Limits the region a light reaches, for stylized lighting. Points
+/// outside the region are only lit by the ambient term.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Falloff {
+    /// The light reaches everywhere.
+    None,
+    /// The light fills a cylinder around the line through its position
+    /// along `axis`. It is at full strength within `inner` of the line
+    /// and fades out by `outer`.
+    Cylindrical {
+        axis: Tuple,
+        inner: f64,
+        outer: f64,
+        curve: Curve,
+    },
+    /// The light fills a cone from its position along `axis`, like a
+    /// spotlight. It is at full strength within `inner` radians of the
+    /// axis and fades out by `outer`.
+    Conical {
+        axis: Tuple,
+        inner: f64,
+        outer: f64,
+        curve: Curve,
+    },
+}
+
+/// How a light weakens with distance `d` from it: its strength is divided
+/// by `constant + linear * d + quadratic * d * d`. Real lights follow the
+/// quadratic term alone; the default of a constant 1 doesn't weaken at
+/// all, as in the book.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    pub fn new(constant: f64, linear: f64, quadratic: f64) -> Attenuation {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    /// Returns the fraction of the light's strength left at the given
+    /// distance, never more than 1.
+    pub fn at(&self, distance: f64) -> f64 {
+        let denominator = self.constant
+            + self.linear * distance
+            + self.quadratic * distance * distance;
+        if denominator <= 1.0 {
+            1.0
+        } else {
+            1.0 / denominator
+        }
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Attenuation {
+        Attenuation::new(1.0, 0.0, 0.0)
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct PointLight {
     pub position: Tuple,
     pub intensity: Color,
+    /// Treats the light as a small sphere of this radius when casting
+    /// shadows, which softens their edges. A radius of zero gives hard
+    /// shadows.
+    pub radius: f64,
+    /// The number of points on the light's sphere that are tested when
+    /// casting soft shadows.
+    pub samples: usize,
+    /// Whether objects block this light. Fill lights are often left
+    /// shadowless so they brighten a scene without adding shadows.
+    pub casts_shadows: bool,
+    pub falloff: Falloff,
+    pub attenuation: Attenuation,
 }
 
 impl PointLight {
@@ -14,8 +125,87 @@ impl PointLight {
         PointLight {
             position,
             intensity,
+            radius: 0.0,
+            samples: 16,
+            casts_shadows: true,
+            falloff: Falloff::None,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    /// Returns the fraction of the light's strength that reaches the
+    /// point, after its attenuation and falloff and ignoring shadows.
+    pub fn strength_at(&self, point: Tuple) -> f64 {
+        let distance = (self.position - point).magnitude();
+        self.attenuation.at(distance) * self.falloff_at(point)
+    }
+
+    /// Returns the fraction of the light's strength that its falloff lets
+    /// reach the point.
+    pub fn falloff_at(&self, point: Tuple) -> f64 {
+        let (inner, outer, curve, offset) = match self.falloff {
+            Falloff::None => return 1.0,
+            Falloff::Cylindrical {
+                axis,
+                inner,
+                outer,
+                curve,
+            } => {
+                let axis = axis.normalize();
+                let to_point = point - self.position;
+                let along = axis * to_point.dot(axis);
+                (inner, outer, curve, (to_point - along).magnitude())
+            }
+            Falloff::Conical {
+                axis,
+                inner,
+                outer,
+                curve,
+            } => {
+                let to_point = point - self.position;
+                // the cone's apex has no direction, and is lit in full
+                if to_point.magnitude() < EPSILON {
+                    return 1.0;
+                }
+                let axis = axis.normalize();
+                let cosine = to_point.normalize().dot(axis).clamp(-1.0, 1.0);
+                (inner, outer, curve, cosine.acos())
+            }
+        };
+        if offset <= inner {
+            1.0
+        } else if offset >= outer {
+            0.0
+        } else {
+            curve.at((offset - inner) / (outer - inner))
         }
     }
+
+    /// Returns the points that shadow rays are cast towards: spread over
+    /// the light's sphere by the sampler, or just its position when the
+    /// light has no radius.
+    pub fn sample_positions(
+        &self,
+        sampler: Sampler,
+        rng: &mut Rng,
+    ) -> Vec<Tuple> {
+        if self.radius <= 0.0 || self.samples <= 1 {
+            return vec![self.position];
+        }
+        // map the unit square onto the sphere, preserving area
+        sampler
+            .points(self.samples, rng)
+            .into_iter()
+            .map(|(s, t)| {
+                let y = 1.0 - 2.0 * s;
+                let ring = (1.0 - y * y).sqrt();
+                let theta = 2.0 * PI * t;
+                let offset =
+                    Tuple::vector(theta.cos() * ring, y, theta.sin() * ring);
+                self.position + offset * self.radius
+            })
+            .collect()
+    }
 }
 
 pub fn lighting(
@@ -24,7 +214,7 @@ pub fn lighting(
     point: Tuple,
     eye_vector: Tuple,
     normal_vector: Tuple,
-    in_shadow: bool,
+    intensity: f64,
 ) -> Color {
     // combine the surface color with the light's color/intensity
     let effective_color = material.color * light.intensity;
@@ -32,16 +222,34 @@ pub fn lighting(
     // find the direction to the light source
     let light_vector = (light.position - point).normalize();
 
+    // toon outlines cover everything else
+    if let Some(toon) = material.toon {
+        if eye_vector.dot(normal_vector) < toon.edge {
+            return Color::black();
+        }
+    }
+
     // compute the ambient contribution
-    let ambient = effective_color * material.ambient;
+    let ambient = if material.casts_ambient {
+        effective_color * material.ambient
+    } else {
+        Color::black()
+    };
 
-    if in_shadow {
+    // intensity is the fraction of the light that reaches the point,
+    // from 0 (fully in shadow) to 1 (fully lit)
+    let intensity = intensity * light.strength_at(point);
+    if intensity <= 0.0 {
         return ambient;
     }
 
     // The value light_dot_normal represents the cosine of the angle between the light vector and the normal vector.
     // A negative number means the light is on the other side of the surface.
-    let light_dot_normal = light_vector.dot(normal_vector);
+    let mut light_dot_normal = light_vector.dot(normal_vector);
+    if let Some(toon) = material.toon {
+        let bands = toon.bands.max(1) as f64;
+        light_dot_normal = (light_dot_normal * bands).ceil() / bands;
+    }
 
     let diffuse;
     let specular;
@@ -50,31 +258,43 @@ pub fn lighting(
         specular = Color::black();
     } else {
         // compute the diffuse contribution
-        diffuse = effective_color * material.diffuse * light_dot_normal;
+        diffuse = if material.casts_diffuse {
+            effective_color * material.diffuse * light_dot_normal
+        } else {
+            Color::black()
+        };
 
         // The value reflect_dot_eye represents the cosine of the angle between the reflection vector and the eye vector.
         // A negative number means the light reflects away from the eye.
         let reflection_vector = (-light_vector).reflect(normal_vector);
         let reflection_dot_eye = reflection_vector.dot(eye_vector);
 
-        if reflection_dot_eye <= 0.0 {
+        if reflection_dot_eye <= 0.0 || !material.casts_specular_highlights {
             specular = Color::black();
         } else {
-            let factor = reflection_dot_eye.powf(material.shininess);
+            let mut factor = reflection_dot_eye.powf(material.shininess);
+            if material.toon.is_some() {
+                // a highlight with a hard edge
+                factor = if factor > 0.5 { 1.0 } else { 0.0 };
+            }
             specular = light.intensity * material.specular * factor;
         }
     }
 
-    ambient + diffuse + specular
+    ambient + (diffuse + specular) * intensity
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use crate::color::Color;
-    use crate::light::{lighting, PointLight};
-    use crate::material::Material;
+    use crate::equal_f64;
+    use crate::light::{lighting, Attenuation, Curve, Falloff, PointLight};
+    use crate::material::{Material, Toon};
+    use crate::rng::Rng;
+    use crate::sampler::Sampler;
     use crate::tuple::Tuple;
-    use std::f32::consts::SQRT_2;
+    use std::f64::consts::{FRAC_PI_4, FRAC_PI_8, SQRT_2};
 
     #[test]
     fn test_a_point_light_has_a_position_and_intensity() {
@@ -83,6 +303,7 @@ mod tests {
         let light = PointLight::new(position, intensity);
         assert_eq!(light.position, position);
         assert_eq!(light.intensity, intensity);
+        assert!(light.casts_shadows);
     }
 
     #[test]
@@ -93,14 +314,8 @@ mod tests {
         let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
         let light =
             PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
-        let result = lighting(
-            material,
-            light,
-            position,
-            eye_vector,
-            normal_vector,
-            false,
-        );
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
         // ambient + diffuse + specular
         // 0.1 + 0.9 + 0.9 = 1.9
         assert_eq!(result, Color::new(1.9, 1.9, 1.9));
@@ -117,14 +332,8 @@ mod tests {
             PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
         // ambient + diffuse + no specular
         // 0.1 + 0.9 + 0.0 = 1.0
-        let result = lighting(
-            material,
-            light,
-            position,
-            eye_vector,
-            normal_vector,
-            false,
-        );
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
         assert_eq!(result, Color::white());
     }
 
@@ -136,14 +345,8 @@ mod tests {
         let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
         let light =
             PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
-        let result = lighting(
-            material,
-            light,
-            position,
-            eye_vector,
-            normal_vector,
-            false,
-        );
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
         // ambient + partial diffuse + no specular
         // 0.1 + 0.9 * sqrt(2)/2.0 + 0 = 0.7364
         assert_eq!(result, Color::new(0.7364, 0.7364, 0.7364));
@@ -157,14 +360,8 @@ mod tests {
         let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
         let light =
             PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
-        let result = lighting(
-            material,
-            light,
-            position,
-            eye_vector,
-            normal_vector,
-            false,
-        );
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
         // ambient + partial diffuse + specular
         // 0.1 + 0.9 * sqrt(2)/2.0 + 0.9 = 1.63639
         assert_eq!(result, Color::new(1.63639, 1.63639, 1.63639));
@@ -178,14 +375,8 @@ mod tests {
         let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
         let light =
             PointLight::new(Tuple::point(0.0, 0.0, 10.0), Color::white());
-        let result = lighting(
-            material,
-            light,
-            position,
-            eye_vector,
-            normal_vector,
-            false,
-        );
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
         // ambient + no diffuse + no specular
         // 0.1 + 0.0 + 0.0 = 0.1
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
@@ -199,16 +390,190 @@ mod tests {
         let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
         let light =
             PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 0.0);
+        // ambient + no diffuse + no specular
+        // 0.1 + 0.0 + 0.0 = 0.1
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn test_a_point_light_without_a_radius_has_one_sample() {
+        let light =
+            PointLight::new(Tuple::point(1.0, 2.0, 3.0), Color::white());
+        assert_eq!(light.radius, 0.0);
+        assert_eq!(
+            light.sample_positions(Sampler::Fibonacci, &mut Rng::new(0)),
+            vec![light.position]
+        );
+    }
+
+    #[test]
+    fn test_samples_lie_on_the_sphere_of_the_light() {
+        let mut light =
+            PointLight::new(Tuple::point(1.0, 2.0, 3.0), Color::white());
+        light.radius = 0.5;
+        light.samples = 8;
+        let samples =
+            light.sample_positions(Sampler::Fibonacci, &mut Rng::new(0));
+        assert_eq!(samples.len(), 8);
+        for sample in samples {
+            assert!(equal_f64((sample - light.position).magnitude(), 0.5));
+        }
+    }
+
+    #[test]
+    fn test_lighting_scales_diffuse_and_specular_by_intensity() {
+        let material = Material::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eye_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 0.5);
+        // ambient + half of diffuse and specular
+        // 0.1 + (0.9 + 0.9) * 0.5 = 1.0
+        assert_eq!(result, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_lighting_with_terms_turned_off() {
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eye_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let light_with = |material: &Material| {
+            lighting(
+                material.clone(),
+                light,
+                position,
+                eye_vector,
+                normal_vector,
+                1.0,
+            )
+        };
+        let mut material = Material::default();
+        material.casts_specular_highlights = false;
+        // 0.1 + 0.9 + 0.0 = 1.0
+        assert_eq!(light_with(&material), Color::white());
+        material.casts_diffuse = false;
+        assert_eq!(light_with(&material), Color::new(0.1, 0.1, 0.1));
+        material.casts_ambient = false;
+        assert_eq!(light_with(&material), Color::black());
+    }
+
+    #[test]
+    fn test_toon_lighting_rounds_the_diffuse_term_up_to_bands() {
+        let mut material = Material::default();
+        material.specular = 0.0;
+        material.toon = Some(Toon::new(2));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eye_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let light =
+            PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
+        // the light's cosine of 0.7071 is rounded up to 1
+        // 0.1 + 0.9 * 1.0 = 1.0
+        assert_eq!(result, Color::white());
+    }
+
+    #[test]
+    fn test_toon_lighting_outlines_surfaces_seen_edge_on() {
+        let mut material = Material::default();
+        material.toon = Some(Toon::new(3));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eye_vector = Tuple::vector(0.0, 1.0, -0.1).normalize();
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let result =
+            lighting(material, light, position, eye_vector, normal_vector, 1.0);
+        assert_eq!(result, Color::black());
+    }
+
+    #[test]
+    fn test_falloff_curves() {
+        assert_eq!(Curve::Linear.at(0.25), 0.75);
+        assert_eq!(Curve::Smooth.at(0.5), 0.5);
+        assert_eq!(Curve::Smooth.at(2.0), 0.0);
+        let table = Curve::Table([1.0, 1.0, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(table.at(0.0), 1.0);
+        assert!(equal_f64(table.at(5.0 / 7.0), 0.0));
+        assert!(equal_f64(table.at(4.5 / 7.0), 0.25));
+    }
+
+    #[test]
+    fn test_a_cylindrical_falloff_fades_away_from_its_axis() {
+        let mut light =
+            PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::white());
+        assert_eq!(light.falloff_at(Tuple::point(100.0, 0.0, 0.0)), 1.0);
+        light.falloff = Falloff::Cylindrical {
+            axis: Tuple::vector(0.0, -2.0, 0.0),
+            inner: 1.0,
+            outer: 3.0,
+            curve: Curve::Linear,
+        };
+        assert_eq!(light.falloff_at(Tuple::point(0.5, -20.0, 0.0)), 1.0);
+        assert_eq!(light.falloff_at(Tuple::point(0.0, 0.0, 2.0)), 0.5);
+        assert_eq!(light.falloff_at(Tuple::point(3.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_a_conical_falloff_fades_away_from_its_axis() {
+        let mut light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        light.falloff = Falloff::Conical {
+            axis: Tuple::vector(0.0, 0.0, 1.0),
+            inner: FRAC_PI_8,
+            outer: FRAC_PI_4,
+            curve: Curve::Smooth,
+        };
+        assert_eq!(light.falloff_at(Tuple::point(0.0, 0.0, 0.0)), 1.0);
+        assert_eq!(light.falloff_at(Tuple::point(0.0, 0.0, -20.0)), 0.0);
+        assert_eq!(light.falloff_at(Tuple::point(10.0, 0.0, 0.0)), 0.0);
+        assert_eq!(light.falloff_at(light.position), 1.0);
+        let material = Material::default();
+        let eye_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let outside = Tuple::point(10.0, 0.0, 0.0);
+        let result =
+            lighting(material, light, outside, eye_vector, normal_vector, 1.0);
+        // only ambient
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn test_attenuation_weakens_a_light_with_distance() {
+        let a = Attenuation::new(1.0, 0.5, 0.25);
+        assert_eq!(a.at(0.0), 1.0);
+        assert_eq!(a.at(2.0), 1.0 / 3.0);
+        assert_eq!(Attenuation::default().at(100.0), 1.0);
+        // never brighter than the light itself
+        assert_eq!(Attenuation::new(0.0, 0.0, 1.0).at(0.5), 1.0);
+    }
+
+    #[test]
+    fn test_lighting_with_an_attenuated_light() {
+        let eye_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let normal_vector = Tuple::vector(0.0, 0.0, -1.0);
+        let mut light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        light.attenuation = Attenuation::new(0.0, 0.0, 0.02);
+        let strength = light.strength_at(Tuple::point(0.0, 0.0, 0.0));
+        assert!((strength - 0.5).abs() < 0.0001);
         let result = lighting(
-            material,
+            Material::default(),
             light,
-            position,
+            Tuple::point(0.0, 0.0, 0.0),
             eye_vector,
             normal_vector,
-            true,
+            1.0,
         );
-        // ambient + no diffuse + no specular
-        // 0.1 + 0.0 + 0.0 = 0.1
-        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+        // full ambient, with half of the diffuse and specular terms
+        assert_eq!(result, Color::new(1.0, 1.0, 1.0));
     }
 }
@@ -0,0 +1,253 @@
+use crate::bounds::BoundingBox;
+use crate::color::Color;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::metadata::SceneHash;
+use crate::ray::Ray;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::tuple::Tuple;
+use std::hash::Hasher;
+use std::mem::{size_of, size_of_val};
+
+/// A group of shapes with a level of detail: when the group looks smaller
+/// than `min_pixels` across from where a camera ray starts, the ray only
+/// tests a single sphere around the group, the proxy, instead of every
+/// shape in it. This keeps huge scenes with many distant groups fast.
+///
+/// The size on screen is estimated from the ray's differential, so rays
+/// without one, such as shadow rays and most reflected rays, always see
+/// the shapes themselves. The shapes are placed directly in world space,
+/// so the group's own transform is always the identity.
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub detailed: Vec<Box<dyn Shape>>,
+    /// The simplified stand-in. `new` gives it the average color of the
+    /// shapes.
+    pub proxy: Sphere,
+    /// The size, in pixels, below which the proxy is used.
+    pub min_pixels: f64,
+    pub name: Option<String>,
+}
+
+impl Lod {
+    /// Creates a group whose proxy is a sphere just enclosing the bounds
+    /// of the shapes, colored with the average of their colors.
+    pub fn new(detailed: Vec<Box<dyn Shape>>) -> Lod {
+        let mut proxy = Sphere::default();
+        if !detailed.is_empty() {
+            let total = detailed.iter().fold(Color::black(), |sum, shape| {
+                sum + shape.material().color
+            });
+            proxy.material.color = total * (1.0 / detailed.len() as f64);
+        }
+        let mut lod = Lod {
+            detailed,
+            proxy,
+            min_pixels: 4.0,
+            name: None,
+        };
+        lod.fit_proxy();
+        lod
+    }
+
+    /// Moves and resizes the proxy to enclose the shapes again, keeping
+    /// its material.
+    pub fn fit_proxy(&mut self) {
+        let (min, max) = self.local_bounds();
+        let center = Tuple::point(
+            (min.x + max.x) / 2.0,
+            (min.y + max.y) / 2.0,
+            (min.z + max.z) / 2.0,
+        );
+        let radius = (max - center).magnitude();
+        self.proxy.transform =
+            Matrix4::translation(center.x, center.y, center.z)
+                * Matrix4::scaling(radius, radius, radius);
+    }
+
+    /// Returns roughly how many pixels across the proxy looks from the
+    /// origin of the ray, or `None` if that is unknown or the ray starts
+    /// inside the proxy.
+    fn projected_pixels(&self, ray: Ray) -> Option<f64> {
+        let differential = ray.differential?;
+        let spread = (differential.x_direction.normalize()
+            - ray.direction.normalize())
+        .magnitude();
+        let center = self.proxy.transform * Tuple::point(0.0, 0.0, 0.0);
+        let edge = self.proxy.transform * Tuple::point(1.0, 0.0, 0.0);
+        let radius = (edge - center).magnitude();
+        let distance = (center - ray.origin).magnitude();
+        if spread <= 0.0 || distance <= radius {
+            return None;
+        }
+        Some(2.0 * radius / (distance * spread))
+    }
+}
+
+impl Shape for Lod {
+    fn kind(&self) -> &'static str {
+        "lod"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        Matrix4::identity()
+    }
+
+    /// Applies the transform to every shape in the group and to the
+    /// proxy, on top of their own transforms.
+    fn set_transform(&mut self, transform: Matrix4) {
+        for shape in self.detailed.iter_mut() {
+            shape.set_transform(transform * shape.transform());
+        }
+        self.proxy.transform = transform * self.proxy.transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.proxy.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.proxy.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        match self.projected_pixels(ray) {
+            Some(pixels) if pixels < self.min_pixels => {
+                self.proxy.intersect(ray)
+            }
+            _ => self
+                .detailed
+                .iter()
+                .flat_map(|shape| shape.intersect(ray))
+                .collect(),
+        }
+    }
+
+    /// Hits are always on the shapes in the group or on the proxy, never
+    /// on the group itself, so this is the normal of the proxy.
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        self.proxy.normal_at(point)
+    }
+
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        self.proxy.uv_at(point)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        let mut bounds = BoundingBox::empty();
+        for shape in self.detailed.iter() {
+            bounds.add_box(shape.bounds().into());
+        }
+        bounds.into()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.detailed
+            .iter()
+            .map(|shape| shape.triangle_count())
+            .sum()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        size_of_val(self)
+            + self.detailed.capacity() * size_of::<Box<dyn Shape>>()
+            + self
+                .detailed
+                .iter()
+                .map(|shape| shape.memory_bytes())
+                .sum::<usize>()
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        for shape in self.detailed.iter_mut() {
+            shape.divide(threshold);
+        }
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        self.detailed.hash_into(state);
+        (&self.proxy as &dyn Shape).hash_into(state);
+        self.min_pixels.hash_into(state);
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::lod::Lod;
+    use crate::matrix::Matrix4;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn two_spheres(z: f64) -> Lod {
+        let mut left = Sphere::default();
+        left.transform = Matrix4::translation(-1.0, 0.0, z);
+        let mut right = Sphere::default();
+        right.transform = Matrix4::translation(1.0, 0.0, z);
+        Lod::new(vec![Box::new(left), Box::new(right)])
+    }
+
+    #[test]
+    fn test_the_proxy_encloses_the_group() {
+        let lod = two_spheres(0.0);
+        let (min, max) = lod.bounds();
+        assert_eq!(min, Tuple::point(-2.0, -1.0, -1.0));
+        assert_eq!(max, Tuple::point(2.0, 1.0, 1.0));
+        assert_eq!(
+            lod.proxy.transform * Tuple::point(0.0, 0.0, 1.0),
+            Tuple::point(0.0, 0.0, 6.0_f64.sqrt())
+        );
+    }
+
+    #[test]
+    fn test_the_proxy_has_the_average_color_of_the_group() {
+        let mut lod = two_spheres(0.0);
+        lod.detailed[0].material_mut().color = Color::new(1.0, 0.0, 0.0);
+        lod.detailed[1].material_mut().color = Color::new(0.0, 0.0, 1.0);
+        let lod = Lod::new(lod.detailed);
+        assert_eq!(lod.proxy.material.color, Color::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn test_rays_without_a_differential_see_the_detailed_shapes() {
+        let lod = two_spheres(100.0);
+        let r =
+            Ray::new(Tuple::point(1.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = lod.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].object, &*lod.detailed[1]);
+        assert_eq!(xs[0].t, 99.0);
+    }
+
+    #[test]
+    fn test_distant_groups_are_replaced_by_their_proxy() {
+        let c = Camera::new(11, 11, FRAC_PI_2);
+        let ray = c.pixel_ray(5, 5);
+        let near = two_spheres(-4.0);
+        let xs = near.intersect(ray);
+        assert!(xs.iter().all(|i| i.object != &near.proxy as &dyn Shape));
+        let far = two_spheres(-100.0);
+        let xs = far.intersect(ray);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].object, &far.proxy as &dyn Shape);
+    }
+}
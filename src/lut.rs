@@ -0,0 +1,262 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A color lookup table, as stored in `.cube` files, for grading a
+/// finished image into a particular look. Grading is the last step,
+/// after exposure and tone mapping, and most tables expect colors that
+/// are already sRGB-encoded for display.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lut {
+    pub shape: LutShape,
+    /// The input colors that map to the first entries of the table.
+    pub domain_min: Color,
+    /// The input colors that map to the last entries of the table.
+    pub domain_max: Color,
+    /// The output colors. For a cube, red changes fastest, then green,
+    /// then blue.
+    table: Vec<Color>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LutShape {
+    /// A curve for each channel, looked up separately: the red output
+    /// only depends on the red input, and so on.
+    Curves { size: usize },
+    /// A cube of colors indexed by all three channels at once, which
+    /// can also shift hues and saturation.
+    Cube { size: usize },
+}
+
+impl Lut {
+    /// Parses the contents of a `.cube` file, holding either a 1D table
+    /// (`LUT_1D_SIZE`) or a 3D one (`LUT_3D_SIZE`).
+    pub fn parse(source: &str) -> io::Result<Lut> {
+        let mut shape = None;
+        let mut domain_min = Color::black();
+        let mut domain_max = Color::white();
+        let mut table = vec![];
+        for line in source.lines() {
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(word) if !word.starts_with('#') => word,
+                _ => continue,
+            };
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => {
+                    let size = parse_size(words.next())?;
+                    shape = Some(LutShape::Curves { size });
+                }
+                "LUT_3D_SIZE" => {
+                    let size = parse_size(words.next())?;
+                    shape = Some(LutShape::Cube { size });
+                }
+                "DOMAIN_MIN" => domain_min = parse_color(words)?,
+                "DOMAIN_MAX" => domain_max = parse_color(words)?,
+                _ => table.push(parse_color(line.split_whitespace())?),
+            }
+        }
+        let shape = shape.ok_or_else(|| invalid("missing the LUT size"))?;
+        let empty_domain = domain_min.red >= domain_max.red
+            || domain_min.green >= domain_max.green
+            || domain_min.blue >= domain_max.blue;
+        if empty_domain {
+            return Err(invalid(
+                "DOMAIN_MIN must be below DOMAIN_MAX in every channel",
+            ));
+        }
+        let expected = match shape {
+            LutShape::Curves { size } => size,
+            LutShape::Cube { size } => size * size * size,
+        };
+        if table.len() != expected {
+            return Err(invalid(&format!(
+                "expected {} entries, found {}",
+                expected,
+                table.len()
+            )));
+        }
+        Ok(Lut {
+            shape,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Reads and parses a `.cube` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Lut> {
+        Lut::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Looks up a color, interpolating between the nearest entries.
+    /// Inputs outside the domain are clamped to it.
+    pub fn apply(&self, color: Color) -> Color {
+        let scale = |value: f64, min: f64, max: f64| {
+            ((value - min) / (max - min)).clamp(0.0, 1.0)
+        };
+        let red = scale(color.red, self.domain_min.red, self.domain_max.red);
+        let green =
+            scale(color.green, self.domain_min.green, self.domain_max.green);
+        let blue =
+            scale(color.blue, self.domain_min.blue, self.domain_max.blue);
+        match self.shape {
+            LutShape::Curves { size } => {
+                let curve = |value: f64, channel: fn(Color) -> f64| {
+                    let (low, high, t) = neighbors(value, size);
+                    let low = channel(self.table[low]);
+                    low + (channel(self.table[high]) - low) * t
+                };
+                Color::new(
+                    curve(red, |c| c.red),
+                    curve(green, |c| c.green),
+                    curve(blue, |c| c.blue),
+                )
+            }
+            LutShape::Cube { size } => {
+                let (r0, r1, tr) = neighbors(red, size);
+                let (g0, g1, tg) = neighbors(green, size);
+                let (b0, b1, tb) = neighbors(blue, size);
+                let at = |r: usize, g: usize, b: usize| {
+                    self.table[r + size * (g + size * b)]
+                };
+                let lerp = |a: Color, b: Color, t: f64| a * (1.0 - t) + b * t;
+                let blend_red =
+                    |g: usize, b: usize| lerp(at(r0, g, b), at(r1, g, b), tr);
+                let near = lerp(blend_red(g0, b0), blend_red(g1, b0), tg);
+                let far = lerp(blend_red(g0, b1), blend_red(g1, b1), tg);
+                lerp(near, far, tb)
+            }
+        }
+    }
+
+    /// Grades every pixel of the image.
+    pub fn grade(&self, canvas: &Canvas) -> Canvas {
+        let mut graded = canvas.clone();
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                graded.write_pixel(x, y, self.apply(canvas.pixel_at(x, y)));
+            }
+        }
+        graded
+    }
+}
+
+/// Returns the entries on either side of a value from 0 to 1 in a table
+/// of `size` entries, and how far the value is between them.
+fn neighbors(value: f64, size: usize) -> (usize, usize, f64) {
+    let position = value * (size - 1) as f64;
+    let low = (position.floor() as usize).min(size - 1);
+    let high = (low + 1).min(size - 1);
+    (low, high, position - low as f64)
+}
+
+fn parse_size(word: Option<&str>) -> io::Result<usize> {
+    match word.and_then(|word| word.parse::<usize>().ok()) {
+        Some(size) if size >= 2 => Ok(size),
+        _ => Err(invalid("the LUT size must be a number of at least 2")),
+    }
+}
+
+fn parse_color<'a, I>(mut words: I) -> io::Result<Color>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut next = || {
+        words
+            .next()
+            .and_then(|word| word.parse::<f64>().ok())
+            .ok_or_else(|| invalid("expected three numbers"))
+    };
+    Ok(Color::new(next()?, next()?, next()?))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::lut::{Lut, LutShape};
+    use std::io;
+
+    /// A 2x2x2 cube that maps every color to itself.
+    const IDENTITY_CUBE: &str = "TITLE \"identity\"\n\
+                                 # red changes fastest\n\
+                                 LUT_3D_SIZE 2\n\
+                                 \n\
+                                 0 0 0\n1 0 0\n0 1 0\n1 1 0\n\
+                                 0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    #[test]
+    fn test_an_identity_cube_leaves_colors_unchanged() {
+        let lut = Lut::parse(IDENTITY_CUBE).unwrap();
+        assert_eq!(lut.shape, LutShape::Cube { size: 2 });
+        let color = Color::new(0.2, 0.5, 0.9);
+        assert_eq!(lut.apply(color), color);
+        // inputs are clamped to the domain
+        assert_eq!(
+            lut.apply(Color::new(2.0, -1.0, 0.5)),
+            Color::new(1.0, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn test_a_cube_interpolates_between_entries() {
+        let inverted = "LUT_3D_SIZE 2\n\
+                        1 1 1\n0 1 1\n1 0 1\n0 0 1\n\
+                        1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut::parse(inverted).unwrap();
+        assert_eq!(
+            lut.apply(Color::new(0.25, 0.5, 1.0)),
+            Color::new(0.75, 0.5, 0.0)
+        );
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(1, 0, Color::white());
+        let graded = lut.grade(&canvas);
+        assert_eq!(graded.pixel_at(0, 0), Color::white());
+        assert_eq!(graded.pixel_at(1, 0), Color::black());
+    }
+
+    #[test]
+    fn test_curves_grade_each_channel_separately() {
+        let curves = "LUT_1D_SIZE 3\n\
+                      DOMAIN_MIN 0 0 0\n\
+                      DOMAIN_MAX 2 2 2\n\
+                      0 0 0\n0.5 0.25 0\n1 1 1\n";
+        let lut = Lut::parse(curves).unwrap();
+        assert_eq!(lut.shape, LutShape::Curves { size: 3 });
+        assert_eq!(
+            lut.apply(Color::new(1.0, 1.0, 1.0)),
+            Color::new(0.5, 0.25, 0.0)
+        );
+        assert_eq!(
+            lut.apply(Color::new(1.5, 0.5, 2.0)),
+            Color::new(0.75, 0.125, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_malformed_cube_files_are_rejected() {
+        let truncated = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n";
+        let error = Lut::parse(truncated).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(Lut::parse("0 0 0\n1 1 1\n").is_err());
+        assert!(Lut::parse("LUT_1D_SIZE 2\n0 0 zero\n1 1 1\n").is_err());
+    }
+
+    #[test]
+    fn test_an_empty_domain_is_rejected() {
+        let flat = "LUT_1D_SIZE 2\n\
+                    DOMAIN_MIN 0 0 0\n\
+                    DOMAIN_MAX 1 0 1\n\
+                    0 0 0\n1 1 1\n";
+        let error = Lut::parse(flat).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
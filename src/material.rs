@@ -1,12 +1,83 @@
 use crate::color::Color;
+use crate::noise::NoiseBump;
+use crate::pattern::Pattern;
+use std::sync::Arc;
 
+/// Cel shading, a flat, cartoon-like look where light falls off in a few
+/// hard steps instead of smoothly.
 #[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Toon {
+    /// The number of levels the diffuse term is rounded up to.
+    pub bands: usize,
+    /// Surfaces seen this close to edge-on are drawn black, outlining
+    /// silhouettes. This is the cosine of the angle between the eye and
+    /// the normal, so 0 draws no outlines.
+    pub edge: f64,
+}
+
+impl Toon {
+    pub fn new(bands: usize) -> Toon {
+        Toon { bands, edge: 0.3 }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Material {
-    pub ambient: f32,
-    pub diffuse: f32,
-    pub specular: f32,
-    pub shininess: f32,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
     pub color: Color,
+    /// Replaces `color` with one that varies across the surface.
+    pub pattern: Option<Arc<dyn Pattern>>,
+    pub reflective: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    /// How much the refractive index varies across the spectrum, as the
+    /// difference between the indices for blue and red light. Anything
+    /// above zero refracts each color channel separately, producing
+    /// chromatic fringes.
+    pub dispersion: f64,
+    /// Blurs reflections and refractions by spreading their rays over a
+    /// cone, where the roughness is the tangent of the cone's half-angle.
+    /// Zero gives perfect mirrors and clear glass.
+    pub roughness: f64,
+    /// The number of rays averaged for each rough reflection or
+    /// refraction. Every rough bounce multiplies the work by this count,
+    /// so it is worth combining with `max_depth`.
+    pub glossy_samples: usize,
+    /// Tilts the surface normals to fake small bumps without changing
+    /// the shape of the surface.
+    pub bump: Option<NoiseBump>,
+    /// Caps how many further reflections are traced from surfaces with
+    /// this material, regardless of the world's limit. `None` means only
+    /// the world's limit applies.
+    pub max_depth: Option<usize>,
+    /// Scales how far shading points are pushed off the surface before
+    /// casting shadow, reflection and refraction rays. Raise it to fix
+    /// shadow acne on very large objects, or lower it for very small
+    /// ones.
+    pub shadow_bias: f64,
+    /// Whether the ambient term is added. Turning off individual terms
+    /// helps when debugging lighting, and gives flatter, stylized looks.
+    pub casts_ambient: bool,
+    /// Whether light is scattered by the diffuse term, both from the
+    /// light and, with indirect lighting, from other surfaces.
+    pub casts_diffuse: bool,
+    /// Whether the light produces specular highlights.
+    pub casts_specular_highlights: bool,
+    /// Shades the material with hard bands of light. `None` shades it
+    /// smoothly, unless the world sets a toon style for every material.
+    pub toon: Option<Toon>,
+    /// Hides the object from camera rays while it still casts shadows
+    /// and shows up in reflections and refractions, so that it can be
+    /// rendered separately and composited back in.
+    pub holdout: bool,
+    /// Decides which medium a ray is in where transparent objects
+    /// overlap, such as ice in water in a glass: the object with the
+    /// highest priority wins, and the surfaces of lower priority objects
+    /// inside it are ignored. Ties go to the object entered last.
+    pub priority: u32,
 }
 
 impl Default for Material {
@@ -17,10 +88,45 @@ impl Default for Material {
             specular: 0.9,
             shininess: 200.0,
             color: Color::white(),
+            pattern: None,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            dispersion: 0.0,
+            roughness: 0.0,
+            glossy_samples: 16,
+            bump: None,
+            max_depth: None,
+            shadow_bias: 1.0,
+            casts_ambient: true,
+            casts_diffuse: true,
+            casts_specular_highlights: true,
+            toon: None,
+            holdout: false,
+            priority: 0,
         }
     }
 }
 
+impl Material {
+    /// A dark, clear, highly reflective material with rippled normals,
+    /// suited to flat water surfaces and rippled glass.
+    #[allow(clippy::field_reassign_with_default)]
+    pub fn water() -> Material {
+        let mut material = Material::default();
+        material.color = Color::new(0.02, 0.05, 0.06);
+        material.ambient = 0.0;
+        material.diffuse = 0.1;
+        material.specular = 1.0;
+        material.shininess = 300.0;
+        material.reflective = 0.9;
+        material.transparency = 0.9;
+        material.refractive_index = 1.333;
+        material.bump = Some(NoiseBump::new(2.0, 0.3));
+        material
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::material::Material;
@@ -32,5 +138,44 @@ mod tests {
         assert_eq!(m.diffuse, 0.9);
         assert_eq!(m.specular, 0.9);
         assert_eq!(m.shininess, 200.0);
+        assert_eq!(m.shadow_bias, 1.0);
+        assert!(m.casts_ambient);
+        assert!(m.casts_diffuse);
+        assert!(m.casts_specular_highlights);
+        assert_eq!(m.toon, None);
+        assert!(!m.holdout);
+        assert_eq!(m.priority, 0);
+    }
+
+    #[test]
+    fn test_reflectivity_for_the_default_material() {
+        let m = Material::default();
+        assert_eq!(m.reflective, 0.0);
+        assert_eq!(m.max_depth, None);
+    }
+
+    #[test]
+    fn test_transparency_and_refractive_index_for_the_default_material() {
+        let m = Material::default();
+        assert_eq!(m.transparency, 0.0);
+        assert_eq!(m.refractive_index, 1.0);
+        assert_eq!(m.dispersion, 0.0);
+    }
+
+    #[test]
+    fn test_the_default_material_is_not_glossy() {
+        let m = Material::default();
+        assert_eq!(m.roughness, 0.0);
+        assert_eq!(m.glossy_samples, 16);
+        assert_eq!(m.bump, None);
+    }
+
+    #[test]
+    fn test_the_water_material_is_clear_and_rippled() {
+        let m = Material::water();
+        assert!(m.transparency > 0.0);
+        assert!(m.reflective > 0.0);
+        assert_eq!(m.refractive_index, 1.333);
+        assert!(m.bump.is_some());
     }
 }
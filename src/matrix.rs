@@ -1,28 +1,36 @@
-use crate::equal_f32;
+use crate::equal_f64;
 use crate::tuple::Tuple;
-use std::ops::Mul;
+use std::ops::{Index, IndexMut, Mul, MulAssign};
+
+/// One of the three coordinate axes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matrix2 {
-    pub rows: [[f32; 2]; 2],
+    pub rows: [[f64; 2]; 2],
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matrix3 {
-    pub rows: [[f32; 3]; 3],
+    pub rows: [[f64; 3]; 3],
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matrix4 {
-    pub rows: [[f32; 4]; 4],
+    pub rows: [[f64; 4]; 4],
 }
 
 impl Matrix2 {
-    pub fn from_rows(rows: [[f32; 2]; 2]) -> Matrix2 {
+    pub fn from_rows(rows: [[f64; 2]; 2]) -> Matrix2 {
         Matrix2 { rows }
     }
 
-    pub fn determinant(&self) -> f32 {
+    pub fn determinant(&self) -> f64 {
         (self.rows[0][0] * self.rows[1][1])
             - (self.rows[1][0] * self.rows[0][1])
     }
@@ -32,7 +40,7 @@ impl PartialEq for Matrix2 {
     fn eq(&self, other: &Matrix2) -> bool {
         for row in 0..2 {
             for col in 0..2 {
-                if !equal_f32(self.rows[row][col], other.rows[row][col]) {
+                if !equal_f64(self.rows[row][col], other.rows[row][col]) {
                     return false;
                 }
             }
@@ -42,7 +50,7 @@ impl PartialEq for Matrix2 {
 }
 
 impl Matrix3 {
-    pub fn from_rows(rows: [[f32; 3]; 3]) -> Matrix3 {
+    pub fn from_rows(rows: [[f64; 3]; 3]) -> Matrix3 {
         Matrix3 { rows }
     }
 
@@ -63,19 +71,31 @@ impl Matrix3 {
         }
     }
 
-    pub fn minor(&self, row: usize, col: usize) -> f32 {
+    pub fn minor(&self, row: usize, col: usize) -> f64 {
         self.submatrix(row, col).determinant()
     }
 
-    pub fn cofactor(&self, row: usize, col: usize) -> f32 {
-        if (row + col) % 2 == 0 {
+    pub fn cofactor(&self, row: usize, col: usize) -> f64 {
+        if (row + col).is_multiple_of(2) {
             self.minor(row, col)
         } else {
             -self.minor(row, col)
         }
     }
 
-    pub fn determinant(&self) -> f32 {
+    /// Returns a new matrix where the rows of the original matrix are
+    /// turned into columns and the columns into rows.
+    pub fn transpose(&self) -> Matrix3 {
+        let mut rows = [[0.0; 3]; 3];
+        for (row, values) in rows.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = self.rows[col][row];
+            }
+        }
+        Matrix3::from_rows(rows)
+    }
+
+    pub fn determinant(&self) -> f64 {
         let mut det = 0.0;
         for col in 0..3 {
             det += self.rows[0][col] * self.cofactor(0, col);
@@ -84,11 +104,46 @@ impl Matrix3 {
     }
 }
 
+impl Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, other: Matrix3) -> Matrix3 {
+        let mut rows = [[0.0; 3]; 3];
+        for (row, values) in rows.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                for i in 0..3 {
+                    *value += self.rows[row][i] * other.rows[i][col];
+                }
+            }
+        }
+        Matrix3::from_rows(rows)
+    }
+}
+
+/// Transforms the x, y and z components of a tuple as a 3D vector. The
+/// result is always a vector, whatever the tuple's w component was.
+impl Mul<Tuple> for Matrix3 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: Tuple) -> Tuple {
+        let x = (self.rows[0][0] * tuple.x)
+            + (self.rows[0][1] * tuple.y)
+            + (self.rows[0][2] * tuple.z);
+        let y = (self.rows[1][0] * tuple.x)
+            + (self.rows[1][1] * tuple.y)
+            + (self.rows[1][2] * tuple.z);
+        let z = (self.rows[2][0] * tuple.x)
+            + (self.rows[2][1] * tuple.y)
+            + (self.rows[2][2] * tuple.z);
+        Tuple::vector(x, y, z)
+    }
+}
+
 impl PartialEq for Matrix3 {
     fn eq(&self, other: &Matrix3) -> bool {
         for row in 0..3 {
             for col in 0..3 {
-                if !equal_f32(self.rows[row][col], other.rows[row][col]) {
+                if !equal_f64(self.rows[row][col], other.rows[row][col]) {
                     return false;
                 }
             }
@@ -98,10 +153,28 @@ impl PartialEq for Matrix3 {
 }
 
 impl Matrix4 {
-    pub fn from_rows(rows: [[f32; 4]; 4]) -> Matrix4 {
+    pub fn from_rows(rows: [[f64; 4]; 4]) -> Matrix4 {
         Matrix4 { rows }
     }
 
+    /// Builds a matrix from its columns rather than its rows.
+    pub fn from_cols(cols: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4::from_rows(cols).transpose()
+    }
+
+    pub fn row(&self, row: usize) -> [f64; 4] {
+        self.rows[row]
+    }
+
+    pub fn col(&self, col: usize) -> [f64; 4] {
+        [
+            self.rows[0][col],
+            self.rows[1][col],
+            self.rows[2][col],
+            self.rows[3][col],
+        ]
+    }
+
     pub fn identity() -> Matrix4 {
         Matrix4::from_rows([
             [1.0, 0.0, 0.0, 0.0],
@@ -165,20 +238,20 @@ impl Matrix4 {
     }
 
     /// Computes the determinant of the submatrix at the given row and column.
-    pub fn minor(&self, row: usize, col: usize) -> f32 {
+    pub fn minor(&self, row: usize, col: usize) -> f64 {
         self.submatrix(row, col).determinant()
     }
 
-    pub fn cofactor(&self, row: usize, col: usize) -> f32 {
+    pub fn cofactor(&self, row: usize, col: usize) -> f64 {
         let minor = self.minor(row, col);
-        if (row + col) % 2 == 0 {
+        if (row + col).is_multiple_of(2) {
             minor
         } else {
             -minor
         }
     }
 
-    pub fn determinant(&self) -> f32 {
+    pub fn determinant(&self) -> f64 {
         let mut det = 0.0;
         for col in 0..4 {
             det += self.rows[0][col] * self.cofactor(0, col);
@@ -187,7 +260,7 @@ impl Matrix4 {
     }
 
     pub fn is_invertible(&self) -> bool {
-        !equal_f32(self.determinant(), 0.0)
+        !equal_f64(self.determinant(), 0.0)
     }
 
     /// Returns the inverse of the matrix, which reverses the effects
@@ -209,9 +282,16 @@ impl Matrix4 {
         ])
     }
 
+    /// Returns the matrix that transforms normal vectors consistently
+    /// with this transformation: the upper-left 3x3 part of its inverse
+    /// transpose. Translation does not affect it.
+    pub fn to_normal_matrix(&self) -> Matrix3 {
+        self.inverse().transpose().submatrix(3, 3)
+    }
+
     /// Returns a transformation matrix that, when applied to a tuple,
     /// moves each component of the tuple by the given values.
-    pub fn translation(x: f32, y: f32, z: f32) -> Matrix4 {
+    pub fn translation(x: f64, y: f64, z: f64) -> Matrix4 {
         Matrix4::from_rows([
             [1.0, 0.0, 0.0, x],
             [0.0, 1.0, 0.0, y],
@@ -220,9 +300,15 @@ impl Matrix4 {
         ])
     }
 
+    /// Returns a translation matrix that moves tuples by the given
+    /// vector.
+    pub fn translation_from(offset: Tuple) -> Matrix4 {
+        Matrix4::translation(offset.x, offset.y, offset.z)
+    }
+
     /// Returns a transformation matrix that, when applied to a tuple,
     /// scales each component of the tuple by the given values.
-    pub fn scaling(x: f32, y: f32, z: f32) -> Matrix4 {
+    pub fn scaling(x: f64, y: f64, z: f64) -> Matrix4 {
         Matrix4::from_rows([
             [x, 0.0, 0.0, 0.0],
             [0.0, y, 0.0, 0.0],
@@ -231,9 +317,25 @@ impl Matrix4 {
         ])
     }
 
+    /// Returns a scaling matrix that scales all three components by
+    /// the same amount.
+    pub fn scaling_uniform(factor: f64) -> Matrix4 {
+        Matrix4::scaling(factor, factor, factor)
+    }
+
+    /// Returns a transformation matrix that, when applied to a tuple,
+    /// rotates the tuple around the given axis.
+    pub fn rotation(axis: Axis, radians: f64) -> Matrix4 {
+        match axis {
+            Axis::X => Matrix4::rotation_x(radians),
+            Axis::Y => Matrix4::rotation_y(radians),
+            Axis::Z => Matrix4::rotation_z(radians),
+        }
+    }
+
     /// Returns a transformation matrix that, when applied to a tuple,
     /// rotates the tuple around the x-axis.
-    pub fn rotation_x(radians: f32) -> Matrix4 {
+    pub fn rotation_x(radians: f64) -> Matrix4 {
         Matrix4::from_rows([
             [1.0, 0.0, 0.0, 0.0],
             [0.0, radians.cos(), -radians.sin(), 0.0],
@@ -244,7 +346,7 @@ impl Matrix4 {
 
     /// Returns a transformation matrix that, when applied to a tuple,
     /// rotates the tuple around the y-axis.
-    pub fn rotation_y(radians: f32) -> Matrix4 {
+    pub fn rotation_y(radians: f64) -> Matrix4 {
         Matrix4::from_rows([
             [radians.cos(), 0.0, radians.sin(), 0.0],
             [0.0, 1.0, 0.0, 0.0],
@@ -255,7 +357,7 @@ impl Matrix4 {
 
     /// Returns a transformation matrix that, when applied to a tuple,
     /// rotates the tuple around the z-axis.
-    pub fn rotation_z(radians: f32) -> Matrix4 {
+    pub fn rotation_z(radians: f64) -> Matrix4 {
         Matrix4::from_rows([
             [radians.cos(), -radians.sin(), 0.0, 0.0],
             [radians.sin(), radians.cos(), 0.0, 0.0],
@@ -268,12 +370,12 @@ impl Matrix4 {
     /// changes each component of the table in proportion to the other
     /// two components.
     pub fn shearing(
-        x_y: f32,
-        x_z: f32,
-        y_x: f32,
-        y_z: f32,
-        z_x: f32,
-        z_y: f32,
+        x_y: f64,
+        x_z: f64,
+        y_x: f64,
+        y_z: f64,
+        z_x: f64,
+        z_y: f64,
     ) -> Matrix4 {
         Matrix4::from_rows([
             [1.0, x_y, x_z, 0.0],
@@ -306,7 +408,7 @@ impl PartialEq for Matrix4 {
     fn eq(&self, other: &Matrix4) -> bool {
         for row in 0..4 {
             for col in 0..4 {
-                if !equal_f32(self.rows[row][col], other.rows[row][col]) {
+                if !equal_f64(self.rows[row][col], other.rows[row][col]) {
                     return false;
                 }
             }
@@ -315,33 +417,34 @@ impl PartialEq for Matrix4 {
     }
 }
 
-impl Mul for Matrix4 {
-    type Output = Matrix4;
+impl Index<(usize, usize)> for Matrix4 {
+    type Output = f64;
 
-    fn mul(self, other: Matrix4) -> Matrix4 {
-        let mut values = Vec::with_capacity(16);
-        for row in 0..4 {
-            for col in 0..4 {
-                let mut value = 0.0;
+    fn index(&self, (row, col): (usize, usize)) -> &f64 {
+        &self.rows[row][col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix4 {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut f64 {
+        &mut self.rows[row][col]
+    }
+}
+
+impl Matrix4 {
+    fn multiply(&self, other: &Matrix4) -> Matrix4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (row, values) in rows.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
                 for i in 0..4 {
-                    value += self.rows[row][i] * other.rows[i][col];
+                    *value += self.rows[row][i] * other.rows[i][col];
                 }
-                values.push(value);
             }
         }
-        Matrix4::from_rows([
-            [values[0], values[1], values[2], values[3]],
-            [values[4], values[5], values[6], values[7]],
-            [values[8], values[9], values[10], values[11]],
-            [values[12], values[13], values[14], values[15]],
-        ])
+        Matrix4::from_rows(rows)
     }
-}
-
-impl Mul<Tuple> for Matrix4 {
-    type Output = Tuple;
 
-    fn mul(self, tuple: Tuple) -> Tuple {
+    fn multiply_tuple(&self, tuple: &Tuple) -> Tuple {
         let x = (self.rows[0][0] * tuple.x)
             + (self.rows[0][1] * tuple.y)
             + (self.rows[0][2] * tuple.z)
@@ -362,12 +465,88 @@ impl Mul<Tuple> for Matrix4 {
     }
 }
 
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        self.multiply(&other)
+    }
+}
+
+impl Mul<&Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: &Matrix4) -> Matrix4 {
+        self.multiply(other)
+    }
+}
+
+impl Mul<Matrix4> for &Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        self.multiply(&other)
+    }
+}
+
+impl Mul<&Matrix4> for &Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: &Matrix4) -> Matrix4 {
+        self.multiply(other)
+    }
+}
+
+impl MulAssign for Matrix4 {
+    fn mul_assign(&mut self, other: Matrix4) {
+        *self = self.multiply(&other);
+    }
+}
+
+impl MulAssign<&Matrix4> for Matrix4 {
+    fn mul_assign(&mut self, other: &Matrix4) {
+        *self = self.multiply(other);
+    }
+}
+
+impl Mul<Tuple> for Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: Tuple) -> Tuple {
+        self.multiply_tuple(&tuple)
+    }
+}
+
+impl Mul<&Tuple> for Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: &Tuple) -> Tuple {
+        self.multiply_tuple(tuple)
+    }
+}
+
+impl Mul<Tuple> for &Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: Tuple) -> Tuple {
+        self.multiply_tuple(&tuple)
+    }
+}
+
+impl Mul<&Tuple> for &Matrix4 {
+    type Output = Tuple;
+
+    fn mul(self, tuple: &Tuple) -> Tuple {
+        self.multiply_tuple(tuple)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::equal_f32;
-    use crate::matrix::{Matrix2, Matrix3, Matrix4};
+    use crate::equal_f64;
+    use crate::matrix::{Axis, Matrix2, Matrix3, Matrix4};
     use crate::tuple::Tuple;
-    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+    use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
 
     #[test]
     fn test_constructing_and_inspecting_a_4x4_matrix() {
@@ -651,9 +830,9 @@ mod tests {
         let b = a.inverse();
         assert_eq!(a.determinant(), 532.0);
         assert_eq!(a.cofactor(2, 3), -160.0);
-        assert!(equal_f32(b.rows[3][2], -160.0 / 532.0));
+        assert!(equal_f64(b.rows[3][2], -160.0 / 532.0));
         assert_eq!(a.cofactor(3, 2), 105.0);
-        assert!(equal_f32(b.rows[2][3], 105.0 / 532.0));
+        assert!(equal_f64(b.rows[2][3], 105.0 / 532.0));
         assert_eq!(
             b,
             Matrix4::from_rows([
@@ -925,4 +1104,159 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn test_multiplying_matrices_by_reference() {
+        let a = Matrix4::translation(1.0, 2.0, 3.0);
+        let b = Matrix4::scaling(2.0, 3.0, 4.0);
+        let p = Tuple::point(1.0, 1.0, 1.0);
+        assert_eq!(&a * &b, a * b);
+        assert_eq!(a * &b, a * b);
+        assert_eq!(&a * b, a * b);
+        assert_eq!(&a * &p, a * p);
+        assert_eq!(a * &p, a * p);
+        assert_eq!(&a * p, a * p);
+    }
+
+    #[test]
+    fn test_multiply_assigning_a_matrix() {
+        let a = Matrix4::translation(1.0, 2.0, 3.0);
+        let b = Matrix4::rotation_x(FRAC_PI_4);
+        let mut m = a;
+        m *= b;
+        assert_eq!(m, a * b);
+        let mut m = a;
+        m *= &b;
+        assert_eq!(m, a * b);
+    }
+
+    #[test]
+    fn test_indexing_a_matrix() {
+        let mut m = Matrix4::from_rows([
+            [1.0, 2.0, 3.0, 4.0],
+            [5.5, 6.5, 7.5, 8.5],
+            [9.0, 10.0, 11.0, 12.0],
+            [13.5, 14.5, 15.5, 16.5],
+        ]);
+        assert_eq!(m[(0, 3)], 4.0);
+        assert_eq!(m[(3, 0)], 13.5);
+        m[(1, 2)] = -1.0;
+        assert_eq!(m.rows[1][2], -1.0);
+    }
+
+    #[test]
+    fn test_accessing_rows_and_columns_of_a_matrix() {
+        let m = Matrix4::from_rows([
+            [1.0, 2.0, 3.0, 4.0],
+            [5.0, 6.0, 7.0, 8.0],
+            [9.0, 8.0, 7.0, 6.0],
+            [5.0, 4.0, 3.0, 2.0],
+        ]);
+        assert_eq!(m.row(1), [5.0, 6.0, 7.0, 8.0]);
+        assert_eq!(m.col(1), [2.0, 6.0, 8.0, 4.0]);
+    }
+
+    #[test]
+    fn test_constructing_a_matrix_from_columns() {
+        let m = Matrix4::from_cols([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [5.0, -3.0, 2.0, 1.0],
+        ]);
+        assert_eq!(m, Matrix4::translation(5.0, -3.0, 2.0));
+        assert_eq!(m.col(3), [5.0, -3.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn test_transposing_a_3x3_matrix() {
+        let m = Matrix3::from_rows([
+            [1.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0],
+            [7.0, 8.0, 9.0],
+        ]);
+        let t = Matrix3::from_rows([
+            [1.0, 4.0, 7.0],
+            [2.0, 5.0, 8.0],
+            [3.0, 6.0, 9.0],
+        ]);
+        assert_eq!(m.transpose(), t);
+    }
+
+    #[test]
+    fn test_multiplying_two_3x3_matrices() {
+        let a = Matrix3::from_rows([
+            [1.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0],
+            [7.0, 8.0, 9.0],
+        ]);
+        let b = Matrix3::from_rows([
+            [-2.0, 1.0, 2.0],
+            [3.0, 2.0, 1.0],
+            [4.0, 3.0, 6.0],
+        ]);
+        let product = Matrix3::from_rows([
+            [16.0, 14.0, 22.0],
+            [31.0, 32.0, 49.0],
+            [46.0, 50.0, 76.0],
+        ]);
+        assert_eq!(a * b, product);
+    }
+
+    #[test]
+    fn test_a_3x3_matrix_multiplied_by_a_tuple() {
+        let m = Matrix3::from_rows([
+            [1.0, 2.0, 3.0],
+            [2.0, 4.0, 4.0],
+            [8.0, 6.0, 4.0],
+        ]);
+        let t = Tuple::point(1.0, 2.0, 3.0);
+        assert_eq!(m * t, Tuple::vector(14.0, 22.0, 32.0));
+    }
+
+    #[test]
+    fn test_the_normal_matrix_ignores_translation() {
+        let m = Matrix4::translation(1.0, 2.0, 3.0)
+            * Matrix4::scaling(2.0, 4.0, 8.0);
+        let n = m.to_normal_matrix();
+        assert_eq!(
+            n,
+            Matrix3::from_rows([
+                [0.5, 0.0, 0.0],
+                [0.0, 0.25, 0.0],
+                [0.0, 0.0, 0.125],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rotating_around_an_axis() {
+        assert_eq!(
+            Matrix4::rotation(Axis::X, FRAC_PI_4),
+            Matrix4::rotation_x(FRAC_PI_4)
+        );
+        assert_eq!(
+            Matrix4::rotation(Axis::Y, FRAC_PI_4),
+            Matrix4::rotation_y(FRAC_PI_4)
+        );
+        assert_eq!(
+            Matrix4::rotation(Axis::Z, FRAC_PI_4),
+            Matrix4::rotation_z(FRAC_PI_4)
+        );
+    }
+
+    #[test]
+    fn test_scaling_uniformly() {
+        let transform = Matrix4::scaling_uniform(2.0);
+        let p = Tuple::point(-4.0, 6.0, 8.0);
+        assert_eq!(transform * p, Tuple::point(-8.0, 12.0, 16.0));
+    }
+
+    #[test]
+    fn test_translating_by_a_vector() {
+        let transform =
+            Matrix4::translation_from(Tuple::vector(5.0, -3.0, 2.0));
+        assert_eq!(transform, Matrix4::translation(5.0, -3.0, 2.0));
+    }
 }
@@ -0,0 +1,418 @@
+use crate::accel::AccelStructure;
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::metadata::SceneHash;
+use crate::ray::Ray;
+use crate::shape::Shape;
+use crate::triangle::{barycentric, intersect_triangle, Triangle};
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::mem::{size_of, size_of_val};
+use std::sync::Arc;
+
+/// The vertices and faces of a mesh, kept in flat arrays rather than in a
+/// struct per triangle. Faces refer to their corners by index, so a
+/// corner shared by several faces is stored once.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MeshData {
+    /// Three coordinates per vertex.
+    pub positions: Vec<f64>,
+    /// Three components per vertex, interpolated across each face for
+    /// smooth shading. Empty for flat faces.
+    pub normals: Vec<f64>,
+    /// Two texture coordinates per vertex. When empty, the texture
+    /// coordinates are the barycentric coordinates on each face.
+    pub uvs: Vec<f64>,
+    /// Three vertex indices per face, in the order of the corners given
+    /// to `Triangle::new`.
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn position(&self, vertex: usize) -> Tuple {
+        let p = &self.positions[3 * vertex..3 * vertex + 3];
+        Tuple::point(p[0], p[1], p[2])
+    }
+
+    fn normal(&self, vertex: usize) -> Tuple {
+        let n = &self.normals[3 * vertex..3 * vertex + 3];
+        Tuple::vector(n[0], n[1], n[2])
+    }
+
+    fn uv(&self, vertex: usize) -> (f64, f64) {
+        (self.uvs[2 * vertex], self.uvs[2 * vertex + 1])
+    }
+
+    fn corners(&self, face: usize) -> [usize; 3] {
+        let i = &self.indices[3 * face..3 * face + 3];
+        [i[0] as usize, i[1] as usize, i[2] as usize]
+    }
+
+    /// Returns the first corner of the face and its two edges from it.
+    fn edges(&self, face: usize) -> (Tuple, Tuple, Tuple) {
+        let [a, b, c] = self.corners(face);
+        let p1 = self.position(a);
+        (p1, self.position(b) - p1, self.position(c) - p1)
+    }
+
+    fn face_bounds(&self, face: usize) -> (Tuple, Tuple) {
+        let points = self.corners(face).map(|vertex| self.position(vertex));
+        let min = |axis: fn(&Tuple) -> f64| {
+            points.iter().map(axis).fold(f64::INFINITY, f64::min)
+        };
+        let max = |axis: fn(&Tuple) -> f64| {
+            points.iter().map(axis).fold(f64::NEG_INFINITY, f64::max)
+        };
+        (
+            Tuple::point(min(|p| p.x), min(|p| p.y), min(|p| p.z)),
+            Tuple::point(max(|p| p.x), max(|p| p.y), max(|p| p.z)),
+        )
+    }
+}
+
+/// A triangle mesh stored compactly, for large models. The vertex data
+/// and the hierarchy over the faces are shared between clones, so the
+/// same model can be placed many times with different transformations
+/// and materials for little more than the cost of one.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    data: Arc<MeshData>,
+    faces: Arc<Bvh>,
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl Mesh {
+    /// Creates a mesh from its vertex data, building a hierarchy over the
+    /// faces. Panics if the arrays don't agree on the number of vertices
+    /// or a face refers to a vertex that doesn't exist.
+    pub fn new(data: MeshData) -> Mesh {
+        let vertices = data.vertex_count();
+        assert!(
+            data.positions.len().is_multiple_of(3)
+                && data.indices.len().is_multiple_of(3)
+        );
+        assert!(data.normals.is_empty() || data.normals.len() == 3 * vertices);
+        assert!(data.uvs.is_empty() || data.uvs.len() == 2 * vertices);
+        assert!(data.indices.iter().all(|&i| (i as usize) < vertices));
+        let bounds: Vec<BoundingBox> = (0..data.face_count())
+            .map(|face| data.face_bounds(face).into())
+            .collect();
+        Mesh {
+            faces: Arc::new(Bvh::build(&bounds)),
+            data: Arc::new(data),
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+
+    /// Gathers separate triangles into a mesh, storing corners shared by
+    /// several triangles once. The triangles' transformations and
+    /// materials are not kept.
+    pub fn from_triangles(triangles: &[Triangle]) -> Mesh {
+        let mut data = MeshData::default();
+        let mut vertices: HashMap<[u64; 3], u32> = HashMap::new();
+        for triangle in triangles.iter() {
+            for &p in [triangle.p1, triangle.p2, triangle.p3].iter() {
+                let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+                let index = *vertices.entry(key).or_insert_with(|| {
+                    data.positions.extend_from_slice(&[p.x, p.y, p.z]);
+                    (data.positions.len() / 3 - 1) as u32
+                });
+                data.indices.push(index);
+            }
+        }
+        Mesh::new(data)
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    /// Returns the normal at the given barycentric coordinates on a face.
+    fn face_normal(&self, face: usize, u: f64, v: f64) -> Tuple {
+        if self.data.normals.is_empty() {
+            let (_, e1, e2) = self.data.edges(face);
+            return e2.cross(e1).normalize();
+        }
+        let [a, b, c] = self.data.corners(face);
+        let n = self.data.normal(a) * (1.0 - u - v)
+            + self.data.normal(b) * u
+            + self.data.normal(c) * v;
+        n.normalize()
+    }
+
+    /// Returns the texture coordinates at the given barycentric
+    /// coordinates on a face, or the barycentric coordinates themselves
+    /// when the mesh has no texture coordinates.
+    fn face_uv(&self, face: usize, u: f64, v: f64) -> (f64, f64) {
+        if self.data.uvs.is_empty() {
+            return (u, v);
+        }
+        let [a, b, c] = self.data.corners(face);
+        let (uv1, uv2, uv3) =
+            (self.data.uv(a), self.data.uv(b), self.data.uv(c));
+        let w = 1.0 - u - v;
+        (
+            uv1.0 * w + uv2.0 * u + uv3.0 * v,
+            uv1.1 * w + uv2.1 * u + uv3.1 * v,
+        )
+    }
+
+    /// Finds the face a point on the surface lies on, and the point's
+    /// barycentric coordinates on it. This tests every face, so it is only
+    /// used when the face that was hit isn't known.
+    fn face_at(&self, point: Tuple) -> Option<(usize, f64, f64)> {
+        let mut nearest: Option<(usize, f64, f64, f64)> = None;
+        for face in 0..self.data.face_count() {
+            let (p1, e1, e2) = self.data.edges(face);
+            let (u, v) = barycentric(point, p1, e1, e2);
+            let inside =
+                u >= -EPSILON && v >= -EPSILON && u + v <= 1.0 + EPSILON;
+            let distance = (point - p1).dot(e2.cross(e1).normalize()).abs();
+            if inside && nearest.is_none_or(|(.., best)| distance < best) {
+                nearest = Some((face, u, v, distance));
+            }
+        }
+        nearest.map(|(face, u, v, _)| (face, u, v))
+    }
+}
+
+impl Shape for Mesh {
+    fn kind(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Hits remember the face and their barycentric coordinates on it.
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        self.faces
+            .candidates(transformed_ray)
+            .into_iter()
+            .filter_map(|face| {
+                let (p1, e1, e2) = self.data.edges(face);
+                let (t, u, v) =
+                    intersect_triangle(transformed_ray, p1, e1, e2)?;
+                let mut hit = Intersection::with_uv(t, self, u, v);
+                hit.face = Some(face);
+                Some(hit)
+            })
+            .collect()
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        match self.face_at(point) {
+            Some((face, u, v)) => self.face_normal(face, u, v),
+            None => Tuple::vector(0.0, 1.0, 0.0),
+        }
+    }
+
+    fn local_normal_at_hit(&self, point: Tuple, hit: &Intersection) -> Tuple {
+        match (hit.face, hit.u, hit.v) {
+            (Some(face), Some(u), Some(v)) => self.face_normal(face, u, v),
+            _ => self.local_normal_at(point),
+        }
+    }
+
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        match self.face_at(point) {
+            Some((face, u, v)) => self.face_uv(face, u, v),
+            None => (0.0, 0.0),
+        }
+    }
+
+    fn local_uv_at_hit(&self, point: Tuple, hit: &Intersection) -> (f64, f64) {
+        match (hit.face, hit.u, hit.v) {
+            (Some(face), Some(u), Some(v)) => self.face_uv(face, u, v),
+            _ => self.local_uv_at(point),
+        }
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        (0..self.data.face_count())
+            .map(|face| self.data.face_bounds(face))
+            .reduce(|(min1, max1), (min2, max2)| {
+                (
+                    Tuple::point(
+                        min1.x.min(min2.x),
+                        min1.y.min(min2.y),
+                        min1.z.min(min2.z),
+                    ),
+                    Tuple::point(
+                        max1.x.max(max2.x),
+                        max1.y.max(max2.y),
+                        max1.z.max(max2.z),
+                    ),
+                )
+            })
+            .unwrap_or((
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::point(0.0, 0.0, 0.0),
+            ))
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.data.face_count()
+    }
+
+    /// Counts the shared vertex data and hierarchy in full, even when
+    /// other clones share it.
+    fn memory_bytes(&self) -> usize {
+        size_of_val(self)
+            + (self.data.positions.capacity()
+                + self.data.normals.capacity()
+                + self.data.uvs.capacity())
+                * size_of::<f64>()
+            + self.data.indices.capacity() * size_of::<u32>()
+            + self.faces.memory_bytes()
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        self.data.positions.hash_into(state);
+        self.data.normals.hash_into(state);
+        self.data.uvs.hash_into(state);
+        self.data.indices.hash_into(state);
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::displacement::Displacement;
+    use crate::material::Material;
+    use crate::mesh::{Mesh, MeshData};
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+
+    /// A unit square in the xz plane, facing up, as two faces.
+    fn square() -> MeshData {
+        MeshData {
+            positions: vec![
+                0.0, 0.0, 0.0, //
+                1.0, 0.0, 0.0, //
+                1.0, 0.0, 1.0, //
+                0.0, 0.0, 1.0,
+            ],
+            normals: vec![],
+            uvs: vec![],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    #[test]
+    fn test_a_ray_hits_a_face_of_a_mesh() {
+        let m = Mesh::new(square());
+        assert_eq!((m.data().vertex_count(), m.triangle_count()), (4, 2));
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let xs = m.intersect(Ray::new(Tuple::point(0.25, 2.0, 0.75), down));
+        assert_eq!(xs.len(), 1);
+        assert_eq!((xs[0].t, xs[0].face), (2.0, Some(1)));
+        assert_eq!(
+            m.normal_at(Tuple::point(0.25, 0.0, 0.75)),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+        let miss = Ray::new(Tuple::point(1.5, 2.0, 0.5), down);
+        assert!(m.intersect(miss).is_empty());
+    }
+
+    #[test]
+    fn test_vertex_normals_are_interpolated_across_a_face() {
+        let mut data = square();
+        // tilted towards +x on the far edge
+        let tilted = [0.6, 0.8, 0.0];
+        data.normals =
+            [[0.0, 1.0, 0.0], tilted, tilted, [0.0, 1.0, 0.0]].concat();
+        let m = Mesh::new(data);
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let r = Ray::new(Tuple::point(0.5, 1.0, 0.25), down);
+        let mut hit = m.intersect(r).remove(0);
+        hit.prepare_hit(r);
+        let expected = Tuple::vector(0.3, 0.9, 0.0).normalize();
+        assert_eq!(hit.normal_vector, Some(expected));
+        assert_eq!(m.normal_at(Tuple::point(0.5, 0.0, 0.25)), expected);
+    }
+
+    #[test]
+    fn test_texture_coordinates_are_interpolated_across_a_face() {
+        let mut data = square();
+        data.uvs = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let m = Mesh::new(data);
+        let (u, v) = m.uv_at(Tuple::point(0.25, 0.0, 0.75));
+        assert!((u - 0.25).abs() < 0.0001 && (v - 0.75).abs() < 0.0001);
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let xs = m.intersect(Ray::new(Tuple::point(0.25, 2.0, 0.75), down));
+        let point = Tuple::point(0.25, 0.0, 0.75);
+        let (u, v) = m.uv_at_hit(point, &xs[0]);
+        assert!((u - 0.25).abs() < 0.0001 && (v - 0.75).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_gathering_triangles_into_a_mesh_shares_their_corners() {
+        let mut d = Displacement::new(Canvas::new(2, 2), 1.0);
+        d.divisions = 4;
+        let triangles = d.displace_quad(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+            &Material::default(),
+        );
+        let m = Mesh::from_triangles(&triangles);
+        assert_eq!((m.data().vertex_count(), m.triangle_count()), (25, 32));
+        let separate: usize = triangles.iter().map(|t| t.memory_bytes()).sum();
+        assert!(m.memory_bytes() * 2 < separate);
+        let mut w = World::new();
+        w.objects.push(Box::new(m.clone()));
+        w.objects.push(Box::new(m));
+        assert_eq!(w.stats().triangles, 64);
+        let r = Ray::new(
+            Tuple::point(0.3, 1.0, 0.6),
+            Tuple::vector(0.0, -1.0, 0.0),
+        );
+        assert_eq!(Scene::new(&w).hit_sorted(r).unwrap().t, 1.0);
+    }
+}
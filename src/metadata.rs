@@ -0,0 +1,467 @@
+use crate::camera::{Camera, Lens};
+use crate::canvas::Canvas;
+use crate::color::{Color, ColorSpace};
+use crate::environment::Environment;
+use crate::irradiance::Indirect;
+use crate::light::{Curve, Falloff, PointLight};
+use crate::material::{Material, Toon};
+use crate::matrix::Matrix4;
+use crate::noise::NoiseBump;
+use crate::pattern::Pattern;
+use crate::sampler::Sampler;
+use crate::scene_file::SceneValue;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::world::{ReflectionMode, World};
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How an image was rendered, written into image files so that they can
+/// be traced back to the scene and settings that produced them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RenderInfo {
+    /// A hash of everything in the world and camera that affects the
+    /// image. Renders with equal hashes came from the same scene, at
+    /// least with the same version of the renderer.
+    pub scene_hash: u64,
+    pub width: usize,
+    pub height: usize,
+    /// The number of camera rays traced for every pixel.
+    pub samples: usize,
+    pub duration: Duration,
+}
+
+impl RenderInfo {
+    /// Describes a render of the world through the camera that took
+    /// `duration`.
+    pub fn new(
+        world: &World,
+        camera: &Camera,
+        duration: Duration,
+    ) -> RenderInfo {
+        RenderInfo {
+            scene_hash: scene_hash(world, camera),
+            width: camera.hsize,
+            height: camera.vsize,
+            samples: camera.samples_per_pixel(),
+            duration,
+        }
+    }
+
+    /// Returns the settings as named text values, in the order they are
+    /// written to files.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("scene-hash", format!("{:016x}", self.scene_hash)),
+            ("resolution", format!("{}x{}", self.width, self.height)),
+            ("samples", self.samples.to_string()),
+            ("duration", format!("{:.3}s", self.duration.as_secs_f64())),
+        ]
+    }
+}
+
+/// Hashes everything that affects a render.
+pub fn scene_hash(world: &World, camera: &Camera) -> u64 {
+    let mut state = Fnv::new();
+    world.objects.hash_into(&mut state);
+    world.lights.hash_into(&mut state);
+    world.max_depth.hash_into(&mut state);
+    world.environment.hash_into(&mut state);
+    world.reflection_mode.hash_into(&mut state);
+    world.shadows.hash_into(&mut state);
+    world.sampler.hash_into(&mut state);
+    world.indirect.hash_into(&mut state);
+    world.toon.hash_into(&mut state);
+    camera.hash_into(&mut state);
+    state.finish()
+}
+
+/// The FNV-1a hash, used for scene hashes rather than the standard
+/// library's hasher, whose results may change between Rust releases.
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv::new()
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Something whose settings and geometry affect a render. Only those are
+/// hashed, never state worked out while rendering, such as lazily
+/// loaded meshes, so a scene hashes the same before and after a render.
+/// Numbers are hashed as little-endian bytes, so hashes are the same on
+/// every machine.
+pub trait SceneHash {
+    fn hash_into(&self, state: &mut dyn Hasher);
+}
+
+impl SceneHash for u64 {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        state.write(&self.to_le_bytes());
+    }
+}
+
+impl SceneHash for u32 {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        u64::from(*self).hash_into(state);
+    }
+}
+
+impl SceneHash for usize {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (*self as u64).hash_into(state);
+    }
+}
+
+impl SceneHash for f64 {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.to_bits().hash_into(state);
+    }
+}
+
+impl SceneHash for bool {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        state.write(&[u8::from(*self)]);
+    }
+}
+
+/// Strings end in a byte that never appears in UTF-8, so that `"ab",
+/// "c"` and `"a", "bc"` hash differently.
+impl SceneHash for str {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        state.write(self.as_bytes());
+        state.write(&[0xff]);
+    }
+}
+
+impl<T: SceneHash + ?Sized> SceneHash for &T {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (**self).hash_into(state);
+    }
+}
+
+impl<T: SceneHash + ?Sized> SceneHash for Box<T> {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (**self).hash_into(state);
+    }
+}
+
+impl<T: SceneHash + ?Sized> SceneHash for Arc<T> {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (**self).hash_into(state);
+    }
+}
+
+impl<T: SceneHash> SceneHash for Option<T> {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        match self {
+            Some(value) => {
+                true.hash_into(state);
+                value.hash_into(state);
+            }
+            None => false.hash_into(state),
+        }
+    }
+}
+
+impl<T: SceneHash> SceneHash for [T] {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.len().hash_into(state);
+        for value in self {
+            value.hash_into(state);
+        }
+    }
+}
+
+impl<T: SceneHash> SceneHash for Vec<T> {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.as_slice().hash_into(state);
+    }
+}
+
+impl SceneHash for Tuple {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        [self.x, self.y, self.z, self.w].hash_into(state);
+    }
+}
+
+impl SceneHash for Color {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        [self.red, self.green, self.blue].hash_into(state);
+    }
+}
+
+impl SceneHash for Matrix4 {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        for row in 0..4 {
+            self.row(row).hash_into(state);
+        }
+    }
+}
+
+impl SceneHash for Canvas {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.width.hash_into(state);
+        self.height.hash_into(state);
+        (self.color_space == ColorSpace::Srgb).hash_into(state);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.pixel_at(x, y).hash_into(state);
+            }
+        }
+    }
+}
+
+impl SceneHash for dyn Shape {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.kind().hash_into(state);
+        self.transform().hash_into(state);
+        self.material().hash_into(state);
+        self.back_material().hash_into(state);
+        self.hash_settings(state);
+    }
+}
+
+impl SceneHash for dyn Pattern {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.hash_settings(state);
+    }
+}
+
+impl SceneHash for SceneValue<'_> {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        match self {
+            SceneValue::Number(number) => number.hash_into(state),
+            SceneValue::Flag(flag) => flag.hash_into(state),
+            SceneValue::Point(point) => point.hash_into(state),
+            SceneValue::Shapes(shapes) => shapes.hash_into(state),
+        }
+    }
+}
+
+impl SceneHash for Material {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        [self.ambient, self.diffuse, self.specular, self.shininess]
+            .hash_into(state);
+        self.color.hash_into(state);
+        self.pattern.hash_into(state);
+        [
+            self.reflective,
+            self.transparency,
+            self.refractive_index,
+            self.dispersion,
+            self.roughness,
+        ]
+        .hash_into(state);
+        self.glossy_samples.hash_into(state);
+        self.bump.hash_into(state);
+        self.max_depth.hash_into(state);
+        self.shadow_bias.hash_into(state);
+        [
+            self.casts_ambient,
+            self.casts_diffuse,
+            self.casts_specular_highlights,
+        ]
+        .hash_into(state);
+        self.toon.hash_into(state);
+        self.holdout.hash_into(state);
+        self.priority.hash_into(state);
+    }
+}
+
+impl SceneHash for NoiseBump {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        [self.frequency, self.amplitude].hash_into(state);
+        self.octaves.hash_into(state);
+    }
+}
+
+impl SceneHash for Toon {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.bands.hash_into(state);
+        self.edge.hash_into(state);
+    }
+}
+
+impl SceneHash for PointLight {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.position.hash_into(state);
+        self.intensity.hash_into(state);
+        self.radius.hash_into(state);
+        self.samples.hash_into(state);
+        self.casts_shadows.hash_into(state);
+        match self.falloff {
+            Falloff::None => 0usize.hash_into(state),
+            Falloff::Cylindrical {
+                axis,
+                inner,
+                outer,
+                curve,
+            } => {
+                1usize.hash_into(state);
+                axis.hash_into(state);
+                [inner, outer].hash_into(state);
+                curve.hash_into(state);
+            }
+            Falloff::Conical {
+                axis,
+                inner,
+                outer,
+                curve,
+            } => {
+                2usize.hash_into(state);
+                axis.hash_into(state);
+                [inner, outer].hash_into(state);
+                curve.hash_into(state);
+            }
+        }
+        let attenuation = self.attenuation;
+        [
+            attenuation.constant,
+            attenuation.linear,
+            attenuation.quadratic,
+        ]
+        .hash_into(state);
+    }
+}
+
+impl SceneHash for Curve {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        match self {
+            Curve::Linear => 0usize.hash_into(state),
+            Curve::Smooth => 1usize.hash_into(state),
+            Curve::Table(table) => {
+                2usize.hash_into(state);
+                table.hash_into(state);
+            }
+        }
+    }
+}
+
+impl SceneHash for Environment {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        match self {
+            Environment::Uniform(color) => {
+                0usize.hash_into(state);
+                color.hash_into(state);
+            }
+            Environment::Map(canvas) => {
+                1usize.hash_into(state);
+                canvas.hash_into(state);
+            }
+        }
+    }
+}
+
+impl SceneHash for ReflectionMode {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (*self as usize).hash_into(state);
+    }
+}
+
+impl SceneHash for Sampler {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        (*self as usize).hash_into(state);
+    }
+}
+
+impl SceneHash for Indirect {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        match *self {
+            Indirect::Off => 0usize.hash_into(state),
+            Indirect::Sampled { samples } => {
+                1usize.hash_into(state);
+                samples.hash_into(state);
+            }
+            Indirect::Cached { samples, max_error } => {
+                2usize.hash_into(state);
+                samples.hash_into(state);
+                max_error.hash_into(state);
+            }
+        }
+    }
+}
+
+impl SceneHash for Camera {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        self.hsize.hash_into(state);
+        self.vsize.hash_into(state);
+        self.field_of_view.hash_into(state);
+        self.transform.hash_into(state);
+        (self.quality as usize).hash_into(state);
+        (self.mode as usize).hash_into(state);
+        self.lens.hash_into(state);
+        self.seed.hash_into(state);
+    }
+}
+
+impl SceneHash for Lens {
+    fn hash_into(&self, state: &mut dyn Hasher) {
+        [self.aperture, self.focal_distance, self.rotation].hash_into(state);
+        [self.samples, self.blades].hash_into(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::{Camera, Lens};
+    use crate::color::Color;
+    use crate::metadata::{scene_hash, RenderInfo};
+    use crate::pattern::StripePattern;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_describing_a_render() {
+        let w = World::default();
+        let mut c = Camera::new(20, 10, FRAC_PI_2);
+        let info = RenderInfo::new(&w, &c, Duration::from_millis(1500));
+        assert_eq!(info.samples, 1);
+        let entries = info.entries();
+        assert_eq!(entries[1], ("resolution", "20x10".to_string()));
+        assert_eq!(entries[2], ("samples", "1".to_string()));
+        assert_eq!(entries[3], ("duration", "1.500s".to_string()));
+        c.lens = Some(Lens::new(0.1, 5.0));
+        let info = RenderInfo::new(&w, &c, Duration::from_secs(1));
+        assert_eq!(info.samples, 16);
+    }
+
+    #[test]
+    fn test_the_scene_hash_changes_with_the_scene() {
+        let w = World::default();
+        let c = Camera::new(20, 10, FRAC_PI_2);
+        assert_eq!(scene_hash(&w, &c), scene_hash(&w.clone(), &c));
+        let mut moved = w.clone();
+        moved.lights[0].position.x += 1.0;
+        assert_ne!(scene_hash(&moved, &c), scene_hash(&w, &c));
+        let mut shadowless = w.clone();
+        shadowless.shadows = false;
+        assert_ne!(scene_hash(&shadowless, &c), scene_hash(&w, &c));
+        let mut striped = w.clone();
+        striped.objects[0].material_mut().pattern =
+            Some(Arc::new(StripePattern::new(Color::white(), Color::black())));
+        assert_ne!(scene_hash(&striped, &c), scene_hash(&w, &c));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::tuple::Tuple;
+
+/// The step used to estimate the slope of the noise.
+const GRADIENT_STEP: f64 = 0.001;
+
+/// Returns smooth gradient noise at the given point, between roughly -1
+/// and 1, that is zero at every point with integer coordinates.
+pub fn perlin(point: Tuple) -> f64 {
+    let (xi, yi, zi) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let (x, y, z) = (point.x - xi, point.y - yi, point.z - zi);
+    let (xi, yi, zi) = (xi as i32, yi as i32, zi as i32);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        grad(
+            hash(xi + dx, yi + dy, zi + dz),
+            x - dx as f64,
+            y - dy as f64,
+            z - dz as f64,
+        )
+    };
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+            lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+        ),
+    )
+}
+
+/// Sums several octaves of Perlin noise, each at twice the frequency and
+/// half the amplitude of the one before, for more natural detail.
+pub fn fractal(point: Tuple, octaves: usize) -> f64 {
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    for _ in 0..octaves {
+        total += perlin(point * frequency) * amplitude;
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    total
+}
+
+/// Bump mapping that tilts surface normals as if the surface were
+/// displaced by fractal noise, for ripples, dents and the like. The
+/// noise is sampled in world space.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct NoiseBump {
+    /// How many bumps fit in one unit of world space.
+    pub frequency: f64,
+    /// How strongly the normals are tilted.
+    pub amplitude: f64,
+    pub octaves: usize,
+}
+
+impl NoiseBump {
+    pub fn new(frequency: f64, amplitude: f64) -> NoiseBump {
+        NoiseBump {
+            frequency,
+            amplitude,
+            octaves: 3,
+        }
+    }
+
+    /// Tilts a unit normal against the slope of the noise at `point`.
+    pub fn perturb(&self, point: Tuple, normal: Tuple) -> Tuple {
+        let height = |offset: Tuple| {
+            fractal((point + offset) * self.frequency, self.octaves)
+        };
+        let slope = |axis: Tuple| {
+            (height(axis * GRADIENT_STEP) - height(axis * -GRADIENT_STEP))
+                / (2.0 * GRADIENT_STEP)
+        };
+        let gradient = Tuple::vector(
+            slope(Tuple::vector(1.0, 0.0, 0.0)),
+            slope(Tuple::vector(0.0, 1.0, 0.0)),
+            slope(Tuple::vector(0.0, 0.0, 1.0)),
+        ) / self.frequency;
+        // only the part of the slope along the surface tilts the normal
+        let tangential = gradient - normal * gradient.dot(normal);
+        (normal - tangential * self.amplitude).normalize()
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Scrambles lattice coordinates into a pseudo-random number.
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32)
+        .wrapping_mul(0x8da6_b343)
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841))
+        .wrapping_add((z as u32).wrapping_mul(0xcb1a_b31f));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h
+}
+
+/// Dots the offset from a lattice corner with one of the twelve
+/// gradient directions of improved Perlin noise, chosen by the hash.
+fn grad(hash: u32, x: f64, y: f64, z: f64) -> f64 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::{fractal, perlin, NoiseBump};
+    use crate::tuple::Tuple;
+    use crate::{equal_f64, EPSILON};
+
+    #[test]
+    fn test_perlin_noise_is_zero_on_the_lattice() {
+        assert_eq!(perlin(Tuple::point(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(perlin(Tuple::point(3.0, -2.0, 7.0)), 0.0);
+    }
+
+    #[test]
+    fn test_perlin_noise_is_smooth_and_bounded() {
+        let mut values = vec![];
+        for i in 0..1000 {
+            let t = i as f64 * 0.037;
+            let p = Tuple::point(t, t * 0.7 + 0.3, t * 1.3 - 0.2);
+            let value = perlin(p);
+            assert!(value.abs() <= 1.0);
+            let nearby = perlin(p + Tuple::vector(0.001, 0.0, 0.0));
+            assert!((value - nearby).abs() < 0.01);
+            values.push(value);
+        }
+        assert!(values.iter().any(|&v| v > 0.1));
+        assert!(values.iter().any(|&v| v < -0.1));
+    }
+
+    #[test]
+    fn test_fractal_noise_with_one_octave_is_perlin_noise() {
+        let p = Tuple::point(1.3, 2.6, -0.4);
+        assert_eq!(fractal(p, 1), perlin(p));
+        assert_ne!(fractal(p, 3), perlin(p));
+    }
+
+    #[test]
+    fn test_a_noise_bump_tilts_a_normal() {
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+        let p = Tuple::point(0.3, 0.0, 0.6);
+        let bumped = NoiseBump::new(4.0, 0.5).perturb(p, normal);
+        assert!(equal_f64(bumped.magnitude(), 1.0));
+        assert!(bumped != normal);
+        assert!(bumped.dot(normal) > 0.0);
+        let flat = NoiseBump::new(4.0, 0.0).perturb(p, normal);
+        assert!((flat - normal).magnitude() < EPSILON);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::group::Group;
+use crate::shape::Shape;
+use crate::triangle::{SmoothTriangle, Triangle};
+use crate::tuple::Tuple;
+use std::collections::BTreeMap;
+
+/// The geometry read from a Wavefront OBJ file. Vertices (`v`), vertex
+/// normals (`vn`), polygonal faces (`f`) and named groups (`g`) are
+/// understood. Blank lines and comments are skipped, and every other
+/// line is skipped and counted.
+#[derive(Clone, Debug)]
+pub struct ObjFile {
+    pub vertices: Vec<Tuple>,
+    pub normals: Vec<Tuple>,
+    /// The faces that come before any named group.
+    pub default_group: Group,
+    pub groups: BTreeMap<String, Group>,
+    /// The number of lines that weren't understood.
+    pub ignored_lines: usize,
+}
+
+impl ObjFile {
+    /// Parses the contents of an OBJ file. Faces with more than three
+    /// vertices are split into a fan of triangles around their first
+    /// vertex, and faces that give a normal for every vertex become
+    /// smooth triangles.
+    pub fn parse(source: &str) -> ObjFile {
+        let mut obj = ObjFile {
+            vertices: vec![],
+            normals: vec![],
+            default_group: Group::new(vec![]),
+            groups: BTreeMap::new(),
+            ignored_lines: 0,
+        };
+        let mut current_group: Option<String> = None;
+        for line in source.lines() {
+            let mut words = line.split_whitespace();
+            let understood = match words.next() {
+                Some("v") => parse_tuple(words)
+                    .map(|(x, y, z)| obj.vertices.push(Tuple::point(x, y, z)))
+                    .is_some(),
+                Some("vn") => parse_tuple(words)
+                    .map(|(x, y, z)| obj.normals.push(Tuple::vector(x, y, z)))
+                    .is_some(),
+                Some("f") => match obj.parse_face(words) {
+                    Some(triangles) => {
+                        let group = match &current_group {
+                            Some(name) => obj.groups.get_mut(name).unwrap(),
+                            None => &mut obj.default_group,
+                        };
+                        for triangle in triangles {
+                            group.add_child(triangle);
+                        }
+                        true
+                    }
+                    None => false,
+                },
+                Some("g") => match words.next() {
+                    Some(name) => {
+                        obj.groups
+                            .entry(name.to_string())
+                            .or_insert_with(|| Group::new(vec![]));
+                        current_group = Some(name.to_string());
+                        true
+                    }
+                    None => false,
+                },
+                None => true,
+                Some(word) => word.starts_with('#'),
+            };
+            if !understood {
+                obj.ignored_lines += 1;
+            }
+        }
+        obj
+    }
+
+    /// Returns one group holding the faces outside named groups and a
+    /// subgroup for each named group, ready to be placed in a world.
+    pub fn to_group(&self) -> Group {
+        let mut group = Group::new(self.default_group.children().to_vec());
+        for named in self.groups.values() {
+            group.add_child(Box::new(named.clone()));
+        }
+        group
+    }
+
+    /// Turns the vertex references of a face into triangles, or returns
+    /// `None` if any reference is malformed or out of range.
+    fn parse_face<'a, I>(&self, references: I) -> Option<Vec<Box<dyn Shape>>>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut corners = vec![];
+        for reference in references {
+            // a reference is vertex/texture/normal, where the last two
+            // may be left out
+            let mut parts = reference.split('/');
+            let vertex = resolve(&self.vertices, parts.next()?)?;
+            let _texture = parts.next();
+            let normal = match parts.next() {
+                Some(index) => Some(resolve(&self.normals, index)?),
+                None => None,
+            };
+            corners.push((vertex, normal));
+        }
+        if corners.len() < 3 {
+            return None;
+        }
+        let (p1, n1) = corners[0];
+        let triangles = corners[1..]
+            .windows(2)
+            .map(|pair| {
+                let ((p2, n2), (p3, n3)) = (pair[0], pair[1]);
+                let triangle: Box<dyn Shape> = match (n1, n2, n3) {
+                    (Some(n1), Some(n2), Some(n3)) => {
+                        Box::new(SmoothTriangle::new(p1, p2, p3, n1, n2, n3))
+                    }
+                    _ => Box::new(Triangle::new(p1, p2, p3)),
+                };
+                triangle
+            })
+            .collect();
+        Some(triangles)
+    }
+}
+
+/// Parses three numbers, ignoring any that follow, such as the optional
+/// weight of a vertex.
+fn parse_tuple<'a, I>(mut words: I) -> Option<(f64, f64, f64)>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut next = || words.next()?.parse::<f64>().ok();
+    Some((next()?, next()?, next()?))
+}
+
+/// Looks up a one-based index, where negative indices count back from
+/// the last item read so far.
+fn resolve(items: &[Tuple], index: &str) -> Option<Tuple> {
+    let index: i64 = index.parse().ok()?;
+    let position = if index < 0 {
+        items.len() as i64 + index
+    } else {
+        index - 1
+    };
+    if position < 0 {
+        return None;
+    }
+    items.get(position as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::obj_file::ObjFile;
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+
+    #[test]
+    fn test_ignoring_unrecognized_lines() {
+        let gibberish = "There was a young lady named Bright\n\
+                         who traveled much faster than light.\n\
+                         She set out one day\n\
+                         in a relative way,\n\
+                         and came back the previous night.";
+        let obj = ObjFile::parse(gibberish);
+        assert_eq!(obj.ignored_lines, 5);
+        let obj = ObjFile::parse("# a comment\n\nv 1 2 3\n");
+        assert_eq!(obj.ignored_lines, 0);
+    }
+
+    #[test]
+    fn test_vertex_records() {
+        let obj = ObjFile::parse(
+            "v -1 1 0\n\
+             v -1.0000 0.5000 0.0000\n\
+             v 1 0 0\n\
+             v 1 1 0",
+        );
+        assert_eq!(obj.vertices[0], Tuple::point(-1.0, 1.0, 0.0));
+        assert_eq!(obj.vertices[1], Tuple::point(-1.0, 0.5, 0.0));
+        assert_eq!(obj.vertices[2], Tuple::point(1.0, 0.0, 0.0));
+        assert_eq!(obj.vertices[3], Tuple::point(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_triangulating_polygons() {
+        let obj = ObjFile::parse(
+            "v -1 1 0\n\
+             v -1 0 0\n\
+             v 1 0 0\n\
+             v 1 1 0\n\
+             v 0 2 0\n\
+             \n\
+             f 1 2 3 4 5",
+        );
+        let children = obj.default_group.children();
+        assert_eq!(children.len(), 3);
+        let v = &obj.vertices;
+        // each triangle is hit at its centroid
+        let corners =
+            [(v[0], v[1], v[2]), (v[0], v[2], v[3]), (v[0], v[3], v[4])];
+        for (child, (p1, p2, p3)) in children.iter().zip(corners.iter()) {
+            let center = (*p1 + *p2 + *p3) / 3.0;
+            let r = Ray::new(
+                Tuple::point(center.x, center.y, -1.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            );
+            assert_eq!(child.intersect(r).len(), 1);
+            assert_eq!(child.local_bounds().0.x, p1.x.min(p2.x).min(p3.x));
+        }
+    }
+
+    #[test]
+    fn test_triangles_in_groups() {
+        let obj = ObjFile::parse(
+            "v -1 1 0\n\
+             v -1 0 0\n\
+             v 1 0 0\n\
+             v 1 1 0\n\
+             g FirstGroup\n\
+             f 1 2 3\n\
+             g SecondGroup\n\
+             f 1 3 4",
+        );
+        assert!(obj.default_group.children().is_empty());
+        assert_eq!(obj.groups["FirstGroup"].children().len(), 1);
+        assert_eq!(obj.groups["SecondGroup"].children().len(), 1);
+        let group = obj.to_group();
+        assert_eq!(group.children().len(), 2);
+        assert!(group.children().iter().all(|c| c.kind() == "group"));
+        assert_eq!(group.triangle_count(), 2);
+    }
+
+    #[test]
+    fn test_faces_with_normals() {
+        let obj = ObjFile::parse(
+            "v 0 1 0\n\
+             v -1 0 0\n\
+             v 1 0 0\n\
+             vn -1 0 0\n\
+             vn 1 0 0\n\
+             vn 0 1 0\n\
+             f 1//3 2//1 3//2\n\
+             f 1/0/3 2/102/1 3/14/2\n\
+             f -3 -2 -1",
+        );
+        assert_eq!(obj.normals[2], Tuple::vector(0.0, 1.0, 0.0));
+        let children = obj.default_group.children();
+        assert_eq!(children[0].kind(), "smooth triangle");
+        assert_eq!(children[1].kind(), "smooth triangle");
+        assert_eq!(children[2].kind(), "triangle");
+        let top = Tuple::point(0.0, 1.0, 0.0);
+        assert_eq!(children[0].normal_at(top), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(children[1].normal_at(top), Tuple::vector(0.0, 1.0, 0.0));
+        // references past the vertices read so far are ignored
+        let obj = ObjFile::parse("v 0 1 0\nf 1 2 3");
+        assert_eq!(obj.ignored_lines, 1);
+        assert!(obj.default_group.children().is_empty());
+    }
+}
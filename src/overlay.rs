@@ -0,0 +1,142 @@
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::tuple::Tuple;
+use crate::world::World;
+
+/// How far in front of the camera lines are cut off, so that boxes
+/// around or behind the camera don't wrap around the image.
+const NEAR_PLANE: f64 = 0.001;
+
+/// Draws the edges of every object's bounding box onto an image
+/// rendered with the same camera, to check that the boxes really
+/// enclose their objects.
+pub fn draw_bounding_boxes(
+    canvas: &mut Canvas,
+    world: &World,
+    camera: &Camera,
+    color: Color,
+) {
+    for object in world.objects.iter() {
+        let (min, max) = object.bounds();
+        draw_box(canvas, camera, min, max, color);
+    }
+}
+
+/// Draws the edges of an axis-aligned box given in world space.
+pub fn draw_box(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    min: Tuple,
+    max: Tuple,
+    color: Color,
+) {
+    let corner = |i: usize| {
+        Tuple::point(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    };
+    // every edge joins two corners that differ in exactly one bit
+    for i in 0..8 {
+        for &axis in [1, 2, 4].iter() {
+            if i & axis == 0 {
+                draw_edge(canvas, camera, corner(i), corner(i | axis), color);
+            }
+        }
+    }
+}
+
+fn draw_edge(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    from: Tuple,
+    to: Tuple,
+    color: Color,
+) {
+    let mut from = camera.transform * from;
+    let mut to = camera.transform * to;
+    // keep only the part of the edge in front of the camera
+    if from.z > -NEAR_PLANE && to.z > -NEAR_PLANE {
+        return;
+    }
+    if from.z > -NEAR_PLANE {
+        from = from + (to - from) * ((-NEAR_PLANE - from.z) / (to.z - from.z));
+    } else if to.z > -NEAR_PLANE {
+        to = to + (from - to) * ((-NEAR_PLANE - to.z) / (from.z - to.z));
+    }
+    canvas.draw_line(
+        camera.camera_point_to_pixel(from),
+        camera.camera_point_to_pixel(to),
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::matrix::Matrix4;
+    use crate::overlay::{draw_bounding_boxes, draw_box};
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(21, 21, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        c
+    }
+
+    #[test]
+    fn test_drawing_the_bounding_boxes_of_a_world() {
+        let w = World::default();
+        let c = camera();
+        let mut image = Canvas::new(21, 21);
+        draw_bounding_boxes(&mut image, &w, &c, Color::white());
+        // the front face of the outer sphere's box is 4 units away, so
+        // its edges are a quarter of the half-width from the center
+        assert_eq!(image.pixel_at(10, 7), Color::white());
+        assert_eq!(image.pixel_at(7, 10), Color::white());
+        assert_eq!(image.pixel_at(10, 10), Color::black());
+        assert_eq!(image.pixel_at(0, 0), Color::black());
+    }
+
+    #[test]
+    fn test_boxes_behind_the_camera_are_not_drawn() {
+        let c = camera();
+        let mut image = Canvas::new(21, 21);
+        draw_box(
+            &mut image,
+            &c,
+            Tuple::point(-1.0, -1.0, -9.0),
+            Tuple::point(1.0, 1.0, -7.0),
+            Color::white(),
+        );
+        for y in 0..21 {
+            for x in 0..21 {
+                assert_eq!(image.pixel_at(x, y), Color::black());
+            }
+        }
+    }
+
+    #[test]
+    fn test_boxes_around_the_camera_are_clipped() {
+        let c = camera();
+        let mut image = Canvas::new(21, 21);
+        draw_box(
+            &mut image,
+            &c,
+            Tuple::point(-1.0, -1.0, -6.0),
+            Tuple::point(1.0, 1.0, 0.0),
+            Color::white(),
+        );
+        assert_eq!(image.pixel_at(10, 8), Color::white());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::tuple::Tuple;
+use std::f64::consts::PI;
+
+/// How a panoramic image wraps around the viewer. In both, the center
+/// column looks towards positive z and the columns run once around the
+/// vertical axis.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Panorama {
+    /// Rows are spaced evenly by angle from the zenith (top) to the nadir
+    /// (bottom), covering every direction.
+    Equirectangular,
+    /// The image is wrapped around a vertical cylinder, covering the
+    /// given vertical field of view around the horizon.
+    Cylindrical { vertical_fov: f64 },
+}
+
+impl Panorama {
+    /// Returns the color the panorama shows in the given direction, or
+    /// black where a cylindrical panorama doesn't reach.
+    pub fn color_at(&self, canvas: &Canvas, direction: Tuple) -> Color {
+        let direction = direction.normalize();
+        let u = 0.5 + direction.x.atan2(direction.z) / (2.0 * PI);
+        let v = match *self {
+            Panorama::Equirectangular => {
+                direction.y.clamp(-1.0, 1.0).acos() / PI
+            }
+            Panorama::Cylindrical { vertical_fov } => {
+                let horizontal = (direction.x * direction.x
+                    + direction.z * direction.z)
+                    .sqrt();
+                let height = direction.y / horizontal;
+                0.5 - height / (2.0 * (vertical_fov / 2.0).tan())
+            }
+        };
+        if !(0.0..=1.0).contains(&v) {
+            return Color::black();
+        }
+        let x = ((u * canvas.width as f64) as usize).min(canvas.width - 1);
+        let y = ((v * canvas.height as f64) as usize).min(canvas.height - 1);
+        canvas.linear_pixel_at(x, y)
+    }
+}
+
+/// Extracts an ordinary perspective view from a panoramic image, as if
+/// taken by a camera at the center of the panorama turned by `yaw`
+/// around the vertical axis (0 looks towards positive z) and tilted by
+/// `pitch` above the horizon. One panoramic render can so yield many
+/// views.
+pub fn extract_view(
+    canvas: &Canvas,
+    panorama: Panorama,
+    hsize: usize,
+    vsize: usize,
+    field_of_view: f64,
+    yaw: f64,
+    pitch: f64,
+) -> Canvas {
+    let forward = Tuple::vector(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        yaw.cos() * pitch.cos(),
+    );
+    let right = Tuple::vector(yaw.cos(), 0.0, -yaw.sin());
+    let up = forward.cross(right);
+    let half_view = (field_of_view / 2.0).tan();
+    let aspect = (hsize as f64) / (vsize as f64);
+    let (half_width, half_height) = if aspect >= 1.0 {
+        (half_view, half_view / aspect)
+    } else {
+        (half_view * aspect, half_view)
+    };
+    let pixel_size = (half_width * 2.0) / (hsize as f64);
+    let mut view = Canvas::new(hsize, vsize);
+    for y in 0..vsize {
+        for x in 0..hsize {
+            let offset_x = ((x as f64) + 0.5) * pixel_size - half_width;
+            let offset_y = half_height - ((y as f64) + 0.5) * pixel_size;
+            let direction = forward + right * offset_x + up * offset_y;
+            view.write_pixel(x, y, panorama.color_at(canvas, direction));
+        }
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::panorama::{extract_view, Panorama};
+    use crate::tuple::Tuple;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    /// An 8x4 panorama whose columns are all different shades of red and
+    /// whose top row is white.
+    fn columns() -> Canvas {
+        let mut canvas = Canvas::new(8, 4);
+        for x in 0..8 {
+            for y in 0..4 {
+                canvas.write_pixel(x, y, Color::new(x as f64 / 8.0, 0.0, 0.0));
+            }
+            canvas.write_pixel(x, 0, Color::white());
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_looking_up_an_equirectangular_panorama() {
+        let p = Panorama::Equirectangular;
+        let canvas = columns();
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(0.0, 0.0, 1.0)),
+            Color::new(0.5, 0.0, 0.0)
+        );
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(1.0, 0.0, 0.0)),
+            Color::new(0.75, 0.0, 0.0)
+        );
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(0.0, 1.0, 0.01)),
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn test_a_cylindrical_panorama_only_covers_its_field_of_view() {
+        let p = Panorama::Cylindrical {
+            vertical_fov: FRAC_PI_2,
+        };
+        let canvas = columns();
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(0.0, 0.0, 1.0)),
+            Color::new(0.5, 0.0, 0.0)
+        );
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(0.0, 0.9, 1.0)),
+            Color::white()
+        );
+        assert_eq!(
+            p.color_at(&canvas, Tuple::vector(0.0, 1.1, 1.0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_extracting_views_from_a_panorama() {
+        let canvas = columns();
+        let p = Panorama::Equirectangular;
+        let front = extract_view(&canvas, p, 3, 3, FRAC_PI_4, 0.0, 0.0);
+        assert_eq!(front.pixel_at(1, 1), Color::new(0.5, 0.0, 0.0));
+        let right = extract_view(&canvas, p, 3, 3, FRAC_PI_4, FRAC_PI_2, 0.0);
+        assert_eq!(right.pixel_at(1, 1), Color::new(0.75, 0.0, 0.0));
+        // the view's right edge turns further in the same direction
+        assert_eq!(right.pixel_at(2, 1), Color::new(0.75, 0.0, 0.0));
+        assert_eq!(front.pixel_at(2, 1), Color::new(0.5, 0.0, 0.0));
+        let up = extract_view(&canvas, p, 3, 3, FRAC_PI_4, 0.0, 1.5);
+        assert_eq!(up.pixel_at(1, 1), Color::white());
+    }
+}
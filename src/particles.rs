@@ -0,0 +1,250 @@
+use crate::group::Group;
+use crate::matrix::Matrix4;
+use crate::rng::Rng;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+
+/// Maximum number of rejected candidates per particle before Poisson disk
+/// sampling gives up on filling the region.
+const POISSON_DISK_ATTEMPTS: usize = 30;
+
+/// The number of particles a subgroup may hold before `instantiate`
+/// splits it further.
+const DIVIDE_THRESHOLD: usize = 8;
+
+/// How particles are spread across the region of a particle field.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Distribution {
+    /// Particles sit at the centers of the cells of a regular grid.
+    Grid,
+    /// Particles are placed uniformly at random inside the region.
+    Volume,
+    /// Particles are placed at random, but never closer than
+    /// `min_distance` to each other.
+    PoissonDisk { min_distance: f64 },
+}
+
+/// Scatters many copies of a shape inside an axis-aligned box, for
+/// scenes with snow, stars, confetti and the like. The same seed always
+/// produces the same particles.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParticleField {
+    pub min: Tuple,
+    pub max: Tuple,
+    pub count: usize,
+    pub distribution: Distribution,
+    pub seed: u64,
+}
+
+impl ParticleField {
+    pub fn new(
+        min: Tuple,
+        max: Tuple,
+        count: usize,
+        distribution: Distribution,
+    ) -> ParticleField {
+        assert!(min.is_point());
+        assert!(max.is_point());
+        ParticleField {
+            min,
+            max,
+            count,
+            distribution,
+            seed: 0,
+        }
+    }
+
+    /// Returns the positions of the particles. Poisson disk sampling may
+    /// return fewer than `count` positions when the region is too small
+    /// to hold them all.
+    pub fn positions(&self) -> Vec<Tuple> {
+        match self.distribution {
+            Distribution::Grid => self.grid_positions(),
+            Distribution::Volume => {
+                let mut random = Rng::new(self.seed);
+                (0..self.count)
+                    .map(|_| self.random_point(&mut random))
+                    .collect()
+            }
+            Distribution::PoissonDisk { min_distance } => {
+                self.poisson_disk_positions(min_distance)
+            }
+        }
+    }
+
+    /// Returns a group with one copy of `template` per particle, each
+    /// moved to the particle's position after applying the template's own
+    /// transform. The group is divided into subgroups, so that rays skip
+    /// the particles they can't hit.
+    pub fn instantiate(&self, template: &dyn Shape) -> Group {
+        let particles = self
+            .positions()
+            .into_iter()
+            .map(|position| {
+                let mut particle = template.box_clone();
+                particle.set_transform(
+                    Matrix4::translation(position.x, position.y, position.z)
+                        * template.transform(),
+                );
+                particle
+            })
+            .collect();
+        let mut group = Group::new(particles);
+        group.divide(DIVIDE_THRESHOLD);
+        group
+    }
+
+    fn grid_positions(&self) -> Vec<Tuple> {
+        let cells = (self.count as f64).cbrt().ceil().max(1.0) as usize;
+        let size = (self.max - self.min) / (cells as f64);
+        let mut positions = Vec::with_capacity(self.count);
+        'fill: for z in 0..cells {
+            for y in 0..cells {
+                for x in 0..cells {
+                    if positions.len() == self.count {
+                        break 'fill;
+                    }
+                    positions.push(Tuple::point(
+                        self.min.x + size.x * ((x as f64) + 0.5),
+                        self.min.y + size.y * ((y as f64) + 0.5),
+                        self.min.z + size.z * ((z as f64) + 0.5),
+                    ));
+                }
+            }
+        }
+        positions
+    }
+
+    fn poisson_disk_positions(&self, min_distance: f64) -> Vec<Tuple> {
+        let mut random = Rng::new(self.seed);
+        let mut positions: Vec<Tuple> = Vec::with_capacity(self.count);
+        let mut attempts = 0;
+        while positions.len() < self.count
+            && attempts < self.count * POISSON_DISK_ATTEMPTS
+        {
+            let candidate = self.random_point(&mut random);
+            if positions
+                .iter()
+                .all(|&p| (p - candidate).magnitude() >= min_distance)
+            {
+                positions.push(candidate);
+            } else {
+                attempts += 1;
+            }
+        }
+        positions
+    }
+
+    fn random_point(&self, random: &mut Rng) -> Tuple {
+        Tuple::point(
+            self.min.x + (self.max.x - self.min.x) * random.next_f64(),
+            self.min.y + (self.max.y - self.min.y) * random.next_f64(),
+            self.min.z + (self.max.z - self.min.z) * random.next_f64(),
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::cube::Cube;
+    use crate::equal_f64;
+    use crate::matrix::Matrix4;
+    use crate::particles::{Distribution, ParticleField};
+    use crate::ray::Ray;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+
+    fn unit_field(count: usize, distribution: Distribution) -> ParticleField {
+        ParticleField::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(1.0, 1.0, 1.0),
+            count,
+            distribution,
+        )
+    }
+
+    #[test]
+    fn test_a_grid_places_particles_at_cell_centers() {
+        let field = unit_field(8, Distribution::Grid);
+        let positions = field.positions();
+        assert_eq!(positions.len(), 8);
+        assert_eq!(positions[0], Tuple::point(0.25, 0.25, 0.25));
+        assert_eq!(positions[7], Tuple::point(0.75, 0.75, 0.75));
+    }
+
+    #[test]
+    fn test_volume_particles_stay_inside_the_region() {
+        let field = unit_field(100, Distribution::Volume);
+        let positions = field.positions();
+        assert_eq!(positions.len(), 100);
+        for p in positions {
+            assert!(p.x >= 0.0 && p.x < 1.0);
+            assert!(p.y >= 0.0 && p.y < 1.0);
+            assert!(p.z >= 0.0 && p.z < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_the_same_seed_produces_the_same_particles() {
+        let mut field = unit_field(10, Distribution::Volume);
+        let first = field.positions();
+        assert_eq!(first, field.positions());
+        field.seed = 42;
+        assert_ne!(first, field.positions());
+    }
+
+    #[test]
+    fn test_poisson_disk_particles_keep_their_distance() {
+        let field =
+            unit_field(20, Distribution::PoissonDisk { min_distance: 0.2 });
+        let positions = field.positions();
+        for (i, a) in positions.iter().enumerate() {
+            for b in positions.iter().skip(i + 1) {
+                assert!((*a - *b).magnitude() >= 0.2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_instantiating_a_particle_field() {
+        let field = unit_field(8, Distribution::Grid);
+        let mut template = Sphere::default();
+        template.transform = Matrix4::scaling(0.1, 0.1, 0.1);
+        let particles = field.instantiate(&template);
+        assert_eq!(
+            particles.bounds(),
+            (
+                Tuple::point(0.15, 0.15, 0.15),
+                Tuple::point(0.85, 0.85, 0.85)
+            )
+        );
+        let r = Ray::new(
+            Tuple::point(0.25, 0.25, -1.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = particles.intersect(r);
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].object.kind(), "sphere");
+    }
+
+    #[test]
+    fn test_instantiating_cubes() {
+        let field = unit_field(64, Distribution::Grid);
+        let mut template = Cube::default();
+        template.transform = Matrix4::scaling(0.05, 0.05, 0.05);
+        let particles = field.instantiate(&template);
+        // the cubes are split into subgroups
+        assert!(particles.children().len() < 64);
+        let r = Ray::new(
+            Tuple::point(-1.0, 0.125, 0.125),
+            Tuple::vector(1.0, 0.0, 0.0),
+        );
+        let mut xs = particles.intersect(r);
+        xs.sort();
+        assert_eq!(xs.len(), 8);
+        assert!(xs.iter().all(|i| i.object.kind() == "cube"));
+        assert!(equal_f64(xs[0].t, 1.075));
+    }
+}
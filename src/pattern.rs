@@ -0,0 +1,762 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::matrix::Matrix4;
+use crate::metadata::SceneHash;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::uv::{sample_bilinear, sample_spherical, UvMapping};
+use std::fmt::Debug;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+/// Something that varies the color of a surface from point to point.
+/// Every pattern has its own transformation, applied on top of the
+/// transformation of the object it is on.
+pub trait Pattern: Debug + Send + Sync {
+    fn transform(&self) -> Matrix4;
+
+    /// Returns the color at a point in pattern space.
+    fn pattern_at(&self, point: Tuple) -> Color;
+
+    /// Returns the color at a point in the space of a pattern that this
+    /// one is nested in, applying this pattern's own transformation.
+    fn nested_pattern_at(&self, point: Tuple) -> Color {
+        self.pattern_at(self.transform().inverse() * point)
+    }
+
+    /// Returns the color at a point in world space on the given object.
+    fn pattern_at_shape(
+        &self,
+        object: &dyn Shape,
+        world_point: Tuple,
+    ) -> Color {
+        let object_point = object.transform().inverse() * world_point;
+        self.pattern_at_object(object, object_point, self.transform().inverse())
+    }
+
+    /// Returns the color at a point in the object space of the given
+    /// object, given the inverse of the pattern's transformation, so that
+    /// shading many points with the same pattern inverts it only once.
+    fn pattern_at_object(
+        &self,
+        _object: &dyn Shape,
+        object_point: Tuple,
+        inverse: Matrix4,
+    ) -> Color {
+        self.pattern_at(inverse * object_point)
+    }
+
+    /// Feeds the pattern's kind and settings into a hasher, for
+    /// `scene_hash`.
+    fn hash_settings(&self, state: &mut dyn Hasher);
+}
+
+/// Patterns are compared by identity, since there is no general way to
+/// tell whether two patterns produce the same colors.
+impl PartialEq for dyn Pattern {
+    fn eq(&self, other: &dyn Pattern) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+/// The same color everywhere, for nesting plain colors in other
+/// patterns.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SolidPattern {
+    pub color: Color,
+}
+
+impl Pattern for SolidPattern {
+    fn transform(&self) -> Matrix4 {
+        Matrix4::identity()
+    }
+
+    fn pattern_at(&self, _point: Tuple) -> Color {
+        self.color
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "solid".hash_into(state);
+        self.color.hash_into(state);
+    }
+}
+
+fn solid(color: Color) -> Arc<dyn Pattern> {
+    Arc::new(SolidPattern { color })
+}
+
+/// Alternates between two colors, or two other patterns, in stripes
+/// along the x axis, one unit wide.
+#[derive(Clone, Debug)]
+pub struct StripePattern {
+    pub a: Arc<dyn Pattern>,
+    pub b: Arc<dyn Pattern>,
+    pub transform: Matrix4,
+}
+
+impl StripePattern {
+    pub fn new(a: Color, b: Color) -> StripePattern {
+        StripePattern::nested(solid(a), solid(b))
+    }
+
+    /// Uses patterns in place of the two colors.
+    pub fn nested(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>) -> StripePattern {
+        StripePattern {
+            a,
+            b,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for StripePattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        if point.x.floor().rem_euclid(2.0) == 0.0 {
+            self.a.nested_pattern_at(point)
+        } else {
+            self.b.nested_pattern_at(point)
+        }
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "stripes".hash_into(state);
+        self.a.hash_into(state);
+        self.b.hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Blends from one color or pattern to the other along the x axis,
+/// starting over every unit.
+#[derive(Clone, Debug)]
+pub struct GradientPattern {
+    pub a: Arc<dyn Pattern>,
+    pub b: Arc<dyn Pattern>,
+    pub transform: Matrix4,
+}
+
+impl GradientPattern {
+    pub fn new(a: Color, b: Color) -> GradientPattern {
+        GradientPattern::nested(solid(a), solid(b))
+    }
+
+    /// Uses patterns in place of the two colors.
+    pub fn nested(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>) -> GradientPattern {
+        GradientPattern {
+            a,
+            b,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for GradientPattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let fraction = point.x - point.x.floor();
+        let a = self.a.nested_pattern_at(point);
+        a + (self.b.nested_pattern_at(point) - a) * fraction
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "gradient".hash_into(state);
+        self.a.hash_into(state);
+        self.b.hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Alternates between two colors or patterns in concentric rings around
+/// the y axis, one unit wide.
+#[derive(Clone, Debug)]
+pub struct RingPattern {
+    pub a: Arc<dyn Pattern>,
+    pub b: Arc<dyn Pattern>,
+    pub transform: Matrix4,
+}
+
+impl RingPattern {
+    pub fn new(a: Color, b: Color) -> RingPattern {
+        RingPattern::nested(solid(a), solid(b))
+    }
+
+    /// Uses patterns in place of the two colors.
+    pub fn nested(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>) -> RingPattern {
+        RingPattern {
+            a,
+            b,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for RingPattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let distance = (point.x * point.x + point.z * point.z).sqrt();
+        if distance.floor().rem_euclid(2.0) == 0.0 {
+            self.a.nested_pattern_at(point)
+        } else {
+            self.b.nested_pattern_at(point)
+        }
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "rings".hash_into(state);
+        self.a.hash_into(state);
+        self.b.hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Alternates between two colors or patterns in unit cubes, like a
+/// three-dimensional checkerboard.
+#[derive(Clone, Debug)]
+pub struct CheckersPattern {
+    pub a: Arc<dyn Pattern>,
+    pub b: Arc<dyn Pattern>,
+    pub transform: Matrix4,
+}
+
+impl CheckersPattern {
+    pub fn new(a: Color, b: Color) -> CheckersPattern {
+        CheckersPattern::nested(solid(a), solid(b))
+    }
+
+    /// Uses patterns in place of the two colors.
+    pub fn nested(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>) -> CheckersPattern {
+        CheckersPattern {
+            a,
+            b,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for CheckersPattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let sum = point.x.floor() + point.y.floor() + point.z.floor();
+        if sum.rem_euclid(2.0) == 0.0 {
+            self.a.nested_pattern_at(point)
+        } else {
+            self.b.nested_pattern_at(point)
+        }
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "checkers".hash_into(state);
+        self.a.hash_into(state);
+        self.b.hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Averages two patterns, such as stripes crossing at right angles.
+#[derive(Clone, Debug)]
+pub struct BlendPattern {
+    pub a: Arc<dyn Pattern>,
+    pub b: Arc<dyn Pattern>,
+    pub transform: Matrix4,
+}
+
+impl BlendPattern {
+    pub fn new(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>) -> BlendPattern {
+        BlendPattern {
+            a,
+            b,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for BlendPattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        (self.a.nested_pattern_at(point) + self.b.nested_pattern_at(point))
+            * 0.5
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "blend".hash_into(state);
+        self.a.hash_into(state);
+        self.b.hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// What an image pattern shows outside the bounds of its image.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WrapMode {
+    /// The image is tiled endlessly.
+    Repeat,
+    /// The pixels on the edges of the image are stretched outwards.
+    Clamp,
+}
+
+/// An image laid flat on the xz-plane of pattern space like a poster,
+/// centered on the origin and sized in world units. The top of the
+/// image faces negative z, so it appears upright on a wall that was
+/// rotated a quarter turn around the x-axis to face the camera.
+#[derive(Clone, Debug)]
+pub struct PlanarImagePattern {
+    pub canvas: Canvas,
+    pub width: f64,
+    pub height: f64,
+    pub wrap: WrapMode,
+    pub transform: Matrix4,
+}
+
+impl PlanarImagePattern {
+    pub fn new(canvas: Canvas, width: f64, height: f64) -> PlanarImagePattern {
+        assert!(width > 0.0 && height > 0.0);
+        PlanarImagePattern {
+            canvas,
+            width,
+            height,
+            wrap: WrapMode::Clamp,
+            transform: Matrix4::identity(),
+        }
+    }
+
+    fn wrap(&self, coordinate: f64) -> f64 {
+        match self.wrap {
+            WrapMode::Repeat => coordinate - coordinate.floor(),
+            WrapMode::Clamp => coordinate.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Pattern for PlanarImagePattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let u = self.wrap(point.x / self.width + 0.5);
+        let v = self.wrap(point.z / self.height + 0.5);
+        let x = ((u * self.canvas.width as f64) as usize)
+            .min(self.canvas.width - 1);
+        let y = ((v * self.canvas.height as f64) as usize)
+            .min(self.canvas.height - 1);
+        self.canvas.linear_pixel_at(x, y)
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "planar image".hash_into(state);
+        self.canvas.hash_into(state);
+        [self.width, self.height].hash_into(state);
+        (self.wrap == WrapMode::Repeat).hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Textures a surface without texture coordinates by projecting an
+/// image onto it along each of the three axes and blending the three by
+/// how squarely the surface faces each axis. The image repeats every
+/// `scale` units of pattern space.
+#[derive(Clone, Debug)]
+pub struct TriplanarPattern {
+    pub canvas: Canvas,
+    pub scale: f64,
+    /// How quickly the projections give way to one another as the normal
+    /// turns. Higher values give narrower, sharper transitions.
+    pub sharpness: f64,
+    pub transform: Matrix4,
+}
+
+impl TriplanarPattern {
+    pub fn new(canvas: Canvas, scale: f64) -> TriplanarPattern {
+        assert!(scale > 0.0);
+        TriplanarPattern {
+            canvas,
+            scale,
+            sharpness: 4.0,
+            transform: Matrix4::identity(),
+        }
+    }
+
+    /// Returns the color at a point in pattern space on a surface with
+    /// the given normal, also in pattern space.
+    pub fn pattern_at_normal(&self, point: Tuple, normal: Tuple) -> Color {
+        let weight = |n: f64| n.abs().powf(self.sharpness);
+        let (wx, wy, wz) =
+            (weight(normal.x), weight(normal.y), weight(normal.z));
+        let total = wx + wy + wz;
+        if total <= 0.0 {
+            return self.project(point.x, point.z);
+        }
+        (self.project(point.z, point.y) * wx
+            + self.project(point.x, point.z) * wy
+            + self.project(point.x, point.y) * wz)
+            * (1.0 / total)
+    }
+
+    fn project(&self, s: f64, t: f64) -> Color {
+        sample_bilinear(
+            &self.canvas,
+            s / self.scale,
+            t / self.scale,
+            WrapMode::Repeat,
+            WrapMode::Repeat,
+        )
+    }
+}
+
+impl Pattern for TriplanarPattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    /// Without a normal, only the projection from above is used.
+    fn pattern_at(&self, point: Tuple) -> Color {
+        self.project(point.x, point.z)
+    }
+
+    fn pattern_at_object(
+        &self,
+        object: &dyn Shape,
+        object_point: Tuple,
+        inverse: Matrix4,
+    ) -> Color {
+        let mut object_normal = object.local_normal_at(object_point);
+        object_normal.w = 0.0;
+        let pattern_point = inverse * object_point;
+        let mut pattern_normal = inverse.transpose() * object_normal;
+        pattern_normal.w = 0.0;
+        self.pattern_at_normal(pattern_point, pattern_normal.normalize())
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "triplanar".hash_into(state);
+        self.canvas.hash_into(state);
+        [self.scale, self.sharpness].hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+/// Wraps an image around a surface by turning points into texture
+/// coordinates with a mapping, such as an equirectangular map of the
+/// earth around a sphere. Colors are blended from the nearest pixels.
+#[derive(Clone, Debug)]
+pub struct ImagePattern {
+    pub canvas: Canvas,
+    pub mapping: UvMapping,
+    pub transform: Matrix4,
+}
+
+impl ImagePattern {
+    pub fn new(canvas: Canvas, mapping: UvMapping) -> ImagePattern {
+        ImagePattern {
+            canvas,
+            mapping,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for ImagePattern {
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Color {
+        let (u, v) = self.mapping.uv_at(point);
+        match self.mapping {
+            UvMapping::Spherical => sample_spherical(&self.canvas, u, v),
+            UvMapping::Planar | UvMapping::Cylindrical => sample_bilinear(
+                &self.canvas,
+                u,
+                v,
+                WrapMode::Repeat,
+                WrapMode::Repeat,
+            ),
+            // every face shows the whole image, so nothing wraps
+            UvMapping::Cube => sample_bilinear(
+                &self.canvas,
+                u,
+                v,
+                WrapMode::Clamp,
+                WrapMode::Clamp,
+            ),
+        }
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        "image".hash_into(state);
+        self.canvas.hash_into(state);
+        (self.mapping as usize).hash_into(state);
+        self.transform.hash_into(state);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::cube::Cube;
+    use crate::matrix::Matrix4;
+    use crate::pattern::{
+        BlendPattern, CheckersPattern, GradientPattern, ImagePattern, Pattern,
+        PlanarImagePattern, RingPattern, SolidPattern, StripePattern,
+        TriplanarPattern, WrapMode,
+    };
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use crate::uv::UvMapping;
+    use std::f64::consts::FRAC_PI_2;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_a_stripe_pattern_alternates_in_x() {
+        let p = StripePattern::new(Color::white(), Color::black());
+        assert_eq!(p.a.pattern_at(Tuple::point(0.0, 0.0, 0.0)), Color::white());
+        assert_eq!(p.b.pattern_at(Tuple::point(0.0, 0.0, 0.0)), Color::black());
+        let examples = [
+            ((0.0, 1.0, 2.0), Color::white()),
+            ((0.9, 0.0, 0.0), Color::white()),
+            ((1.0, 0.0, 0.0), Color::black()),
+            ((-0.1, 0.0, 0.0), Color::black()),
+            ((-1.0, 0.0, 0.0), Color::black()),
+            ((-1.1, 0.0, 0.0), Color::white()),
+        ];
+        for &((x, y, z), color) in examples.iter() {
+            assert_eq!(p.pattern_at(Tuple::point(x, y, z)), color);
+        }
+    }
+
+    #[test]
+    fn test_stripes_with_an_object_and_a_pattern_transformation() {
+        let mut object = Sphere::default();
+        object.transform = Matrix4::scaling(2.0, 2.0, 2.0);
+        let mut p = StripePattern::new(Color::white(), Color::black());
+        p.transform = Matrix4::translation(0.5, 0.0, 0.0);
+        assert_eq!(
+            p.pattern_at_shape(&object, Tuple::point(2.5, 0.0, 0.0)),
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn test_a_gradient_linearly_interpolates_between_colors() {
+        let p = GradientPattern::new(Color::white(), Color::black());
+        let examples = [
+            (0.0, Color::white()),
+            (0.25, Color::new(0.75, 0.75, 0.75)),
+            (0.5, Color::new(0.5, 0.5, 0.5)),
+            (0.75, Color::new(0.25, 0.25, 0.25)),
+        ];
+        for &(x, color) in examples.iter() {
+            assert_eq!(p.pattern_at(Tuple::point(x, 0.0, 0.0)), color);
+        }
+    }
+
+    #[test]
+    fn test_a_ring_should_extend_in_both_x_and_z() {
+        let p = RingPattern::new(Color::white(), Color::black());
+        assert_eq!(p.pattern_at(Tuple::point(0.0, 0.0, 0.0)), Color::white());
+        assert_eq!(p.pattern_at(Tuple::point(1.0, 0.0, 0.0)), Color::black());
+        assert_eq!(p.pattern_at(Tuple::point(0.0, 0.0, 1.0)), Color::black());
+        // 0.708 = just slightly more than sqrt(2)/2
+        assert_eq!(
+            p.pattern_at(Tuple::point(0.708, 0.0, 0.708)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_checkers_should_repeat_in_every_dimension() {
+        let p = CheckersPattern::new(Color::white(), Color::black());
+        let examples = [
+            ((0.0, 0.0, 0.0), Color::white()),
+            ((0.99, 0.0, 0.0), Color::white()),
+            ((1.01, 0.0, 0.0), Color::black()),
+            ((0.0, 0.99, 0.0), Color::white()),
+            ((0.0, 1.01, 0.0), Color::black()),
+            ((0.0, 0.0, 0.99), Color::white()),
+            ((0.0, 0.0, 1.01), Color::black()),
+            ((-0.5, 0.0, -0.5), Color::white()),
+        ];
+        for &((x, y, z), color) in examples.iter() {
+            assert_eq!(p.pattern_at(Tuple::point(x, y, z)), color);
+        }
+    }
+
+    /// A 2x2 image with a different color in each corner.
+    fn corners() -> Canvas {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(1, 0, Color::new(0.0, 1.0, 0.0));
+        canvas.write_pixel(0, 1, Color::new(0.0, 0.0, 1.0));
+        canvas.write_pixel(1, 1, Color::white());
+        canvas
+    }
+
+    #[test]
+    fn test_a_planar_image_is_centered_and_sized_in_world_units() {
+        let p = PlanarImagePattern::new(corners(), 4.0, 2.0);
+        assert_eq!(
+            p.pattern_at(Tuple::point(-1.5, 0.0, -0.5)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            p.pattern_at(Tuple::point(1.5, 0.0, -0.5)),
+            Color::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            p.pattern_at(Tuple::point(-1.5, 3.0, 0.5)),
+            Color::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(p.pattern_at(Tuple::point(1.5, 0.0, 0.5)), Color::white());
+    }
+
+    #[test]
+    fn test_a_clamped_planar_image_stretches_its_edges() {
+        let p = PlanarImagePattern::new(corners(), 4.0, 2.0);
+        assert_eq!(
+            p.pattern_at(Tuple::point(-10.0, 0.0, -10.0)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(p.pattern_at(Tuple::point(10.0, 0.0, 10.0)), Color::white());
+    }
+
+    #[test]
+    fn test_a_repeating_planar_image_is_tiled() {
+        let mut p = PlanarImagePattern::new(corners(), 4.0, 2.0);
+        p.wrap = WrapMode::Repeat;
+        assert_eq!(
+            p.pattern_at(Tuple::point(2.5, 0.0, -0.5)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(p.pattern_at(Tuple::point(-2.5, 0.0, 2.5)), Color::white());
+    }
+
+    #[test]
+    fn test_checkers_of_stripes() {
+        let red = Color::new(1.0, 0.0, 0.0);
+        let mut stripes = StripePattern::new(red, Color::black());
+        stripes.transform = Matrix4::scaling(0.5, 1.0, 1.0);
+        let white = Arc::new(SolidPattern {
+            color: Color::white(),
+        });
+        let p = CheckersPattern::nested(white, Arc::new(stripes));
+        assert_eq!(p.pattern_at(Tuple::point(0.5, 0.0, 0.0)), Color::white());
+        // the stripes are half as wide inside the black squares
+        assert_eq!(p.pattern_at(Tuple::point(1.25, 0.0, 0.0)), red);
+        assert_eq!(p.pattern_at(Tuple::point(1.75, 0.0, 0.0)), Color::black());
+    }
+
+    #[test]
+    fn test_blending_two_patterns_averages_them() {
+        let horizontal = StripePattern::new(Color::white(), Color::black());
+        let mut vertical = horizontal.clone();
+        vertical.transform = Matrix4::rotation_y(FRAC_PI_2);
+        let p = BlendPattern::new(Arc::new(horizontal), Arc::new(vertical));
+        assert_eq!(p.pattern_at(Tuple::point(0.5, 0.0, -0.5)), Color::white());
+        assert_eq!(
+            p.pattern_at(Tuple::point(1.5, 0.0, -0.5)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(p.pattern_at(Tuple::point(1.5, 0.0, -1.5)), Color::black());
+    }
+
+    #[test]
+    fn test_triplanar_projections_follow_the_normal() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Color::white());
+        let p = TriplanarPattern::new(canvas, 2.0);
+        // half a unit in along one image axis lands on the white pixel
+        let point = Tuple::point(0.5, 0.5, 1.5);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        let side = Tuple::vector(1.0, 0.0, 0.0);
+        assert_eq!(p.pattern_at_normal(point, up), Color::white());
+        assert_eq!(p.pattern_at_normal(point, side), Color::black());
+        let diagonal = Tuple::vector(1.0, 1.0, 0.0).normalize();
+        assert_eq!(
+            p.pattern_at_normal(point, diagonal),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(p.pattern_at(point), Color::white());
+    }
+
+    #[test]
+    fn test_triplanar_mapping_a_transformed_cube() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Color::white());
+        let p = TriplanarPattern::new(canvas, 2.0);
+        let mut cube = Cube::default();
+        cube.transform = Matrix4::translation(0.0, 0.0, 1.0);
+        // the top of the cube is projected from above, the side from x
+        assert_eq!(
+            p.pattern_at_shape(&cube, Tuple::point(0.5, 1.0, 1.5)),
+            Color::white()
+        );
+        assert_eq!(
+            p.pattern_at_shape(&cube, Tuple::point(1.0, 0.5, 0.5)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_an_image_mapped_around_a_sphere() {
+        // a white northern hemisphere with a red stripe at u = 0.5
+        let mut canvas = Canvas::new(4, 2);
+        for x in 0..4 {
+            canvas.write_pixel(x, 0, Color::white());
+        }
+        canvas.write_pixel(2, 1, Color::new(1.0, 0.0, 0.0));
+        let p = ImagePattern::new(canvas, UvMapping::Spherical);
+        let object = Sphere::default();
+        assert_eq!(
+            p.pattern_at_shape(&object, Tuple::point(0.0, 1.0, 0.0)),
+            Color::white()
+        );
+        let south = Tuple::point(0.0, -0.9, 0.0);
+        assert_eq!(
+            p.pattern_at_shape(&object, south),
+            Color::new(0.25, 0.0, 0.0)
+        );
+        // the middle of the red pixel, at u = 0.625 and v = 0.25
+        let angle = 0.125 * 2.0 * std::f64::consts::PI;
+        let point = Tuple::point(angle.sin(), -1.0, angle.cos());
+        assert_eq!(p.pattern_at(point), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_an_image_mapped_onto_every_face_of_a_cube() {
+        let p = ImagePattern::new(corners(), UvMapping::Cube);
+        let top_left = p.pattern_at(Tuple::point(-0.9, 0.9, 1.0));
+        assert_eq!(p.pattern_at(Tuple::point(1.0, 0.9, 0.9)), top_left);
+        assert_eq!(p.pattern_at(Tuple::point(-0.9, 1.0, -0.9)), top_left);
+        assert_ne!(p.pattern_at(Tuple::point(0.9, -0.9, 1.0)), top_left);
+    }
+
+    #[test]
+    fn test_a_pattern_with_both_an_object_and_a_pattern_transformation() {
+        let mut object = Sphere::default();
+        object.transform = Matrix4::scaling(2.0, 2.0, 2.0);
+        let mut p = PlanarImagePattern::new(corners(), 4.0, 2.0);
+        p.transform = Matrix4::translation(1.0, 0.0, 0.0);
+        assert_eq!(
+            p.pattern_at_shape(&object, Tuple::point(4.0, 0.0, -1.0)),
+            Color::new(0.0, 1.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::scene_file::SceneSettings;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+
+/// An infinite flat surface, the xz plane in object space.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Plane {
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl Shape for Plane {
+    fn kind(&self) -> &'static str {
+        "plane"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        // a ray parallel to the plane never hits it, and one within it
+        // would only graze its edge
+        if transformed_ray.direction.y.abs() < EPSILON {
+            return vec![];
+        }
+        let t = -transformed_ray.origin.y / transformed_ray.direction.y;
+        vec![Intersection::new(t, self)]
+    }
+
+    fn local_normal_at(&self, _point: Tuple) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+    }
+
+    /// Returns texture coordinates that repeat every unit along x and z.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0))
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        (
+            Tuple::point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, 0.0, f64::INFINITY),
+        )
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+impl Default for Plane {
+    fn default() -> Plane {
+        Plane {
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::color::Color;
+    use crate::intersection::Intersection;
+    use crate::matrix::Matrix4;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+
+    #[test]
+    fn test_the_normal_of_a_plane_is_constant_everywhere() {
+        let p = Plane::default();
+        let n1 = p.local_normal_at(Tuple::point(0.0, 0.0, 0.0));
+        let n2 = p.local_normal_at(Tuple::point(10.0, 0.0, -10.0));
+        let n3 = p.local_normal_at(Tuple::point(-5.0, 0.0, 150.0));
+        assert_eq!(n1, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(n2, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(n3, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_intersect_with_a_ray_parallel_to_the_plane() {
+        let p = Plane::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert!(p.local_intersect(r).is_empty());
+    }
+
+    #[test]
+    fn test_intersect_with_a_coplanar_ray() {
+        let p = Plane::default();
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(p.local_intersect(r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_intersecting_a_plane_from_above() {
+        let p = Plane::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+        );
+        assert_eq!(p.local_intersect(r), vec![Intersection::new(1.0, &p)]);
+    }
+
+    #[test]
+    fn test_a_ray_intersecting_a_plane_from_below() {
+        let p = Plane::default();
+        let r = Ray::new(
+            Tuple::point(0.0, -1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(p.local_intersect(r), vec![Intersection::new(1.0, &p)]);
+    }
+
+    #[test]
+    fn test_the_normal_of_a_transformed_plane() {
+        let mut p = Plane::default();
+        p.transform = Matrix4::rotation_x(std::f64::consts::FRAC_PI_2);
+        let n = p.normal_at(Tuple::point(0.0, 0.0, 3.0));
+        assert_eq!(n, Tuple::vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_a_plane_is_unbounded() {
+        let mut p = Plane::default();
+        p.transform = Matrix4::rotation_x(std::f64::consts::FRAC_PI_4);
+        let (min, max) = p.bounds();
+        assert_eq!(min.y, f64::NEG_INFINITY);
+        assert_eq!(max.x, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_a_plane_in_a_world_with_spheres() {
+        let mut w = World::default();
+        let mut floor = Plane::default();
+        floor.transform = Matrix4::translation(0.0, -1.0, 0.0);
+        floor.material.color = Color::new(0.5, 0.5, 0.5);
+        w.objects.push(Box::new(floor));
+        let r = Ray::new(
+            Tuple::point(0.0, 5.0, -5.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+        );
+        let scene = Scene::new(&w);
+        let hit = scene.hit_sorted(r).unwrap();
+        assert_eq!(hit.t, 6.0);
+        assert_eq!(hit.object, &*w.objects[2]);
+        assert_ne!(scene.color_at(r), Color::black());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::aov::Aov;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::lut::Lut;
+use crate::tuple::Tuple;
+
+/// A lens effect or grading step applied to a finished image.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PostEffect {
+    /// Darkens the image towards its corners. A strength of 1 makes the
+    /// corners black.
+    Vignette { strength: f64 },
+    /// Makes bright areas glow: every channel above `threshold` is
+    /// blurred over `radius` pixels and added back, scaled by
+    /// `intensity`.
+    Bloom {
+        threshold: f64,
+        radius: usize,
+        intensity: f64,
+    },
+    /// Shifts the red channel outwards and the blue channel inwards by
+    /// `amount` times the distance from the center, fringing edges near
+    /// the border of the image.
+    ChromaticAberration { amount: f64 },
+    /// Scales the image so that the pixel at `percentile`, from 0 to 100,
+    /// of the luminances of the pixels that aren't black becomes middle
+    /// gray. A percentile of 50 exposes for the median pixel; higher
+    /// percentiles keep more of the highlights from blowing out.
+    AutoExposure { percentile: f64 },
+    /// Grades the image with a lookup table. The image is encoded as
+    /// sRGB first, as most tables expect, so this belongs after every
+    /// other effect and leaves an sRGB image.
+    Grade(Lut),
+}
+
+/// The luminance that auto-exposure maps the chosen percentile to.
+const MIDDLE_GRAY: f64 = 0.18;
+
+/// Applies the effects to the image in order.
+pub fn apply_effects(canvas: &Canvas, effects: &[PostEffect]) -> Canvas {
+    effects
+        .iter()
+        .fold(canvas.clone(), |image, effect| match effect {
+            PostEffect::Vignette { strength } => vignette(&image, *strength),
+            PostEffect::Bloom {
+                threshold,
+                radius,
+                intensity,
+            } => bloom(&image, *threshold, *radius, *intensity),
+            PostEffect::ChromaticAberration { amount } => {
+                chromatic_aberration(&image, *amount)
+            }
+            PostEffect::AutoExposure { percentile } => {
+                auto_expose(&image, *percentile)
+            }
+            PostEffect::Grade(lut) => lut.grade(&image.to_srgb()),
+        })
+}
+
+pub fn vignette(canvas: &Canvas, strength: f64) -> Canvas {
+    let (center_x, center_y) = center(canvas);
+    let max_distance2 = center_x * center_x + center_y * center_y;
+    map_pixels(canvas, |x, y, color| {
+        let dx = pixel_center(x) - center_x;
+        let dy = pixel_center(y) - center_y;
+        let falloff = (dx * dx + dy * dy) / max_distance2;
+        color * (1.0 - strength * falloff).max(0.0)
+    })
+}
+
+pub fn bloom(
+    canvas: &Canvas,
+    threshold: f64,
+    radius: usize,
+    intensity: f64,
+) -> Canvas {
+    let bright = map_pixels(canvas, |_, _, color| {
+        Color::new(
+            (color.red - threshold).max(0.0),
+            (color.green - threshold).max(0.0),
+            (color.blue - threshold).max(0.0),
+        )
+    });
+    let glow = box_blur(&bright, radius);
+    map_pixels(canvas, |x, y, color| {
+        color + glow.pixel_at(x, y) * intensity
+    })
+}
+
+pub fn chromatic_aberration(canvas: &Canvas, amount: f64) -> Canvas {
+    let (center_x, center_y) = center(canvas);
+    // each channel is read from nearer to or farther from the center,
+    // which moves it the other way in the result
+    let sample = |x: usize, y: usize, scale: f64| {
+        let sx = center_x + (pixel_center(x) - center_x) * scale;
+        let sy = center_y + (pixel_center(y) - center_y) * scale;
+        let sx = (sx.max(0.0) as usize).min(canvas.width - 1);
+        let sy = (sy.max(0.0) as usize).min(canvas.height - 1);
+        canvas.pixel_at(sx, sy)
+    };
+    map_pixels(canvas, |x, y, color| {
+        Color::new(
+            sample(x, y, 1.0 - amount).red,
+            color.green,
+            sample(x, y, 1.0 + amount).blue,
+        )
+    })
+}
+
+/// Returns the factor that brings the luminance at `percentile` of the
+/// pixels that aren't black to middle gray, or 1 if every pixel is
+/// black. Black pixels are left out so that a background where rays
+/// miss everything doesn't brighten the whole image.
+pub fn auto_exposure(canvas: &Canvas, percentile: f64) -> f64 {
+    let mut luminances: Vec<f64> = (0..canvas.height)
+        .flat_map(|y| (0..canvas.width).map(move |x| (x, y)))
+        .map(|(x, y)| canvas.pixel_at(x, y).luminance())
+        .filter(|&luminance| luminance > 0.0)
+        .collect();
+    if luminances.is_empty() {
+        return 1.0;
+    }
+    let rank = percentile.clamp(0.0, 100.0) / 100.0;
+    let index = (rank * (luminances.len() - 1) as f64).round() as usize;
+    let (_, luminance, _) = luminances
+        .select_nth_unstable_by(index, |a, b| a.partial_cmp(b).unwrap());
+    MIDDLE_GRAY / *luminance
+}
+
+pub fn auto_expose(canvas: &Canvas, percentile: f64) -> Canvas {
+    let exposure = auto_exposure(canvas, percentile);
+    map_pixels(canvas, |_, _, color| color * exposure)
+}
+
+/// Draws lines along the silhouettes and creases of a render, found by
+/// comparing the depth and normal buffers of neighboring pixels.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Outline {
+    pub color: Color,
+    /// How much farther a neighbor may be, as a fraction of the nearer
+    /// depth, before there is an edge between them.
+    pub depth_threshold: f64,
+    /// The smallest cosine of the angle between the normals of neighbors
+    /// without an edge between them. Lower values only outline sharper
+    /// creases.
+    pub normal_threshold: f64,
+}
+
+impl Default for Outline {
+    fn default() -> Outline {
+        Outline {
+            color: Color::black(),
+            depth_threshold: 0.1,
+            normal_threshold: 0.8,
+        }
+    }
+}
+
+/// Composites outlines over the image wherever the depth or normal
+/// changes sharply from a pixel to any of its four neighbors, or where
+/// the background meets an object. The buffers must match the image in
+/// size, as rendered by `Camera::render_depth` and
+/// `Camera::render_normals`.
+pub fn outlines(
+    canvas: &Canvas,
+    depth: &Aov<Option<f64>>,
+    normals: &Aov<Option<Tuple>>,
+    outline: Outline,
+) -> Canvas {
+    assert!(depth.width == canvas.width && depth.height == canvas.height);
+    assert!(normals.width == canvas.width && normals.height == canvas.height);
+    let is_edge = |(x1, y1): (usize, usize), (x2, y2): (usize, usize)| {
+        let depths = (depth.value_at(x1, y1), depth.value_at(x2, y2));
+        let normals = (normals.value_at(x1, y1), normals.value_at(x2, y2));
+        match (depths, normals) {
+            ((Some(d1), Some(d2)), (Some(n1), Some(n2))) => {
+                (d1 - d2).abs() > outline.depth_threshold * d1.min(d2)
+                    || n1.dot(n2) < outline.normal_threshold
+            }
+            ((None, None), _) => false,
+            _ => true,
+        }
+    };
+    map_pixels(canvas, |x, y, color| {
+        let mut neighbors = vec![];
+        if x > 0 {
+            neighbors.push((x - 1, y));
+        }
+        if x + 1 < canvas.width {
+            neighbors.push((x + 1, y));
+        }
+        if y > 0 {
+            neighbors.push((x, y - 1));
+        }
+        if y + 1 < canvas.height {
+            neighbors.push((x, y + 1));
+        }
+        if neighbors.into_iter().any(|other| is_edge((x, y), other)) {
+            outline.color
+        } else {
+            color
+        }
+    })
+}
+
+/// Averages every pixel with its neighbors up to `radius` pixels away,
+/// horizontally and then vertically.
+fn box_blur(canvas: &Canvas, radius: usize) -> Canvas {
+    let pass = |canvas: &Canvas, horizontal: bool| {
+        map_pixels(canvas, |x, y, _| {
+            let (position, length) = if horizontal {
+                (x, canvas.width)
+            } else {
+                (y, canvas.height)
+            };
+            let first = position.saturating_sub(radius);
+            let last = (position + radius).min(length - 1);
+            let sum = (first..=last).fold(Color::black(), |sum, i| {
+                sum + if horizontal {
+                    canvas.pixel_at(i, y)
+                } else {
+                    canvas.pixel_at(x, i)
+                }
+            });
+            sum * (1.0 / (2 * radius + 1) as f64)
+        })
+    };
+    pass(&pass(canvas, true), false)
+}
+
+fn map_pixels<F>(canvas: &Canvas, f: F) -> Canvas
+where
+    F: Fn(usize, usize, Color) -> Color,
+{
+    let mut result = Canvas::new(canvas.width, canvas.height);
+    result.color_space = canvas.color_space;
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            result.write_pixel(x, y, f(x, y, canvas.pixel_at(x, y)));
+        }
+    }
+    result
+}
+
+fn center(canvas: &Canvas) -> (f64, f64) {
+    (canvas.width as f64 / 2.0, canvas.height as f64 / 2.0)
+}
+
+fn pixel_center(i: usize) -> f64 {
+    i as f64 + 0.5
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::aov::Aov;
+    use crate::camera::Camera;
+    use crate::canvas::Canvas;
+    use crate::color::Color;
+    use crate::lut::Lut;
+    use crate::matrix::Matrix4;
+    use crate::post::{
+        apply_effects, auto_expose, auto_exposure, bloom, chromatic_aberration,
+        outlines, vignette, Outline, PostEffect,
+    };
+    use crate::scene::Scene;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_6;
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                canvas.write_pixel(x, y, color);
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn test_a_vignette_darkens_the_corners() {
+        let image = vignette(&filled(9, 9, Color::white()), 1.0);
+        assert_eq!(image.pixel_at(4, 4), Color::white());
+        assert!(image.pixel_at(0, 0).red < 0.25);
+        assert!(image.pixel_at(0, 4).red > image.pixel_at(0, 0).red);
+    }
+
+    #[test]
+    fn test_bloom_spreads_only_bright_pixels() {
+        let mut canvas = filled(5, 5, Color::new(0.5, 0.5, 0.5));
+        canvas.write_pixel(2, 2, Color::new(4.0, 4.0, 4.0));
+        let image = bloom(&canvas, 1.0, 1, 1.0);
+        assert_eq!(image.pixel_at(1, 1), Color::new(0.83333, 0.83333, 0.83333));
+        assert_eq!(image.pixel_at(4, 4), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(image.pixel_at(2, 2), Color::new(4.33333, 4.33333, 4.33333));
+    }
+
+    #[test]
+    fn test_chromatic_aberration_separates_channels_away_from_the_center() {
+        let mut canvas = Canvas::new(9, 1);
+        canvas.write_pixel(6, 0, Color::white());
+        canvas.write_pixel(7, 0, Color::white());
+        let image = chromatic_aberration(&canvas, 0.3);
+        assert_eq!(image.pixel_at(7, 0).green, 1.0);
+        // red moves outwards, blue inwards
+        assert_eq!(image.pixel_at(6, 0).red, 0.0);
+        assert_eq!(image.pixel_at(8, 0).red, 1.0);
+        assert_eq!(image.pixel_at(6, 0).blue, 1.0);
+        assert_eq!(image.pixel_at(7, 0).blue, 0.0);
+        assert_eq!(image.pixel_at(4, 0), canvas.pixel_at(4, 0));
+    }
+
+    #[test]
+    fn test_auto_exposure_brings_a_percentile_to_middle_gray() {
+        let mut canvas = Canvas::new(4, 1);
+        canvas.write_pixel(1, 0, Color::new(0.09, 0.09, 0.09));
+        canvas.write_pixel(2, 0, Color::new(0.36, 0.36, 0.36));
+        canvas.write_pixel(3, 0, Color::new(0.36, 0.36, 0.36));
+        // the black pixel is left out
+        assert!((auto_exposure(&canvas, 50.0) - 0.5).abs() < 1e-5);
+        assert!((auto_exposure(&canvas, 0.0) - 2.0).abs() < 1e-5);
+        let image = auto_expose(&canvas, 50.0);
+        assert_eq!(image.pixel_at(2, 0), Color::new(0.18, 0.18, 0.18));
+        assert_eq!(image.pixel_at(0, 0), Color::black());
+        assert_eq!(auto_exposure(&Canvas::new(2, 2), 50.0), 1.0);
+    }
+
+    #[test]
+    fn test_outlines_follow_depth_and_normal_edges() {
+        let canvas = filled(4, 1, Color::white());
+        let mut depth = Aov::new(4, 1, Some(1.0));
+        depth.write_value(3, 0, Some(5.0));
+        let mut normals = Aov::new(4, 1, Some(Tuple::vector(0.0, 0.0, -1.0)));
+        normals.write_value(0, 0, Some(Tuple::vector(1.0, 0.0, 0.0)));
+        let image = outlines(&canvas, &depth, &normals, Outline::default());
+        let row: Vec<Color> = (0..4).map(|x| image.pixel_at(x, 0)).collect();
+        assert_eq!(
+            row,
+            vec![
+                Color::black(),
+                Color::black(),
+                Color::black(),
+                Color::black()
+            ]
+        );
+        normals.write_value(0, 0, Some(Tuple::vector(0.0, 0.0, -1.0)));
+        depth.write_value(3, 0, Some(1.05));
+        let image = outlines(&canvas, &depth, &normals, Outline::default());
+        assert_eq!(image, canvas);
+    }
+
+    #[test]
+    fn test_outlining_the_silhouette_of_a_render() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_6);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let image = c.render(&World::default());
+        let mut outline = Outline::default();
+        outline.color = Color::new(1.0, 0.0, 0.0);
+        let scene = Scene::new(&w);
+        let outlined = outlines(
+            &image,
+            &c.render_depth(&scene),
+            &c.render_normals(&scene),
+            outline,
+        );
+        assert_eq!(outlined.pixel_at(0, 0), image.pixel_at(0, 0));
+        assert_eq!(outlined.pixel_at(5, 5), image.pixel_at(5, 5));
+        let red = (0..11).filter(|&x| outlined.pixel_at(x, 5) == outline.color);
+        assert!(red.count() >= 2);
+    }
+
+    #[test]
+    fn test_applying_effects_in_order() {
+        let canvas = filled(9, 9, Color::white());
+        let effects = [
+            PostEffect::Bloom {
+                threshold: 2.0,
+                radius: 1,
+                intensity: 1.0,
+            },
+            PostEffect::Vignette { strength: 1.0 },
+        ];
+        let image = apply_effects(&canvas, &effects);
+        assert_eq!(image, vignette(&canvas, 1.0));
+        assert_eq!(apply_effects(&canvas, &[]), canvas);
+    }
+
+    #[test]
+    fn test_grading_encodes_the_image_as_srgb_first() {
+        let canvas = filled(2, 2, Color::new(0.2, 0.2, 0.2));
+        let identity = Lut::parse("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").unwrap();
+        let graded = apply_effects(&canvas, &[PostEffect::Grade(identity)]);
+        assert_eq!(graded, canvas.to_srgb());
+    }
+}
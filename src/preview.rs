@@ -0,0 +1,243 @@
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::matrix::Matrix4;
+use crate::scene::Scene;
+use crate::tuple::Tuple;
+use std::f64::consts::FRAC_PI_2;
+
+/// Keeps the camera from looking straight up or down, where the view
+/// transformation is undefined.
+const MAX_PITCH: f64 = FRAC_PI_2 * 0.99;
+
+/// A first-person camera controller that can be moved and turned in
+/// small steps, as when driven by the keyboard and mouse.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FlyCamera {
+    pub position: Tuple,
+    /// Rotation around the y-axis, where 0 looks towards positive z.
+    pub yaw: f64,
+    /// Rotation above (positive) or below (negative) the horizon.
+    pub pitch: f64,
+}
+
+impl FlyCamera {
+    pub fn new(position: Tuple, yaw: f64, pitch: f64) -> FlyCamera {
+        assert!(position.is_point());
+        FlyCamera {
+            position,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+        }
+    }
+
+    /// Returns the direction in which the camera is looking.
+    pub fn forward(&self) -> Tuple {
+        Tuple::vector(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos(),
+        )
+    }
+
+    /// Returns the horizontal direction to the right of the camera.
+    pub fn right(&self) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+            .cross(self.forward())
+            .normalize()
+    }
+
+    /// Moves the camera relative to the direction it is looking, with
+    /// `up` always following the world's y-axis.
+    pub fn move_by(&mut self, forward: f64, right: f64, up: f64) {
+        self.position = self.position
+            + self.forward() * forward
+            + self.right() * right
+            + Tuple::vector(0.0, up, 0.0);
+    }
+
+    pub fn turn(&mut self, yaw: f64, pitch: f64) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        Matrix4::view_transform(
+            self.position,
+            self.position + self.forward(),
+            Tuple::vector(0.0, 1.0, 0.0),
+        )
+    }
+}
+
+/// Renders the scene with a copy of the camera whose resolution is
+/// divided by `scale`, trading detail for speed.
+pub fn render_preview(scene: &Scene, camera: &Camera, scale: usize) -> Canvas {
+    assert!(scale > 0);
+    let mut preview = Camera::new(
+        (camera.hsize / scale).max(1),
+        (camera.vsize / scale).max(1),
+        camera.field_of_view,
+    );
+    preview.transform = camera.transform;
+    preview.render_scene(&scene.for_camera(&preview))
+}
+
+/// Opens a window that continuously re-renders the world at a reduced
+/// resolution. WASD moves the camera, Q and E move it down and up, the
+/// arrow keys or dragging with the left mouse button turn it, and
+/// Escape closes the window. Fails if the window can't be opened or
+/// drawn to.
+#[cfg(feature = "preview")]
+pub fn run(
+    world: &crate::world::World,
+    camera: &Camera,
+    scale: usize,
+) -> Result<(), minifb::Error> {
+    use minifb::{Key, MouseButton, MouseMode, Window, WindowOptions};
+
+    const MOVE_STEP: f64 = 0.25;
+    const TURN_STEP: f64 = 0.05;
+    const MOUSE_SENSITIVITY: f64 = 0.005;
+
+    let mut window = Window::new(
+        "ray tracer preview",
+        camera.hsize,
+        camera.vsize,
+        WindowOptions::default(),
+    )?;
+    window.set_target_fps(30);
+
+    let inverse = camera.transform.inverse();
+    let origin = inverse * Tuple::point(0.0, 0.0, 0.0);
+    let forward = inverse * Tuple::vector(0.0, 0.0, -1.0);
+    let mut fly = FlyCamera::new(
+        origin,
+        forward.x.atan2(forward.z),
+        forward.y.clamp(-1.0, 1.0).asin(),
+    );
+
+    let scene = Scene::new(world);
+    let mut frame_camera = *camera;
+    let mut buffer = vec![0u32; camera.hsize * camera.vsize];
+    let mut last_mouse: Option<(f64, f64)> = None;
+    let mut dirty = true;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        for key in window.get_keys() {
+            match key {
+                Key::W => fly.move_by(MOVE_STEP, 0.0, 0.0),
+                Key::S => fly.move_by(-MOVE_STEP, 0.0, 0.0),
+                Key::D => fly.move_by(0.0, MOVE_STEP, 0.0),
+                Key::A => fly.move_by(0.0, -MOVE_STEP, 0.0),
+                Key::E => fly.move_by(0.0, 0.0, MOVE_STEP),
+                Key::Q => fly.move_by(0.0, 0.0, -MOVE_STEP),
+                Key::Left => fly.turn(-TURN_STEP, 0.0),
+                Key::Right => fly.turn(TURN_STEP, 0.0),
+                Key::Up => fly.turn(0.0, TURN_STEP),
+                Key::Down => fly.turn(0.0, -TURN_STEP),
+                _ => continue,
+            }
+            dirty = true;
+        }
+
+        let mouse = window
+            .get_mouse_pos(MouseMode::Pass)
+            .map(|(x, y)| (f64::from(x), f64::from(y)));
+        if window.get_mouse_down(MouseButton::Left) {
+            if let (Some((x, y)), Some((last_x, last_y))) = (mouse, last_mouse)
+            {
+                if x != last_x || y != last_y {
+                    fly.turn(
+                        (x - last_x) * MOUSE_SENSITIVITY,
+                        (last_y - y) * MOUSE_SENSITIVITY,
+                    );
+                    dirty = true;
+                }
+            }
+        }
+        last_mouse = mouse;
+
+        if dirty {
+            frame_camera.transform = fly.transform();
+            // the window shows the pixels as they are, like an image file
+            let canvas = render_preview(&scene, &frame_camera, scale).to_srgb();
+            for y in 0..camera.vsize {
+                for x in 0..camera.hsize {
+                    let pixel = canvas.pixel_at(
+                        (x * canvas.width / camera.hsize).min(canvas.width - 1),
+                        (y * canvas.height / camera.vsize)
+                            .min(canvas.height - 1),
+                    );
+                    let [red, green, blue] =
+                        [pixel.red, pixel.green, pixel.blue].map(|c| {
+                            (c * 255.0).round().clamp(0.0, 255.0) as u32
+                        });
+                    buffer[y * camera.hsize + x] =
+                        (red << 16) | (green << 8) | blue;
+                }
+            }
+            dirty = false;
+        }
+
+        window.update_with_buffer(&buffer, camera.hsize, camera.vsize)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::matrix::Matrix4;
+    use crate::preview::{render_preview, FlyCamera};
+    use crate::scene::Scene;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn test_a_fly_camera_looking_down_the_z_axis() {
+        let c = FlyCamera::new(Tuple::point(0.0, 0.0, -5.0), 0.0, 0.0);
+        assert_eq!(c.forward(), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(c.right(), Tuple::vector(1.0, 0.0, 0.0));
+        assert_eq!(
+            c.transform(),
+            Matrix4::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            )
+        );
+    }
+
+    #[test]
+    fn test_moving_a_fly_camera_follows_its_heading() {
+        let mut c = FlyCamera::new(Tuple::point(0.0, 0.0, 0.0), 0.0, 0.0);
+        c.turn(FRAC_PI_2, 0.0);
+        c.move_by(2.0, 1.0, 3.0);
+        assert_eq!(c.position, Tuple::point(2.0, 3.0, -1.0));
+    }
+
+    #[test]
+    fn test_a_fly_camera_cannot_look_straight_up() {
+        let mut c = FlyCamera::new(Tuple::point(0.0, 0.0, 0.0), 0.0, 0.0);
+        c.turn(0.0, PI);
+        assert!(c.pitch < FRAC_PI_2);
+        assert!(c.forward().z > 0.0);
+    }
+
+    #[test]
+    fn test_rendering_a_preview_at_reduced_resolution() {
+        let w = World::default();
+        let mut c = Camera::new(22, 22, FRAC_PI_2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let image = render_preview(&Scene::new(&w), &c, 2);
+        assert_eq!(image.width, 11);
+        assert_eq!(image.height, 11);
+        assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+/// An event reported while rendering, so that other programs can show
+/// how far a render has got.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Progress {
+    /// The render started, with the size of the image, the number of
+    /// camera rays traced for every pixel, and the number of threads
+    /// rendering it.
+    Started {
+        width: usize,
+        height: usize,
+        samples: usize,
+        threads: usize,
+    },
+    /// `done` of the image's `total` rows are finished. Rows finish in
+    /// bands, not always from the top down, and `done` only grows.
+    Rows { done: usize, total: usize },
+    /// The render finished after tracing `camera_rays` camera rays.
+    Finished {
+        duration: Duration,
+        camera_rays: usize,
+    },
+}
+
+impl Progress {
+    /// Describes the event as a JSON object on a single line, to be
+    /// written as JSON lines, one event per line.
+    pub fn to_json(&self) -> String {
+        match *self {
+            Progress::Started {
+                width,
+                height,
+                samples,
+                threads,
+            } => format!(
+                "{{\"event\": \"started\", \"width\": {}, \"height\": {}, \
+                 \"samples\": {}, \"threads\": {}}}",
+                width, height, samples, threads
+            ),
+            Progress::Rows { done, total } => format!(
+                "{{\"event\": \"rows\", \"done\": {}, \"total\": {}}}",
+                done, total
+            ),
+            Progress::Finished {
+                duration,
+                camera_rays,
+            } => format!(
+                "{{\"event\": \"finished\", \"seconds\": {:.6}, \
+                 \"camera-rays\": {}, \"rays-per-second\": {:.0}}}",
+                duration.as_secs_f64(),
+                camera_rays,
+                camera_rays as f64 / duration.as_secs_f64().max(1e-9)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::progress::Progress;
+    use std::time::Duration;
+
+    #[test]
+    fn test_describing_progress_as_json() {
+        let started = Progress::Started {
+            width: 20,
+            height: 10,
+            samples: 1,
+            threads: 4,
+        };
+        assert_eq!(
+            started.to_json(),
+            "{\"event\": \"started\", \"width\": 20, \"height\": 10, \
+             \"samples\": 1, \"threads\": 4}"
+        );
+        let rows = Progress::Rows { done: 3, total: 10 };
+        assert_eq!(
+            rows.to_json(),
+            "{\"event\": \"rows\", \"done\": 3, \"total\": 10}"
+        );
+        let finished = Progress::Finished {
+            duration: Duration::from_millis(500),
+            camera_rays: 200,
+        };
+        assert_eq!(
+            finished.to_json(),
+            "{\"event\": \"finished\", \"seconds\": 0.500000, \
+             \"camera-rays\": 200, \"rays-per-second\": 400}"
+        );
+    }
+}
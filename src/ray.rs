@@ -5,26 +5,63 @@ use crate::tuple::Tuple;
 pub struct Ray {
     pub origin: Tuple,
     pub direction: Tuple,
+    pub differential: Option<RayDifferential>,
+}
+
+/// Two auxiliary rays, offset by one pixel horizontally and vertically
+/// from a main ray, that track how large the area covered by a single
+/// pixel becomes as the ray travels through the scene.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RayDifferential {
+    pub x_origin: Tuple,
+    pub x_direction: Tuple,
+    pub y_origin: Tuple,
+    pub y_direction: Tuple,
 }
 
 impl Ray {
     pub fn new(origin: Tuple, direction: Tuple) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            differential: None,
+        }
+    }
+
+    pub fn with_differential(
+        origin: Tuple,
+        direction: Tuple,
+        differential: RayDifferential,
+    ) -> Ray {
+        Ray {
+            origin,
+            direction,
+            differential: Some(differential),
+        }
     }
 
-    pub fn position(&self, t: f32) -> Tuple {
+    pub fn position(&self, t: f64) -> Tuple {
         self.origin + (self.direction * t)
     }
 
     pub fn transform(&self, matrix: Matrix4) -> Ray {
-        Ray::new(matrix * self.origin, matrix * self.direction)
+        Ray {
+            origin: matrix * self.origin,
+            direction: matrix * self.direction,
+            differential: self.differential.map(|d| RayDifferential {
+                x_origin: matrix * d.x_origin,
+                x_direction: matrix * d.x_direction,
+                y_origin: matrix * d.y_origin,
+                y_direction: matrix * d.y_direction,
+            }),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::matrix::Matrix4;
-    use crate::ray::Ray;
+    use crate::ray::{Ray, RayDifferential};
     use crate::tuple::Tuple;
 
     #[test]
@@ -65,4 +102,27 @@ mod tests {
         assert_eq!(r2.origin, Tuple::point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, Tuple::vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn test_transforming_a_ray_transforms_its_differential() {
+        let d = RayDifferential {
+            x_origin: Tuple::point(1.0, 0.0, 0.0),
+            x_direction: Tuple::vector(0.0, 1.0, 0.0),
+            y_origin: Tuple::point(0.0, 1.0, 0.0),
+            y_direction: Tuple::vector(0.0, 0.0, 1.0),
+        };
+        let r = Ray::with_differential(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            d,
+        );
+        let m = Matrix4::translation(3.0, 4.0, 5.0)
+            * Matrix4::scaling(2.0, 2.0, 2.0);
+        let r2 = r.transform(m);
+        let d2 = r2.differential.unwrap();
+        assert_eq!(d2.x_origin, Tuple::point(5.0, 4.0, 5.0));
+        assert_eq!(d2.x_direction, Tuple::vector(0.0, 2.0, 0.0));
+        assert_eq!(d2.y_origin, Tuple::point(3.0, 6.0, 5.0));
+        assert_eq!(d2.y_direction, Tuple::vector(0.0, 0.0, 2.0));
+    }
 }
@@ -0,0 +1,87 @@
+/// A small, fast, seedable pseudo-random number generator (PCG32). Every
+/// stochastic part of the renderer draws from one of these, so the same
+/// seed always renders the same image, and since each pixel gets its own
+/// generator, pixels can be rendered in any order or in parallel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Creates the generator for one sample of one pixel. Generators for
+    /// different pixels and samples produce unrelated sequences.
+    pub fn for_pixel(seed: u64, x: usize, y: usize, sample: usize) -> Rng {
+        let key = [x as u64, y as u64, sample as u64]
+            .iter()
+            .fold(mix(seed), |key, &value| mix(key ^ value));
+        Rng::new(key)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Returns a number in the range [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        ((self.next_u32() >> 8) as f64) / ((1u32 << 24) as f64)
+    }
+}
+
+/// Scrambles the bits of a number (the SplitMix64 finalizer), so that
+/// nearby inputs give unrelated outputs.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_the_same_seed_produces_the_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let a: Vec<u32> = (0..8).map(|_| a.next_u32()).collect();
+        let b: Vec<u32> = (0..8).map(|_| b.next_u32()).collect();
+        let c: Vec<u32> = (0..8).map(|_| c.next_u32()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_floats_are_spread_over_the_unit_interval() {
+        let mut rng = Rng::new(7);
+        let values: Vec<f64> = (0..1000).map(|_| rng.next_f64()).collect();
+        assert!(values.iter().all(|&v| (0.0..1.0).contains(&v)));
+        let mean = values.iter().sum::<f64>() / 1000.0;
+        assert!((mean - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_every_pixel_and_sample_gets_its_own_sequence() {
+        let first = |mut rng: Rng| rng.next_u32();
+        let pixel = first(Rng::for_pixel(1, 2, 3, 0));
+        assert_eq!(pixel, first(Rng::for_pixel(1, 2, 3, 0)));
+        assert_ne!(pixel, first(Rng::for_pixel(0, 2, 3, 0)));
+        assert_ne!(pixel, first(Rng::for_pixel(1, 3, 2, 0)));
+        assert_ne!(pixel, first(Rng::for_pixel(1, 2, 3, 1)));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::rng::Rng;
+use crate::tuple::{build_onb, Tuple};
+use std::f64::consts::PI;
+
+/// How the points that soft shadows, glossy surfaces and lenses average
+/// over are chosen. Every backend returns points spread over the unit
+/// square, randomized per pixel by an `Rng`; the low-discrepancy ones
+/// cover it more evenly than plain random points, so the averages
+/// converge with fewer samples.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sampler {
+    /// A jittered Fibonacci lattice: evenly spaced for any count.
+    Fibonacci,
+    /// Independent uniformly random points.
+    Random,
+    /// The Halton sequence in bases 2 and 3.
+    Halton,
+    /// The first two dimensions of the Sobol sequence.
+    Sobol,
+}
+
+impl Sampler {
+    /// Returns `count` points in [0, 1) x [0, 1).
+    pub fn points(&self, count: usize, rng: &mut Rng) -> Vec<(f64, f64)> {
+        match self {
+            Sampler::Fibonacci => {
+                let rotation = rng.next_f64();
+                (0..count)
+                    .map(|i| {
+                        let s = ((i as f64) + rng.next_f64()) / (count as f64);
+                        let t = (i as f64) * GOLDEN_ANGLE_TURNS + rotation;
+                        (s, t.fract())
+                    })
+                    .collect()
+            }
+            Sampler::Random => (0..count)
+                .map(|_| (rng.next_f64(), rng.next_f64()))
+                .collect(),
+            Sampler::Halton => rotate(
+                (0..count)
+                    .map(|i| (radical_inverse(2, i), radical_inverse(3, i))),
+                rng,
+            ),
+            Sampler::Sobol => rotate((0..count).map(sobol), rng),
+        }
+    }
+
+    /// Returns `count` directions in the hemisphere around the normal,
+    /// more of them close to the normal, in proportion to the cosine of
+    /// their angle with it, as diffuse surfaces gather light.
+    pub(crate) fn cosine_directions(
+        &self,
+        normal: Tuple,
+        count: usize,
+        rng: &mut Rng,
+    ) -> Vec<Tuple> {
+        let (tangent, bitangent) = build_onb(normal);
+        self.points(count, rng)
+            .into_iter()
+            .map(|(s, t)| {
+                let radius = s.sqrt();
+                let theta = 2.0 * PI * t;
+                tangent * (theta.cos() * radius)
+                    + bitangent * (theta.sin() * radius)
+                    + normal * (1.0 - s).max(0.0).sqrt()
+            })
+            .collect()
+    }
+}
+
+/// The golden angle as a fraction of a full turn.
+const GOLDEN_ANGLE_TURNS: f64 = 0.381_966;
+
+/// Shifts every point by the same random offset, wrapping around the
+/// unit square, which keeps the points evenly spread while giving every
+/// pixel a different pattern.
+fn rotate<I>(points: I, rng: &mut Rng) -> Vec<(f64, f64)>
+where
+    I: Iterator<Item = (f64, f64)>,
+{
+    let (du, dv) = (rng.next_f64(), rng.next_f64());
+    points
+        .map(|(u, v)| ((u + du).fract(), (v + dv).fract()))
+        .collect()
+}
+
+/// Mirrors the digits of `index` in the given base around the decimal
+/// point.
+fn radical_inverse(base: usize, mut index: usize) -> f64 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / (base as f64);
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    result
+}
+
+/// Returns the point at `index` of the two-dimensional Sobol sequence.
+fn sobol(index: usize) -> (f64, f64) {
+    let first = (index as u32).reverse_bits();
+    let mut second = 0u32;
+    let mut direction = 1u32 << 31;
+    let mut bits = index as u32;
+    while bits != 0 {
+        if bits & 1 != 0 {
+            second ^= direction;
+        }
+        bits >>= 1;
+        direction ^= direction >> 1;
+    }
+    (to_unit(first), to_unit(second))
+}
+
+fn to_unit(bits: u32) -> f64 {
+    ((bits >> 8) as f64) / ((1u32 << 24) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equal_f64;
+    use crate::rng::Rng;
+    use crate::sampler::{radical_inverse, sobol, Sampler};
+
+    #[test]
+    fn test_the_radical_inverse() {
+        assert_eq!(radical_inverse(2, 0), 0.0);
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert_eq!(radical_inverse(3, 1), 1.0 / 3.0);
+        assert!(equal_f64(radical_inverse(3, 5), 7.0 / 9.0));
+    }
+
+    #[test]
+    fn test_the_first_points_of_the_sobol_sequence() {
+        let points: Vec<(f64, f64)> = (0..4).map(sobol).collect();
+        assert_eq!(
+            points,
+            vec![(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]
+        );
+    }
+
+    #[test]
+    fn test_every_sampler_covers_the_unit_square() {
+        let samplers = [
+            Sampler::Fibonacci,
+            Sampler::Random,
+            Sampler::Halton,
+            Sampler::Sobol,
+        ];
+        for sampler in samplers.iter() {
+            let points = sampler.points(64, &mut Rng::new(3));
+            assert_eq!(points.len(), 64);
+            for &(u, v) in points.iter() {
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+            }
+            // every quarter of the square gets some of the points
+            for quarter in 0..4 {
+                let (left, top) = ((quarter % 2) as f64, (quarter / 2) as f64);
+                assert!(points.iter().any(|&(u, v)| {
+                    (u * 2.0).floor() == left && (v * 2.0).floor() == top
+                }));
+            }
+        }
+    }
+
+    #[test]
+    fn test_low_discrepancy_points_estimate_areas_more_accurately() {
+        // the fraction of points inside a quarter circle approximates
+        // its area, pi / 4
+        let error = |sampler: Sampler| {
+            let points = sampler.points(256, &mut Rng::new(11));
+            let inside =
+                points.iter().filter(|&&(u, v)| u * u + v * v < 1.0).count();
+            ((inside as f64) / 256.0 - std::f64::consts::FRAC_PI_4).abs()
+        };
+        let random = error(Sampler::Random);
+        assert!(error(Sampler::Halton) < random);
+        assert!(error(Sampler::Sobol) < random);
+    }
+}
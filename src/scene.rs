@@ -0,0 +1,439 @@
+use crate::accel::AccelStructure;
+use crate::camera::{Camera, Quality, DRAFT_MAX_DEPTH};
+use crate::color::Color;
+use crate::intersection::{find_hit, find_true_hit, Intersection};
+use crate::irradiance::IrradianceCache;
+use crate::light::PointLight;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::world::{ReflectionMode, World};
+use std::sync::Arc;
+
+/// A world compiled for rendering: the inverse of every object's
+/// transformation is computed, the acceleration structure is built, and
+/// the irradiance cache is filled if the world uses one. A scene borrows
+/// its world instead of copying it, so the world can't be edited while
+/// the scene exists, and any number of threads and frames can render
+/// from it at once; to change something, edit the world and compile a
+/// new scene.
+#[derive(Clone)]
+pub struct Scene<'w> {
+    world: &'w World,
+    /// The inverse of every object's transformation, in the order of
+    /// `World::objects`.
+    inverses: Arc<[Matrix4]>,
+    structure: Option<Arc<dyn AccelStructure>>,
+    irradiance_cache: Option<IrradianceCache>,
+    shadows: bool,
+    max_depth: usize,
+    reflection_mode: ReflectionMode,
+}
+
+impl<'w> Scene<'w> {
+    /// Compiles the world's objects, which is all that is needed to look
+    /// rays up in it. The inverses and acceleration structure kept by
+    /// `World::update` are reused while they still fit the objects.
+    pub fn new(world: &'w World) -> Scene<'w> {
+        let inverses: Vec<Matrix4> = world
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                world
+                    .cached_inverse(i)
+                    .unwrap_or_else(|| object.transform().inverse())
+            })
+            .collect();
+        let bounds = world.object_bounds();
+        Scene {
+            world,
+            inverses: inverses.into(),
+            structure: world
+                .cached_structure(&bounds)
+                .or_else(|| world.accel.build(&bounds)),
+            irradiance_cache: None,
+            shadows: world.shadows,
+            max_depth: world.max_depth,
+            reflection_mode: world.reflection_mode,
+        }
+    }
+
+    /// Compiles the world for rendering through the camera, like
+    /// `Scene::new` followed by `for_camera`.
+    pub fn compile(world: &'w World, camera: &Camera) -> Scene<'w> {
+        Scene::new(world).for_camera(camera)
+    }
+
+    /// Returns a scene that shares this one's compiled objects, set up
+    /// for rendering through the camera: the irradiance cache gathers
+    /// light where the camera looks, and a draft camera turns off
+    /// shadows and traced reflections. An animation compiles its world
+    /// once and calls this for every frame.
+    pub fn for_camera(&self, camera: &Camera) -> Scene<'w> {
+        let world = self.world;
+        let draft = camera.quality == Quality::Draft;
+        let mut scene = Scene {
+            world,
+            inverses: Arc::clone(&self.inverses),
+            structure: self.structure.clone(),
+            irradiance_cache: None,
+            shadows: world.shadows && !draft,
+            max_depth: if draft {
+                world.max_depth.min(DRAFT_MAX_DEPTH)
+            } else {
+                world.max_depth
+            },
+            reflection_mode: if draft {
+                ReflectionMode::Environment
+            } else {
+                world.reflection_mode
+            },
+        };
+        scene.irradiance_cache = camera.irradiance_cache(&scene);
+        scene
+    }
+
+    pub fn world(&self) -> &'w World {
+        self.world
+    }
+
+    /// The cache used by `Indirect::Cached`, if the world uses one.
+    pub fn irradiance_cache(&self) -> Option<&IrradianceCache> {
+        self.irradiance_cache.as_ref()
+    }
+
+    /// Whether objects cast shadows, as in `World::shadows`.
+    pub fn shadows(&self) -> bool {
+        self.shadows
+    }
+
+    /// The number of reflections traced, as in `World::max_depth`.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// How reflections are shaded, as in `World::reflection_mode`.
+    pub fn reflection_mode(&self) -> ReflectionMode {
+        self.reflection_mode
+    }
+
+    fn intersect_object(
+        &self,
+        index: usize,
+        ray: Ray,
+    ) -> Vec<Intersection<'w>> {
+        let world = self.world;
+        world.objects[index]
+            .local_intersect(ray.transform(self.inverses[index]))
+    }
+
+    /// Returns the indices of the objects the ray might hit: those found
+    /// by the acceleration structure, or every object without one.
+    fn candidates(&self, ray: Ray) -> Vec<usize> {
+        match &self.structure {
+            Some(structure) => structure.candidates(ray),
+            None => (0..self.world.objects.len()).collect(),
+        }
+    }
+
+    pub fn intersect(&self, ray: Ray) -> Vec<Intersection<'w>> {
+        self.intersect_candidates(ray, &self.candidates(ray))
+    }
+
+    fn intersect_candidates(
+        &self,
+        ray: Ray,
+        candidates: &[usize],
+    ) -> Vec<Intersection<'w>> {
+        candidates
+            .iter()
+            .flat_map(|&i| self.intersect_object(i, ray))
+            .collect()
+    }
+
+    /// Returns the inverse of the hit object's transformation: the one
+    /// compiled for it when it is one of the candidates itself, rather
+    /// than a shape inside one of them, such as a group.
+    fn inverse_of(&self, object: &dyn Shape, candidates: &[usize]) -> Matrix4 {
+        candidates
+            .iter()
+            .find(|&&i| &*self.world.objects[i] == object)
+            .map_or_else(|| object.transform().inverse(), |&i| self.inverses[i])
+    }
+
+    /// Returns the nearest hit along the ray, without preparing it for
+    /// shading, or `None` if the ray misses everything.
+    pub fn hit_sorted(&self, ray: Ray) -> Option<Intersection<'w>> {
+        let mut intersections = self.intersect(ray);
+        intersections.sort();
+        intersections.into_iter().find(|i| i.t >= 0.0)
+    }
+
+    /// Returns whether anything is hit along the ray before the given
+    /// distance, in units of the ray's direction. Stops at the first
+    /// object found, so it is cheaper than finding the nearest hit.
+    pub fn hit_any(&self, ray: Ray, max_distance: f64) -> bool {
+        self.candidates(ray).into_iter().any(|i| {
+            self.intersect_object(i, ray)
+                .iter()
+                .any(|hit| hit.t >= 0.0 && hit.t < max_distance)
+        })
+    }
+
+    /// Returns the index in `World::objects` of the object visible along
+    /// the ray, if any.
+    pub fn object_id_at(&self, ray: Ray) -> Option<usize> {
+        self.nearest_object(ray, false)
+    }
+
+    /// Like `object_id_at` for a ray from the camera, which passes
+    /// through holdout objects.
+    pub fn camera_object_id_at(&self, ray: Ray) -> Option<usize> {
+        self.nearest_object(ray, true)
+    }
+
+    fn nearest_object(&self, ray: Ray, skip_holdouts: bool) -> Option<usize> {
+        self.candidates(ray)
+            .into_iter()
+            .filter_map(|i| {
+                let mut intersections = self.intersect_object(i, ray);
+                if skip_holdouts {
+                    intersections.retain(|i| !i.object.material().holdout);
+                }
+                find_hit(intersections).map(|hit| (i, hit))
+            })
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Computes the color seen along a camera ray, drawing any random
+    /// samples from a generator with a seed of zero.
+    pub fn color_at(&self, ray: Ray) -> Color {
+        self.camera_color_at(ray, &mut Rng::new(0))
+    }
+
+    /// Computes the color seen along a ray from the camera, which passes
+    /// through holdout objects.
+    pub fn camera_color_at(&self, ray: Ray, rng: &mut Rng) -> Color {
+        match self.camera_hit(ray) {
+            Some(hit) => hit.shade_hit(self, self.max_depth, rng),
+            None => self.environment_color(ray.direction),
+        }
+    }
+
+    /// Computes the color seen along the ray, where `remaining` is the
+    /// number of further reflections that may still be traced and `rng`
+    /// provides the random samples.
+    pub fn color_at_depth(
+        &self,
+        ray: Ray,
+        remaining: usize,
+        rng: &mut Rng,
+    ) -> Color {
+        match self.shading_hit(ray) {
+            Some(hit) => hit.shade_hit(self, remaining, rng),
+            None => self.environment_color(ray.direction),
+        }
+    }
+
+    /// Returns the hit visible along the ray, prepared for shading, or
+    /// `None` if the ray misses everything.
+    pub fn shading_hit(&self, ray: Ray) -> Option<Intersection<'w>> {
+        self.prepared_hit(ray, false)
+    }
+
+    /// Returns the hit visible along a ray from the camera, prepared for
+    /// shading, skipping holdout objects.
+    pub fn camera_hit(&self, ray: Ray) -> Option<Intersection<'w>> {
+        self.prepared_hit(ray, true)
+    }
+
+    /// Like `camera_hit` for four rays at once, such as the rays through a
+    /// 2x2 block of pixels, which neighboring rays let the acceleration
+    /// structure walk together.
+    pub fn camera_hits(
+        &self,
+        rays: &[Ray; 4],
+    ) -> [Option<Intersection<'w>>; 4] {
+        let [c0, c1, c2, c3] = match &self.structure {
+            Some(structure) => structure.packet_candidates(rays),
+            None => {
+                let all: Vec<usize> = (0..self.world.objects.len()).collect();
+                [all.clone(), all.clone(), all.clone(), all]
+            }
+        };
+        [
+            self.prepared_hit_among(rays[0], c0, true),
+            self.prepared_hit_among(rays[1], c1, true),
+            self.prepared_hit_among(rays[2], c2, true),
+            self.prepared_hit_among(rays[3], c3, true),
+        ]
+    }
+
+    fn prepared_hit(
+        &self,
+        ray: Ray,
+        skip_holdouts: bool,
+    ) -> Option<Intersection<'w>> {
+        self.prepared_hit_among(ray, self.candidates(ray), skip_holdouts)
+    }
+
+    fn prepared_hit_among(
+        &self,
+        ray: Ray,
+        candidates: Vec<usize>,
+        skip_holdouts: bool,
+    ) -> Option<Intersection<'w>> {
+        let mut intersections = self.intersect_candidates(ray, &candidates);
+        if skip_holdouts {
+            intersections.retain(|i| !i.object.material().holdout);
+        }
+        intersections.sort();
+        let index = find_true_hit(&intersections)?;
+        let mut hit = intersections[index].clone();
+        hit.prepare_hit_with(ray, self.inverse_of(hit.object, &candidates));
+        hit.prepare_refraction(&intersections);
+        Some(hit)
+    }
+
+    /// Returns the environment's color in the given direction, or black
+    /// if the world has no environment.
+    pub fn environment_color(&self, direction: Tuple) -> Color {
+        self.world
+            .environment
+            .as_ref()
+            .map_or(Color::black(), |env| env.color_at(direction))
+    }
+
+    /// Returns whether anything blocks the center of the light from the
+    /// point.
+    pub fn is_shadowed(&self, light: &PointLight, point: Tuple) -> bool {
+        self.shadows
+            && light.casts_shadows
+            && self.is_shadowed_from(point, light.position)
+    }
+
+    /// Returns the fraction of the given light that reaches the point,
+    /// from 0 when it is fully occluded to 1 when it is fully visible.
+    /// Lights with a radius can be partially visible.
+    pub fn light_intensity_at(
+        &self,
+        point: Tuple,
+        light: &PointLight,
+        rng: &mut Rng,
+    ) -> f64 {
+        if !self.shadows || !light.casts_shadows {
+            return 1.0;
+        }
+        let samples = light.sample_positions(self.world.sampler, rng);
+        let visible = samples
+            .iter()
+            .filter(|&&sample| !self.is_shadowed_from(point, sample))
+            .count();
+        (visible as f64) / (samples.len() as f64)
+    }
+
+    fn is_shadowed_from(&self, point: Tuple, light_position: Tuple) -> bool {
+        let shadow_vector = light_position - point;
+        let distance = shadow_vector.magnitude();
+        let direction = shadow_vector.normalize();
+        self.hit_any(Ray::new(point, direction), distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accel::Accel;
+    use crate::camera::{Camera, Quality};
+    use crate::intersection::Intersection;
+    use crate::irradiance::Indirect;
+    use crate::matrix::Matrix4;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::tuple::Tuple;
+    use crate::world::{ReflectionMode, World};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn is_send_and_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_a_scene_can_be_shared_between_threads() {
+        is_send_and_sync::<Scene>();
+    }
+
+    #[test]
+    fn test_compiling_a_scene_leaves_the_world_alone() {
+        let mut w = World::default();
+        w.indirect = Indirect::Cached {
+            samples: 4,
+            max_error: 0.5,
+        };
+        let c = Camera::new(11, 11, FRAC_PI_2);
+        let scene = Scene::compile(&w, &c);
+        assert!(scene.irradiance_cache().is_some());
+        assert!(Scene::new(&w).irradiance_cache().is_none());
+        assert_eq!(w.dirty_objects().len(), 2);
+    }
+
+    #[test]
+    fn test_a_scene_sees_objects_moved_since_the_last_update() {
+        let mut w = World::default();
+        w.accel = Accel::Bvh;
+        w.update();
+        w.objects[0].set_transform(Matrix4::translation(0.0, 0.0, 10.0));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let scene = Scene::new(&w);
+        assert_eq!(scene.hit_sorted(r).unwrap().t, 4.5);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 5.0), r.direction);
+        assert_eq!(scene.object_id_at(r), Some(0));
+        assert!(scene.is_shadowed(&w.lights[0], Tuple::point(5.0, -5.0, 20.0)));
+    }
+
+    #[test]
+    fn test_a_scene_for_a_draft_camera_skips_expensive_effects() {
+        let mut w = World::default();
+        w.max_depth = 5;
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let scene = Scene::new(&w);
+        let full = scene.for_camera(&c);
+        assert!(full.shadows());
+        assert_eq!(full.max_depth(), 5);
+        assert_eq!(full.reflection_mode(), ReflectionMode::Traced);
+        c.quality = Quality::Draft;
+        let draft = scene.for_camera(&c);
+        assert!(!draft.shadows());
+        assert_eq!(draft.max_depth(), 1);
+        assert_eq!(draft.reflection_mode(), ReflectionMode::Environment);
+        assert_eq!(w.max_depth, 5);
+    }
+
+    #[test]
+    fn test_prepared_hits_keep_the_compiled_inverse() {
+        let mut w = World::default();
+        w.objects[1].set_transform(Matrix4::translation(0.0, 1.0, 0.0));
+        w.update();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.5, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let hit = Scene::new(&w).shading_hit(r).unwrap();
+        assert_eq!(hit.object_inverse, w.cached_inverse(0));
+        let mut unprepared = Intersection::new(hit.t, hit.object);
+        unprepared.prepare_hit(r);
+        assert_eq!(unprepared.normal_vector, hit.normal_vector);
+        assert_eq!(
+            hit.object.uv_at_hit_with(
+                hit.point.unwrap(),
+                &hit,
+                hit.object_inverse()
+            ),
+            hit.object.uv_at_hit(hit.point.unwrap(), &hit)
+        );
+    }
+}
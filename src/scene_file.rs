@@ -0,0 +1,1075 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cone::Cone;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::group::Group;
+use crate::light::PointLight;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::pattern::{
+    CheckersPattern, GradientPattern, Pattern, RingPattern, StripePattern,
+};
+use crate::plane::Plane;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::triangle::Triangle;
+use crate::tuple::Tuple;
+use crate::world::World;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// A world and a camera read from a scene description in the book's
+/// YAML format, which is a list of items that each either `add` a
+/// camera, a light or a shape, or `define` a named material or list of
+/// transforms for later items to refer to. JSON is also accepted, since
+/// it is valid YAML.
+///
+/// ```yaml
+/// - add: camera
+///   width: 100
+///   height: 50
+///   field-of-view: 0.785
+///   from: [0, 1.5, -5]
+///   to: [0, 1, 0]
+///   up: [0, 1, 0]
+///
+/// - add: light
+///   at: [-10, 10, -10]
+///   intensity: [1, 1, 1]
+///
+/// - define: shiny
+///   value:
+///     specular: 0.9
+///     reflective: 0.3
+///
+/// - define: red-shiny
+///   extend: shiny
+///   value:
+///     color: [1, 0.2, 0.2]
+///
+/// - add: sphere
+///   name: ball
+///   material: red-shiny
+///   transform:
+///     - [scale, 0.5, 0.5, 0.5]
+///     - [translate, 0, 1, 0]
+/// ```
+///
+/// Transforms are applied in the order they are listed, and may name
+/// defined lists of transforms. Besides the book's transforms, `[matrix,
+/// ...]` gives a whole matrix as sixteen numbers, row by row. Materials
+/// are either the name of a definition or the settings themselves, on
+/// top of the defaults. Shapes may be given a `name` to find them by. An
+/// item such as `- include: materials.yml` adds the items of another
+/// file in its place, sharing definitions.
+pub struct SceneFile {
+    pub world: World,
+    pub camera: Camera,
+    /// Every file the scene includes, directly or through other included
+    /// files.
+    pub includes: Vec<PathBuf>,
+    /// Problems that don't stop the scene from loading, but probably
+    /// aren't what was meant, such as definitions that are never used.
+    pub warnings: Vec<String>,
+}
+
+impl SceneFile {
+    /// Parses a scene description. The world has no lights except the
+    /// ones the scene adds, and the scene must add a camera. Included
+    /// files are found relative to the current directory.
+    pub fn parse(source: &str) -> io::Result<SceneFile> {
+        SceneFile::parse_in(source, Path::new(""))
+    }
+
+    /// Reads and parses a scene description. Included files are found
+    /// relative to the directory the file is in.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<SceneFile> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        SceneFile::parse_in(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    fn parse_in(source: &str, directory: &Path) -> io::Result<SceneFile> {
+        let mut loader = Loader::new();
+        loader.add_items(source, directory)?;
+        let mut unused: Vec<&String> = loader
+            .definitions
+            .keys()
+            .filter(|name| !loader.used.contains(*name))
+            .collect();
+        unused.sort();
+        for name in unused {
+            loader.warnings.push(format!("`{}` is never used", name));
+        }
+        let camera =
+            loader.camera.ok_or_else(|| invalid("missing a camera"))?;
+        Ok(SceneFile {
+            world: loader.world,
+            camera,
+            includes: loader.includes,
+            warnings: loader.warnings,
+        })
+    }
+}
+
+/// A setting of a shape in a scene file.
+pub enum SceneValue<'a> {
+    Number(f64),
+    Flag(bool),
+    Point(Tuple),
+    /// The shapes inside a group.
+    Shapes(&'a [Box<dyn Shape>]),
+}
+
+/// The settings particular to a kind of shape, by name, such as the
+/// height of a cylinder.
+pub type SceneSettings<'a> = Vec<(&'static str, SceneValue<'a>)>;
+
+impl World {
+    /// Describes the world as seen through the camera in the format
+    /// `SceneFile` reads, so that a world built in code can be saved,
+    /// edited and loaded again. Transforms are written as whole
+    /// matrices. Settings that scene files don't have, such as a light's
+    /// radius or a material's roughness, are left out, and shapes or
+    /// patterns that scene files can't describe are an error.
+    pub fn to_scene_file(&self, camera: &Camera) -> io::Result<String> {
+        let mut out = String::new();
+        // undo `Matrix4::view_transform`, whose rows are the camera's
+        // left, up and backward directions, where up and left are shorter
+        // than a unit by the sine of the angle between forward and the
+        // original up vector
+        let transform = camera.transform;
+        let row = |i: usize| {
+            let [x, y, z, _] = transform.row(i);
+            Tuple::vector(x, y, z)
+        };
+        let (true_up, forward) = (row(1), -row(2));
+        let cosine = (1.0 - true_up.dot(true_up)).max(0.0).sqrt();
+        let from = transform.inverse() * Tuple::point(0.0, 0.0, 0.0);
+        out += "- add: camera\n";
+        out += &format!("  width: {}\n", camera.hsize);
+        out += &format!("  height: {}\n", camera.vsize);
+        out += &format!(
+            "  field-of-view: {}\n",
+            format_number(camera.field_of_view)
+        );
+        out += &format!("  from: {}\n", format_tuple(from));
+        out += &format!("  to: {}\n", format_tuple(from + forward));
+        out += &format!("  up: {}\n", format_tuple(true_up + forward * cosine));
+        for light in self.lights.iter() {
+            out += "\n- add: light\n";
+            out += &format!("  at: {}\n", format_tuple(light.position));
+            out += &format!("  intensity: {}\n", format_color(light.intensity));
+        }
+        for object in self.objects.iter() {
+            out += "\n";
+            write_shape(&mut out, object.as_ref(), "")?;
+        }
+        Ok(out)
+    }
+}
+
+/// What has been read from a scene description and the files it
+/// includes so far.
+struct Loader {
+    world: World,
+    camera: Option<Camera>,
+    /// The values named by `define` items.
+    definitions: HashMap<String, Yaml>,
+    /// The names of the definitions that have been referred to.
+    used: HashSet<String>,
+    includes: Vec<PathBuf>,
+    /// The files being included, innermost last, to catch files that
+    /// include themselves.
+    including: Vec<PathBuf>,
+    /// The named transforms being expanded, innermost last, to catch
+    /// definitions that refer to themselves.
+    expanding: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Loader {
+    fn new() -> Loader {
+        Loader {
+            world: World::new(),
+            camera: None,
+            definitions: HashMap::new(),
+            used: HashSet::new(),
+            includes: vec![],
+            including: vec![],
+            expanding: vec![],
+            warnings: vec![],
+        }
+    }
+
+    /// Adds every item in a scene description, prefixing errors and
+    /// warnings with the position of the item they are about.
+    fn add_items(&mut self, source: &str, directory: &Path) -> io::Result<()> {
+        let documents = YamlLoader::load_from_str(source)
+            .map_err(|error| invalid(&error.to_string()))?;
+        let items = match documents.first() {
+            Some(Yaml::Array(items)) => items,
+            _ => return Err(invalid("expected a list of items")),
+        };
+        for (index, item) in items.iter().enumerate() {
+            let warnings = self.warnings.len();
+            self.add_item(item, directory).map_err(|error| {
+                invalid(&format!("item {}: {}", index + 1, error))
+            })?;
+            for warning in self.warnings[warnings..].iter_mut() {
+                *warning = format!("item {}: {}", index + 1, warning);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_item(&mut self, item: &Yaml, directory: &Path) -> io::Result<()> {
+        if !item["define"].is_badvalue() {
+            return self.define(item);
+        }
+        if !item["include"].is_badvalue() {
+            let path = item["include"]
+                .as_str()
+                .map(|path| directory.join(path))
+                .ok_or_else(|| invalid("an include must be a path"))?;
+            return self.include(&path);
+        }
+        match item["add"].as_str() {
+            Some("camera") => {
+                let camera = parse_camera(item)?;
+                if is_degenerate(camera.transform) {
+                    self.warnings.push(
+                        "the camera looks along its up vector, so it can't \
+                         be oriented"
+                            .to_string(),
+                    );
+                }
+                self.camera = Some(camera);
+            }
+            Some("light") => self.world.lights.push(parse_light(item)?),
+            Some(_) => {
+                let shape = self.shape(item)?;
+                self.world.objects.push(shape);
+            }
+            None => {
+                return Err(invalid(
+                    "every item must add, define or include something",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the items in another file, as if they were written in place
+    /// of the include.
+    fn include(&mut self, path: &Path) -> io::Result<()> {
+        if self.including.iter().any(|including| including == path) {
+            return Err(invalid(&format!(
+                "{} includes itself",
+                path.display()
+            )));
+        }
+        let source = fs::read_to_string(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("couldn't read {}: {}", path.display(), error),
+            )
+        })?;
+        if !self.includes.iter().any(|include| include == path) {
+            self.includes.push(path.to_path_buf());
+        }
+        self.including.push(path.to_path_buf());
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let result = self.add_items(&source, directory).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("{}: {}", path.display(), error),
+            )
+        });
+        self.including.pop();
+        result
+    }
+
+    /// Records a definition. A definition that extends another one adds
+    /// its settings on top of the other's, or its transforms after the
+    /// other's.
+    fn define(&mut self, item: &Yaml) -> io::Result<()> {
+        let name = item["define"]
+            .as_str()
+            .ok_or_else(|| invalid("a definition's name must be a string"))?;
+        let mut value = item["value"].clone();
+        if value.is_badvalue() {
+            return Err(invalid(&format!("`{}` has no value", name)));
+        }
+        if let Some(base) = item["extend"].as_str() {
+            value = match (self.lookup(base)?, value) {
+                (Yaml::Hash(mut merged), Yaml::Hash(settings)) => {
+                    merged.extend(settings);
+                    Yaml::Hash(merged)
+                }
+                (Yaml::Array(mut merged), Yaml::Array(transforms)) => {
+                    merged.extend(transforms);
+                    Yaml::Array(merged)
+                }
+                _ => {
+                    return Err(invalid(&format!(
+                        "`{}` can't extend `{}`, which is a different kind of \
+                         definition",
+                        name, base
+                    )))
+                }
+            };
+        }
+        self.definitions.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn lookup(&mut self, name: &str) -> io::Result<Yaml> {
+        let value =
+            self.definitions.get(name).cloned().ok_or_else(|| {
+                invalid(&format!("`{}` is not defined", name))
+            })?;
+        self.used.insert(name.to_string());
+        Ok(value)
+    }
+
+    fn shape(&mut self, item: &Yaml) -> io::Result<Box<dyn Shape>> {
+        let mut shape: Box<dyn Shape> = match item["add"].as_str() {
+            Some("sphere") => Box::new(Sphere::default()),
+            Some("plane") => Box::new(Plane::default()),
+            Some("cube") => Box::new(Cube::default()),
+            Some("cylinder") => {
+                let mut cylinder = Cylinder::default();
+                cylinder.minimum = number_or(&item["min"], cylinder.minimum)?;
+                cylinder.maximum = number_or(&item["max"], cylinder.maximum)?;
+                cylinder.closed = flag_or(&item["closed"], cylinder.closed)?;
+                Box::new(cylinder)
+            }
+            Some("cone") => {
+                let mut cone = Cone::default();
+                cone.minimum = number_or(&item["min"], cone.minimum)?;
+                cone.maximum = number_or(&item["max"], cone.maximum)?;
+                cone.closed = flag_or(&item["closed"], cone.closed)?;
+                Box::new(cone)
+            }
+            Some("triangle") => Box::new(Triangle::new(
+                point(&item["p1"])?,
+                point(&item["p2"])?,
+                point(&item["p3"])?,
+            )),
+            Some("group") => {
+                let children = match &item["children"] {
+                    Yaml::Array(children) => children
+                        .iter()
+                        .map(|child| self.shape(child))
+                        .collect::<io::Result<_>>()?,
+                    Yaml::BadValue => vec![],
+                    _ => {
+                        return Err(invalid(
+                            "a group's children must be a list",
+                        ))
+                    }
+                };
+                Box::new(Group::new(children))
+            }
+            Some(kind) => {
+                return Err(invalid(&format!(
+                    "unknown kind of item `{}`",
+                    kind
+                )))
+            }
+            None => return Err(invalid("expected a kind of shape to add")),
+        };
+        if !item["material"].is_badvalue() {
+            *shape.material_mut() = self.material(&item["material"])?;
+        }
+        if !item["transform"].is_badvalue() {
+            let transform = self.transform(&item["transform"])?;
+            if is_degenerate(transform) {
+                self.warnings.push(format!(
+                    "the transform of the {} flattens it, so it can't be \
+                     drawn",
+                    shape.kind()
+                ));
+            }
+            shape.set_transform(transform);
+        }
+        match &item["name"] {
+            Yaml::String(name) => shape.set_name(Some(name.clone())),
+            Yaml::BadValue => {}
+            _ => return Err(invalid("a shape's name must be a string")),
+        }
+        Ok(shape)
+    }
+
+    fn material(&mut self, value: &Yaml) -> io::Result<Material> {
+        let settings = match value {
+            Yaml::String(name) => self.lookup(name)?,
+            settings => settings.clone(),
+        };
+        let settings = settings
+            .as_hash()
+            .ok_or_else(|| invalid("a material must be a name or settings"))?;
+        let mut material = Material::default();
+        for (key, value) in settings.iter() {
+            match key.as_str().unwrap_or_default() {
+                "color" => material.color = color(value)?,
+                "ambient" => material.ambient = number(value)?,
+                "diffuse" => material.diffuse = number(value)?,
+                "specular" => material.specular = number(value)?,
+                "shininess" => material.shininess = number(value)?,
+                "reflective" => material.reflective = number(value)?,
+                "transparency" => material.transparency = number(value)?,
+                "refractive-index" => {
+                    material.refractive_index = number(value)?
+                }
+                "pattern" => material.pattern = Some(self.pattern(value)?),
+                key => {
+                    return Err(invalid(&format!(
+                        "unknown material setting `{}`",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(material)
+    }
+
+    /// Combines a list of transforms, where each one is either a list
+    /// such as `[rotate-x, 1.57]` or the name of a defined list.
+    fn transform(&mut self, value: &Yaml) -> io::Result<Matrix4> {
+        let steps = value
+            .as_vec()
+            .ok_or_else(|| invalid("transforms must be given as a list"))?;
+        let mut transform = Matrix4::identity();
+        for step in steps {
+            let step = match step {
+                Yaml::String(name) => {
+                    if self.expanding.contains(name) {
+                        return Err(invalid(&format!(
+                            "`{}` refers to itself",
+                            name
+                        )));
+                    }
+                    let steps = self.lookup(name)?;
+                    self.expanding.push(name.clone());
+                    let step = self.transform(&steps);
+                    self.expanding.pop();
+                    step?
+                }
+                Yaml::Array(words) => parse_transform(words)?,
+                _ => return Err(invalid("expected a transform")),
+            };
+            transform = step * transform;
+        }
+        Ok(transform)
+    }
+
+    fn pattern(&mut self, value: &Yaml) -> io::Result<Arc<dyn Pattern>> {
+        let colors = value["colors"]
+            .as_vec()
+            .filter(|colors| colors.len() == 2)
+            .ok_or_else(|| invalid("a pattern needs a list of two colors"))?;
+        let (a, b) = (color(&colors[0])?, color(&colors[1])?);
+        let transform = if value["transform"].is_badvalue() {
+            Matrix4::identity()
+        } else {
+            self.transform(&value["transform"])?
+        };
+        if is_degenerate(transform) {
+            self.warnings.push(
+                "the transform of a pattern flattens it, so it can't be drawn"
+                    .to_string(),
+            );
+        }
+        let pattern: Arc<dyn Pattern> = match value["type"].as_str() {
+            Some("stripes") => {
+                let mut pattern = StripePattern::new(a, b);
+                pattern.transform = transform;
+                Arc::new(pattern)
+            }
+            Some("gradient") => {
+                let mut pattern = GradientPattern::new(a, b);
+                pattern.transform = transform;
+                Arc::new(pattern)
+            }
+            Some("rings") => {
+                let mut pattern = RingPattern::new(a, b);
+                pattern.transform = transform;
+                Arc::new(pattern)
+            }
+            Some("checkers") => {
+                let mut pattern = CheckersPattern::new(a, b);
+                pattern.transform = transform;
+                Arc::new(pattern)
+            }
+            _ => return Err(invalid("unknown type of pattern")),
+        };
+        Ok(pattern)
+    }
+}
+
+fn parse_camera(item: &Yaml) -> io::Result<Camera> {
+    let size = |value: &Yaml| match value.as_i64() {
+        Some(size) if size > 0 => Ok(size as usize),
+        _ => Err(invalid("the camera's size must be a positive integer")),
+    };
+    let mut camera = Camera::new(
+        size(&item["width"])?,
+        size(&item["height"])?,
+        number(&item["field-of-view"])?,
+    );
+    camera.transform = Matrix4::view_transform(
+        point(&item["from"])?,
+        point(&item["to"])?,
+        vector(&item["up"])?,
+    );
+    Ok(camera)
+}
+
+fn parse_light(item: &Yaml) -> io::Result<PointLight> {
+    Ok(PointLight::new(
+        point(&item["at"])?,
+        color(&item["intensity"])?,
+    ))
+}
+
+fn parse_transform(words: &[Yaml]) -> io::Result<Matrix4> {
+    let operation = words
+        .first()
+        .and_then(Yaml::as_str)
+        .ok_or_else(|| invalid("a transform must start with its name"))?;
+    let arguments = words[1..]
+        .iter()
+        .map(number)
+        .collect::<io::Result<Vec<f64>>>()?;
+    let expected = match operation {
+        "translate" | "scale" => 3,
+        "rotate-x" | "rotate-y" | "rotate-z" => 1,
+        "shear" => 6,
+        "matrix" => 16,
+        _ => {
+            return Err(invalid(&format!("unknown transform `{}`", operation)))
+        }
+    };
+    if arguments.len() != expected {
+        return Err(invalid(&format!(
+            "`{}` takes {} numbers",
+            operation, expected
+        )));
+    }
+    let a = &arguments;
+    Ok(match operation {
+        "translate" => Matrix4::translation(a[0], a[1], a[2]),
+        "scale" => Matrix4::scaling(a[0], a[1], a[2]),
+        "rotate-x" => Matrix4::rotation_x(a[0]),
+        "rotate-y" => Matrix4::rotation_y(a[0]),
+        "rotate-z" => Matrix4::rotation_z(a[0]),
+        "shear" => Matrix4::shearing(a[0], a[1], a[2], a[3], a[4], a[5]),
+        _ => Matrix4::from_rows([
+            [a[0], a[1], a[2], a[3]],
+            [a[4], a[5], a[6], a[7]],
+            [a[8], a[9], a[10], a[11]],
+            [a[12], a[13], a[14], a[15]],
+        ]),
+    })
+}
+
+/// Returns whether a transform flattens everything onto a plane, line
+/// or point, so that it can't be inverted. Tiny scales are fine.
+fn is_degenerate(transform: Matrix4) -> bool {
+    let determinant = transform.determinant();
+    determinant == 0.0 || !determinant.is_finite()
+}
+
+/// Reads a number, which YAML distinguishes into integers and reals.
+fn number(value: &Yaml) -> io::Result<f64> {
+    match value {
+        Yaml::Integer(value) => Ok(*value as f64),
+        Yaml::Real(_) => Ok(value.as_f64().unwrap()),
+        _ => Err(invalid("expected a number")),
+    }
+}
+
+fn number_or(value: &Yaml, default: f64) -> io::Result<f64> {
+    if value.is_badvalue() {
+        Ok(default)
+    } else {
+        number(value)
+    }
+}
+
+fn flag_or(value: &Yaml, default: bool) -> io::Result<bool> {
+    match value {
+        Yaml::BadValue => Ok(default),
+        Yaml::Boolean(flag) => Ok(*flag),
+        _ => Err(invalid("expected true or false")),
+    }
+}
+
+fn triple(value: &Yaml) -> io::Result<(f64, f64, f64)> {
+    match value.as_vec() {
+        Some(values) if values.len() == 3 => Ok((
+            number(&values[0])?,
+            number(&values[1])?,
+            number(&values[2])?,
+        )),
+        _ => Err(invalid("expected a list of three numbers")),
+    }
+}
+
+fn point(value: &Yaml) -> io::Result<Tuple> {
+    let (x, y, z) = triple(value)?;
+    Ok(Tuple::point(x, y, z))
+}
+
+fn vector(value: &Yaml) -> io::Result<Tuple> {
+    let (x, y, z) = triple(value)?;
+    Ok(Tuple::vector(x, y, z))
+}
+
+fn color(value: &Yaml) -> io::Result<Color> {
+    let (red, green, blue) = triple(value)?;
+    Ok(Color::new(red, green, blue))
+}
+
+/// Writes a shape as an item of a list, with every line after the
+/// first indented to line up with it.
+fn write_shape(
+    out: &mut String,
+    shape: &dyn Shape,
+    indent: &str,
+) -> io::Result<()> {
+    let settings = shape.scene_settings().ok_or_else(|| {
+        invalid(&format!(
+            "a {} can't be written to a scene file",
+            shape.kind()
+        ))
+    })?;
+    *out += &format!("{}- add: {}\n", indent, shape.kind());
+    let indent = format!("{}  ", indent);
+    if let Some(name) = shape.name() {
+        // quoted and escaped the same way in YAML as in Rust
+        *out += &format!("{}name: {:?}\n", indent, name);
+    }
+    for (key, value) in settings {
+        match value {
+            SceneValue::Number(number) => {
+                *out +=
+                    &format!("{}{}: {}\n", indent, key, format_number(number))
+            }
+            SceneValue::Flag(flag) => {
+                *out += &format!("{}{}: {}\n", indent, key, flag)
+            }
+            SceneValue::Point(point) => {
+                *out += &format!("{}{}: {}\n", indent, key, format_tuple(point))
+            }
+            SceneValue::Shapes(shapes) => {
+                *out += &format!("{}{}:\n", indent, key);
+                for shape in shapes {
+                    write_shape(out, shape.as_ref(), &indent)?;
+                }
+            }
+        }
+    }
+    write_material(out, shape.material(), &indent)?;
+    let transform = shape.transform();
+    if transform != Matrix4::identity() {
+        let numbers: Vec<String> = (0..4)
+            .flat_map(|row| transform.row(row).to_vec())
+            .map(format_number)
+            .collect();
+        *out += &format!("{}transform:\n", indent);
+        *out += &format!("{}  - [matrix, {}]\n", indent, numbers.join(", "));
+    }
+    Ok(())
+}
+
+/// Writes the settings that differ from the default material, if any.
+fn write_material(
+    out: &mut String,
+    material: &Material,
+    indent: &str,
+) -> io::Result<()> {
+    if material.pattern.is_some() {
+        return Err(invalid("patterns can't be written to scene files"));
+    }
+    let default = Material::default();
+    let mut settings = vec![];
+    if material.color != default.color {
+        settings.push(("color", format_color(material.color)));
+    }
+    let numbers = [
+        ("ambient", material.ambient, default.ambient),
+        ("diffuse", material.diffuse, default.diffuse),
+        ("specular", material.specular, default.specular),
+        ("shininess", material.shininess, default.shininess),
+        ("reflective", material.reflective, default.reflective),
+        ("transparency", material.transparency, default.transparency),
+        (
+            "refractive-index",
+            material.refractive_index,
+            default.refractive_index,
+        ),
+    ];
+    for &(key, value, default) in numbers.iter() {
+        if value != default {
+            settings.push((key, format_number(value)));
+        }
+    }
+    if !settings.is_empty() {
+        *out += &format!("{}material:\n", indent);
+        for (key, value) in settings {
+            *out += &format!("{}  {}: {}\n", indent, key, value);
+        }
+    }
+    Ok(())
+}
+
+/// Formats a number so that it reads back exactly, using YAML's names
+/// for infinities.
+fn format_number(number: f64) -> String {
+    if number == f64::INFINITY {
+        ".inf".to_string()
+    } else if number == f64::NEG_INFINITY {
+        "-.inf".to_string()
+    } else {
+        format!("{:?}", number)
+    }
+}
+
+fn format_tuple(tuple: Tuple) -> String {
+    format!(
+        "[{}, {}, {}]",
+        format_number(tuple.x),
+        format_number(tuple.y),
+        format_number(tuple.z)
+    )
+}
+
+fn format_color(color: Color) -> String {
+    format!(
+        "[{}, {}, {}]",
+        format_number(color.red),
+        format_number(color.green),
+        format_number(color.blue)
+    )
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::cone::Cone;
+    use crate::cylinder::Cylinder;
+    use crate::group::Group;
+    use crate::light::PointLight;
+    use crate::matrix::Matrix4;
+    use crate::pattern::StripePattern;
+    use crate::scene_file::SceneFile;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::FRAC_PI_2;
+    use std::io;
+    use std::sync::Arc;
+
+    const SCENE: &str = "
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [0, 1.5, -5]
+  to: [0, 1, 0]
+  up: [0, 1, 0]
+
+- add: light
+  at: [-10, 10, -10]
+  intensity: [1, 1, 1]
+
+- define: white-material
+  value:
+    color: [1, 1, 1]
+    diffuse: 0.7
+    ambient: 0.1
+
+- define: blue-material
+  extend: white-material
+  value:
+    color: [0.5, 0.5, 1]
+
+- define: standard-transform
+  value:
+    - [translate, 1, -1, 1]
+    - [scale, 0.5, 0.5, 0.5]
+
+- add: cube
+  name: box
+  material: blue-material
+  transform:
+    - standard-transform
+    - [translate, 4, 0, 0]
+
+- add: cylinder
+  min: 0
+  max: 2
+  closed: true
+  material:
+    color: [1, 0, 0]
+    pattern:
+      type: stripes
+      colors: [[1, 1, 1], [0, 0, 0]]
+";
+
+    #[test]
+    fn test_loading_a_scene() {
+        let scene = SceneFile::parse(SCENE).unwrap();
+        assert_eq!(scene.camera.hsize, 100);
+        assert_eq!(scene.camera.vsize, 50);
+        assert_eq!(
+            scene.camera.transform,
+            Matrix4::view_transform(
+                Tuple::point(0.0, 1.5, -5.0),
+                Tuple::point(0.0, 1.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            )
+        );
+        assert_eq!(scene.world.lights.len(), 1);
+        assert_eq!(
+            scene.world.lights[0].position,
+            Tuple::point(-10.0, 10.0, -10.0)
+        );
+        let objects = &scene.world.objects;
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].kind(), "cube");
+        assert_eq!(objects[0].name(), Some("box"));
+        assert_eq!(objects[1].kind(), "cylinder");
+        assert_eq!(objects[1].name(), None);
+        assert!(objects[1].material().pattern.is_some());
+        assert_eq!(objects[1].bounds().1.y, 2.0);
+    }
+
+    #[test]
+    fn test_extended_definitions_inherit_settings() {
+        let scene = SceneFile::parse(SCENE).unwrap();
+        let material = scene.world.objects[0].material();
+        assert_eq!(material.color, Color::new(0.5, 0.5, 1.0));
+        assert_eq!(material.diffuse, 0.7);
+        assert_eq!(material.ambient, 0.1);
+        assert_eq!(material.specular, 0.9);
+    }
+
+    #[test]
+    fn test_transforms_are_applied_in_order() {
+        let scene = SceneFile::parse(SCENE).unwrap();
+        assert_eq!(
+            scene.world.objects[0].transform(),
+            Matrix4::translation(4.0, 0.0, 0.0)
+                * Matrix4::scaling(0.5, 0.5, 0.5)
+                * Matrix4::translation(1.0, -1.0, 1.0)
+        );
+        let json = r#"[
+            {"add": "camera", "width": 10, "height": 10,
+             "field-of-view": 1.5707963267948966, "from": [0, 0, -5],
+             "to": [0, 0, 0], "up": [0, 1, 0]},
+            {"add": "group", "transform": [["rotate-z", 1.5707963267948966]],
+             "children": [{"add": "sphere",
+                           "transform": [["translate", 2, 0, 0]]}]}
+        ]"#;
+        let scene = SceneFile::parse(json).unwrap();
+        assert_eq!(scene.camera.field_of_view, FRAC_PI_2);
+        let group = &scene.world.objects[0];
+        assert_eq!(group.kind(), "group");
+        assert_eq!(
+            group.bounds(),
+            (Tuple::point(-1.0, 1.0, -1.0), Tuple::point(1.0, 3.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_writing_a_world_to_a_scene_file() {
+        let mut w = World::new();
+        w.lights.push(PointLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Color::new(0.5, 0.5, 0.5),
+        ));
+        let mut s = Sphere::default();
+        s.material.color = Color::new(1.0, 0.2, 0.2);
+        s.material.reflective = 0.3;
+        s.name = Some("the \"red\" ball".to_string());
+        s.transform = Matrix4::translation(0.0, 1.0, 0.0)
+            * Matrix4::rotation_y(0.5)
+            * Matrix4::scaling(0.5, 0.5, 0.5);
+        let mut cylinder = Cylinder::default();
+        cylinder.minimum = 0.0;
+        cylinder.maximum = 2.0;
+        cylinder.closed = true;
+        let triangle = Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        );
+        let group = Group::new(vec![Box::new(cylinder), Box::new(triangle)]);
+        w.objects =
+            vec![Box::new(s), Box::new(Cone::default()), Box::new(group)];
+        let mut c = Camera::new(40, 20, 1.2);
+        c.transform = Matrix4::view_transform(
+            Tuple::point(1.0, 1.5, -5.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+
+        let written = w.to_scene_file(&c).unwrap();
+        let scene = SceneFile::parse(&written).unwrap();
+        assert_eq!(scene.camera.hsize, 40);
+        assert_eq!(scene.camera.field_of_view, 1.2);
+        assert_eq!(scene.camera.transform, c.transform);
+        assert_eq!(scene.world.lights, w.lights);
+        let objects = &scene.world.objects;
+        let kinds: Vec<&str> = objects.iter().map(|o| o.kind()).collect();
+        assert_eq!(kinds, ["sphere", "cone", "group"]);
+        // the cone is infinitely long
+        assert!(written.contains("min: -.inf"));
+        assert_eq!(objects[0].name(), Some("the \"red\" ball"));
+        assert_eq!(objects[0].transform(), w.objects[0].transform());
+        assert_eq!(objects[0].material(), w.objects[0].material());
+        assert_eq!(objects[2].bounds(), w.objects[2].bounds());
+        assert_eq!(objects[2].triangle_count(), 1);
+        // the world and camera are otherwise unchanged
+        assert_eq!(scene.world.to_scene_file(&c).unwrap(), written);
+    }
+
+    #[test]
+    fn test_shapes_scene_files_cant_describe_are_not_written() {
+        let c = Camera::new(10, 10, 1.0);
+        let mut w = World::new();
+        let mut s = Sphere::default();
+        s.radius = 2.0;
+        w.objects = vec![Box::new(s)];
+        assert!(w.to_scene_file(&c).is_err());
+        let mut s = Sphere::default();
+        s.material.pattern =
+            Some(Arc::new(StripePattern::new(Color::white(), Color::black())));
+        w.objects = vec![Box::new(s)];
+        assert!(w.to_scene_file(&c).is_err());
+    }
+
+    #[test]
+    fn test_including_other_scene_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("scene-file-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("parts")).unwrap();
+        std::fs::write(
+            dir.join("parts/materials.yml"),
+            "- define: red\n  value:\n    color: [1, 0, 0]\n\
+             - include: light.yml\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("parts/light.yml"),
+            "- add: light\n  at: [0, 10, 0]\n  intensity: [1, 1, 1]\n",
+        )
+        .unwrap();
+        let camera = &SCENE[..SCENE.find("- add: light").unwrap()];
+        let main = format!(
+            "{}- include: parts/materials.yml\n\
+             - add: sphere\n  material: red\n",
+            camera
+        );
+        std::fs::write(dir.join("main.yml"), &main).unwrap();
+        std::fs::write(dir.join("loop.yml"), "- include: loop.yml\n").unwrap();
+
+        let scene = SceneFile::load(dir.join("main.yml")).unwrap();
+        assert_eq!(scene.world.lights.len(), 1);
+        assert_eq!(
+            scene.world.objects[0].material().color,
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            scene.includes,
+            [dir.join("parts/materials.yml"), dir.join("parts/light.yml")]
+        );
+        let error = SceneFile::load(dir.join("loop.yml")).err().unwrap();
+        assert!(error.to_string().contains("includes itself"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_warning_about_likely_mistakes() {
+        let scene = SceneFile::parse(SCENE).unwrap();
+        assert!(scene.warnings.is_empty());
+        let source = format!(
+            "{}- define: unused\n  value: []\n\
+             - add: plane\n  transform:\n    - [scale, 1, 0, 1]\n",
+            SCENE
+        );
+        let scene = SceneFile::parse(&source).unwrap();
+        assert_eq!(
+            scene.warnings,
+            [
+                "item 9: the transform of the plane flattens it, so it \
+                 can't be drawn",
+                "`unused` is never used",
+            ]
+        );
+        // errors say which item they are about
+        let source = format!("{}- add: teapot\n", SCENE);
+        let error = SceneFile::parse(&source).err().unwrap();
+        assert_eq!(error.to_string(), "item 8: unknown kind of item `teapot`");
+    }
+
+    #[test]
+    fn test_malformed_scenes_are_rejected() {
+        let no_camera = "- add: sphere\n";
+        let error = SceneFile::parse(no_camera).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let camera = &SCENE[..SCENE.find("- add: light").unwrap()];
+        for item in [
+            "- add: teapot\n",
+            "- add: sphere\n  name: [a, b]\n",
+            "- add: sphere\n  material: undefined\n",
+            "- add: sphere\n  material:\n    colour: [1, 0, 0]\n",
+            "- add: sphere\n  transform:\n    - [scale, 2]\n",
+            "- add: sphere\n  transform:\n    - [spin, 2]\n",
+            "- add: light\n  at: [0, 0]\n  intensity: [1, 1, 1]\n",
+            "- define: a\n  extend: b\n  value: {}\n",
+            "- [not, an, item]\n",
+        ]
+        .iter()
+        {
+            let source = format!("{}{}", camera, item);
+            assert!(SceneFile::parse(&source).is_err(), "{}", item);
+        }
+    }
+
+    #[test]
+    fn test_transforms_that_refer_to_themselves_are_rejected() {
+        let camera = &SCENE[..SCENE.find("- add: light").unwrap()];
+        let source = format!(
+            "{}- define: a\n  value: [b]\n\
+             - define: b\n  value: [a]\n\
+             - add: sphere\n  transform: [a]\n",
+            camera
+        );
+        let error = SceneFile::parse(&source).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("`a` refers to itself"));
+        let twice = format!(
+            "{}- define: a\n  value:\n    - [scale, 2, 2, 2]\n\
+             - add: sphere\n  transform: [a, a]\n",
+            camera
+        );
+        assert!(SceneFile::parse(&twice).is_ok());
+    }
+}
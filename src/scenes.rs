@@ -0,0 +1,230 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::light::PointLight;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::plane::Plane;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::tuple::Tuple;
+use crate::world::World;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// The names of the built-in scenes, as `by_name` knows them.
+pub const NAMES: &[&str] = &["cornell", "chapter7", "chapter9"];
+
+/// Builds the built-in scene with the given name, or returns `None` for
+/// names that aren't in `NAMES`.
+pub fn by_name(name: &str) -> Option<(World, Camera)> {
+    match name {
+        "cornell" => Some(cornell_box()),
+        "chapter7" => Some(chapter7()),
+        "chapter9" => Some(chapter9()),
+        _ => None,
+    }
+}
+
+/// A Cornell box: a white room with a red left wall and a green right
+/// wall, lit from just below the ceiling, with a short and a tall block.
+/// The room is two units on a side, with the floor at y = 0 and the open
+/// side facing the camera.
+pub fn cornell_box() -> (World, Camera) {
+    let white = Color::new(0.73, 0.73, 0.73);
+    let wall = |transform: Matrix4, color: Color| -> Box<dyn Shape> {
+        Box::new(Plane {
+            transform,
+            material: matte(color),
+            ..Plane::default()
+        })
+    };
+    let block = |transform: Matrix4| -> Box<dyn Shape> {
+        Box::new(Cube {
+            transform,
+            material: matte(white),
+            ..Cube::default()
+        })
+    };
+
+    let mut world = World::new();
+    world.lights =
+        vec![PointLight::new(Tuple::point(0.0, 1.9, 0.0), Color::white())];
+    world.objects = vec![
+        wall(Matrix4::identity(), white),
+        wall(Matrix4::translation(0.0, 2.0, 0.0), white),
+        wall(
+            Matrix4::translation(0.0, 0.0, 1.0)
+                * Matrix4::rotation_x(FRAC_PI_2),
+            white,
+        ),
+        wall(
+            Matrix4::translation(-1.0, 0.0, 0.0)
+                * Matrix4::rotation_z(FRAC_PI_2),
+            Color::new(0.65, 0.05, 0.05),
+        ),
+        wall(
+            Matrix4::translation(1.0, 0.0, 0.0)
+                * Matrix4::rotation_z(FRAC_PI_2),
+            Color::new(0.12, 0.45, 0.15),
+        ),
+        block(
+            Matrix4::translation(0.35, 0.3, -0.3)
+                * Matrix4::rotation_y(-0.3)
+                * Matrix4::scaling(0.3, 0.3, 0.3),
+        ),
+        block(
+            Matrix4::translation(-0.35, 0.6, 0.3)
+                * Matrix4::rotation_y(0.3)
+                * Matrix4::scaling(0.3, 0.6, 0.3),
+        ),
+    ];
+
+    let mut camera = Camera::new(256, 256, 0.8);
+    camera.transform = Matrix4::view_transform(
+        Tuple::point(0.0, 1.0, -3.4),
+        Tuple::point(0.0, 1.0, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    );
+    (world, camera)
+}
+
+/// The scene at the end of the book's chapter 7: three spheres in a
+/// room whose floor and walls are flattened spheres.
+#[allow(clippy::field_reassign_with_default)]
+pub fn chapter7() -> (World, Camera) {
+    let wall_material = matte(Color::new(1.0, 0.9, 0.9));
+    let wall = |transform: Matrix4| -> Box<dyn Shape> {
+        let mut wall = Sphere::default();
+        wall.transform = transform * Matrix4::scaling(10.0, 0.01, 10.0);
+        wall.material = wall_material.clone();
+        Box::new(wall)
+    };
+
+    let mut world = World::new();
+    world.lights = vec![PointLight::new(
+        Tuple::point(-10.0, 10.0, -10.0),
+        Color::white(),
+    )];
+    world.objects = vec![
+        wall(Matrix4::identity()),
+        wall(
+            Matrix4::translation(0.0, 0.0, 5.0)
+                * Matrix4::rotation_y(-FRAC_PI_4)
+                * Matrix4::rotation_x(FRAC_PI_2),
+        ),
+        wall(
+            Matrix4::translation(0.0, 0.0, 5.0)
+                * Matrix4::rotation_y(FRAC_PI_4)
+                * Matrix4::rotation_x(FRAC_PI_2),
+        ),
+    ];
+    world.objects.extend(three_spheres([
+        Color::new(0.1, 1.0, 0.5),
+        Color::new(0.5, 1.0, 0.1),
+        Color::new(1.0, 0.8, 0.1),
+    ]));
+    (world, book_camera(300, 150))
+}
+
+/// The scene at the end of the book's chapter 9, which is chapter 7's
+/// with planes for the floor and walls.
+pub fn chapter9() -> (World, Camera) {
+    let wall = |transform: Matrix4| -> Box<dyn Shape> {
+        Box::new(Plane {
+            transform,
+            material: matte(Color::new(0.9, 0.9, 0.9)),
+            ..Plane::default()
+        })
+    };
+
+    let mut world = World::new();
+    world.lights = vec![PointLight::new(
+        Tuple::point(-10.0, 10.0, -10.0),
+        Color::white(),
+    )];
+    world.objects = vec![
+        wall(Matrix4::identity()),
+        wall(
+            Matrix4::translation(0.0, 0.0, 5.0)
+                * Matrix4::rotation_y(-FRAC_PI_4)
+                * Matrix4::rotation_x(FRAC_PI_2),
+        ),
+        wall(
+            Matrix4::translation(0.0, 0.0, 5.0)
+                * Matrix4::rotation_y(FRAC_PI_4)
+                * Matrix4::rotation_x(FRAC_PI_2),
+        ),
+    ];
+    world.objects.extend(three_spheres([
+        Color::new(0.0, 1.0, 0.0),
+        Color::new(0.0, 0.0, 1.0),
+        Color::new(1.0, 0.0, 0.0),
+    ]));
+    (world, book_camera(500, 250))
+}
+
+/// A material without highlights.
+fn matte(color: Color) -> Material {
+    Material {
+        color,
+        specular: 0.0,
+        ..Material::default()
+    }
+}
+
+/// The large middle, small right and smaller left spheres of the book's
+/// scenes, in the given colors.
+#[allow(clippy::field_reassign_with_default)]
+fn three_spheres(colors: [Color; 3]) -> Vec<Box<dyn Shape>> {
+    let transforms = [
+        Matrix4::translation(-0.5, 1.0, 0.5),
+        Matrix4::translation(1.5, 0.5, -0.5) * Matrix4::scaling(0.5, 0.5, 0.5),
+        Matrix4::translation(-1.5, 0.33, -0.75)
+            * Matrix4::scaling(0.33, 0.33, 0.33),
+    ];
+    transforms
+        .iter()
+        .zip(colors.iter())
+        .map(|(&transform, &color)| -> Box<dyn Shape> {
+            let mut sphere = Sphere::default();
+            sphere.transform = transform;
+            sphere.material.color = color;
+            sphere.material.diffuse = 0.7;
+            sphere.material.specular = 0.3;
+            Box::new(sphere)
+        })
+        .collect()
+}
+
+/// The camera of the book's scenes, looking into the room from above
+/// the floor.
+fn book_camera(hsize: usize, vsize: usize) -> Camera {
+    let mut camera = Camera::new(hsize, vsize, PI / 3.0);
+    camera.transform = Matrix4::view_transform(
+        Tuple::point(0.0, 1.5, -5.0),
+        Tuple::point(0.0, 1.0, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    );
+    camera
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+    use crate::scene::Scene;
+    use crate::scenes::{by_name, NAMES};
+
+    #[test]
+    fn test_building_the_built_in_scenes() {
+        for &name in NAMES {
+            let (world, camera) = by_name(name).unwrap();
+            assert_eq!(world.lights.len(), 1);
+            let hit = Scene::new(&world).color_at(camera.pixel_to_ray(
+                camera.hsize as f64 / 2.0,
+                camera.vsize as f64 / 2.0,
+            ));
+            assert_ne!(hit, Color::black(), "{}", name);
+        }
+        assert!(by_name("teapot").is_none());
+    }
+}
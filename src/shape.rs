@@ -0,0 +1,228 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::metadata::SceneHash;
+use crate::ray::Ray;
+use crate::scene_file::SceneSettings;
+use crate::tuple::Tuple;
+use std::fmt::Debug;
+use std::hash::Hasher;
+use std::mem::size_of_val;
+
+/// Something that can be placed in a world and hit by rays. Every shape
+/// is defined in its own object space, and its transformation places it
+/// in the world; the provided methods take care of converting between
+/// the two, so new kinds of shapes only need to implement the `local_`
+/// methods.
+pub trait Shape: Debug + Send + Sync {
+    /// A short name for the kind of shape, such as `"sphere"`.
+    fn kind(&self) -> &'static str;
+
+    fn transform(&self) -> Matrix4;
+
+    fn set_transform(&mut self, transform: Matrix4);
+
+    fn material(&self) -> &Material;
+
+    fn material_mut(&mut self) -> &mut Material;
+
+    /// The name the shape was given, which identifies it however the
+    /// world's objects are ordered, or `None` if it has none.
+    fn name(&self) -> Option<&str>;
+
+    fn set_name(&mut self, name: Option<String>);
+
+    /// The material seen from inside the shape. `None` uses the same
+    /// material on both sides.
+    fn back_material(&self) -> Option<&Material> {
+        None
+    }
+
+    /// Intersects the shape with a ray that has already been transformed
+    /// into object space.
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>>;
+
+    /// Returns the normal at a point in object space, in object space.
+    fn local_normal_at(&self, point: Tuple) -> Tuple;
+
+    /// Returns the normal at a point in object space for a particular
+    /// hit, for shapes made of many faces that need to know which face
+    /// was hit. Other shapes only need the point.
+    fn local_normal_at_hit(&self, point: Tuple, _hit: &Intersection) -> Tuple {
+        self.local_normal_at(point)
+    }
+
+    /// Returns texture coordinates for a point in object space.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64);
+
+    /// Returns texture coordinates for a point in object space for a
+    /// particular hit, like `local_normal_at_hit`, so that shapes made of
+    /// many faces don't have to search for the face that was hit.
+    fn local_uv_at_hit(&self, point: Tuple, _hit: &Intersection) -> (f64, f64) {
+        self.local_uv_at(point)
+    }
+
+    /// Returns the point in object space with the given texture
+    /// coordinates, undoing `local_uv_at`, or `None` for shapes whose
+    /// texture coordinates can't be turned back into a point.
+    fn local_point_at_uv(&self, _u: f64, _v: f64) -> Option<Tuple> {
+        None
+    }
+
+    /// Returns the minimum and maximum corners of an axis-aligned box in
+    /// object space that contains the shape.
+    fn local_bounds(&self) -> (Tuple, Tuple);
+
+    fn box_clone(&self) -> Box<dyn Shape>;
+
+    fn intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        self.local_intersect(ray.transform(self.transform().inverse()))
+    }
+
+    fn normal_at(&self, world_point: Tuple) -> Tuple {
+        self.normal_at_with(world_point, self.transform().inverse())
+    }
+
+    /// Like `normal_at`, given the inverse of the shape's transformation,
+    /// such as the one a `Scene` keeps, so that it isn't inverted again.
+    fn normal_at_with(&self, world_point: Tuple, inverse: Matrix4) -> Tuple {
+        let object_normal = self.local_normal_at(inverse * world_point);
+        normal_to_world(inverse, object_normal)
+    }
+
+    fn normal_at_hit(&self, world_point: Tuple, hit: &Intersection) -> Tuple {
+        self.normal_at_hit_with(world_point, hit, self.transform().inverse())
+    }
+
+    /// Like `normal_at_hit`, given the inverse of the shape's
+    /// transformation.
+    fn normal_at_hit_with(
+        &self,
+        world_point: Tuple,
+        hit: &Intersection,
+        inverse: Matrix4,
+    ) -> Tuple {
+        let object_normal =
+            self.local_normal_at_hit(inverse * world_point, hit);
+        normal_to_world(inverse, object_normal)
+    }
+
+    /// The number of triangles the shape is made of, for statistics.
+    fn triangle_count(&self) -> usize {
+        0
+    }
+
+    /// The memory used by the shape, including what it owns on the heap.
+    fn memory_bytes(&self) -> usize {
+        size_of_val(self)
+    }
+
+    /// Splits shapes made of other shapes into a hierarchy of subgroups
+    /// with bounding boxes, where no group holds `threshold` or more
+    /// shapes that could be split further. Other shapes are unchanged.
+    fn divide(&mut self, _threshold: usize) {}
+
+    /// Returns the settings that describe the shape in a scene file,
+    /// besides its material and transform, or `None` for shapes that
+    /// scene files can't describe.
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        None
+    }
+
+    /// Feeds the settings particular to the kind of shape into a hasher,
+    /// for `scene_hash`. These are the `scene_settings`, unless the shape
+    /// hashes its own because scene files can't describe it.
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        if let Some(settings) = self.scene_settings() {
+            for (key, value) in settings {
+                key.hash_into(state);
+                value.hash_into(state);
+            }
+        }
+    }
+
+    /// Returns texture coordinates for a point on the shape in world
+    /// space.
+    fn uv_at(&self, world_point: Tuple) -> (f64, f64) {
+        self.local_uv_at(self.transform().inverse() * world_point)
+    }
+
+    fn uv_at_hit(&self, world_point: Tuple, hit: &Intersection) -> (f64, f64) {
+        self.uv_at_hit_with(world_point, hit, self.transform().inverse())
+    }
+
+    /// Like `uv_at_hit`, given the inverse of the shape's transformation.
+    fn uv_at_hit_with(
+        &self,
+        world_point: Tuple,
+        hit: &Intersection,
+        inverse: Matrix4,
+    ) -> (f64, f64) {
+        self.local_uv_at_hit(inverse * world_point, hit)
+    }
+
+    /// Returns the box around the shape in object space.
+    fn bounds_of(&self) -> BoundingBox {
+        BoundingBox::from(self.local_bounds())
+    }
+
+    /// Returns the minimum and maximum corners of an axis-aligned box in
+    /// world space that contains the shape. Shapes that are infinite in
+    /// object space, such as planes, are unbounded in every direction.
+    fn bounds(&self) -> (Tuple, Tuple) {
+        self.bounds_of().transform(self.transform()).into()
+    }
+}
+
+/// Turns a normal in object space into a normalized one in world space,
+/// given the inverse of the object's transformation. This is what
+/// `Matrix4::to_normal_matrix` does, without inverting again.
+fn normal_to_world(inverse: Matrix4, object_normal: Tuple) -> Tuple {
+    (inverse.transpose().submatrix(3, 3) * object_normal).normalize()
+}
+
+impl Clone for Box<dyn Shape> {
+    fn clone(&self) -> Box<dyn Shape> {
+        self.box_clone()
+    }
+}
+
+/// Shapes are compared by identity, so that two identical shapes in a
+/// world are still told apart.
+impl<'a> PartialEq for dyn Shape + 'a {
+    fn eq(&self, other: &(dyn Shape + 'a)) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::Matrix4;
+    use crate::shape::Shape;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+
+    #[test]
+    fn test_shapes_are_compared_by_identity() {
+        let a: Box<dyn Shape> = Box::new(Sphere::default());
+        let b = a.clone();
+        assert!(*a == *a);
+        assert!(*a != *b);
+        assert_eq!(a.material(), b.material());
+    }
+
+    #[test]
+    fn test_the_bounds_of_a_transformed_shape() {
+        let mut s = Sphere::default();
+        s.set_transform(
+            Matrix4::translation(1.0, 0.0, 0.0)
+                * Matrix4::scaling(2.0, 1.0, 1.0),
+        );
+        let (min, max) = s.bounds();
+        assert_eq!(min, Tuple::point(-1.0, -1.0, -1.0));
+        assert_eq!(max, Tuple::point(3.0, 1.0, 1.0));
+        // the box in object space ignores the transform
+        assert_eq!(s.bounds_of().max, Tuple::point(1.0, 1.0, 1.0));
+    }
+}
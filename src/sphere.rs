@@ -2,19 +2,58 @@ use crate::intersection::Intersection;
 use crate::material::Material;
 use crate::matrix::Matrix4;
 use crate::ray::Ray;
+use crate::scene_file::SceneSettings;
+use crate::shape::Shape;
 use crate::tuple::Tuple;
+use crate::uv::spherical_uv;
+use std::f64::consts::PI;
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Sphere {
     pub origin: Tuple,
-    pub radius: f32,
+    pub radius: f64,
     pub transform: Matrix4,
     pub material: Material,
+    pub name: Option<String>,
+    /// The material seen from inside the sphere. `None` uses the same
+    /// material on both sides.
+    pub back_material: Option<Material>,
 }
 
-impl Sphere {
-    pub fn intersect(&self, ray: Ray) -> Vec<Intersection> {
-        let transformed_ray = ray.transform(self.transform.inverse());
+impl Shape for Sphere {
+    fn kind(&self) -> &'static str {
+        "sphere"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    fn back_material(&self) -> Option<&Material> {
+        self.back_material.as_ref()
+    }
+
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
         let sphere_to_ray = transformed_ray.origin - self.origin;
         let a = transformed_ray.direction.dot(transformed_ray.direction);
         let b = 2.0 * transformed_ray.direction.dot(sphere_to_ray);
@@ -26,20 +65,53 @@ impl Sphere {
             let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
             let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
             if t1 < t2 {
-                vec![Intersection::new(t1, *self), Intersection::new(t2, *self)]
+                vec![Intersection::new(t1, self), Intersection::new(t2, self)]
             } else {
-                vec![Intersection::new(t2, *self), Intersection::new(t1, *self)]
+                vec![Intersection::new(t2, self), Intersection::new(t1, self)]
             }
         }
     }
 
-    pub fn normal_at(&self, world_point: Tuple) -> Tuple {
-        let object_point = self.transform.inverse() * world_point;
-        let object_normal = object_point - self.origin;
-        let mut world_normal =
-            self.transform.inverse().transpose() * object_normal;
-        world_normal.w = 0.0;
-        world_normal.normalize()
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        point - self.origin
+    }
+
+    /// Returns texture coordinates with `u` running once around the
+    /// equator and `v` from the south pole (0) to the north pole (1).
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        spherical_uv(point - self.origin)
+    }
+
+    fn local_point_at_uv(&self, u: f64, v: f64) -> Option<Tuple> {
+        let theta = (u - 0.5) * 2.0 * PI;
+        let phi = (v - 0.5) * PI;
+        let ring = phi.cos();
+        let offset =
+            Tuple::vector(theta.sin() * ring, phi.sin(), theta.cos() * ring);
+        Some(self.origin + offset)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        let radius = Tuple::vector(1.0, 1.0, 1.0);
+        (self.origin - radius, self.origin + radius)
+    }
+
+    /// Spheres moved or resized with `origin` and `radius` instead of the
+    /// transform, or with a back material, can't be described.
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        let plain = Sphere::default();
+        if self.origin == plain.origin
+            && self.radius == plain.radius
+            && self.back_material.is_none()
+        {
+            Some(vec![])
+        } else {
+            None
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
     }
 }
 
@@ -50,19 +122,23 @@ impl Default for Sphere {
             radius: 1.0,
             transform: Matrix4::identity(),
             material: Material::default(),
+            back_material: None,
+            name: None,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use crate::intersection::Intersection;
     use crate::material::Material;
     use crate::matrix::Matrix4;
     use crate::ray::Ray;
+    use crate::shape::Shape;
     use crate::sphere::Sphere;
     use crate::tuple::Tuple;
-    use std::f32::consts::{PI, SQRT_2};
+    use std::f64::consts::{PI, SQRT_2};
 
     #[test]
     fn test_a_ray_intersects_a_sphere_at_two_points() {
@@ -73,8 +149,8 @@ mod tests {
         let s = Sphere::default();
         let xs = s.intersect(r);
         assert!(!xs.is_empty());
-        assert_eq!(xs[0], Intersection::new(4.0, s));
-        assert_eq!(xs[1], Intersection::new(6.0, s));
+        assert_eq!(xs[0], Intersection::new(4.0, &s));
+        assert_eq!(xs[1], Intersection::new(6.0, &s));
     }
 
     #[test]
@@ -86,8 +162,8 @@ mod tests {
         let s = Sphere::default();
         let xs = s.intersect(r);
         assert!(!xs.is_empty());
-        assert_eq!(xs[0], Intersection::new(5.0, s));
-        assert_eq!(xs[1], Intersection::new(5.0, s));
+        assert_eq!(xs[0], Intersection::new(5.0, &s));
+        assert_eq!(xs[1], Intersection::new(5.0, &s));
     }
 
     #[test]
@@ -108,8 +184,8 @@ mod tests {
         let s = Sphere::default();
         let xs = s.intersect(r);
         assert!(!xs.is_empty());
-        assert_eq!(xs[0], Intersection::new(-1.0, s));
-        assert_eq!(xs[1], Intersection::new(1.0, s));
+        assert_eq!(xs[0], Intersection::new(-1.0, &s));
+        assert_eq!(xs[1], Intersection::new(1.0, &s));
     }
 
     #[test]
@@ -119,8 +195,8 @@ mod tests {
         let s = Sphere::default();
         let xs = s.intersect(r);
         assert!(!xs.is_empty());
-        assert_eq!(xs[0], Intersection::new(-6.0, s));
-        assert_eq!(xs[1], Intersection::new(-4.0, s));
+        assert_eq!(xs[0], Intersection::new(-6.0, &s));
+        assert_eq!(xs[1], Intersection::new(-4.0, &s));
     }
 
     #[test]
@@ -186,7 +262,7 @@ mod tests {
 
     #[test]
     fn test_the_normal_on_a_sphere_at_a_nonaxial_point() {
-        let sqrt3_over_3 = (3.0 as f32).sqrt() / 3.0;
+        let sqrt3_over_3 = 3.0_f64.sqrt() / 3.0;
         let s = Sphere::default();
         let n =
             s.normal_at(Tuple::point(sqrt3_over_3, sqrt3_over_3, sqrt3_over_3));
@@ -195,7 +271,7 @@ mod tests {
 
     #[test]
     fn test_the_normal_is_a_normalized_vector() {
-        let sqrt3_over_3 = (3.0 as f32).sqrt() / 3.0;
+        let sqrt3_over_3 = 3.0_f64.sqrt() / 3.0;
         let s = Sphere::default();
         let n =
             s.normal_at(Tuple::point(sqrt3_over_3, sqrt3_over_3, sqrt3_over_3));
@@ -203,6 +279,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_computing_the_normal_on_a_translated_sphere() {
         let mut s = Sphere::default();
         s.transform = Matrix4::translation(0.0, 1.0, 0.0);
@@ -224,6 +301,7 @@ mod tests {
         let s = Sphere::default();
         let m = s.material;
         assert_eq!(m, Material::default());
+        assert_eq!(s.back_material, None);
     }
 
     #[test]
@@ -231,7 +309,48 @@ mod tests {
         let mut s = Sphere::default();
         let mut m = Material::default();
         m.ambient = 1.0;
-        s.material = m;
+        s.material = m.clone();
         assert_eq!(s.material, m);
     }
+
+    #[test]
+    fn test_the_bounds_of_a_transformed_sphere() {
+        let mut s = Sphere::default();
+        assert_eq!(
+            s.bounds(),
+            (Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+        );
+        s.transform = Matrix4::translation(1.0, 2.0, 3.0)
+            * Matrix4::scaling(2.0, 1.0, 1.0);
+        assert_eq!(
+            s.bounds(),
+            (Tuple::point(-1.0, 1.0, 2.0), Tuple::point(3.0, 3.0, 4.0))
+        );
+    }
+
+    #[test]
+    fn test_texture_coordinates_on_a_sphere() {
+        let mut s = Sphere::default();
+        s.transform = Matrix4::scaling(2.0, 2.0, 2.0);
+        assert_eq!(s.uv_at(Tuple::point(0.0, 0.0, 2.0)), (0.5, 0.5));
+        assert_eq!(s.uv_at(Tuple::point(2.0, 0.0, 0.0)), (0.75, 0.5));
+        assert_eq!(s.uv_at(Tuple::point(0.0, 2.0, 0.0)).1, 1.0);
+        assert_eq!(s.uv_at(Tuple::point(0.0, -2.0, 0.0)).1, 0.0);
+    }
+
+    #[test]
+    fn test_points_from_texture_coordinates_on_a_sphere() {
+        let s = Sphere::default();
+        assert_eq!(
+            s.local_point_at_uv(0.5, 0.5),
+            Some(Tuple::point(0.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            s.local_point_at_uv(0.75, 0.5),
+            Some(Tuple::point(1.0, 0.0, 0.0))
+        );
+        let point = s.local_point_at_uv(0.3, 0.8).unwrap();
+        let (u, v) = s.local_uv_at(point);
+        assert!((u - 0.3).abs() < 0.0001 && (v - 0.8).abs() < 0.0001);
+    }
 }
@@ -0,0 +1,447 @@
+use crate::bounds::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::metadata::SceneHash;
+use crate::ray::Ray;
+use crate::scene_file::{SceneSettings, SceneValue};
+use crate::shape::Shape;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::hash::Hasher;
+
+/// A flat triangle, the building block of meshes. The edge vectors and
+/// the normal are computed once when the triangle is created, since they
+/// are needed by every intersection test.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Triangle {
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    pub normal: Tuple,
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple) -> Triangle {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        Triangle {
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal: e2.cross(e1).normalize(),
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+}
+
+impl Shape for Triangle {
+    fn kind(&self) -> &'static str {
+        "triangle"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Hits remember their barycentric coordinates.
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        match intersect_triangle(transformed_ray, self.p1, self.e1, self.e2) {
+            Some((t, u, v)) => vec![Intersection::with_uv(t, self, u, v)],
+            None => vec![],
+        }
+    }
+
+    fn local_normal_at(&self, _point: Tuple) -> Tuple {
+        self.normal
+    }
+
+    fn triangle_count(&self) -> usize {
+        1
+    }
+
+    /// Returns the barycentric coordinates of the point: how far it is
+    /// towards `p2` and towards `p3`.
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        barycentric(point, self.p1, self.e1, self.e2)
+    }
+
+    fn local_point_at_uv(&self, u: f64, v: f64) -> Option<Tuple> {
+        Some(self.p1 + self.e1 * u + self.e2 * v)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        corner_bounds([self.p1, self.p2, self.p3])
+    }
+
+    fn scene_settings(&self) -> Option<SceneSettings<'_>> {
+        Some(vec![
+            ("p1", SceneValue::Point(self.p1)),
+            ("p2", SceneValue::Point(self.p2)),
+            ("p3", SceneValue::Point(self.p3)),
+        ])
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+/// A triangle with a normal at each corner, interpolated across it so
+/// that a mesh of flat triangles shades like a smooth surface.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SmoothTriangle {
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub n1: Tuple,
+    pub n2: Tuple,
+    pub n3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    pub transform: Matrix4,
+    pub material: Material,
+    pub name: Option<String>,
+}
+
+impl SmoothTriangle {
+    pub fn new(
+        p1: Tuple,
+        p2: Tuple,
+        p3: Tuple,
+        n1: Tuple,
+        n2: Tuple,
+        n3: Tuple,
+    ) -> SmoothTriangle {
+        SmoothTriangle {
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1: p2 - p1,
+            e2: p3 - p1,
+            transform: Matrix4::identity(),
+            material: Material::default(),
+            name: None,
+        }
+    }
+
+    /// Blends the corner normals by the barycentric coordinates.
+    fn interpolated_normal(&self, u: f64, v: f64) -> Tuple {
+        self.n2 * u + self.n3 * v + self.n1 * (1.0 - u - v)
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn kind(&self) -> &'static str {
+        "smooth triangle"
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Hits remember their barycentric coordinates.
+    fn local_intersect(&self, transformed_ray: Ray) -> Vec<Intersection<'_>> {
+        match intersect_triangle(transformed_ray, self.p1, self.e1, self.e2) {
+            Some((t, u, v)) => vec![Intersection::with_uv(t, self, u, v)],
+            None => vec![],
+        }
+    }
+
+    fn local_normal_at(&self, point: Tuple) -> Tuple {
+        let (u, v) = barycentric(point, self.p1, self.e1, self.e2);
+        self.interpolated_normal(u, v)
+    }
+
+    fn local_normal_at_hit(&self, point: Tuple, hit: &Intersection) -> Tuple {
+        match (hit.u, hit.v) {
+            (Some(u), Some(v)) => self.interpolated_normal(u, v),
+            _ => self.local_normal_at(point),
+        }
+    }
+
+    fn triangle_count(&self) -> usize {
+        1
+    }
+
+    fn local_uv_at(&self, point: Tuple) -> (f64, f64) {
+        barycentric(point, self.p1, self.e1, self.e2)
+    }
+
+    fn local_point_at_uv(&self, u: f64, v: f64) -> Option<Tuple> {
+        Some(self.p1 + self.e1 * u + self.e2 * v)
+    }
+
+    fn local_bounds(&self) -> (Tuple, Tuple) {
+        corner_bounds([self.p1, self.p2, self.p3])
+    }
+
+    fn hash_settings(&self, state: &mut dyn Hasher) {
+        [self.p1, self.p2, self.p3, self.n1, self.n2, self.n3].hash_into(state);
+    }
+
+    fn box_clone(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+fn corner_bounds(corners: [Tuple; 3]) -> (Tuple, Tuple) {
+    let mut bounds = BoundingBox::empty();
+    for corner in corners {
+        bounds.add_point(corner);
+    }
+    bounds.into()
+}
+
+/// Intersects a ray with the triangle with corner `p1` and edges `e1`
+/// and `e2`, returning `t` and the barycentric coordinates of the hit.
+/// Möller and Trumbore, "Fast, Minimum Storage Ray/Triangle
+/// Intersection" (1997).
+pub(crate) fn intersect_triangle(
+    ray: Ray,
+    p1: Tuple,
+    e1: Tuple,
+    e2: Tuple,
+) -> Option<(f64, f64, f64)> {
+    let direction_cross_e2 = ray.direction.cross(e2);
+    let determinant = e1.dot(direction_cross_e2);
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let f = 1.0 / determinant;
+    let p1_to_origin = ray.origin - p1;
+    let u = f * p1_to_origin.dot(direction_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * ray.direction.dot(origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((f * e2.dot(origin_cross_e1), u, v))
+}
+
+/// Returns the barycentric coordinates of a point in the plane of the
+/// triangle with corner `p1` and edges `e1` and `e2`: how far it is
+/// along each edge.
+pub(crate) fn barycentric(
+    point: Tuple,
+    p1: Tuple,
+    e1: Tuple,
+    e2: Tuple,
+) -> (f64, f64) {
+    let p1_to_point = point - p1;
+    let d00 = e1.dot(e1);
+    let d01 = e1.dot(e2);
+    let d11 = e2.dot(e2);
+    let d20 = p1_to_point.dot(e1);
+    let d21 = p1_to_point.dot(e2);
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < EPSILON {
+        return (0.0, 0.0);
+    }
+    (
+        (d11 * d20 - d01 * d21) / denominator,
+        (d00 * d21 - d01 * d20) / denominator,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intersection::Intersection;
+    use crate::ray::Ray;
+    use crate::scene::Scene;
+    use crate::shape::Shape;
+    use crate::triangle::{SmoothTriangle, Triangle};
+    use crate::tuple::Tuple;
+    use crate::world::World;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_constructing_a_triangle() {
+        let t = triangle();
+        assert_eq!(t.e1, Tuple::vector(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2, Tuple::vector(1.0, -1.0, 0.0));
+        assert_eq!(t.normal, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_finding_the_normal_on_a_triangle() {
+        let t = triangle();
+        let n1 = t.local_normal_at(Tuple::point(0.0, 0.5, 0.0));
+        let n2 = t.local_normal_at(Tuple::point(-0.5, 0.75, 0.0));
+        let n3 = t.local_normal_at(Tuple::point(0.5, 0.25, 0.0));
+        assert_eq!(n1, t.normal);
+        assert_eq!(n2, t.normal);
+        assert_eq!(n3, t.normal);
+    }
+
+    #[test]
+    fn test_intersecting_a_ray_parallel_to_the_triangle() {
+        let t = triangle();
+        let r = Ray::new(
+            Tuple::point(0.0, -1.0, -2.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        assert!(t.local_intersect(r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_misses_the_edges_of_a_triangle() {
+        let t = triangle();
+        let rays = [
+            Ray::new(
+                Tuple::point(1.0, 1.0, -2.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+            Ray::new(
+                Tuple::point(-1.0, 1.0, -2.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+            Ray::new(
+                Tuple::point(0.0, -1.0, -2.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+        ];
+        for r in rays.iter() {
+            assert!(t.local_intersect(*r).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_a_ray_strikes_a_triangle() {
+        let t = triangle();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.5, -2.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = t.local_intersect(r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 2.0);
+        assert_eq!(xs[0].u, Some(0.25));
+        assert_eq!(xs[0].v, Some(0.25));
+    }
+
+    #[test]
+    fn test_barycentric_coordinates_round_trip() {
+        let t = triangle();
+        let point = t.local_point_at_uv(0.2, 0.3).unwrap();
+        let (u, v) = t.local_uv_at(point);
+        assert!((u - 0.2).abs() < 0.0001 && (v - 0.3).abs() < 0.0001);
+        assert_eq!(
+            t.local_bounds(),
+            (Tuple::point(-1.0, 0.0, 0.0), Tuple::point(1.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_counting_triangles_in_a_world() {
+        let mut w = World::new();
+        w.objects.push(Box::new(triangle()));
+        w.objects.push(Box::new(triangle()));
+        assert_eq!(w.stats().triangles, 2);
+        let r = Ray::new(
+            Tuple::point(0.0, 0.5, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(Scene::new(&w).hit_sorted(r).unwrap().t, 5.0);
+    }
+
+    fn smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_an_intersection_with_a_smooth_triangle_stores_u_and_v() {
+        let tri = smooth_triangle();
+        let r = Ray::new(
+            Tuple::point(-0.2, 0.3, -2.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = tri.local_intersect(r);
+        assert!((xs[0].u.unwrap() - 0.45).abs() < 0.0001);
+        assert!((xs[0].v.unwrap() - 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_a_smooth_triangle_interpolates_the_normal() {
+        let tri = smooth_triangle();
+        let i = Intersection::with_uv(1.0, &tri, 0.45, 0.25);
+        let n = tri.normal_at_hit(Tuple::point(0.0, 0.0, 0.0), &i);
+        assert_eq!(n, Tuple::vector(-0.5547, 0.83205, 0.0));
+        let mut hit = i.clone();
+        let r = Ray::new(
+            Tuple::point(-0.2, 0.3, -2.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        hit.prepare_hit(r);
+        assert_eq!(hit.normal_vector, Some(n));
+    }
+}
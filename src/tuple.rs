@@ -1,25 +1,25 @@
 #![allow(clippy::float_cmp)]
-use crate::equal_f32;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use crate::equal_f64;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Tuple {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-    pub w: f32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
 }
 
 impl Tuple {
-    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Tuple {
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Tuple {
         Tuple { x, y, z, w }
     }
 
-    pub fn point(x: f32, y: f32, z: f32) -> Tuple {
+    pub fn point(x: f64, y: f64, z: f64) -> Tuple {
         Tuple::new(x, y, z, 1.0)
     }
 
-    pub fn vector(x: f32, y: f32, z: f32) -> Tuple {
+    pub fn vector(x: f64, y: f64, z: f64) -> Tuple {
         Tuple::new(x, y, z, 0.0)
     }
 
@@ -32,7 +32,7 @@ impl Tuple {
     }
 
     /// Gets the distance represented by the vector.
-    pub fn magnitude(&self) -> f32 {
+    pub fn magnitude(&self) -> f64 {
         (self.x.powi(2) + self.y.powi(2) + self.z.powi(2) + self.w.powi(2))
             .sqrt()
     }
@@ -55,7 +55,7 @@ impl Tuple {
     /// dot product of -1 means that they point in opposite directions.
     /// If the two vectors are unit vectors, the dot product is
     /// actually the cosine of the angle between them.
-    pub fn dot(&self, other: Tuple) -> f32 {
+    pub fn dot(&self, other: Tuple) -> f64 {
         (self.x * other.x)
             + (self.y * other.y)
             + (self.z * other.z)
@@ -79,10 +79,10 @@ impl Tuple {
 
 impl PartialEq for Tuple {
     fn eq(&self, other: &Tuple) -> bool {
-        equal_f32(self.x, other.x)
-            && equal_f32(self.y, other.y)
-            && equal_f32(self.z, other.z)
-            && equal_f32(self.w, other.w)
+        equal_f64(self.x, other.x)
+            && equal_f64(self.y, other.y)
+            && equal_f64(self.z, other.z)
+            && equal_f64(self.w, other.w)
     }
 }
 
@@ -120,10 +120,10 @@ impl Neg for Tuple {
     }
 }
 
-impl Mul<f32> for Tuple {
+impl Mul<f64> for Tuple {
     type Output = Tuple;
 
-    fn mul(self, scalar: f32) -> Tuple {
+    fn mul(self, scalar: f64) -> Tuple {
         Tuple::new(
             self.x * scalar,
             self.y * scalar,
@@ -146,10 +146,10 @@ impl Mul<Tuple> for Tuple {
     }
 }
 
-impl Div<f32> for Tuple {
+impl Div<f64> for Tuple {
     type Output = Tuple;
 
-    fn div(self, scalar: f32) -> Tuple {
+    fn div(self, scalar: f64) -> Tuple {
         Tuple::new(
             self.x / scalar,
             self.y / scalar,
@@ -159,11 +159,47 @@ impl Div<f32> for Tuple {
     }
 }
 
+/// Builds an orthonormal basis around a unit vector, returning a tangent
+/// and a bitangent that are perpendicular to the vector and to each
+/// other. Together with the vector they form a right-handed frame, so
+/// `tangent.cross(bitangent)` is the original vector.
+pub fn build_onb(normal: Tuple) -> (Tuple, Tuple) {
+    // Duff et al., "Building an Orthonormal Basis, Revisited" (2017)
+    let sign = 1.0_f64.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    let tangent = Tuple::vector(
+        1.0 + sign * normal.x * normal.x * a,
+        sign * b,
+        -sign * normal.x,
+    );
+    let bitangent = Tuple::vector(b, sign + normal.y * normal.y * a, -normal.y);
+    (tangent, bitangent)
+}
+
+impl AddAssign for Tuple {
+    fn add_assign(&mut self, other: Tuple) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Tuple {
+    fn sub_assign(&mut self, other: Tuple) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f64> for Tuple {
+    fn mul_assign(&mut self, scalar: f64) {
+        *self = *self * scalar;
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::equal_f32;
-    use crate::tuple::Tuple;
-    use std::f32::consts::SQRT_2;
+    use crate::equal_f64;
+    use crate::tuple::{build_onb, Tuple};
+    use std::f64::consts::SQRT_2;
 
     #[test]
     fn test_a_tuple_with_w_equals_1_is_a_point() {
@@ -283,13 +319,13 @@ mod tests {
     #[test]
     fn test_computing_the_magnitude_of_vector_1_2_3() {
         let v = Tuple::vector(1.0, 2.0, 3.0);
-        assert!(equal_f32(v.magnitude(), (14.0 as f32).sqrt()));
+        assert!(equal_f64(v.magnitude(), 14.0_f64.sqrt()));
     }
 
     #[test]
     fn test_computing_the_magnitude_of_vector_neg_1_neg_2_neg_3() {
         let v = Tuple::vector(-1.0, -2.0, -3.0);
-        assert!(equal_f32(v.magnitude(), (14.0 as f32).sqrt()));
+        assert!(equal_f64(v.magnitude(), 14.0_f64.sqrt()));
     }
 
     #[test]
@@ -309,14 +345,14 @@ mod tests {
     fn test_the_magnitude_of_a_normalized_vector() {
         let v = Tuple::vector(1.0, 2.0, 3.0);
         let norm = v.normalize();
-        assert!(equal_f32(norm.magnitude(), 1.0));
+        assert!(equal_f64(norm.magnitude(), 1.0));
     }
 
     #[test]
     fn test_the_dot_product_of_two_tuples() {
         let a = Tuple::vector(1.0, 2.0, 3.0);
         let b = Tuple::vector(2.0, 3.0, 4.0);
-        assert!(equal_f32(a.dot(b), 20.0));
+        assert!(equal_f64(a.dot(b), 20.0));
     }
 
     #[test]
@@ -342,4 +378,35 @@ mod tests {
         let r = v.reflect(n);
         assert_eq!(r, Tuple::vector(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn test_building_an_orthonormal_basis() {
+        let normals = [
+            Tuple::vector(0.0, 0.0, 1.0),
+            Tuple::vector(0.0, 0.0, -1.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(1.0, 2.0, 3.0).normalize(),
+            Tuple::vector(-0.5, 0.2, -0.9).normalize(),
+        ];
+        for n in normals.iter() {
+            let (t, b) = build_onb(*n);
+            assert!(equal_f64(t.magnitude(), 1.0));
+            assert!(equal_f64(b.magnitude(), 1.0));
+            assert!(equal_f64(t.dot(*n), 0.0));
+            assert!(equal_f64(b.dot(*n), 0.0));
+            assert!(equal_f64(t.dot(b), 0.0));
+            assert_eq!(t.cross(b), *n);
+        }
+    }
+
+    #[test]
+    fn test_assignment_operators_on_tuples() {
+        let mut a = Tuple::new(1.0, -2.0, 3.0, 1.0);
+        a += Tuple::vector(1.0, 1.0, 1.0);
+        assert_eq!(a, Tuple::point(2.0, -1.0, 4.0));
+        a -= Tuple::vector(2.0, 2.0, 2.0);
+        assert_eq!(a, Tuple::point(0.0, -3.0, 2.0));
+        a *= 2.0;
+        assert_eq!(a, Tuple::new(0.0, -6.0, 4.0, 2.0));
+    }
 }
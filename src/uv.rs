@@ -0,0 +1,234 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::pattern::WrapMode;
+use crate::plane::Plane;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::tuple::Tuple;
+use crate::EPSILON;
+use std::f64::consts::PI;
+
+/// Returns texture coordinates for a direction from the center of a
+/// sphere, with `u` running once around the equator from 0 up to but not
+/// including 1, and `v` from the south pole (0) to the north pole (1).
+/// At the poles, where every `u` meets, `u` is 0.5.
+pub fn spherical_uv(direction: Tuple) -> (f64, f64) {
+    let p = direction.normalize();
+    let v = 0.5 + p.y.clamp(-1.0, 1.0).asin() / PI;
+    if p.x.abs() < EPSILON && p.z.abs() < EPSILON {
+        return (0.5, v);
+    }
+    // atan2 gives both -PI and PI on the seam behind the sphere, so wrap
+    // them onto the same side
+    let u = (0.5 + p.x.atan2(p.z) / (2.0 * PI)).rem_euclid(1.0);
+    (u, v)
+}
+
+/// How points in pattern space are turned into texture coordinates, for
+/// wrapping an image around a shape. Each mapping gives the texture
+/// coordinates of the untransformed shape of the same name.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UvMapping {
+    /// Around a sphere centered on the origin, as by `spherical_uv`.
+    Spherical,
+    /// Flat along the xz plane, repeating every unit.
+    Planar,
+    /// Once around the y axis, and repeating every unit along it.
+    Cylindrical,
+    /// Onto the cube from -1 to 1 on every axis, with the whole texture
+    /// covering each face once.
+    Cube,
+}
+
+impl UvMapping {
+    pub fn uv_at(&self, point: Tuple) -> (f64, f64) {
+        match self {
+            UvMapping::Spherical => Sphere::default().local_uv_at(point),
+            UvMapping::Planar => Plane::default().local_uv_at(point),
+            UvMapping::Cylindrical => Cylinder::default().local_uv_at(point),
+            UvMapping::Cube => Cube::default().local_uv_at(point),
+        }
+    }
+}
+
+/// Samples the texture at the given coordinates, with `u` running left to
+/// right and `v` bottom to top, blending the four nearest pixels. Pixels
+/// beyond the edges come from the wrap modes, so a repeating texture
+/// blends smoothly across the seam where its edges meet.
+pub fn sample_bilinear(
+    canvas: &Canvas,
+    u: f64,
+    v: f64,
+    wrap_u: WrapMode,
+    wrap_v: WrapMode,
+) -> Color {
+    let x = u * canvas.width as f64 - 0.5;
+    let y = (1.0 - v) * canvas.height as f64 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let pixel = |x: f64, y: f64| {
+        canvas.linear_pixel_at(
+            wrap_index(x as i64, canvas.width, wrap_u),
+            wrap_index(y as i64, canvas.height, wrap_v),
+        )
+    };
+    let top = pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1.0, y0) * tx;
+    let bottom =
+        pixel(x0, y0 + 1.0) * (1.0 - tx) + pixel(x0 + 1.0, y0 + 1.0) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+/// Samples an equirectangular texture wrapped around a sphere. The left
+/// and right edges are blended across the seam. The top and bottom rows
+/// each shrink to a single point at a pole, so past their centers the
+/// color fades into the average of the row, giving each pole one color
+/// instead of a pinched swirl of every pixel in the row.
+pub fn sample_spherical(canvas: &Canvas, u: f64, v: f64) -> Color {
+    let color =
+        sample_bilinear(canvas, u, v, WrapMode::Repeat, WrapMode::Clamp);
+    let row_height = 1.0 / canvas.height as f64;
+    let (row, into_cap) = if v > 1.0 - row_height / 2.0 {
+        (0, (v - (1.0 - row_height / 2.0)) / (row_height / 2.0))
+    } else if v < row_height / 2.0 {
+        (
+            canvas.height - 1,
+            (row_height / 2.0 - v) / (row_height / 2.0),
+        )
+    } else {
+        return color;
+    };
+    let into_cap = into_cap.clamp(0.0, 1.0);
+    color * (1.0 - into_cap) + row_average(canvas, row) * into_cap
+}
+
+fn row_average(canvas: &Canvas, y: usize) -> Color {
+    let sum = (0..canvas.width)
+        .fold(Color::black(), |sum, x| sum + canvas.linear_pixel_at(x, y));
+    sum * (1.0 / canvas.width as f64)
+}
+
+fn wrap_index(index: i64, length: usize, wrap: WrapMode) -> usize {
+    match wrap {
+        WrapMode::Repeat => index.rem_euclid(length as i64) as usize,
+        WrapMode::Clamp => index.clamp(0, length as i64 - 1) as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::Canvas;
+    use crate::color::{Color, ColorSpace};
+    use crate::pattern::WrapMode;
+    use crate::tuple::Tuple;
+    use crate::uv::{
+        sample_bilinear, sample_spherical, spherical_uv, UvMapping,
+    };
+
+    #[test]
+    fn test_spherical_coordinates_on_the_seam_and_at_the_poles() {
+        assert_eq!(spherical_uv(Tuple::vector(0.0, 0.0, 1.0)), (0.5, 0.5));
+        assert_eq!(spherical_uv(Tuple::vector(-1.0, 0.0, 0.0)), (0.25, 0.5));
+        // both sides of the seam behind the sphere start at 0
+        assert_eq!(spherical_uv(Tuple::vector(0.0, 0.0, -1.0)).0, 0.0);
+        assert_eq!(spherical_uv(Tuple::vector(-0.0, 0.0, -1.0)).0, 0.0);
+        assert_eq!(spherical_uv(Tuple::vector(0.0, 1.0, 0.0)), (0.5, 1.0));
+        assert_eq!(spherical_uv(Tuple::vector(0.0, -2.0, 0.0)), (0.5, 0.0));
+    }
+
+    #[test]
+    fn test_planar_and_cylindrical_mappings() {
+        let planar = UvMapping::Planar;
+        assert_eq!(planar.uv_at(Tuple::point(0.25, 0.0, 0.5)), (0.25, 0.5));
+        assert_eq!(planar.uv_at(Tuple::point(0.25, 0.0, -0.25)), (0.25, 0.75));
+        assert_eq!(planar.uv_at(Tuple::point(0.25, 0.5, -0.25)), (0.25, 0.75));
+        assert_eq!(planar.uv_at(Tuple::point(1.25, 0.0, 0.5)), (0.25, 0.5));
+        let cylindrical = UvMapping::Cylindrical;
+        assert_eq!(cylindrical.uv_at(Tuple::point(0.0, 0.0, 1.0)), (0.5, 0.0));
+        assert_eq!(
+            cylindrical.uv_at(Tuple::point(0.5, 0.25, 0.0)),
+            (0.75, 0.25)
+        );
+        assert_eq!(
+            cylindrical.uv_at(Tuple::point(-1.0, 1.5, 0.0)),
+            (0.25, 0.5)
+        );
+    }
+
+    #[test]
+    fn test_cube_mapping_covers_every_face() {
+        let cube = UvMapping::Cube;
+        let examples = [
+            // front, back, left, right, top, bottom
+            ((-0.5, 0.5, 1.0), (0.25, 0.75)),
+            ((0.5, -0.5, -1.0), (0.25, 0.25)),
+            ((-1.0, 0.5, -0.5), (0.25, 0.75)),
+            ((1.0, -0.5, 0.5), (0.25, 0.25)),
+            ((-0.5, 1.0, -0.5), (0.25, 0.75)),
+            ((0.5, -1.0, 0.5), (0.75, 0.75)),
+        ];
+        for &((x, y, z), uv) in examples.iter() {
+            assert_eq!(cube.uv_at(Tuple::point(x, y, z)), uv);
+        }
+        // the top edge of a face is the top of the texture, not a seam
+        // back to its bottom
+        assert_eq!(cube.uv_at(Tuple::point(0.5, 1.0, 1.5)), (0.75, 1.0));
+    }
+
+    #[test]
+    fn test_bilinear_sampling_blends_across_a_repeating_seam() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(1, 0, Color::white());
+        let clamp = WrapMode::Clamp;
+        let repeat = WrapMode::Repeat;
+        assert_eq!(
+            sample_bilinear(&canvas, 0.25, 0.5, clamp, clamp),
+            Color::black()
+        );
+        assert_eq!(
+            sample_bilinear(&canvas, 0.5, 0.5, clamp, clamp),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        // at the seam, the first and last columns are blended
+        assert_eq!(
+            sample_bilinear(&canvas, 0.0, 0.5, repeat, clamp),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(
+            sample_bilinear(&canvas, 0.0, 0.5, clamp, clamp),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn test_the_poles_of_a_spherical_texture_have_one_color() {
+        let mut canvas = Canvas::new(4, 2);
+        canvas.write_pixel(0, 0, Color::white());
+        canvas.write_pixel(3, 1, Color::new(0.0, 0.0, 4.0));
+        let north = Color::new(0.25, 0.25, 0.25);
+        for &u in [0.0, 0.125, 0.5, 0.9].iter() {
+            assert_eq!(sample_spherical(&canvas, u, 1.0), north);
+            assert_eq!(
+                sample_spherical(&canvas, u, 0.0),
+                Color::new(0.0, 0.0, 1.0)
+            );
+        }
+        assert_eq!(sample_spherical(&canvas, 0.125, 0.75), Color::white());
+    }
+
+    #[test]
+    fn test_sampling_an_srgb_texture_gives_linear_colors() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.write_pixel(0, 0, Color::new(0.73536, 0.73536, 0.73536));
+        canvas.color_space = ColorSpace::Srgb;
+        let color = sample_bilinear(
+            &canvas,
+            0.5,
+            0.5,
+            WrapMode::Clamp,
+            WrapMode::Clamp,
+        );
+        assert_eq!(color, Color::new(0.5, 0.5, 0.5));
+    }
+}
@@ -1,55 +1,355 @@
+use crate::accel::{Accel, AccelStructure};
+use crate::bounds::BoundingBox;
+use crate::camera::Camera;
 use crate::color::Color;
-use crate::intersection::{find_hit, Intersection};
+use crate::environment::Environment;
+use crate::intersection::Intersection;
+use crate::irradiance::Indirect;
 use crate::light::PointLight;
+use crate::material::Toon;
 use crate::matrix::Matrix4;
 use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::shape::Shape;
 use crate::sphere::Sphere;
 use crate::tuple::Tuple;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct World {
-    pub light: Option<PointLight>,
-    pub objects: Vec<Sphere>,
+    /// Every light in the scene. Their contributions are added together.
+    pub lights: Vec<PointLight>,
+    pub objects: Vec<Box<dyn Shape>>,
+    /// The maximum number of reflections traced from a camera ray.
+    pub max_depth: usize,
+    /// What rays that miss every object see. Without an environment they
+    /// see black.
+    pub environment: Option<Environment>,
+    pub reflection_mode: ReflectionMode,
+    /// Whether objects block light. Turning shadows off makes every point
+    /// fully lit, which is much faster to render.
+    pub shadows: bool,
+    /// Chooses the points that soft shadows, glossy surfaces and camera
+    /// lenses average over.
+    pub sampler: Sampler,
+    /// How light bouncing between diffuse surfaces is handled.
+    pub indirect: Indirect,
+    /// Shades every material without a toon style of its own with this
+    /// one.
+    pub toon: Option<Toon>,
+    /// The structure rays use to skip objects they can't hit, built when
+    /// the world is compiled into a `Scene`, unless the one kept by the
+    /// last `update` still fits the objects.
+    pub accel: Accel,
+    cache: Vec<CachedTransform>,
+    structure: Option<CachedStructure>,
+}
+
+/// What a call to `World::update` had to rebuild.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct WorldUpdate {
+    /// The number of objects whose transformation had to be inverted
+    /// again.
+    pub objects: usize,
+    /// Whether the acceleration structure had to be built again, because
+    /// objects were added, removed or moved, or `World::accel` changed.
+    pub structure: bool,
+}
+
+/// A summary of what a world contains, for reporting and for sanity
+/// checks before long renders.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WorldStats {
+    /// The number of objects of each kind, such as `"sphere"`.
+    pub objects_by_type: BTreeMap<&'static str, usize>,
+    pub triangles: usize,
+    pub lights: usize,
+    /// The minimum and maximum corners of a box containing every object,
+    /// or `None` for an empty world.
+    pub bounds: Option<(Tuple, Tuple)>,
+    /// A rough estimate of the memory used by the world, in bytes.
+    pub memory_bytes: usize,
+}
+
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (kind, count) in self.objects_by_type.iter() {
+            writeln!(f, "{}: {}", kind, count)?;
+        }
+        writeln!(f, "triangles: {}", self.triangles)?;
+        writeln!(f, "lights: {}", self.lights)?;
+        match self.bounds {
+            Some((min, max)) => writeln!(
+                f,
+                "bounds: ({}, {}, {}) to ({}, {}, {})",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )?,
+            None => writeln!(f, "bounds: empty")?,
+        }
+        write!(f, "memory: {} KiB", self.memory_bytes.div_ceil(1024))
+    }
+}
+
+/// How reflected colors are computed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ReflectionMode {
+    /// Reflection rays are traced through the scene.
+    Traced,
+    /// Reflection rays only look up the environment, skipping the scene
+    /// entirely. Much cheaper, but objects don't reflect each other.
+    Environment,
+}
+
+/// The default limit on how many times a ray may be reflected.
+const MAX_DEPTH: usize = 5;
+
+/// The inverse of an object's transformation, stored along with the
+/// transformation it was computed from so that entries made stale by
+/// mutating the object can be detected.
+#[derive(Copy, Clone, Debug)]
+struct CachedTransform {
+    transform: Matrix4,
+    inverse: Matrix4,
+}
+
+/// The acceleration structure built by the last `World::update`, stored
+/// along with the bounds it was built over so that it is only reused
+/// while every object stays where it was.
+#[derive(Clone, Debug)]
+struct CachedStructure {
+    bounds: Vec<BoundingBox>,
+    structure: Arc<dyn AccelStructure>,
+}
+
+impl CachedTransform {
+    fn new(transform: Matrix4) -> CachedTransform {
+        CachedTransform {
+            transform,
+            inverse: transform.inverse(),
+        }
+    }
 }
 
 impl World {
     pub fn new() -> World {
         World {
-            light: None,
+            lights: vec![],
             objects: vec![],
+            max_depth: MAX_DEPTH,
+            environment: None,
+            reflection_mode: ReflectionMode::Traced,
+            shadows: true,
+            sampler: Sampler::Fibonacci,
+            indirect: Indirect::Off,
+            toon: None,
+            accel: Accel::None,
+            cache: vec![],
+            structure: None,
         }
     }
 
-    pub fn intersect(&self, ray: Ray) -> Vec<Intersection> {
+    /// Returns the index in `objects` of the object with the given name,
+    /// which finds it again however the objects were reordered.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
         self.objects
             .iter()
-            .flat_map(|object| object.intersect(ray))
+            .position(|object| object.name() == Some(name))
+    }
+
+    /// Returns the object the camera sees at the center of the given
+    /// pixel, or `None` where there is only background. Compiles the
+    /// world for the one lookup; to pick many pixels, compile a `Scene`
+    /// once and use `Camera::object_at_pixel`.
+    pub fn object_at_pixel(
+        &self,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+    ) -> Option<&dyn Shape> {
+        camera.object_at_pixel(&Scene::new(self), x, y)
+    }
+
+    /// Returns the nearest hit along the ray, like `Scene::hit_sorted`,
+    /// compiling the world for the one query.
+    pub fn hit_sorted(&self, ray: Ray) -> Option<Intersection<'_>> {
+        Scene::new(self).hit_sorted(ray)
+    }
+
+    /// Returns whether anything is hit along the ray before the given
+    /// distance, like `Scene::hit_any`, compiling the world for the one
+    /// query.
+    pub fn hit_any(&self, ray: Ray, max_distance: f64) -> bool {
+        Scene::new(self).hit_any(ray, max_distance)
+    }
+
+    /// Returns the fraction of the light that reaches the point, like
+    /// `Scene::light_intensity_at`, compiling the world for the one
+    /// query.
+    pub fn light_intensity_at(
+        &self,
+        point: Tuple,
+        light: &PointLight,
+        rng: &mut Rng,
+    ) -> f64 {
+        Scene::new(self).light_intensity_at(point, light, rng)
+    }
+
+    /// Counts the objects and lights in the world and measures its extent.
+    pub fn stats(&self) -> WorldStats {
+        let mut objects_by_type = BTreeMap::new();
+        for object in self.objects.iter() {
+            *objects_by_type.entry(object.kind()).or_insert(0) += 1;
+        }
+        let bounds = self.objects.iter().map(|object| object.bounds()).reduce(
+            |(min, max), (object_min, object_max)| {
+                (
+                    Tuple::point(
+                        min.x.min(object_min.x),
+                        min.y.min(object_min.y),
+                        min.z.min(object_min.z),
+                    ),
+                    Tuple::point(
+                        max.x.max(object_max.x),
+                        max.y.max(object_max.y),
+                        max.z.max(object_max.z),
+                    ),
+                )
+            },
+        );
+        let environment_bytes = match &self.environment {
+            Some(Environment::Map(canvas)) => {
+                canvas.width * canvas.height * size_of::<Color>()
+            }
+            _ => 0,
+        };
+        WorldStats {
+            triangles: self
+                .objects
+                .iter()
+                .map(|object| object.triangle_count())
+                .sum(),
+            objects_by_type,
+            lights: self.lights.len(),
+            bounds,
+            memory_bytes: size_of::<World>()
+                + self.objects.capacity() * size_of::<Box<dyn Shape>>()
+                + self
+                    .objects
+                    .iter()
+                    .map(|object| object.memory_bytes())
+                    .sum::<usize>()
+                + self.cache.capacity() * size_of::<CachedTransform>()
+                + environment_bytes,
+        }
+    }
+
+    /// Returns the indices of the objects whose cached data is missing
+    /// or out of date because the object changed since the last call
+    /// to `update`.
+    pub fn dirty_objects(&self) -> Vec<usize> {
+        (0..self.objects.len())
+            .filter(|&i| self.cached_inverse(i).is_none())
             .collect()
     }
 
-    pub fn color_at(&self, ray: Ray) -> Color {
-        let intersections = self.intersect(ray);
-        let hit = find_hit(intersections);
-        match hit {
-            Some(mut intersection) => {
-                intersection.prepare_hit(ray);
-                intersection.shade_hit(self)
+    /// Rebuilds the cached data of the objects that changed since the
+    /// last update, and the acceleration structure if any object's
+    /// bounds changed, leaving everything else untouched, and returns
+    /// what had to be rebuilt. Call this after mutating the world between
+    /// frames, so that compiling it into a `Scene` only inverts the
+    /// transformations that changed and reuses the structure when
+    /// nothing moved.
+    pub fn update(&mut self) -> WorldUpdate {
+        let dirty = self.dirty_objects();
+        self.cache.truncate(self.objects.len());
+        for &i in dirty.iter() {
+            let entry = CachedTransform::new(self.objects[i].transform());
+            if i < self.cache.len() {
+                self.cache[i] = entry;
+            } else {
+                self.cache.push(entry);
             }
-            None => Color::black(),
+        }
+
+        let bounds = self.object_bounds();
+        let rebuild = self.accel != Accel::None
+            && self.cached_structure(&bounds).is_none();
+        if rebuild {
+            self.structure = self
+                .accel
+                .build(&bounds)
+                .map(|structure| CachedStructure { bounds, structure });
+        } else if self.accel == Accel::None {
+            self.structure = None;
+        }
+        WorldUpdate {
+            objects: dirty.len(),
+            structure: rebuild,
         }
     }
 
-    pub fn is_shadowed(&self, point: Tuple) -> bool {
-        let shadow_vector = self.light.unwrap().position - point;
-        let distance = shadow_vector.magnitude();
-        let direction = shadow_vector.normalize();
-        let shadow_ray = Ray::new(point, direction);
-        let intersections = self.intersect(shadow_ray);
-        let hit = find_hit(intersections);
-        hit.is_some() && hit.unwrap().t < distance
+    /// Moves the objects and lights of another world into this one. The
+    /// other world's settings are ignored.
+    pub fn merge(&mut self, other: World) {
+        self.merge_transformed(other, Matrix4::identity());
+    }
+
+    /// Like `merge`, but first applies a transformation to everything in
+    /// the other world, so that the same prop can be placed several
+    /// times in a larger scene.
+    pub fn merge_transformed(&mut self, other: World, transform: Matrix4) {
+        for mut object in other.objects {
+            object.set_transform(transform * object.transform());
+            self.objects.push(object);
+        }
+        for mut light in other.lights {
+            light.position = transform * light.position;
+            self.lights.push(light);
+        }
+    }
+
+    /// Returns the bounds of every object in world space, in the order of
+    /// `objects`, which the acceleration structure is built over.
+    pub(crate) fn object_bounds(&self) -> Vec<BoundingBox> {
+        self.objects
+            .iter()
+            .map(|object| object.bounds().into())
+            .collect()
+    }
+
+    /// Returns the acceleration structure built by the last `update`, or
+    /// `None` if it was built over different bounds or is not the kind
+    /// `accel` asks for.
+    pub(crate) fn cached_structure(
+        &self,
+        bounds: &[BoundingBox],
+    ) -> Option<Arc<dyn AccelStructure>> {
+        self.structure
+            .as_ref()
+            .filter(|cached| {
+                cached.structure.accel() == self.accel
+                    && cached.bounds == bounds
+            })
+            .map(|cached| Arc::clone(&cached.structure))
+    }
+
+    /// Returns the inverse of the object's transformation computed by
+    /// the last `update`, or `None` if the object changed since.
+    pub(crate) fn cached_inverse(&self, index: usize) -> Option<Matrix4> {
+        self.cache
+            .get(index)
+            .filter(|entry| entry.transform == self.objects[index].transform())
+            .map(|entry| entry.inverse)
     }
 }
 
 impl Default for World {
+    #[allow(clippy::field_reassign_with_default)]
     fn default() -> World {
         let light =
             PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
@@ -63,28 +363,46 @@ impl Default for World {
         s2.transform = Matrix4::scaling(0.5, 0.5, 0.5);
 
         World {
-            light: Some(light),
-            objects: vec![s1, s2],
+            lights: vec![light],
+            objects: vec![Box::new(s1), Box::new(s2)],
+            max_depth: MAX_DEPTH,
+            environment: None,
+            reflection_mode: ReflectionMode::Traced,
+            shadows: true,
+            sampler: Sampler::Fibonacci,
+            indirect: Indirect::Off,
+            toon: None,
+            accel: Accel::None,
+            cache: vec![],
+            structure: None,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
+    use crate::accel::Accel;
     use crate::color::Color;
+    use crate::environment::Environment;
     use crate::intersection::Intersection;
     use crate::light::PointLight;
+    use crate::material::Toon;
     use crate::matrix::Matrix4;
+    use crate::plane::Plane;
     use crate::ray::Ray;
+    use crate::rng::Rng;
+    use crate::sampler::Sampler;
+    use crate::scene::Scene;
     use crate::sphere::Sphere;
     use crate::tuple::Tuple;
-    use crate::world::World;
+    use crate::world::{ReflectionMode, World, WorldUpdate};
 
     #[test]
     fn test_creating_a_world() {
         let w = World::new();
         assert!(w.objects.is_empty());
-        assert!(w.light.is_none());
+        assert!(w.lights.is_empty());
     }
 
     #[test]
@@ -98,10 +416,12 @@ mod tests {
         let mut s2 = Sphere::default();
         s2.transform = Matrix4::scaling(0.5, 0.5, 0.5);
         let w = World::default();
-        assert!(w.light.is_some());
-        assert_eq!(w.light.unwrap(), light);
-        assert!(w.objects.contains(&s1));
-        assert!(w.objects.contains(&s2));
+        assert_eq!(w.lights, vec![light]);
+        assert_eq!(w.objects.len(), 2);
+        assert_eq!(w.objects[0].material(), &s1.material);
+        assert_eq!(w.objects[0].transform(), s1.transform);
+        assert_eq!(w.objects[1].material(), &s2.material);
+        assert_eq!(w.objects[1].transform(), s2.transform);
     }
 
     #[test]
@@ -111,7 +431,7 @@ mod tests {
             Tuple::point(0.0, 0.0, -5.0),
             Tuple::vector(0.0, 0.0, 1.0),
         );
-        let mut xs = w.intersect(r);
+        let mut xs = Scene::new(&w).intersect(r);
         xs.sort();
         assert_eq!(xs.len(), 4);
         assert_eq!(xs[0].t, 4.0);
@@ -127,7 +447,7 @@ mod tests {
             Tuple::point(0.0, 0.0, -5.0),
             Tuple::vector(0.0, 1.0, 0.0),
         );
-        let c = w.color_at(r);
+        let c = Scene::new(&w).color_at(r);
         assert_eq!(c, Color::black());
     }
 
@@ -138,22 +458,53 @@ mod tests {
             Tuple::point(0.0, 0.0, -5.0),
             Tuple::vector(0.0, 0.0, 1.0),
         );
-        let c = w.color_at(r);
+        let c = Scene::new(&w).color_at(r);
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn test_the_color_with_a_toon_style_for_the_whole_world() {
+        let mut w = World::default();
+        w.toon = Some(Toon::new(1));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        // ambient and the full diffuse term, without a highlight
+        // (0.1 + 0.7) * (0.8, 1.0, 0.6)
+        assert_eq!(Scene::new(&w).color_at(r), Color::new(0.64, 0.8, 0.48));
+    }
+
+    #[test]
+    fn test_holdout_objects_are_hidden_from_camera_rays_only() {
+        let mut w = World::default();
+        w.objects[0].material_mut().holdout = true;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let scene = Scene::new(&w);
+        // the camera sees the inner sphere through the outer one
+        assert_eq!(scene.camera_hit(r).unwrap().t, 4.5);
+        assert_eq!(scene.shading_hit(r).unwrap().t, 4.0);
+        assert_eq!(scene.camera_object_id_at(r), Some(1));
+        assert_eq!(scene.object_id_at(r), Some(0));
+        // which the outer sphere still shadows, leaving only ambient
+        assert_eq!(scene.color_at(r), Color::new(0.1, 0.1, 0.1));
+    }
+
     #[test]
     fn test_the_color_with_an_intersection_behind_the_ray() {
         let mut w = World::default();
-        w.objects[0].material.ambient = 1.0;
-        w.objects[1].material.ambient = 1.0;
-        let inner = w.objects[1];
+        w.objects[0].material_mut().ambient = 1.0;
+        w.objects[1].material_mut().ambient = 1.0;
+        let inner = &*w.objects[1];
         let r = Ray::new(
             Tuple::point(0.0, 0.0, 0.75),
             Tuple::vector(0.0, 0.0, -1.0),
         );
-        let c = w.color_at(r);
-        assert_eq!(c, inner.material.color);
+        let c = Scene::new(&w).color_at(r);
+        assert_eq!(c, inner.material().color);
     }
 
     #[test]
@@ -161,46 +512,490 @@ mod tests {
     {
         let w = World::default();
         let p = Tuple::point(0.0, 10.0, 0.0);
-        assert!(!w.is_shadowed(p));
+        assert!(!Scene::new(&w).is_shadowed(&w.lights[0], p));
     }
 
     #[test]
     fn test_the_shadow_when_an_object_is_between_the_point_and_the_light() {
         let w = World::default();
         let p = Tuple::point(10.0, -10.0, 10.0);
-        assert!(w.is_shadowed(p));
+        assert!(Scene::new(&w).is_shadowed(&w.lights[0], p));
+    }
+
+    #[test]
+    fn test_the_stats_of_the_default_world() {
+        let w = World::default();
+        let stats = w.stats();
+        assert_eq!(stats.objects_by_type.get("sphere"), Some(&2));
+        assert_eq!(stats.triangles, 0);
+        assert_eq!(stats.lights, 1);
+        assert_eq!(
+            stats.bounds,
+            Some((Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0)))
+        );
+        assert!(stats.memory_bytes >= 2 * std::mem::size_of::<Sphere>());
+        assert!(stats.to_string().contains("sphere: 2"));
+    }
+
+    #[test]
+    fn test_the_stats_of_an_empty_world() {
+        let stats = World::new().stats();
+        assert!(stats.objects_by_type.is_empty());
+        assert_eq!(stats.lights, 0);
+        assert_eq!(stats.bounds, None);
+    }
+
+    #[test]
+    fn test_nothing_is_shadowed_when_shadows_are_off() {
+        let mut w = World::default();
+        w.shadows = false;
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        let scene = Scene::new(&w);
+        assert!(!scene.is_shadowed(&w.lights[0], p));
+        assert_eq!(
+            scene.light_intensity_at(p, &w.lights[0], &mut Rng::new(0)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_a_light_that_casts_no_shadows_reaches_every_point() {
+        let mut w = World::default();
+        let mut light = w.lights[0];
+        light.casts_shadows = false;
+        w.lights = vec![light];
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        let scene = Scene::new(&w);
+        assert!(!scene.is_shadowed(&w.lights[0], p));
+        assert_eq!(scene.light_intensity_at(p, &light, &mut Rng::new(0)), 1.0);
     }
 
     #[test]
     fn test_there_is_no_shadow_when_an_object_is_behind_the_light() {
         let w = World::default();
         let p = Tuple::point(-20.0, 20.0, -20.0);
-        assert!(!w.is_shadowed(p));
+        assert!(!Scene::new(&w).is_shadowed(&w.lights[0], p));
     }
 
     #[test]
     fn test_there_is_no_shadow_when_an_object_is_behind_the_point() {
         let w = World::default();
         let p = Tuple::point(-2.0, 2.0, -2.0);
-        assert!(!w.is_shadowed(p));
+        assert!(!Scene::new(&w).is_shadowed(&w.lights[0], p));
     }
 
     #[test]
     fn test_shade_hit_is_given_an_intersection_in_shadow() {
         let mut world = World::default();
-        world.light = Some(PointLight::new(
+        world.lights = vec![PointLight::new(
             Tuple::point(0.0, 0.0, -10.0),
             Color::white(),
-        ));
+        )];
         let s1 = Sphere::default();
         let mut s2 = Sphere::default();
         s2.transform = Matrix4::translation(0.0, 0.0, 10.0);
-        world.objects = vec![s1, s2];
+        world.objects = vec![Box::new(s1), Box::new(s2)];
         let r =
             Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
-        let mut i = Intersection::new(4.0, s2);
+        let mut i = Intersection::new(4.0, &*world.objects[1]);
         i.prepare_hit(r);
-        let c = i.shade_hit(&world);
+        let c =
+            i.shade_hit(&Scene::new(&world), world.max_depth, &mut Rng::new(0));
         assert_eq!(c, Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn test_shade_hit_adds_up_every_light() {
+        let mut w = World::default();
+        // behind the sphere, so it only adds its ambient term
+        w.lights.push(PointLight::new(
+            Tuple::point(0.0, 0.0, 10.0),
+            Color::white(),
+        ));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut i = Intersection::new(4.0, &*w.objects[0]);
+        i.prepare_hit(r);
+        let c = i.shade_hit(&Scene::new(&w), w.max_depth, &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.46066, 0.57583, 0.3455));
+        assert_eq!(w.stats().lights, 2);
+    }
+
+    #[test]
+    fn test_updating_a_world_only_rebuilds_changed_objects() {
+        let mut w = World::default();
+        assert_eq!(w.dirty_objects(), vec![0, 1]);
+        assert_eq!(w.update().objects, 2);
+        assert!(w.dirty_objects().is_empty());
+        assert_eq!(w.update().objects, 0);
+        w.objects[1].set_transform(Matrix4::translation(0.0, 0.0, 10.0));
+        assert_eq!(w.dirty_objects(), vec![1]);
+        assert_eq!(w.update().objects, 1);
+        assert!(w.dirty_objects().is_empty());
+    }
+
+    #[test]
+    fn test_updating_an_unchanged_world_rebuilds_nothing() {
+        let mut w = World::default();
+        w.accel = Accel::Bvh;
+        let first = w.update();
+        assert_eq!(first.objects, 2);
+        assert!(first.structure);
+        assert_eq!(w.update(), WorldUpdate::default());
+        // the scene shares the structure kept by the update
+        let bounds = w.object_bounds();
+        assert!(w.cached_structure(&bounds).is_some());
+        w.objects[0].set_transform(Matrix4::translation(0.0, 0.0, 10.0));
+        assert_eq!(
+            w.update(),
+            WorldUpdate {
+                objects: 1,
+                structure: true,
+            }
+        );
+        w.objects.truncate(1);
+        assert_eq!(
+            w.update(),
+            WorldUpdate {
+                objects: 0,
+                structure: true,
+            }
+        );
+        w.accel = Accel::None;
+        assert_eq!(w.update(), WorldUpdate::default());
+        assert!(w.cached_structure(&w.object_bounds()).is_none());
+    }
+
+    #[test]
+    fn test_intersecting_a_world_with_stale_cached_data() {
+        let mut w = World::default();
+        w.update();
+        w.objects[1].set_transform(Matrix4::translation(0.0, 0.0, 10.0));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let mut xs = Scene::new(&w).intersect(r);
+        xs.sort();
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[2].t, 14.0);
+        assert_eq!(xs[3].t, 16.0);
+    }
+
+    #[test]
+    fn test_adding_objects_marks_them_dirty() {
+        let mut w = World::default();
+        w.update();
+        w.objects.push(Box::new(Sphere::default()));
+        assert_eq!(w.dirty_objects(), vec![2]);
+        w.objects.truncate(1);
+        assert!(w.dirty_objects().is_empty());
+        assert_eq!(w.update().objects, 0);
+    }
+
+    #[test]
+    fn test_every_acceleration_structure_finds_the_same_hits() {
+        let mut w = World::new();
+        for i in 0..8 {
+            for j in 0..8 {
+                let mut s = Sphere::default();
+                s.transform =
+                    Matrix4::translation(3.0 * i as f64, 3.0 * j as f64, 0.0)
+                        * Matrix4::scaling(0.5, 0.5, 0.5);
+                w.objects.push(Box::new(s));
+            }
+        }
+        let mut floor = Plane::default();
+        floor.transform = Matrix4::translation(0.0, -1.0, 0.0);
+        w.objects.push(Box::new(floor));
+        let ray = |i: usize| {
+            let origin = Tuple::point(-2.0, 0.7 * i as f64, -10.0);
+            let target = Tuple::point(0.8 * i as f64, 10.0, 0.0);
+            Ray::new(origin, (target - origin).normalize())
+        };
+        let hits = |scene: &Scene| {
+            let mut hits = vec![];
+            for i in 0..32 {
+                let ray = ray(i);
+                hits.push((scene.object_id_at(ray), scene.hit_any(ray, 100.0)));
+            }
+            hits
+        };
+        let packet_hits = |scene: &Scene| {
+            let mut hits = vec![];
+            for i in (0..32).step_by(4) {
+                let rays = [ray(i), ray(i + 1), ray(i + 2), ray(i + 3)];
+                for (ray, hit) in rays.iter().zip(scene.camera_hits(&rays)) {
+                    let t = scene.camera_hit(*ray).map(|hit| hit.t);
+                    assert_eq!(hit.map(|hit| hit.t), t);
+                    hits.push(t);
+                }
+            }
+            hits
+        };
+        let expected = hits(&Scene::new(&w));
+        let expected_packets = packet_hits(&Scene::new(&w));
+        assert!(expected.iter().filter(|(id, _)| id.is_some()).count() > 5);
+        for &accel in [Accel::Bvh, Accel::KdTree].iter() {
+            w.accel = accel;
+            let scene = Scene::new(&w);
+            assert_eq!(hits(&scene), expected);
+            assert_eq!(packet_hits(&scene), expected_packets);
+        }
+        // objects added since the last update are compiled too
+        let mut near = Sphere::default();
+        near.transform = Matrix4::translation(-2.0, 0.0, -8.0);
+        w.objects.push(Box::new(near));
+        let r = Ray::new(
+            Tuple::point(-2.0, 0.0, -10.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(Scene::new(&w).object_id_at(r), Some(65));
+    }
+
+    #[test]
+    fn test_the_nearest_hit_in_a_world() {
+        let w = World::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let hit = w.hit_sorted(r).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.object, &*w.objects[0]);
+        let inside = Ray::new(Tuple::point(0.0, 0.0, 0.0), r.direction);
+        assert_eq!(w.hit_sorted(inside).unwrap().t, 0.5);
+        assert_eq!(Scene::new(&w).hit_sorted(inside).unwrap().t, 0.5);
+        let away = Ray::new(r.origin, Tuple::vector(0.0, 0.0, -1.0));
+        assert!(w.hit_sorted(away).is_none());
+    }
+
+    #[test]
+    fn test_whether_anything_is_hit_before_a_distance() {
+        let w = World::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert!(w.hit_any(r, 10.0));
+        assert!(w.hit_any(r, 4.1));
+        assert!(!w.hit_any(r, 4.0));
+        assert!(!Scene::new(&w).hit_any(r, 4.0));
+        let away = Ray::new(r.origin, Tuple::vector(0.0, 0.0, -1.0));
+        assert!(!w.hit_any(away, 100.0));
+    }
+
+    #[test]
+    fn test_merging_worlds() {
+        let mut w = World::new();
+        w.merge(World::default());
+        w.merge_transformed(
+            World::default(),
+            Matrix4::translation(5.0, 0.0, 0.0),
+        );
+        assert_eq!(w.objects.len(), 4);
+        assert_eq!(
+            w.objects[0].material(),
+            World::default().objects[0].material()
+        );
+        assert_eq!(
+            w.objects[3].transform(),
+            Matrix4::translation(5.0, 0.0, 0.0)
+                * Matrix4::scaling(0.5, 0.5, 0.5)
+        );
+        assert_eq!(w.lights.len(), 2);
+        assert_eq!(w.lights[0], World::default().lights[0]);
+        assert_eq!(w.dirty_objects(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_merging_transforms_the_incoming_light() {
+        let mut w = World::new();
+        w.merge_transformed(
+            World::default(),
+            Matrix4::translation(5.0, 0.0, 0.0),
+        );
+        assert_eq!(w.lights[0].position, Tuple::point(-5.0, 10.0, -10.0));
+        // a lit prop keeps its lights in a lit scene
+        let mut w = World::default();
+        w.merge_transformed(
+            World::default(),
+            Matrix4::translation(5.0, 0.0, 0.0),
+        );
+        assert_eq!(w.lights.len(), 2);
+        assert_eq!(w.lights[1].position, Tuple::point(-5.0, 10.0, -10.0));
+    }
+
+    #[test]
+    fn test_the_object_id_visible_along_a_ray() {
+        let mut w = World::default();
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(Scene::new(&w).object_id_at(r), Some(0));
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(Scene::new(&w).object_id_at(r), Some(1));
+        w.objects.swap(0, 1);
+        assert_eq!(Scene::new(&w).object_id_at(r), Some(0));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(Scene::new(&w).object_id_at(r), None);
+    }
+
+    #[test]
+    fn test_color_at_with_mutually_reflective_surfaces() {
+        let mut w = World::new();
+        w.lights =
+            vec![PointLight::new(Tuple::point(0.0, 0.0, 0.0), Color::white())];
+        let mut mirror = Sphere::default();
+        mirror.transform = Matrix4::scaling(10.0, 10.0, 10.0);
+        mirror.material.reflective = 1.0;
+        w.objects = vec![Box::new(mirror)];
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        // the ray bounces between the walls of the sphere until the depth
+        // limit is reached
+        let c = Scene::new(&w).color_at(r);
+        assert!(c.red > 0.0);
+    }
+
+    #[test]
+    fn test_a_material_depth_limit_overrides_the_world_limit() {
+        let mut w = World::new();
+        w.lights =
+            vec![PointLight::new(Tuple::point(0.0, 0.0, 0.0), Color::white())];
+        let mut mirror = Sphere::default();
+        mirror.transform = Matrix4::scaling(10.0, 10.0, 10.0);
+        mirror.material.reflective = 0.5;
+        w.objects = vec![Box::new(mirror)];
+        let r =
+            Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let unlimited = Scene::new(&w).color_at(r);
+        w.objects[0].material_mut().max_depth = Some(1);
+        let capped = Scene::new(&w).color_at(r);
+        assert_ne!(capped, unlimited);
+        w.objects[0].material_mut().max_depth = None;
+        w.max_depth = 1;
+        assert_eq!(Scene::new(&w).color_at(r), capped);
+    }
+
+    #[test]
+    fn test_a_light_with_a_radius_casts_a_penumbra() {
+        let mut w = World::default();
+        let mut light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        light.radius = 1.0;
+        light.samples = 32;
+        w.lights = vec![light];
+        let scene = Scene::new(&w);
+        // fully behind the spheres, at the edge of the shadow, and
+        // fully out of it
+        let umbra = scene.light_intensity_at(
+            Tuple::point(0.0, 0.0, 10.0),
+            &light,
+            &mut Rng::new(0),
+        );
+        let penumbra = scene.light_intensity_at(
+            Tuple::point(2.0, 0.0, 10.0),
+            &light,
+            &mut Rng::new(0),
+        );
+        let lit = scene.light_intensity_at(
+            Tuple::point(5.0, 0.0, 10.0),
+            &light,
+            &mut Rng::new(0),
+        );
+        assert_eq!(umbra, 0.0);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_eq!(lit, 1.0);
+    }
+
+    #[test]
+    fn test_every_sampler_estimates_the_same_penumbra() {
+        let mut w = World::default();
+        let mut light =
+            PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        light.radius = 1.0;
+        light.samples = 256;
+        w.lights = vec![light];
+        let p = Tuple::point(2.0, 0.0, 10.0);
+        let reference =
+            Scene::new(&w).light_intensity_at(p, &light, &mut Rng::new(0));
+        for &sampler in
+            [Sampler::Random, Sampler::Halton, Sampler::Sobol].iter()
+        {
+            w.sampler = sampler;
+            let estimate =
+                Scene::new(&w).light_intensity_at(p, &light, &mut Rng::new(0));
+            assert!((estimate - reference).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_light_intensity_at_for_a_point_light() {
+        let w = World::default();
+        let light = w.lights[0];
+        let points = [
+            (Tuple::point(0.0, 1.0001, 0.0), 1.0),
+            (Tuple::point(-1.0001, 0.0, 0.0), 1.0),
+            (Tuple::point(0.0, 0.0, -1.0001), 1.0),
+            (Tuple::point(0.0, 0.0, 1.0001), 0.0),
+            (Tuple::point(1.0001, 0.0, 0.0), 0.0),
+            (Tuple::point(0.0, -1.0001, 0.0), 0.0),
+            (Tuple::point(0.0, 0.0, 0.0), 0.0),
+        ];
+        for (point, expected) in points.iter() {
+            assert_eq!(
+                w.light_intensity_at(*point, &light, &mut Rng::new(0)),
+                *expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_a_ray_that_misses_sees_the_environment() {
+        let mut w = World::default();
+        w.environment = Some(Environment::Uniform(Color::new(0.2, 0.3, 0.4)));
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(Scene::new(&w).color_at(r), Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn test_environment_reflections_skip_the_scene() {
+        let mut w = World::new();
+        w.lights = vec![PointLight::new(
+            Tuple::point(0.0, 0.0, -10.0),
+            Color::white(),
+        )];
+        let mut mirror = Sphere::default();
+        mirror.material.ambient = 0.0;
+        mirror.material.diffuse = 0.0;
+        mirror.material.specular = 0.0;
+        mirror.material.reflective = 0.5;
+        let mut blocker = Sphere::default();
+        blocker.transform = Matrix4::translation(0.0, 0.0, -3.0)
+            * Matrix4::scaling(0.5, 0.5, 0.5);
+        w.objects = vec![Box::new(mirror), Box::new(blocker)];
+        w.environment = Some(Environment::Uniform(Color::new(0.2, 0.4, 0.6)));
+        // the reflection of a ray hitting the mirror from the side, seen
+        // from the blocker, bounces back towards the blocker
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -1.5),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let traced = Scene::new(&w).color_at(r);
+        w.reflection_mode = ReflectionMode::Environment;
+        let cheap = Scene::new(&w).color_at(r);
+        assert_eq!(cheap, Color::new(0.1, 0.2, 0.3));
+        assert_ne!(traced, cheap);
+    }
 }